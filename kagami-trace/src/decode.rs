@@ -1,13 +1,97 @@
 use std::io::Cursor;
 
-use image::{ImageReader, Limits};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, Frames, ImageDecoder, ImageFormat, ImageReader, Limits};
 
 use crate::{
-    MAX_ENCODED_BYTES, MAX_IMAGE_DIMENSION, MAX_IMAGE_PIXELS, Trace, TraceError, TraceOptions,
-    TraceResult, trace_rgba,
+    MAX_ENCODED_BYTES, MAX_IMAGE_DIMENSION, MAX_IMAGE_PIXELS, Trace, TraceError, TraceFrame,
+    TraceOptions, TraceResult, trace_rgba, trace_rgba_frames, validate_animation,
 };
 
+// Browsers play GIF delays this short at 100 ms, and animations are authored against that.
+const MIN_FRAME_DELAY_MS: u32 = 20;
+const DEFAULT_FRAME_DELAY_MS: u32 = 100;
+
 pub fn trace_image(encoded: &[u8], options: &TraceOptions) -> TraceResult<Trace> {
+    validate_encoded(encoded)?;
+    let mut reader = ImageReader::new(Cursor::new(encoded))
+        .with_guessed_format()
+        .map_err(|error| TraceError::Decode(error.to_string()))?;
+    reader.limits(decode_limits());
+    let decoded = reader
+        .decode()
+        .map_err(|error| TraceError::Decode(error.to_string()))?
+        .into_rgba8();
+    trace_rgba(decoded.width(), decoded.height(), decoded.as_raw(), options)
+}
+
+// Traces every frame of an animated GIF or APNG with one shared palette. Frames come back
+// composited at the full canvas size, each with its display time.
+pub fn trace_animation(encoded: &[u8], options: &TraceOptions) -> TraceResult<Vec<TraceFrame>> {
+    validate_encoded(encoded)?;
+    let frames = match image::guess_format(encoded) {
+        Ok(ImageFormat::Gif) => {
+            let mut decoder = GifDecoder::new(Cursor::new(encoded)).map_err(decode_error)?;
+            decoder.set_limits(decode_limits()).map_err(decode_error)?;
+            decoder.into_frames()
+        }
+        Ok(ImageFormat::Png) => {
+            let mut decoder = PngDecoder::new(Cursor::new(encoded)).map_err(decode_error)?;
+            decoder.set_limits(decode_limits()).map_err(decode_error)?;
+            if !decoder.is_apng().map_err(decode_error)? {
+                return Err(TraceError::Decode(
+                    "PNG input has no animation; trace it as a still".to_string(),
+                ));
+            }
+            decoder.apng().map_err(decode_error)?.into_frames()
+        }
+        _ => {
+            return Err(TraceError::Decode(
+                "animated tracing accepts GIF or APNG input".to_string(),
+            ));
+        }
+    };
+    let (width, height, durations, pixels) = collect_frames(frames)?;
+    let frames: Vec<&[u8]> = pixels.iter().map(Vec::as_slice).collect();
+    let traces = trace_rgba_frames(width, height, &frames, options)?;
+    Ok(traces
+        .into_iter()
+        .zip(durations)
+        .map(|(trace, duration_ms)| TraceFrame { duration_ms, trace })
+        .collect())
+}
+
+type DecodedFrames = (u32, u32, Vec<u32>, Vec<Vec<u8>>);
+
+fn collect_frames(frames: Frames<'_>) -> TraceResult<DecodedFrames> {
+    let (mut width, mut height) = (0, 0);
+    let mut durations = Vec::new();
+    let mut pixels = Vec::new();
+    for frame in frames {
+        let frame = frame.map_err(decode_error)?;
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let delay = numerator / denominator.max(1);
+        durations.push(if delay < MIN_FRAME_DELAY_MS {
+            DEFAULT_FRAME_DELAY_MS
+        } else {
+            delay
+        });
+        let buffer = frame.into_buffer();
+        if pixels.is_empty() {
+            (width, height) = buffer.dimensions();
+        } else if buffer.dimensions() != (width, height) {
+            return Err(TraceError::Dimensions(
+                "animation frames differ in size".to_string(),
+            ));
+        }
+        validate_animation(width, height, pixels.len() + 1)?;
+        pixels.push(buffer.into_raw());
+    }
+    Ok((width, height, durations, pixels))
+}
+
+fn validate_encoded(encoded: &[u8]) -> TraceResult<()> {
     if encoded.is_empty() {
         return Err(TraceError::Decode("input is empty".to_string()));
     }
@@ -18,25 +102,25 @@ pub fn trace_image(encoded: &[u8], options: &TraceOptions) -> TraceResult<Trace>
             MAX_ENCODED_BYTES
         )));
     }
+    Ok(())
+}
 
-    let mut reader = ImageReader::new(Cursor::new(encoded))
-        .with_guessed_format()
-        .map_err(|error| TraceError::Decode(error.to_string()))?;
+fn decode_limits() -> Limits {
     let mut limits = Limits::default();
     limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
     limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
     limits.max_alloc = Some(MAX_IMAGE_PIXELS * 8);
-    reader.limits(limits);
-    let decoded = reader
-        .decode()
-        .map_err(|error| TraceError::Decode(error.to_string()))?
-        .into_rgba8();
-    trace_rgba(decoded.width(), decoded.height(), decoded.as_raw(), options)
+    limits
+}
+
+fn decode_error(error: image::ImageError) -> TraceError {
+    TraceError::Decode(error.to_string())
 }
 
 #[cfg(test)]
 mod tests {
-    use image::{DynamicImage, ImageBuffer, ImageFormat, Rgba};
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, DynamicImage, Frame, ImageBuffer, ImageFormat, Rgba};
     use std::io::Cursor;
 
     use super::*;
@@ -57,6 +141,31 @@ mod tests {
         assert_eq!(trace.path_count(), 1);
     }
 
+    #[test]
+    fn gif_frames_are_traced_with_their_delays() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            for color in [[255, 0, 0, 255], [0, 0, 255, 255]] {
+                let buffer = ImageBuffer::from_pixel(4, 4, Rgba(color));
+                let delay = Delay::from_numer_denom_ms(80, 1);
+                encoder
+                    .encode_frame(Frame::from_parts(buffer, 0, 0, delay))
+                    .unwrap();
+            }
+        }
+
+        let frames = trace_animation(&bytes, &TraceOptions::default()).unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].duration_ms, 80);
+        assert_eq!(frames[0].trace.palette, frames[1].trace.palette);
+        assert_ne!(
+            frames[0].trace.layers[0].palette_index,
+            frames[1].trace.layers[0].palette_index
+        );
+    }
+
     #[test]
     fn invalid_encoded_input_is_reported() {
         assert!(matches!(
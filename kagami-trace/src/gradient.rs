@@ -0,0 +1,491 @@
+use crate::{Color, GradientShape, GradientStop, LayerGradient, Path, Point, Trace, TraceLayer};
+
+// A ramp is only worth a gradient with a few bands, and each band has to be a strip across it (or
+// a ring around it): summed, their extents along the ramp may cover it at most this many times,
+// which leaves room for traced edges that wobble into their neighbours.
+const MIN_GRADIENT_LAYERS: usize = 4;
+const MAX_BAND_SPREAD: f32 = 2.0;
+const TOUCH_DISTANCE: f32 = 1.5;
+// Ramps this close to horizontal or vertical are snapped to it, so ASS can use rectangle clips.
+const AXIS_SNAP_DEGREES: f32 = 2.0;
+
+impl Trace {
+    // Merges groups of touching layers whose colors differ by at most `max_step` per channel from
+    // a neighbour, and whose shapes are strips across one direction or rings around one point,
+    // into a single layer with a linear or radial `gradient` holding one stop per merged layer.
+    // Layers with a folded outline or an existing gradient are left alone. Returns how many
+    // gradients were made.
+    pub fn detect_gradients(&mut self, max_step: u8) -> usize {
+        if max_step == 0 {
+            return 0;
+        }
+        let stats: Vec<Option<LayerStats>> = self
+            .layers
+            .iter()
+            .map(|layer| {
+                let color = self.color(layer)?;
+                LayerStats::new(layer, color)
+            })
+            .collect();
+
+        let mut groups = DisjointSet::new(stats.len());
+        let mut by_left: Vec<(usize, &LayerStats)> = stats
+            .iter()
+            .enumerate()
+            .filter_map(|(index, stats)| Some((index, stats.as_ref()?)))
+            .collect();
+        by_left.sort_by(|a, b| a.1.bounds[0].total_cmp(&b.1.bounds[0]));
+        for (position, (index, layer)) in by_left.iter().enumerate() {
+            for (other, neighbour) in &by_left[position + 1..] {
+                if neighbour.bounds[0] > layer.bounds[2] + TOUCH_DISTANCE {
+                    break;
+                }
+                if touches(layer.bounds, neighbour.bounds)
+                    && color_step(layer.color, neighbour.color) <= max_step
+                {
+                    groups.join(*index, *other);
+                }
+            }
+        }
+
+        let mut members: Vec<Vec<usize>> = vec![Vec::new(); stats.len()];
+        for (index, stats) in stats.iter().enumerate() {
+            if stats.is_some() {
+                members[groups.root(index)].push(index);
+            }
+        }
+        let mut merged: Vec<Option<TraceLayer>> = vec![None; self.layers.len()];
+        let mut removed = vec![false; self.layers.len()];
+        for group in members
+            .iter()
+            .filter(|group| group.len() >= MIN_GRADIENT_LAYERS)
+        {
+            let group_stats: Vec<&LayerStats> = group
+                .iter()
+                .map(|index| stats[*index].as_ref().unwrap())
+                .collect();
+            let Some((shape, offsets)) = fit(&group_stats) else {
+                continue;
+            };
+            let mut order: Vec<(f32, usize)> =
+                offsets.into_iter().zip(group.iter().copied()).collect();
+            order.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let stops = order
+                .iter()
+                .map(|(offset, index)| GradientStop {
+                    offset: offset.clamp(0.0, 1.0),
+                    palette_index: self.layers[*index].palette_index,
+                })
+                .collect();
+            let middle = order
+                .iter()
+                .min_by(|a, b| (a.0 - 0.5).abs().total_cmp(&(b.0 - 0.5).abs()))
+                .map(|(_, index)| self.layers[*index].palette_index)
+                .unwrap();
+            let layer = TraceLayer {
+                palette_index: middle,
+                sample_pixel_count: group
+                    .iter()
+                    .map(|index| self.layers[*index].sample_pixel_count)
+                    .sum(),
+                paths: order
+                    .iter()
+                    .flat_map(|(_, index)| self.layers[*index].paths.iter().cloned())
+                    .collect(),
+                outline: None,
+                gradient: Some(LayerGradient { shape, stops }),
+            };
+            for index in group {
+                removed[*index] = true;
+            }
+            merged[group[0]] = Some(layer);
+        }
+
+        let found = merged.iter().flatten().count();
+        let layers = std::mem::take(&mut self.layers);
+        self.layers = layers
+            .into_iter()
+            .zip(merged.into_iter().zip(removed))
+            .filter_map(|(layer, (merged, removed))| merged.or((!removed).then_some(layer)))
+            .collect();
+        found
+    }
+}
+
+struct LayerStats {
+    color: Color,
+    area: f64,
+    centroid: Point,
+    bounds: [f32; 4],
+    points: Vec<Point>,
+    polygons: Vec<Vec<Point>>,
+}
+
+impl LayerStats {
+    // `None` for layers that cannot be part of a gradient.
+    fn new(layer: &TraceLayer, color: Color) -> Option<Self> {
+        if layer.outline.is_some() || layer.gradient.is_some() || layer.paths.is_empty() {
+            return None;
+        }
+        let polygons: Vec<Vec<Point>> = layer.paths.iter().map(vertices).collect();
+        let (mut area, mut x, mut y) = (0.0, 0.0, 0.0);
+        for (path, polygon) in layer.paths.iter().zip(&polygons) {
+            let weight = if path.hole { -path.area } else { path.area };
+            let center = polygon_centroid(polygon);
+            area += weight;
+            x += weight * center.x as f64;
+            y += weight * center.y as f64;
+        }
+        if area <= 0.0 {
+            return None;
+        }
+        let points: Vec<Point> = polygons.iter().flatten().copied().collect();
+        let bounds = points.iter().fold(
+            [
+                f32::INFINITY,
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                f32::NEG_INFINITY,
+            ],
+            |bounds, point| {
+                [
+                    bounds[0].min(point.x),
+                    bounds[1].min(point.y),
+                    bounds[2].max(point.x),
+                    bounds[3].max(point.y),
+                ]
+            },
+        );
+        Some(Self {
+            color,
+            area,
+            centroid: Point {
+                x: (x / area) as f32,
+                y: (y / area) as f32,
+            },
+            bounds,
+            points,
+            polygons,
+        })
+    }
+
+    // Even-odd over all of the layer's contours, holes included.
+    fn contains(&self, point: Point) -> bool {
+        let mut inside = false;
+        for polygon in &self.polygons {
+            for (index, a) in polygon.iter().enumerate() {
+                let b = polygon[(index + 1) % polygon.len()];
+                if (a.y > point.y) != (b.y > point.y)
+                    && point.x < a.x + (b.x - a.x) * (point.y - a.y) / (b.y - a.y)
+                {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+}
+
+// The better of a linear and a radial fit, with each layer's offset on it.
+fn fit(layers: &[&LayerStats]) -> Option<(GradientShape, Vec<f32>)> {
+    let total: f64 = layers.iter().map(|layer| layer.area).sum();
+    let center = Point {
+        x: (layers
+            .iter()
+            .map(|layer| layer.area * layer.centroid.x as f64)
+            .sum::<f64>()
+            / total) as f32,
+        y: (layers
+            .iter()
+            .map(|layer| layer.area * layer.centroid.y as f64)
+            .sum::<f64>()
+            / total) as f32,
+    };
+
+    let direction = principal_direction(layers, center);
+    let linear = ranges(layers, |layer| {
+        let along =
+            |point: &Point| (point.x - center.x) * direction.x + (point.y - center.y) * direction.y;
+        extent(layer.points.iter().map(along))
+    });
+    let radial = ranges(layers, |layer| {
+        let (low, high) = extent(
+            layer
+                .points
+                .iter()
+                .map(|point| (point.x - center.x).hypot(point.y - center.y)),
+        );
+        // Boundary points alone would put a disk's middle at its rim.
+        (if layer.contains(center) { 0.0 } else { low }, high)
+    });
+
+    let linear_spread = spread(&linear);
+    let radial_spread = spread(&radial);
+    if linear_spread.min(radial_spread) > MAX_BAND_SPREAD {
+        return None;
+    }
+    if linear_spread <= radial_spread {
+        let (low, high) = extent(linear.iter().flat_map(|(low, high)| [*low, *high]));
+        let at = |t: f32| Point {
+            x: center.x + direction.x * t,
+            y: center.y + direction.y * t,
+        };
+        let offsets = linear
+            .iter()
+            .map(|(from, to)| ((from + to) / 2.0 - low) / (high - low))
+            .collect();
+        Some((
+            GradientShape::Linear {
+                from: at(low),
+                to: at(high),
+            },
+            offsets,
+        ))
+    } else {
+        let radius = radial.iter().map(|(_, high)| *high).fold(0.0, f32::max);
+        let offsets = radial
+            .iter()
+            .map(|(from, to)| (from + to) / 2.0 / radius)
+            .collect();
+        Some((GradientShape::Radial { center, radius }, offsets))
+    }
+}
+
+// The axis the layers' centroids spread along, weighted by area.
+fn principal_direction(layers: &[&LayerStats], center: Point) -> Point {
+    let (mut xx, mut yy, mut xy) = (0.0, 0.0, 0.0);
+    for layer in layers {
+        let (dx, dy) = (
+            (layer.centroid.x - center.x) as f64,
+            (layer.centroid.y - center.y) as f64,
+        );
+        xx += layer.area * dx * dx;
+        yy += layer.area * dy * dy;
+        xy += layer.area * dx * dy;
+    }
+    let mut angle = (0.5 * (2.0 * xy).atan2(xx - yy)) as f32;
+    let quarter = std::f32::consts::FRAC_PI_2;
+    let nearest_axis = (angle / quarter).round() * quarter;
+    if (angle - nearest_axis).abs() <= AXIS_SNAP_DEGREES.to_radians() {
+        angle = nearest_axis;
+    }
+    Point {
+        x: angle.cos(),
+        y: angle.sin(),
+    }
+}
+
+fn ranges(layers: &[&LayerStats], range: impl Fn(&LayerStats) -> (f32, f32)) -> Vec<(f32, f32)> {
+    layers.iter().map(|layer| range(layer)).collect()
+}
+
+// How many times the layers' ranges cover their union's extent; 1 for perfect strips.
+fn spread(ranges: &[(f32, f32)]) -> f32 {
+    let (low, high) = extent(ranges.iter().flat_map(|(low, high)| [*low, *high]));
+    if high - low <= f32::EPSILON {
+        return f32::INFINITY;
+    }
+    ranges.iter().map(|(low, high)| high - low).sum::<f32>() / (high - low)
+}
+
+fn extent(values: impl Iterator<Item = f32>) -> (f32, f32) {
+    values.fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), value| {
+        (low.min(value), high.max(value))
+    })
+}
+
+fn vertices(path: &Path) -> Vec<Point> {
+    let mut points = vec![path.start];
+    points.extend(path.segments.iter().map(|segment| segment.end()));
+    points.pop();
+    points
+}
+
+fn polygon_centroid(polygon: &[Point]) -> Point {
+    let (mut twice_area, mut x, mut y) = (0.0f64, 0.0f64, 0.0f64);
+    for (index, a) in polygon.iter().enumerate() {
+        let b = polygon[(index + 1) % polygon.len()];
+        let cross = a.x as f64 * b.y as f64 - b.x as f64 * a.y as f64;
+        twice_area += cross;
+        x += (a.x + b.x) as f64 * cross;
+        y += (a.y + b.y) as f64 * cross;
+    }
+    if twice_area.abs() <= f64::EPSILON {
+        let count = polygon.len().max(1) as f32;
+        return Point {
+            x: polygon.iter().map(|point| point.x).sum::<f32>() / count,
+            y: polygon.iter().map(|point| point.y).sum::<f32>() / count,
+        };
+    }
+    Point {
+        x: (x / (3.0 * twice_area)) as f32,
+        y: (y / (3.0 * twice_area)) as f32,
+    }
+}
+
+fn touches(a: [f32; 4], b: [f32; 4]) -> bool {
+    a[0] <= b[2] + TOUCH_DISTANCE
+        && b[0] <= a[2] + TOUCH_DISTANCE
+        && a[1] <= b[3] + TOUCH_DISTANCE
+        && b[1] <= a[3] + TOUCH_DISTANCE
+}
+
+fn color_step(a: Color, b: Color) -> u8 {
+    [
+        a.r.abs_diff(b.r),
+        a.g.abs_diff(b.g),
+        a.b.abs_diff(b.b),
+        a.a.abs_diff(b.a),
+    ]
+    .into_iter()
+    .max()
+    .unwrap_or(0)
+}
+
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn root(&mut self, mut index: usize) -> usize {
+        while self.parent[index] != index {
+            self.parent[index] = self.parent[self.parent[index]];
+            index = self.parent[index];
+        }
+        index
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a != b {
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Segment, TRACE_SCHEMA_VERSION};
+
+    use super::*;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Path {
+        let corners = [
+            (x + width, y),
+            (x + width, y + height),
+            (x, y + height),
+            (x, y),
+        ];
+        Path {
+            start: Point { x, y },
+            segments: corners
+                .into_iter()
+                .map(|(x, y)| Segment::Line { to: Point { x, y } })
+                .collect(),
+            hole: false,
+            area: (width * height) as f64,
+        }
+    }
+
+    fn trace(palette: Vec<Color>, layers: Vec<Vec<Path>>) -> Trace {
+        Trace {
+            schema_version: TRACE_SCHEMA_VERSION,
+            width: 64,
+            height: 64,
+            sampled_width: 64,
+            sampled_height: 64,
+            palette,
+            layers: layers
+                .into_iter()
+                .enumerate()
+                .map(|(index, paths)| TraceLayer {
+                    palette_index: index as u16,
+                    sample_pixel_count: 0,
+                    paths,
+                    outline: None,
+                    gradient: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn gray(value: u8) -> Color {
+        Color {
+            r: value,
+            g: value,
+            b: value,
+            a: 255,
+        }
+    }
+
+    #[test]
+    fn vertical_strips_become_one_horizontal_ramp() {
+        let mut strips = trace(
+            (0..8).map(|step| gray(100 + step * 8)).collect(),
+            (0..8)
+                .map(|step| vec![rect(step as f32 * 4.0, 0.0, 4.0, 16.0)])
+                .collect(),
+        );
+        assert_eq!(
+            strips.detect_gradients(4),
+            0,
+            "steps of 8 are too far apart"
+        );
+        assert_eq!(strips.detect_gradients(8), 1);
+        strips.validate().unwrap();
+
+        assert_eq!(strips.layers.len(), 1);
+        let gradient = strips.layers[0].gradient.as_ref().unwrap();
+        let GradientShape::Linear { from, to } = gradient.shape else {
+            panic!("{:?}", gradient.shape);
+        };
+        assert_eq!((from.y, to.y), (8.0, 8.0), "snapped to the x axis");
+        assert!((from.x.min(to.x), from.x.max(to.x)) == (0.0, 32.0));
+        assert_eq!(gradient.stops.len(), 8);
+        assert_eq!(strips.layers[0].paths.len(), 8);
+        let color = gradient.color_at(
+            &strips.palette,
+            gradient.shape.offset_at(Point { x: 6.0, y: 3.0 }),
+        );
+        assert_eq!(color, Some(gray(108)));
+
+        let svg = strips.to_svg();
+        assert!(svg.contains("<linearGradient id=\"kg0\" gradientUnits=\"userSpaceOnUse\""));
+        assert_eq!(svg.matches("<stop ").count(), 8);
+        assert!(svg.contains("fill=\"url(#kg0)\" fill-opacity=\"1\""));
+    }
+
+    #[test]
+    fn rings_become_a_radial_ramp_and_unrelated_shapes_stay() {
+        let square_ring = |inner: f32, outer: f32| {
+            let mut hole = rect(32.0 - inner, 32.0 - inner, inner * 2.0, inner * 2.0);
+            hole.hole = true;
+            vec![
+                rect(32.0 - outer, 32.0 - outer, outer * 2.0, outer * 2.0),
+                hole,
+            ]
+        };
+        let mut palette: Vec<Color> = (0..5).map(|step| gray(200 - step * 6)).collect();
+        palette.push(gray(10));
+        let mut layers = vec![vec![rect(28.0, 28.0, 8.0, 8.0)]];
+        layers.extend((1..5).map(|step| square_ring(step as f32 * 4.0, step as f32 * 4.0 + 4.0)));
+        layers.push(vec![rect(0.0, 0.0, 6.0, 6.0)]);
+        let mut rings = trace(palette, layers);
+
+        assert_eq!(rings.detect_gradients(8), 1);
+        rings.validate().unwrap();
+        assert_eq!(rings.layers.len(), 2);
+        let gradient = rings.layers[0].gradient.as_ref().unwrap();
+        assert!(
+            matches!(gradient.shape, GradientShape::Radial { center, .. } if center == Point { x: 32.0, y: 32.0 })
+        );
+        assert_eq!(gradient.stops[0].palette_index, 0);
+        assert!(rings.layers[1].gradient.is_none());
+    }
+}
@@ -1,23 +1,37 @@
 mod contour;
+mod gradient;
 mod model;
 mod options;
+mod outline;
 mod quantize;
 mod svg;
 
 #[cfg(feature = "decode")]
 mod decode;
+#[cfg(feature = "svg-import")]
+mod svg_import;
 
+use std::borrow::Cow;
 use std::fmt;
 
-pub use model::{Color, Path, Point, Segment, TRACE_SCHEMA_VERSION, Trace, TraceLayer};
+pub use model::{
+    Color, GradientShape, GradientStop, LayerGradient, LayerOutline, Path, Point, Segment,
+    TRACE_SCHEMA_VERSION, Trace, TraceFrame, TraceLayer,
+};
 pub use options::{MAX_COLOR_COUNT, TraceOptions, TracePreset};
 
 #[cfg(feature = "decode")]
-pub use decode::trace_image;
+pub use decode::{trace_animation, trace_image};
+#[cfg(feature = "svg-import")]
+pub use svg_import::{MAX_GRADIENT_BANDS, SvgImportOptions, import_svg};
 
 pub const MAX_IMAGE_DIMENSION: u32 = 8192;
 pub const MAX_IMAGE_PIXELS: u64 = 32 * 1024 * 1024;
 pub const MAX_ENCODED_BYTES: usize = 32 * 1024 * 1024;
+pub const MAX_ANIMATION_FRAMES: usize = 600;
+// Across all frames: 192 MiB of decoded RGBA, about 23 frames of 1080p, since animation and
+// clip requests come in over public HTTP routes.
+pub const MAX_ANIMATION_PIXELS: u64 = 48 * 1024 * 1024;
 pub(crate) const TRANSPARENT_LABEL: u16 = u16::MAX;
 
 #[derive(Debug)]
@@ -64,6 +78,137 @@ pub fn trace_rgba(
 ) -> TraceResult<Trace> {
     options.validate().map_err(TraceError::InvalidOptions)?;
     validate_dimensions(width, height)?;
+    validate_pixel_length(width, height, pixels)?;
+
+    let (sampled_width, sampled_height) = sampled_dimensions(width, height, options.max_dimension);
+    let trace_pixels = prepare_pixels(
+        pixels,
+        width,
+        height,
+        sampled_width,
+        sampled_height,
+        options,
+    );
+    let quantized = quantize::quantize(
+        &trace_pixels,
+        options.alpha_threshold,
+        options.color_count as usize,
+        options.preserve_gradients,
+    );
+    if quantized.palette.is_empty() {
+        return Ok(Trace::new(
+            width,
+            height,
+            sampled_width,
+            sampled_height,
+            Vec::new(),
+            Vec::new(),
+        ));
+    }
+
+    let mut labels = vec![frame_labels(
+        &trace_pixels,
+        sampled_width,
+        sampled_height,
+        &quantized,
+        options,
+    )];
+    let palette = compact_labels(&mut labels, quantized.palette);
+    Ok(trace_labels(
+        &labels[0],
+        palette,
+        width,
+        height,
+        sampled_width,
+        sampled_height,
+        options,
+    ))
+}
+
+// Traces the frames of an animation against one palette clustered from all of them, so a color
+// keeps its palette index from frame to frame instead of drifting between per-frame clusters.
+// Every frame is `width`x`height` RGBA; the returned traces are in frame order.
+pub fn trace_rgba_frames(
+    width: u32,
+    height: u32,
+    frames: &[&[u8]],
+    options: &TraceOptions,
+) -> TraceResult<Vec<Trace>> {
+    options.validate().map_err(TraceError::InvalidOptions)?;
+    validate_dimensions(width, height)?;
+    validate_animation(width, height, frames.len())?;
+    for pixels in frames {
+        validate_pixel_length(width, height, pixels)?;
+    }
+
+    let (sampled_width, sampled_height) = sampled_dimensions(width, height, options.max_dimension);
+    let prepared: Vec<Cow<'_, [u8]>> = frames
+        .iter()
+        .map(|pixels| {
+            prepare_pixels(
+                pixels,
+                width,
+                height,
+                sampled_width,
+                sampled_height,
+                options,
+            )
+        })
+        .collect();
+    // The palette is clustered from a strided sample of every frame, kept within the still-image
+    // pixel budget. Colors the sample missed fall back to their nearest palette entry.
+    let frame_pixels = sampled_width as u64 * sampled_height as u64;
+    let stride = (frame_pixels * prepared.len() as u64)
+        .div_ceil(MAX_IMAGE_PIXELS)
+        .max(1) as usize;
+    let mut sample = Vec::new();
+    for pixels in &prepared {
+        for rgba in pixels.chunks_exact(4).step_by(stride) {
+            sample.extend_from_slice(rgba);
+        }
+    }
+    let quantized = quantize::quantize(
+        &sample,
+        options.alpha_threshold,
+        options.color_count as usize,
+        options.preserve_gradients,
+    );
+    drop(sample);
+    if quantized.palette.is_empty() {
+        let empty = Trace::new(
+            width,
+            height,
+            sampled_width,
+            sampled_height,
+            Vec::new(),
+            Vec::new(),
+        );
+        return Ok(vec![empty; frames.len()]);
+    }
+
+    let mut labels: Vec<Vec<u16>> = prepared
+        .iter()
+        .map(|pixels| frame_labels(pixels, sampled_width, sampled_height, &quantized, options))
+        .collect();
+    drop(prepared);
+    let palette = compact_labels(&mut labels, quantized.palette);
+    Ok(labels
+        .iter()
+        .map(|labels| {
+            trace_labels(
+                labels,
+                palette.clone(),
+                width,
+                height,
+                sampled_width,
+                sampled_height,
+                options,
+            )
+        })
+        .collect())
+}
+
+fn validate_pixel_length(width: u32, height: u32, pixels: &[u8]) -> TraceResult<()> {
     let expected = (width as usize)
         .checked_mul(height as usize)
         .and_then(|value| value.checked_mul(4))
@@ -74,10 +219,43 @@ pub fn trace_rgba(
             actual: pixels.len(),
         });
     }
+    Ok(())
+}
 
-    let (sampled_width, sampled_height) = sampled_dimensions(width, height, options.max_dimension);
-    let resized = if sampled_width != width || sampled_height != height {
-        Some(resize_rgba(
+pub(crate) fn validate_animation(width: u32, height: u32, frames: usize) -> TraceResult<()> {
+    if frames == 0 {
+        return Err(TraceError::Dimensions(
+            "an animation needs at least one frame".to_string(),
+        ));
+    }
+    if frames > MAX_ANIMATION_FRAMES {
+        return Err(TraceError::TooLarge(format!(
+            "{} frames exceeds the {} frame limit",
+            frames, MAX_ANIMATION_FRAMES
+        )));
+    }
+    let pixels = width as u64 * height as u64 * frames as u64;
+    if pixels > MAX_ANIMATION_PIXELS {
+        return Err(TraceError::TooLarge(format!(
+            "{} animation pixels exceeds the {} pixel limit",
+            pixels, MAX_ANIMATION_PIXELS
+        )));
+    }
+    Ok(())
+}
+
+// Resamples to the tracing resolution and applies the optional color cleanup, borrowing the
+// input when neither changes it.
+fn prepare_pixels<'a>(
+    pixels: &'a [u8],
+    width: u32,
+    height: u32,
+    sampled_width: u32,
+    sampled_height: u32,
+    options: &TraceOptions,
+) -> Cow<'a, [u8]> {
+    let sampled = if sampled_width != width || sampled_height != height {
+        Cow::Owned(resize_rgba(
             pixels,
             width,
             height,
@@ -85,39 +263,29 @@ pub fn trace_rgba(
             sampled_height,
         ))
     } else {
-        None
+        Cow::Borrowed(pixels)
     };
-    let sampled_pixels = resized.as_deref().unwrap_or(pixels);
-    let smoothed = if options.color_smoothing > 0 {
-        Some(edge_aware_smooth_rgba(
-            sampled_pixels,
+    if options.color_smoothing > 0 {
+        Cow::Owned(edge_aware_smooth_rgba(
+            &sampled,
             sampled_width,
             sampled_height,
             options.color_smoothing,
             options.alpha_threshold,
         ))
     } else {
-        None
-    };
-    let trace_pixels = smoothed.as_deref().unwrap_or(sampled_pixels);
-    let quantized = quantize::quantize(
-        trace_pixels,
-        options.alpha_threshold,
-        options.color_count as usize,
-        options.preserve_gradients,
-    );
-    if quantized.palette.is_empty() {
-        return Ok(Trace::new(
-            width,
-            height,
-            sampled_width,
-            sampled_height,
-            Vec::new(),
-            Vec::new(),
-        ));
+        sampled
     }
+}
 
-    let mut labels = labels_from_pixels(trace_pixels, options.alpha_threshold, &quantized);
+fn frame_labels(
+    pixels: &[u8],
+    sampled_width: u32,
+    sampled_height: u32,
+    quantized: &quantize::Quantized,
+    options: &TraceOptions,
+) -> Vec<u16> {
+    let mut labels = labels_from_pixels(pixels, options.alpha_threshold, quantized);
     despeckle(
         &mut labels,
         sampled_width,
@@ -125,20 +293,37 @@ pub fn trace_rgba(
         options.min_area,
         &quantized.palette,
     );
-    let (palette, counts) = compact_labels(&mut labels, quantized.palette);
+    labels
+}
+
+fn trace_labels(
+    labels: &[u16],
+    palette: Vec<Color>,
+    width: u32,
+    height: u32,
+    sampled_width: u32,
+    sampled_height: u32,
+    options: &TraceOptions,
+) -> Trace {
     if palette.is_empty() {
-        return Ok(Trace::new(
+        return Trace::new(
             width,
             height,
             sampled_width,
             sampled_height,
             Vec::new(),
             Vec::new(),
-        ));
+        );
+    }
+    let mut counts = vec![0u64; palette.len()];
+    for label in labels.iter().copied() {
+        if label != TRANSPARENT_LABEL {
+            counts[label as usize] += 1;
+        }
     }
 
     let paths = contour::paths_for_labels(
-        &labels,
+        labels,
         sampled_width,
         sampled_height,
         palette.len(),
@@ -157,18 +342,20 @@ pub fn trace_rgba(
                 palette_index: index as u16,
                 sample_pixel_count: counts[index],
                 paths,
+                outline: None,
+                gradient: None,
             })
         })
         .collect();
 
-    Ok(Trace::new(
+    Trace::new(
         width,
         height,
         sampled_width,
         sampled_height,
         palette,
         layers,
-    ))
+    )
 }
 
 fn validate_dimensions(width: u32, height: u32) -> TraceResult<()> {
@@ -350,11 +537,24 @@ fn labels_from_pixels(
                 b: rgba[2],
                 a: rgba[3],
             };
-            quantized.label(color).unwrap_or(TRANSPARENT_LABEL)
+            quantized
+                .label(color)
+                .or_else(|| nearest_label(&quantized.palette, color))
+                .unwrap_or(TRANSPARENT_LABEL)
         })
         .collect()
 }
 
+fn nearest_label(palette: &[Color], color: Color) -> Option<u16> {
+    palette
+        .iter()
+        .enumerate()
+        .min_by(|(_, left), (_, right)| {
+            color_distance(color, **left).total_cmp(&color_distance(color, **right))
+        })
+        .map(|(index, _)| index as u16)
+}
+
 fn despeckle(labels: &mut Vec<u16>, width: u32, height: u32, min_area: u32, palette: &[Color]) {
     if min_area <= 1 || labels.is_empty() {
         return;
@@ -438,9 +638,11 @@ fn color_distance(left: Color, right: Color) -> f32 {
     red * red + green * green + blue * blue + alpha * alpha
 }
 
-fn compact_labels(labels: &mut [u16], palette: Vec<Color>) -> (Vec<Color>, Vec<u64>) {
+// Drops unused palette entries and renumbers the rest by how many pixels use them across all
+// `frames`, so every frame indexes the same compacted palette.
+fn compact_labels(frames: &mut [Vec<u16>], palette: Vec<Color>) -> Vec<Color> {
     let mut counts = vec![0u64; palette.len()];
-    for label in labels.iter().copied() {
+    for label in frames.iter().flatten().copied() {
         if label != TRANSPARENT_LABEL {
             counts[label as usize] += 1;
         }
@@ -456,18 +658,16 @@ fn compact_labels(labels: &mut [u16], palette: Vec<Color>) -> (Vec<Color>, Vec<u
 
     let mut remap = vec![TRANSPARENT_LABEL; palette.len()];
     let mut compact_palette = Vec::with_capacity(active.len());
-    let mut compact_counts = Vec::with_capacity(active.len());
     for (new_index, old_index) in active.into_iter().enumerate() {
         remap[old_index] = new_index as u16;
         compact_palette.push(palette[old_index]);
-        compact_counts.push(counts[old_index]);
     }
-    for label in labels {
+    for label in frames.iter_mut().flatten() {
         if *label != TRANSPARENT_LABEL {
             *label = remap[*label as usize];
         }
     }
-    (compact_palette, compact_counts)
+    compact_palette
 }
 
 fn color_sort_key(color: Color) -> u32 {
@@ -599,6 +799,34 @@ mod tests {
         assert_eq!(smoothed, edge);
     }
 
+    #[test]
+    fn animation_frames_share_one_palette() {
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let first = [red, red, red, blue].concat();
+        let second = [blue, blue, blue, red].concat();
+        let traces = trace_rgba_frames(2, 2, &[&first, &second], &options()).unwrap();
+
+        assert_eq!(traces.len(), 2);
+        assert_eq!(traces[0].palette, traces[1].palette);
+        assert_eq!(traces[0].palette.len(), 2);
+        let red_index = traces[0]
+            .palette
+            .iter()
+            .position(|color| color.r == 255)
+            .unwrap() as u16;
+        let red_layer = |trace: &Trace| {
+            trace
+                .layers
+                .iter()
+                .find(|layer| layer.palette_index == red_index)
+                .map(|layer| layer.sample_pixel_count)
+        };
+        assert_eq!(red_layer(&traces[0]), Some(3));
+        assert_eq!(red_layer(&traces[1]), Some(1));
+        assert!(trace_rgba_frames(2, 2, &[], &options()).is_err());
+    }
+
     #[test]
     fn small_island_is_reassigned_to_its_neighbor() {
         let mut labels = vec![0, 0, 0, 0, 1, 0, 0, 0, 0];
@@ -63,6 +63,102 @@ pub struct TraceLayer {
     pub palette_index: u16,
     pub sample_pixel_count: u64,
     pub paths: Vec<Path>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outline: Option<LayerOutline>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gradient: Option<LayerGradient>,
+}
+
+// An outline folded into a layer by `Trace::fold_outlines`: drawn `width` output pixels outside
+// the layer's contours in the given palette color.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LayerOutline {
+    pub palette_index: u16,
+    pub width: f32,
+}
+
+// A ramp found by `Trace::detect_gradients`: the layer's contours are painted with colors
+// interpolated between the stops instead of its flat palette color, which stays as a fallback.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LayerGradient {
+    pub shape: GradientShape,
+    pub stops: Vec<GradientStop>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GradientShape {
+    // Offset 0 at `from` and 1 at `to`, constant along lines across them.
+    Linear { from: Point, to: Point },
+    // Offset 0 at `center` and 1 at `radius` from it.
+    Radial { center: Point, radius: f32 },
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GradientStop {
+    pub offset: f32,
+    pub palette_index: u16,
+}
+
+impl GradientShape {
+    // Where `point` falls on the ramp; not clamped, so points past either end fall outside 0..1.
+    pub fn offset_at(&self, point: Point) -> f32 {
+        match *self {
+            GradientShape::Linear { from, to } => {
+                let (dx, dy) = (to.x - from.x, to.y - from.y);
+                ((point.x - from.x) * dx + (point.y - from.y) * dy) / (dx * dx + dy * dy)
+            }
+            GradientShape::Radial { center, radius } => {
+                (point.x - center.x).hypot(point.y - center.y) / radius
+            }
+        }
+    }
+
+    // Output pixels from offset 0 to offset 1.
+    pub fn length(&self) -> f32 {
+        match *self {
+            GradientShape::Linear { from, to } => (to.x - from.x).hypot(to.y - from.y),
+            GradientShape::Radial { radius, .. } => radius,
+        }
+    }
+}
+
+impl LayerGradient {
+    // The color at `offset`, interpolated in sRGB between the surrounding stops and held at the
+    // first and last stop past the ends.
+    pub fn color_at(&self, palette: &[Color], offset: f32) -> Option<Color> {
+        let color = |stop: &GradientStop| palette.get(stop.palette_index as usize).copied();
+        let (first, last) = (self.stops.first()?, self.stops.last()?);
+        if offset <= first.offset {
+            return color(first);
+        }
+        for pair in self.stops.windows(2) {
+            if offset > pair[1].offset {
+                continue;
+            }
+            let (from, to) = (color(&pair[0])?, color(&pair[1])?);
+            let span = pair[1].offset - pair[0].offset;
+            let mix = if span > 0.0 {
+                (offset - pair[0].offset) / span
+            } else {
+                1.0
+            };
+            let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * mix).round() as u8;
+            return Some(Color {
+                r: channel(from.r, to.r),
+                g: channel(from.g, to.g),
+                b: channel(from.b, to.b),
+                a: channel(from.a, to.a),
+            });
+        }
+        color(last)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TraceFrame {
+    pub duration_ms: u32,
+    pub trace: Trace,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -145,6 +241,20 @@ impl Trace {
                     layer.palette_index
                 ));
             }
+            if let Some(outline) = layer.outline {
+                if outline.palette_index as usize >= self.palette.len() {
+                    return Err(format!(
+                        "outline palette index {} is out of range",
+                        outline.palette_index
+                    ));
+                }
+                if !outline.width.is_finite() || outline.width <= 0.0 {
+                    return Err("trace outline width must be positive and finite".to_string());
+                }
+            }
+            if let Some(gradient) = &layer.gradient {
+                validate_gradient(gradient, self.palette.len())?;
+            }
             for path in &layer.paths {
                 validate_point(path.start)?;
                 if path.segments.is_empty() {
@@ -177,6 +287,42 @@ impl Trace {
     }
 }
 
+fn validate_gradient(gradient: &LayerGradient, palette_len: usize) -> Result<(), String> {
+    match gradient.shape {
+        GradientShape::Linear { from, to } => {
+            validate_point(from)?;
+            validate_point(to)?;
+        }
+        GradientShape::Radial { center, radius } => {
+            validate_point(center)?;
+            if !radius.is_finite() {
+                return Err("trace points must be finite".to_string());
+            }
+        }
+    }
+    let length = gradient.shape.length();
+    if length.is_nan() || length <= 0.0 {
+        return Err("trace gradient must have a non-zero length".to_string());
+    }
+    if gradient.stops.len() < 2 {
+        return Err("trace gradient needs at least two stops".to_string());
+    }
+    let mut previous = 0.0;
+    for stop in &gradient.stops {
+        if stop.palette_index as usize >= palette_len {
+            return Err(format!(
+                "gradient palette index {} is out of range",
+                stop.palette_index
+            ));
+        }
+        if !(previous..=1.0).contains(&stop.offset) {
+            return Err("trace gradient stops must be ordered offsets from 0 to 1".to_string());
+        }
+        previous = stop.offset;
+    }
+    Ok(())
+}
+
 fn validate_point(point: Point) -> Result<(), String> {
     if !point.x.is_finite() || !point.y.is_finite() {
         return Err("trace points must be finite".to_string());
@@ -0,0 +1,262 @@
+use crate::{LayerOutline, Path, Point, Segment, Trace, TraceLayer};
+
+// Shared contour edges are traced independently for each layer, so a fill's outer path and the
+// matching hole in its outline agree only to within fitting error.
+const MATCH_AREA_TOLERANCE: f64 = 0.15;
+const MIN_ENCLOSED_SHARE: f64 = 0.85;
+
+impl Trace {
+    // Folds thin outline layers into the layer they surround. A layer qualifies when its mean
+    // band width is at most `max_width` output pixels and every one of its components either
+    // wraps parts of exactly one other layer or lines that layer's holes; it is then removed and
+    // recorded as the other layer's `outline`. Returns how many layers were folded.
+    pub fn fold_outlines(&mut self, max_width: f32) -> usize {
+        if !max_width.is_finite() || max_width <= 0.0 {
+            return 0;
+        }
+        let mut folded = 0;
+        while let Some((band, fill, width)) = find_outline(&self.layers, max_width) {
+            self.layers[fill].outline = Some(LayerOutline {
+                palette_index: self.layers[band].palette_index,
+                width,
+            });
+            self.layers.remove(band);
+            folded += 1;
+        }
+        folded
+    }
+}
+
+fn find_outline(layers: &[TraceLayer], max_width: f32) -> Option<(usize, usize, f32)> {
+    for (band_index, band) in layers.iter().enumerate() {
+        if band.outline.is_some() || band.gradient.is_some() {
+            continue;
+        }
+        let width = band_width(&band.paths);
+        if width <= 0.0 || width > max_width {
+            continue;
+        }
+        let components = components(&band.paths);
+        let mut fills = layers.iter().enumerate().filter(|(index, fill)| {
+            *index != band_index
+                && fill.outline.is_none()
+                && wraps(&components, &fill.paths, max_width)
+        });
+        if let (Some((fill_index, _)), None) = (fills.next(), fills.next()) {
+            return Some((band_index, fill_index, width));
+        }
+    }
+    None
+}
+
+// Whether `components` of a band surround `fill`: most of the fill's outer area sits in the
+// band's holes, and each band component either has such a hole or traces one of the fill's.
+fn wraps(components: &[(&Path, Vec<&Path>)], fill: &[Path], max_width: f32) -> bool {
+    let fill_outers: Vec<&Path> = fill.iter().filter(|path| !path.hole).collect();
+    let fill_holes: Vec<&Path> = fill.iter().filter(|path| path.hole).collect();
+    let total: f64 = fill_outers.iter().map(|path| path.area).sum();
+    if total <= 0.0 {
+        return false;
+    }
+    let band_holes: Vec<&Path> = components
+        .iter()
+        .flat_map(|(_, holes)| holes.iter().copied())
+        .collect();
+    let enclosed: f64 = fill_outers
+        .iter()
+        .filter(|outer| {
+            band_holes
+                .iter()
+                .any(|hole| same_contour(outer, hole, max_width))
+        })
+        .map(|outer| outer.area)
+        .sum();
+    if enclosed < total * MIN_ENCLOSED_SHARE {
+        return false;
+    }
+    components.iter().all(|(outer, holes)| {
+        holes.iter().any(|hole| {
+            fill_outers
+                .iter()
+                .any(|fill| same_contour(fill, hole, max_width))
+        }) || fill_holes
+            .iter()
+            .any(|hole| same_contour(outer, hole, max_width))
+    })
+}
+
+fn same_contour(left: &Path, right: &Path, max_width: f32) -> bool {
+    if (left.area - right.area).abs() > left.area.max(right.area) * MATCH_AREA_TOLERANCE {
+        return false;
+    }
+    let (left, right) = (bounds(left), bounds(right));
+    let slack = max_width + 1.0;
+    left.iter()
+        .zip(right.iter())
+        .all(|(left, right)| (left - right).abs() <= slack)
+}
+
+// Each outer path followed by the holes traced inside it.
+fn components(paths: &[Path]) -> Vec<(&Path, Vec<&Path>)> {
+    let mut components: Vec<(&Path, Vec<&Path>)> = Vec::new();
+    for path in paths {
+        match components.last_mut() {
+            Some((_, holes)) if path.hole => holes.push(path),
+            _ => components.push((path, Vec::new())),
+        }
+    }
+    components
+}
+
+// Mean width of a band: its area over half the length of all its edges.
+fn band_width(paths: &[Path]) -> f32 {
+    let area: f64 = paths
+        .iter()
+        .map(|path| if path.hole { -path.area } else { path.area })
+        .sum();
+    let perimeter: f64 = paths.iter().map(perimeter).sum();
+    if area <= 0.0 || perimeter <= 0.0 {
+        return 0.0;
+    }
+    (2.0 * area / perimeter) as f32
+}
+
+fn perimeter(path: &Path) -> f64 {
+    let mut length = 0.0;
+    let mut from = path.start;
+    for segment in &path.segments {
+        length += match segment {
+            Segment::Line { to } => distance(from, *to),
+            // The mean of chord and control polygon is a close upper bound for a cubic's length.
+            Segment::Cubic {
+                control_1,
+                control_2,
+                to,
+            } => {
+                let polygon = distance(from, *control_1)
+                    + distance(*control_1, *control_2)
+                    + distance(*control_2, *to);
+                (polygon + distance(from, *to)) * 0.5
+            }
+        };
+        from = segment.end();
+    }
+    length + distance(from, path.start)
+}
+
+fn bounds(path: &Path) -> [f32; 4] {
+    let mut bounds = [path.start.x, path.start.y, path.start.x, path.start.y];
+    for segment in &path.segments {
+        let to = segment.end();
+        bounds = [
+            bounds[0].min(to.x),
+            bounds[1].min(to.y),
+            bounds[2].max(to.x),
+            bounds[3].max(to.y),
+        ];
+    }
+    bounds
+}
+
+fn distance(from: Point, to: Point) -> f64 {
+    ((to.x - from.x) as f64).hypot((to.y - from.y) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Color, TRACE_SCHEMA_VERSION};
+
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32, hole: bool) -> Path {
+        let corners = [(x + size, y), (x + size, y + size), (x, y + size), (x, y)];
+        Path {
+            start: Point { x, y },
+            segments: corners
+                .into_iter()
+                .map(|(x, y)| Segment::Line { to: Point { x, y } })
+                .collect(),
+            hole,
+            area: (size * size) as f64,
+        }
+    }
+
+    fn layer(palette_index: u16, paths: Vec<Path>) -> TraceLayer {
+        TraceLayer {
+            palette_index,
+            sample_pixel_count: 0,
+            paths,
+            outline: None,
+            gradient: None,
+        }
+    }
+
+    fn trace(layers: Vec<TraceLayer>) -> Trace {
+        let color = |value| Color {
+            r: value,
+            g: value,
+            b: value,
+            a: 255,
+        };
+        Trace {
+            schema_version: TRACE_SCHEMA_VERSION,
+            width: 100,
+            height: 100,
+            sampled_width: 100,
+            sampled_height: 100,
+            palette: vec![color(255), color(0), color(128)],
+            layers,
+        }
+    }
+
+    #[test]
+    fn thin_band_around_one_fill_becomes_its_outline() {
+        let mut traced = trace(vec![
+            layer(0, vec![square(12.0, 12.0, 20.0, false)]),
+            layer(
+                1,
+                vec![
+                    square(10.0, 10.0, 24.0, false),
+                    square(12.0, 12.0, 20.0, true),
+                ],
+            ),
+        ]);
+
+        assert_eq!(traced.fold_outlines(4.0), 1);
+        assert_eq!(traced.layers.len(), 1);
+        let outline = traced.layers[0].outline.unwrap();
+        assert_eq!(outline.palette_index, 1);
+        assert!((outline.width - 2.0).abs() < 0.1, "{}", outline.width);
+        traced.validate().unwrap();
+    }
+
+    #[test]
+    fn thick_or_shared_bands_are_kept_as_layers() {
+        let band = layer(
+            1,
+            vec![
+                square(0.0, 0.0, 60.0, false),
+                square(12.0, 12.0, 20.0, true),
+            ],
+        );
+        let mut thick = trace(vec![layer(0, vec![square(12.0, 12.0, 20.0, false)]), band]);
+        assert_eq!(thick.fold_outlines(4.0), 0);
+
+        let shared = layer(
+            1,
+            vec![
+                square(10.0, 10.0, 24.0, false),
+                square(12.0, 12.0, 20.0, true),
+                square(50.0, 10.0, 24.0, false),
+                square(52.0, 12.0, 20.0, true),
+            ],
+        );
+        let mut two_fills = trace(vec![
+            layer(0, vec![square(12.0, 12.0, 20.0, false)]),
+            layer(2, vec![square(52.0, 12.0, 20.0, false)]),
+            shared,
+        ]);
+        assert_eq!(two_fills.fold_outlines(4.0), 0);
+        assert_eq!(two_fills.layers.len(), 3);
+    }
+}
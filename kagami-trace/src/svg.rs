@@ -1,6 +1,6 @@
 use std::fmt::Write;
 
-use crate::{Segment, Trace};
+use crate::{GradientShape, LayerGradient, Segment, Trace};
 
 impl Trace {
     pub fn to_svg(&self) -> String {
@@ -19,10 +19,20 @@ impl Trace {
             "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
             self.width, self.height, self.width, self.height
         );
-        for layer in &self.layers {
+        for (index, layer) in self.layers.iter().enumerate() {
             let Some(color) = self.color(layer) else {
                 continue;
             };
+            // A gradient layer paints with a `<*Gradient>` defined right before it; the stops
+            // carry the alpha, so the fill and underlap opacity stay at 1.
+            let gradient = layer
+                .gradient
+                .as_ref()
+                .and_then(|gradient| self.svg_gradient(index, gradient));
+            let (paint, opacity) = match &gradient {
+                Some(_) => (format!("url(#kg{index})"), "1".to_string()),
+                None => (color.hex_rgb(), format_opacity(color.a)),
+            };
             let mut data = String::new();
             for path in &layer.paths {
                 let _ = write!(
@@ -57,29 +67,71 @@ impl Trace {
                 }
                 data.push('Z');
             }
-            let underlap = if seam_overlap > 0.0 {
+            let outline = layer.outline.and_then(|outline| {
+                Some((outline, self.palette.get(outline.palette_index as usize)?))
+            });
+            let underlap = if let Some((outline, outline_color)) = outline {
+                format!(
+                    " stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\" paint-order=\"stroke fill\"",
+                    outline_color.hex_rgb(),
+                    format_opacity(outline_color.a),
+                    format_number((outline.width + seam_overlap) * 2.0),
+                )
+            } else if seam_overlap > 0.0 {
                 format!(
                     " stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\" paint-order=\"stroke fill\"",
-                    color.hex_rgb(),
-                    format_opacity(color.a),
+                    paint,
+                    opacity,
                     format_number(seam_overlap * 2.0),
                 )
             } else {
                 String::new()
             };
+            if let Some(gradient) = &gradient {
+                output.push_str(gradient);
+            }
             let _ = write!(
                 output,
                 "<path data-color-index=\"{}\" fill=\"{}\" fill-opacity=\"{}\" fill-rule=\"nonzero\"{} d=\"{}\"/>",
-                layer.palette_index,
-                color.hex_rgb(),
-                format_opacity(color.a),
-                underlap,
-                data,
+                layer.palette_index, paint, opacity, underlap, data,
             );
         }
         output.push_str("</svg>");
         output
     }
+
+    fn svg_gradient(&self, index: usize, gradient: &LayerGradient) -> Option<String> {
+        let mut output = match gradient.shape {
+            GradientShape::Linear { from, to } => format!(
+                "<linearGradient id=\"kg{index}\" gradientUnits=\"userSpaceOnUse\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\">",
+                format_number(from.x),
+                format_number(from.y),
+                format_number(to.x),
+                format_number(to.y),
+            ),
+            GradientShape::Radial { center, radius } => format!(
+                "<radialGradient id=\"kg{index}\" gradientUnits=\"userSpaceOnUse\" cx=\"{}\" cy=\"{}\" r=\"{}\">",
+                format_number(center.x),
+                format_number(center.y),
+                format_number(radius),
+            ),
+        };
+        for stop in &gradient.stops {
+            let color = self.palette.get(stop.palette_index as usize)?;
+            let _ = write!(
+                output,
+                "<stop offset=\"{}\" stop-color=\"{}\" stop-opacity=\"{}\"/>",
+                format_number(stop.offset),
+                color.hex_rgb(),
+                format_opacity(color.a),
+            );
+        }
+        output.push_str(match gradient.shape {
+            GradientShape::Linear { .. } => "</linearGradient>",
+            GradientShape::Radial { .. } => "</radialGradient>",
+        });
+        Some(output)
+    }
 }
 
 pub(crate) fn format_number(value: f32) -> String {
@@ -141,6 +193,8 @@ mod tests {
                     hole: false,
                     area: 10.0,
                 }],
+                outline: None,
+                gradient: None,
             }],
         );
 
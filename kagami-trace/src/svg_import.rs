@@ -0,0 +1,764 @@
+// Vector input: an SVG parsed by usvg and flattened into the same model the tracer produces, so
+// designer logos reach SVG/ASS export with their exact curves instead of being rasterized and
+// traced again. Every visible fill and stroke becomes a layer in paint order; strokes are
+// expanded to outlines, quadratics become cubics, and group opacity is multiplied into the
+// colors. Linear gradients are cut into bands of flat color. Clip paths, masks, filters, patterns
+// and embedded images are refused rather than approximated.
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use usvg::tiny_skia_path::{self, PathSegment, PathStroker};
+use usvg::{FillRule, Group, Node, Paint, PaintOrder, Stop, Transform};
+
+use crate::{
+    Color, MAX_ENCODED_BYTES, Path, Point, Segment, Trace, TraceError, TraceLayer, TraceResult,
+    validate_dimensions,
+};
+
+pub const MAX_GRADIENT_BANDS: u16 = 256;
+const MAX_IMPORT_SEGMENTS: usize = 1_000_000;
+const MIN_PATH_AREA: f64 = 1e-6;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SvgImportOptions {
+    // Most bands a linear gradient is cut into; fewer are used when the ramp is short or has
+    // few distinct colors. 1 paints every gradient in its middle color.
+    pub gradient_bands: u16,
+    // Largest distance, in output pixels, between a curve and the polygon it is flattened to.
+    // Only gradient bands and fill-rule checks flatten; flat fills keep their curves.
+    pub flatten_tolerance: f32,
+}
+
+impl Default for SvgImportOptions {
+    fn default() -> Self {
+        Self {
+            gradient_bands: 32,
+            flatten_tolerance: 0.25,
+        }
+    }
+}
+
+impl SvgImportOptions {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_GRADIENT_BANDS).contains(&self.gradient_bands) {
+            return Err(format!(
+                "gradient_bands must be between 1 and {MAX_GRADIENT_BANDS}"
+            ));
+        }
+        if !self.flatten_tolerance.is_finite() || !(0.01..=4.0).contains(&self.flatten_tolerance) {
+            return Err("flatten_tolerance must be finite and between 0.01 and 4".to_string());
+        }
+        Ok(())
+    }
+}
+
+// The trace has the SVG's size with its viewBox applied, and sampled dimensions equal to it.
+pub fn import_svg(data: &[u8], options: &SvgImportOptions) -> TraceResult<Trace> {
+    options.validate().map_err(TraceError::InvalidOptions)?;
+    if data.len() > MAX_ENCODED_BYTES {
+        return Err(TraceError::TooLarge(format!(
+            "{} encoded bytes exceeds the {} byte limit",
+            data.len(),
+            MAX_ENCODED_BYTES
+        )));
+    }
+    let tree = usvg::Tree::from_data(data, &svg_options())
+        .map_err(|error| TraceError::Decode(error.to_string()))?;
+    let size = tree.size();
+    let width = size.width().ceil() as u32;
+    let height = size.height().ceil() as u32;
+    validate_dimensions(width, height)?;
+
+    let mut import = Import {
+        options,
+        palette: Vec::new(),
+        layers: Vec::new(),
+        segments: 0,
+    };
+    import.group(tree.root(), 1.0)?;
+    Ok(Trace::new(
+        width,
+        height,
+        width,
+        height,
+        import.palette,
+        import.layers,
+    ))
+}
+
+// No external files or embedded images: the importer runs on uploads.
+fn svg_options() -> usvg::Options<'static> {
+    usvg::Options {
+        resources_dir: None,
+        image_href_resolver: usvg::ImageHrefResolver {
+            resolve_data: Box::new(|_, _, _| None),
+            resolve_string: Box::new(|_, _| None),
+        },
+        ..usvg::Options::default()
+    }
+}
+
+struct Import<'a> {
+    options: &'a SvgImportOptions,
+    palette: Vec<Color>,
+    layers: Vec<TraceLayer>,
+    segments: usize,
+}
+
+impl Import<'_> {
+    fn group(&mut self, group: &Group, opacity: f32) -> TraceResult<()> {
+        if group.clip_path().is_some() || group.mask().is_some() || !group.filters().is_empty() {
+            return Err(unsupported("clip paths, masks and filters"));
+        }
+        let opacity = opacity * group.opacity().get();
+        for node in group.children() {
+            match node {
+                Node::Group(child) => self.group(child, opacity)?,
+                Node::Path(path) => self.path(path, opacity)?,
+                Node::Image(_) => return Err(unsupported("embedded images")),
+                // Text needs fonts the importer does not load, so it has to be converted to paths
+                // first rather than silently left out.
+                Node::Text(_) => return Err(unsupported("text elements")),
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&mut self, path: &usvg::Path, opacity: f32) -> TraceResult<()> {
+        if !path.is_visible() {
+            return Ok(());
+        }
+        let transform = path.abs_transform();
+        let passes = match path.paint_order() {
+            PaintOrder::FillAndStroke => [false, true],
+            PaintOrder::StrokeAndFill => [true, false],
+        };
+        for stroke_pass in passes {
+            if stroke_pass {
+                let Some(stroke) = path.stroke() else {
+                    continue;
+                };
+                let stroke_options = stroke.to_tiny_skia();
+                let resolution = PathStroker::compute_resolution_scale(&transform);
+                let data = match &stroke_options.dash {
+                    Some(dash) => match path.data().dash(dash, resolution) {
+                        Some(dashed) => Cow::Owned(dashed),
+                        None => continue,
+                    },
+                    None => Cow::Borrowed(path.data()),
+                };
+                if let Some(outline) = data.stroke(&stroke_options, resolution) {
+                    let opacity = opacity * stroke.opacity().get();
+                    self.paint(
+                        &outline,
+                        stroke.paint(),
+                        opacity,
+                        FillRule::NonZero,
+                        transform,
+                    )?;
+                }
+            } else if let Some(fill) = path.fill() {
+                let opacity = opacity * fill.opacity().get();
+                self.paint(path.data(), fill.paint(), opacity, fill.rule(), transform)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn paint(
+        &mut self,
+        data: &tiny_skia_path::Path,
+        paint: &Paint,
+        opacity: f32,
+        rule: FillRule,
+        transform: Transform,
+    ) -> TraceResult<()> {
+        let Some(data) = data.clone().transform(transform) else {
+            return Ok(());
+        };
+        let tolerance = self.options.flatten_tolerance;
+        let paths = oriented_paths(
+            contours(&data),
+            matches!(rule, FillRule::EvenOdd),
+            tolerance,
+        );
+        match paint {
+            Paint::Color(color) => {
+                let color = rgba(*color, opacity);
+                self.push(color, paths, color.a == 255)
+            }
+            Paint::LinearGradient(gradient) => {
+                let ramp = transform
+                    .pre_concat(gradient.transform())
+                    .invert()
+                    .and_then(|inverse| {
+                        Ramp::new(
+                            inverse,
+                            (gradient.x1(), gradient.y1()),
+                            (gradient.x2(), gradient.y2()),
+                        )
+                    });
+                let Some(ramp) = ramp else {
+                    // A zero-length gradient paints its last stop.
+                    let color = stop_color(gradient.stops(), 1.0, opacity);
+                    return self.push(color, paths, color.a == 255);
+                };
+                let bands = ramp.bands(&paths, gradient.stops(), opacity, self.options);
+                for (color, band) in bands {
+                    self.push(color, band, true)?;
+                }
+                Ok(())
+            }
+            Paint::RadialGradient(gradient) => {
+                let color = stop_color(gradient.stops(), 0.5, opacity);
+                self.push(color, paths, color.a == 255)
+            }
+            Paint::Pattern(_) => Err(unsupported("pattern fills")),
+        }
+    }
+
+    // Consecutive paints of one color share a layer when that cannot change the result: opaque
+    // overlaps look the same either way, and gradient bands never overlap.
+    fn push(&mut self, color: Color, paths: Vec<Path>, mergeable: bool) -> TraceResult<()> {
+        if paths.is_empty() || color.a == 0 {
+            return Ok(());
+        }
+        self.segments += paths.iter().map(|path| path.segments.len()).sum::<usize>();
+        if self.segments > MAX_IMPORT_SEGMENTS {
+            return Err(TraceError::TooLarge(format!(
+                "SVG has more than {MAX_IMPORT_SEGMENTS} path segments"
+            )));
+        }
+        let palette_index = match self.palette.iter().position(|known| *known == color) {
+            Some(index) => index,
+            None => {
+                self.palette.push(color);
+                self.palette.len() - 1
+            }
+        };
+        let palette_index = u16::try_from(palette_index)
+            .map_err(|_| TraceError::TooLarge("SVG uses too many colors".to_string()))?;
+        let area = paths.iter().map(|path| path.area).sum::<f64>().round() as u64;
+        if mergeable
+            && let Some(last) = self.layers.last_mut()
+            && last.palette_index == palette_index
+        {
+            last.sample_pixel_count += area;
+            last.paths.extend(paths);
+            return Ok(());
+        }
+        if self.layers.len() >= u16::MAX as usize {
+            return Err(TraceError::TooLarge(
+                "SVG has too many painted shapes".to_string(),
+            ));
+        }
+        self.layers.push(TraceLayer {
+            palette_index,
+            sample_pixel_count: area,
+            paths,
+            outline: None,
+            gradient: None,
+        });
+        Ok(())
+    }
+}
+
+fn unsupported(what: &str) -> TraceError {
+    TraceError::Decode(format!(
+        "SVG {what} are not supported; flatten them in the editor before importing"
+    ))
+}
+
+fn rgba(color: usvg::Color, opacity: f32) -> Color {
+    Color {
+        r: color.red,
+        g: color.green,
+        b: color.blue,
+        a: (opacity.clamp(0.0, 1.0) * 255.0).round() as u8,
+    }
+}
+
+// The gradient's color at `t`, interpolated in sRGB between the surrounding stops and padded
+// past the first and last one.
+fn stop_color(stops: &[Stop], t: f32, opacity: f32) -> Color {
+    let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+        return Color::TRANSPARENT;
+    };
+    if t <= first.offset().get() {
+        return rgba(first.color(), opacity * first.opacity().get());
+    }
+    for pair in stops.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        let (start, end) = (from.offset().get(), to.offset().get());
+        if t > end {
+            continue;
+        }
+        let mix = if end > start {
+            (t - start) / (end - start)
+        } else {
+            1.0
+        };
+        let channel = |a: u8, b: u8| a as f32 + (b as f32 - a as f32) * mix;
+        let (a, b) = (from.color(), to.color());
+        let alpha = from.opacity().get() + (to.opacity().get() - from.opacity().get()) * mix;
+        return Color {
+            r: channel(a.red, b.red).round() as u8,
+            g: channel(a.green, b.green).round() as u8,
+            b: channel(a.blue, b.blue).round() as u8,
+            a: ((alpha * opacity).clamp(0.0, 1.0) * 255.0).round() as u8,
+        };
+    }
+    rgba(last.color(), opacity * last.opacity().get())
+}
+
+// A linear gradient's position as an affine function of output coordinates,
+// t = x * dx + y * dy + offset, so bands are strips between two values of t.
+struct Ramp {
+    dx: f32,
+    dy: f32,
+    offset: f32,
+}
+
+impl Ramp {
+    fn new(inverse: Transform, start: (f32, f32), end: (f32, f32)) -> Option<Self> {
+        let (vx, vy) = (end.0 - start.0, end.1 - start.1);
+        let length = vx * vx + vy * vy;
+        if !length.is_finite() || length <= f32::EPSILON {
+            return None;
+        }
+        let at = |x: f32, y: f32| {
+            let gx = inverse.sx * x + inverse.kx * y + inverse.tx;
+            let gy = inverse.ky * x + inverse.sy * y + inverse.ty;
+            ((gx - start.0) * vx + (gy - start.1) * vy) / length
+        };
+        let offset = at(0.0, 0.0);
+        Some(Self {
+            dx: at(1.0, 0.0) - offset,
+            dy: at(0.0, 1.0) - offset,
+            offset,
+        })
+    }
+
+    fn at(&self, point: Point) -> f32 {
+        point.x * self.dx + point.y * self.dy + self.offset
+    }
+
+    // Equal steps of t across the part of the ramp the shape covers. The count is capped by the
+    // options, by one band per two output pixels and by the largest channel change, and equal
+    // neighbours are merged; the outer bands run to infinity to cover the padded ends.
+    fn bands(
+        &self,
+        paths: &[Path],
+        stops: &[Stop],
+        opacity: f32,
+        options: &SvgImportOptions,
+    ) -> Vec<(Color, Vec<Path>)> {
+        let polygons: Vec<Vec<Point>> = paths
+            .iter()
+            .map(|path| flatten(path, options.flatten_tolerance))
+            .collect();
+        let (low, high) = polygons
+            .iter()
+            .flatten()
+            .map(|point| self.at(*point))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), t| {
+                (low.min(t), high.max(t))
+            });
+        let (low, high) = (low.max(0.0), high.min(1.0));
+        if polygons.is_empty() || low >= high {
+            let color = stop_color(stops, low.clamp(0.0, 1.0), opacity);
+            return vec![(color, paths.to_vec())];
+        }
+        let pixels = (high - low) / self.dx.hypot(self.dy);
+        let (mut darkest, mut brightest) = ([u8::MAX; 4], [0; 4]);
+        for step in 0..=16 {
+            let color = stop_color(stops, low + (high - low) * step as f32 / 16.0, opacity);
+            for (channel, value) in [color.r, color.g, color.b, color.a].into_iter().enumerate() {
+                darkest[channel] = darkest[channel].min(value);
+                brightest[channel] = brightest[channel].max(value);
+            }
+        }
+        let levels = (0..4)
+            .map(|channel| brightest[channel] - darkest[channel])
+            .max()
+            .unwrap_or(0);
+        let count = (options.gradient_bands as f32)
+            .min((pixels / 2.0).ceil())
+            .min(levels as f32)
+            .max(1.0) as usize;
+
+        let mut bands: Vec<(Color, Vec<Path>)> = Vec::new();
+        for band in 0..count {
+            let from = low + (high - low) * band as f32 / count as f32;
+            let to = low + (high - low) * (band + 1) as f32 / count as f32;
+            let color = stop_color(stops, (from + to) / 2.0, opacity);
+            let clipped: Vec<Path> = polygons
+                .iter()
+                .filter_map(|polygon| {
+                    let polygon = if band > 0 {
+                        self.clip(polygon, from, true)
+                    } else {
+                        polygon.clone()
+                    };
+                    let polygon = if band + 1 < count {
+                        self.clip(&polygon, to, false)
+                    } else {
+                        polygon
+                    };
+                    polygon_path(&polygon)
+                })
+                .collect();
+            match bands.last_mut() {
+                Some((last, paths)) if *last == color => paths.extend(clipped),
+                _ => bands.push((color, clipped)),
+            }
+        }
+        bands
+    }
+
+    // Sutherland-Hodgman against one side of the line t = `at`. A concave polygon may come out
+    // with zero-width bridges along the cut, which add no area.
+    fn clip(&self, polygon: &[Point], at: f32, keep_above: bool) -> Vec<Point> {
+        let inside = |point: &Point| (self.at(*point) >= at) == keep_above;
+        let mut output = Vec::with_capacity(polygon.len() + 2);
+        for (index, current) in polygon.iter().enumerate() {
+            let previous = &polygon[(index + polygon.len() - 1) % polygon.len()];
+            if inside(current) != inside(previous) {
+                let (a, b) = (self.at(*previous), self.at(*current));
+                let mix = (at - a) / (b - a);
+                output.push(Point {
+                    x: previous.x + (current.x - previous.x) * mix,
+                    y: previous.y + (current.y - previous.y) * mix,
+                });
+            }
+            if inside(current) {
+                output.push(*current);
+            }
+        }
+        output
+    }
+}
+
+// Closed subpaths in output coordinates. Open subpaths are closed the way a fill closes them.
+fn contours(data: &tiny_skia_path::Path) -> Vec<Path> {
+    let mut contours = Vec::new();
+    let mut current: Option<(Point, Vec<Segment>)> = None;
+    let mut last = Point::default();
+    let point = |p: tiny_skia_path::Point| Point { x: p.x, y: p.y };
+    for segment in data.segments() {
+        match segment {
+            PathSegment::MoveTo(to) => {
+                close(&mut current, &mut contours);
+                last = point(to);
+                current = Some((last, Vec::new()));
+            }
+            PathSegment::LineTo(to) => {
+                let to = point(to);
+                current
+                    .get_or_insert((last, Vec::new()))
+                    .1
+                    .push(Segment::Line { to });
+                last = to;
+            }
+            PathSegment::QuadTo(control, to) => {
+                let (control, to) = (point(control), point(to));
+                let towards = |from: Point| Point {
+                    x: from.x + (control.x - from.x) * 2.0 / 3.0,
+                    y: from.y + (control.y - from.y) * 2.0 / 3.0,
+                };
+                let segment = Segment::Cubic {
+                    control_1: towards(last),
+                    control_2: towards(to),
+                    to,
+                };
+                current.get_or_insert((last, Vec::new())).1.push(segment);
+                last = to;
+            }
+            PathSegment::CubicTo(control_1, control_2, to) => {
+                let segment = Segment::Cubic {
+                    control_1: point(control_1),
+                    control_2: point(control_2),
+                    to: point(to),
+                };
+                current.get_or_insert((last, Vec::new())).1.push(segment);
+                last = point(to);
+            }
+            PathSegment::Close => {
+                if let Some((start, _)) = &current {
+                    last = *start;
+                }
+                close(&mut current, &mut contours);
+            }
+        }
+    }
+    close(&mut current, &mut contours);
+    contours
+}
+
+fn close(current: &mut Option<(Point, Vec<Segment>)>, contours: &mut Vec<Path>) {
+    let Some((start, mut segments)) = current.take() else {
+        return;
+    };
+    let Some(end) = segments.last().map(Segment::end) else {
+        return;
+    };
+    if (end.x - start.x).abs() > 0.0005 || (end.y - start.y).abs() > 0.0005 {
+        segments.push(Segment::Line { to: start });
+    } else if let Some(Segment::Line { to } | Segment::Cubic { to, .. }) = segments.last_mut() {
+        *to = start;
+    }
+    contours.push(Path {
+        start,
+        segments,
+        hole: false,
+        area: 0.0,
+    });
+}
+
+// Rewinds contours so the nonzero rule the model uses fills what the SVG's rule filled: outer
+// contours run with positive area and holes against them. Even-odd holes are found by how many
+// other contours enclose them; nonzero paths keep their windings, flipped as a whole if they run
+// backwards so overlapping layers of one color still add up.
+fn oriented_paths(contours: Vec<Path>, even_odd: bool, tolerance: f32) -> Vec<Path> {
+    let polygons: Vec<Vec<Point>> = contours
+        .iter()
+        .map(|path| flatten(path, tolerance))
+        .collect();
+    let areas: Vec<f64> = polygons
+        .iter()
+        .map(|polygon| signed_area(polygon))
+        .collect();
+    let backwards = areas.iter().sum::<f64>() < 0.0;
+    contours
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| areas[*index].abs() > MIN_PATH_AREA)
+        .map(|(index, path)| {
+            let hole = if even_odd {
+                let probe = polygons[index][0];
+                let depth = polygons
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, polygon)| *other != index && contains(polygon, probe))
+                    .count();
+                depth % 2 == 1
+            } else {
+                (areas[index] < 0.0) != backwards
+            };
+            let path = if (areas[index] < 0.0) != hole {
+                reversed(path)
+            } else {
+                path
+            };
+            Path {
+                hole,
+                area: areas[index].abs(),
+                ..path
+            }
+        })
+        .collect()
+}
+
+fn reversed(path: Path) -> Path {
+    let mut points = vec![path.start];
+    points.extend(path.segments.iter().map(Segment::end));
+    let segments = path
+        .segments
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(index, segment)| {
+            let to = points[index];
+            match segment {
+                Segment::Line { .. } => Segment::Line { to },
+                Segment::Cubic {
+                    control_1,
+                    control_2,
+                    ..
+                } => Segment::Cubic {
+                    control_1: *control_2,
+                    control_2: *control_1,
+                    to,
+                },
+            }
+        })
+        .collect();
+    Path {
+        start: path.start,
+        segments,
+        ..path
+    }
+}
+
+// The contour as a polygon, each cubic cut into enough lines to stay within `tolerance`.
+fn flatten(path: &Path, tolerance: f32) -> Vec<Point> {
+    let mut points = vec![path.start];
+    for segment in &path.segments {
+        let from = *points.last().unwrap();
+        match segment {
+            Segment::Line { to } => points.push(*to),
+            Segment::Cubic {
+                control_1: c1,
+                control_2: c2,
+                to,
+            } => {
+                let second_difference = |a: Point, b: Point, c: Point| {
+                    (a.x - 2.0 * b.x + c.x).hypot(a.y - 2.0 * b.y + c.y)
+                };
+                let bend = second_difference(from, *c1, *c2).max(second_difference(*c1, *c2, *to));
+                let steps = (0.75 * bend / tolerance).sqrt().ceil().clamp(1.0, 64.0) as usize;
+                for step in 1..=steps {
+                    let t = step as f32 / steps as f32;
+                    let u = 1.0 - t;
+                    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                    points.push(Point {
+                        x: a * from.x + b * c1.x + c * c2.x + d * to.x,
+                        y: a * from.y + b * c1.y + c * c2.y + d * to.y,
+                    });
+                }
+            }
+        }
+    }
+    points.pop();
+    points
+}
+
+// Positive for contours that run clockwise on screen (y down).
+fn signed_area(polygon: &[Point]) -> f64 {
+    let mut twice_area = 0.0;
+    for (index, a) in polygon.iter().enumerate() {
+        let b = polygon[(index + 1) % polygon.len()];
+        twice_area += a.x as f64 * b.y as f64 - b.x as f64 * a.y as f64;
+    }
+    twice_area * 0.5
+}
+
+fn contains(polygon: &[Point], point: Point) -> bool {
+    let mut inside = false;
+    for (index, a) in polygon.iter().enumerate() {
+        let b = polygon[(index + 1) % polygon.len()];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (b.x - a.x) * (point.y - a.y) / (b.y - a.y)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// A clipped band polygon as a line-only path, or `None` if nothing of it is left.
+fn polygon_path(polygon: &[Point]) -> Option<Path> {
+    let area = signed_area(polygon);
+    if polygon.len() < 3 || area.abs() <= MIN_PATH_AREA {
+        return None;
+    }
+    let mut segments: Vec<Segment> = polygon[1..]
+        .iter()
+        .map(|to| Segment::Line { to: *to })
+        .collect();
+    segments.push(Segment::Line { to: polygon[0] });
+    Some(Path {
+        start: polygon[0],
+        segments,
+        hole: area < 0.0,
+        area: area.abs(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(svg: &str) -> Trace {
+        let trace = import_svg(svg.as_bytes(), &SvgImportOptions::default()).unwrap();
+        trace.validate().unwrap();
+        trace
+    }
+
+    #[test]
+    fn shapes_keep_their_curves_paint_order_and_even_odd_holes() {
+        let trace = import(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 50 25" width="100" height="50">
+                <g opacity="0.5"><rect width="50" height="25" fill="#336699"/></g>
+                <path fill-rule="evenodd" fill="#fff" d="M5 5h10v10H5zM8 8h4v4H8z"/>
+                <circle cx="35" cy="12" r="5" fill="#fff" stroke="#000" stroke-width="2"/>
+            </svg>"##,
+        );
+
+        assert_eq!((trace.width, trace.height), (100, 50));
+        let colors: Vec<Color> = trace
+            .layers
+            .iter()
+            .map(|layer| trace.color(layer).unwrap())
+            .collect();
+        assert_eq!(
+            colors[0],
+            Color {
+                r: 0x33,
+                g: 0x66,
+                b: 0x99,
+                a: 128
+            }
+        );
+        assert_eq!(
+            trace.layers.len(),
+            3,
+            "white square and circle share a layer"
+        );
+        assert_eq!(
+            colors[2],
+            Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 255
+            }
+        );
+
+        let white = &trace.layers[1];
+        let holes: Vec<&Path> = white.paths.iter().filter(|path| path.hole).collect();
+        assert_eq!(holes.len(), 1);
+        assert!(
+            (holes[0].area - 64.0).abs() < 0.01,
+            "viewBox scale applies to areas"
+        );
+        assert!(white.paths.iter().any(|path| {
+            path.segments
+                .iter()
+                .any(|segment| matches!(segment, Segment::Cubic { .. }))
+        }));
+    }
+
+    #[test]
+    fn linear_gradients_become_flat_bands_and_patterns_are_refused() {
+        let trace = import(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="16">
+                <linearGradient id="ramp"><stop offset="0" stop-color="#000"/><stop offset="1" stop-color="#fff"/></linearGradient>
+                <rect width="64" height="16" fill="url(#ramp)"/>
+            </svg>"##,
+        );
+
+        assert_eq!(trace.layers.len(), 32);
+        let reds: Vec<u8> = trace
+            .layers
+            .iter()
+            .map(|layer| trace.color(layer).unwrap().r)
+            .collect();
+        assert!(reds.windows(2).all(|pair| pair[0] < pair[1]), "{reds:?}");
+        let area: f64 = trace
+            .layers
+            .iter()
+            .flat_map(|layer| &layer.paths)
+            .map(|path| path.area)
+            .sum();
+        assert!((area - 1024.0).abs() < 0.01);
+
+        let pattern = r##"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="8">
+            <pattern id="dots" width="2" height="2" patternUnits="userSpaceOnUse"><rect width="1" height="1"/></pattern>
+            <rect width="8" height="8" fill="url(#dots)"/>
+        </svg>"##;
+        assert!(import_svg(pattern.as_bytes(), &SvgImportOptions::default()).is_err());
+    }
+}
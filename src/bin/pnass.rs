@@ -1,8 +1,19 @@
 use clap::Parser;
-use pandora_toolchain::libkagami::core::{PandoraMeta, ScriptInfo, SubstationAlpha};
+use pandora_toolchain::libkagami::core::{PandoraMeta, ResampleMode, ScriptInfo, SubstationAlpha};
 use pandora_toolchain::libkagami::complex::overrides::ASSOverride;
 use pandora_toolchain::libkagami::complex::types::AssTime;
+use pandora_toolchain::libkagami::drawing::geometry::{clip_mask, Affine, AssTransform};
+use pandora_toolchain::libkagami::lint::{lint, LintOptions};
+use pandora_toolchain::libkagami::motion::{apply_motion, parse_motion};
+use pandora_toolchain::libkagami::spellcheck::{spellcheck, Dictionary, Glossary, DICTIONARIES_DIR};
+use pandora_toolchain::libkagami::styles::apply_styles;
 use pandora_toolchain::libkagami::tags::{ASSLine, ASSText};
+use pandora_toolchain::libkagami::templater::apply_templates;
+use pandora_toolchain::libkagami::timing::{
+    apply_sync_map, parse_offset, parse_sync_map, retime, shift, shift_frames, EventFilter, FrameTimes, TimeOffset,
+};
+use pandora_toolchain::libkagami::tpp::{apply_tpp, parse_keyframes, TppOptions};
+use pandora_toolchain::libkagami::textsubs::{to_srt, to_webvtt, TextExportOptions};
 use pandora_toolchain::lib::protocol::core::{Protocol, Schema, ToolInfo};
 use pandora_toolchain::lib::logging::tool::ToolLog;
 use pandora_toolchain::{pn_data, pn_emit, pn_schema};
@@ -20,7 +31,7 @@ struct Args {
     input: String,
 
     #[arg(long)]
-    output: String,
+    output: Option<String>,
 
     #[arg(long)]
     merge: Option<String>,
@@ -31,6 +42,33 @@ struct Args {
     #[arg(long)]
     duration_centiseconds: Option<u64>,
 
+    #[arg(long)]
+    resample: Option<String>,
+
+    #[arg(long)]
+    apply_styles: Option<String>,
+
+    #[arg(long)]
+    add_styles: Option<String>,
+
+    #[arg(long)]
+    motion: Option<String>,
+
+    #[arg(long)]
+    motion_lines: Option<String>,
+
+    #[arg(long)]
+    motion_origin: Option<usize>,
+
+    #[arg(long)]
+    karaoke_templater: bool,
+
+    #[arg(long = "fonts-dir")]
+    fonts_dir: Vec<String>,
+
+    #[arg(long)]
+    bake_clips: bool,
+
     #[arg(long)]
     set_layer: Option<u16>,
 
@@ -49,6 +87,73 @@ struct Args {
     #[arg(long)]
     no_adv_parsing: bool,
 
+    #[arg(long)]
+    lint: bool,
+
+    #[arg(long)]
+    max_cps: Option<f32>,
+
+    #[arg(long)]
+    spellcheck: Option<String>,
+
+    #[arg(long, default_value = DICTIONARIES_DIR)]
+    dictionaries: String,
+
+    #[arg(long)]
+    glossary: Option<String>,
+
+    // Offsets such as `-250ms` start with a hyphen; PNASS_RETIME passes the value as its own arg.
+    #[arg(long, allow_hyphen_values = true)]
+    shift: Option<String>,
+
+    #[arg(long)]
+    retime: Option<String>,
+
+    #[arg(long)]
+    fps: Option<f64>,
+
+    #[arg(long)]
+    timecodes: Option<String>,
+
+    #[arg(long)]
+    sync_map: Option<String>,
+
+    #[arg(long)]
+    tpp: bool,
+
+    #[arg(long)]
+    keyframes: Option<String>,
+
+    #[arg(long)]
+    lead_in: Option<i64>,
+
+    #[arg(long)]
+    lead_out: Option<i64>,
+
+    #[arg(long)]
+    link_gap: Option<i64>,
+
+    #[arg(long)]
+    link_bias: Option<f64>,
+
+    #[arg(long)]
+    kf_start: Option<String>,
+
+    #[arg(long)]
+    kf_end: Option<String>,
+
+    #[arg(long)]
+    only_styles: Option<String>,
+
+    #[arg(long)]
+    only_layers: Option<String>,
+
+    #[arg(long)]
+    export: Option<String>,
+
+    #[arg(long)]
+    merge_simultaneous: bool,
+
     #[arg(long)]
     negkey: Option<String>,
 
@@ -67,8 +172,8 @@ async fn main() {
     let args = Args::parse();
     let mut log = ToolLog::open(args.logfile.as_deref());
     log.line(&format!(
-        "start input={} output={} merge={:?} inject={:?} duration_cs={:?}",
-        args.input, args.output, args.merge, args.inject, args.duration_centiseconds
+        "start input={} output={:?} merge={:?} inject={:?} duration_cs={:?} lint={}",
+        args.input, args.output, args.merge, args.inject, args.duration_centiseconds, args.lint
     ));
 
     let mut proto = Protocol::new(vec![1]);
@@ -83,20 +188,175 @@ async fn main() {
     );
 
     let wrap_style = parse_wrap_style_arg(args.wrap_style.as_deref());
-    let adv_parsing = !args.no_adv_parsing;
+    let resample = match args.resample.as_deref().filter(|v| !v.trim().is_empty()).map(str::parse::<ResampleMode>) {
+        Some(Err(e)) => {
+            eprintln!("pnass: --resample: {}", e);
+            std::process::exit(1);
+        }
+        Some(Ok(mode)) => Some(mode),
+        None => None,
+    };
+    if args.export.as_deref().is_some_and(|format| !matches!(format, "srt" | "vtt" | "json")) {
+        eprintln!("pnass: --export must be srt, vtt or json");
+        std::process::exit(1);
+    }
+    if args.max_cps.is_some_and(|cps| !(1.0..=100.0).contains(&cps)) {
+        eprintln!("pnass: --max-cps must be between 1 and 100");
+        std::process::exit(1);
+    }
+    // Empty values come from tool specs that always pass the flag; they mean "not set".
+    let shift_arg = args.shift.clone().filter(|v| !v.trim().is_empty());
+    let retime_arg = args.retime.clone().filter(|v| !v.trim().is_empty());
+    let timing = shift_arg.is_some()
+        || retime_arg.is_some()
+        || args.tpp
+        || args.sync_map.as_deref().is_some_and(|v| !v.is_empty());
+    let motion = args.motion.clone().filter(|path| !path.is_empty());
+    // The lint rules read parsed override tags, retiming rescales them, the templater splits
+    // syllables at them, motion tracking rewrites them, clip baking cuts parsed drawings and the
+    // JSON export writes them out structured, so these always parse.
+    let adv_parsing = !args.no_adv_parsing
+        || args.lint
+        || args.export.as_deref() == Some("json")
+        || timing
+        || motion.is_some()
+        || args.karaoke_templater
+        || args.bake_clips;
     log.line(&format!("loading input (adv_parsing={})", adv_parsing));
-    let mut sub = SubstationAlpha::load(PathBuf::from(&args.input), adv_parsing).await;
+    let mut sub = load_or_exit(&args.input, adv_parsing, &proto, &neg, &mut log).await;
     log.line(&format!(
         "input loaded: {} events, {} styles",
         sub.events.len(),
         sub.v4p_styles.len()
     ));
 
-    if let Some(t) = args.title {
+    // Before anything splits or merges lines, so event numbers match the TL file.
+    let spellcheck_lang = args.spellcheck.clone().filter(|v| !v.trim().is_empty());
+    let glossary_path = args.glossary.clone().filter(|v| !v.trim().is_empty());
+    if spellcheck_lang.is_some() || glossary_path.is_some() {
+        run_spellcheck(&sub, spellcheck_lang.as_deref(), &args.dictionaries, glossary_path.as_deref(), &proto, &neg, &mut log);
+    }
+
+    if args.lint {
+        let options = LintOptions {
+            max_cps: args.max_cps.unwrap_or(LintOptions::default().max_cps),
+        };
+        let findings = lint(&sub, &options);
+        log.line(&format!("lint: {} findings", findings.len()));
+        for finding in findings {
+            let severity = finding.severity().as_str();
+            let code = finding.rule.code();
+            let event = finding.event.map(|n| n.to_string()).unwrap_or_default();
+            let message = finding.message;
+            println!("{}", pn_emit!(protocol = proto, negkey = &neg,
+                schema = [leaf, [leaf, leaf, leaf, leaf]],
+                data = ["4", [severity, code, event, message]]).unwrap());
+        }
+        log.line("done");
+        return;
+    }
+    let Some(output) = args.output.clone() else {
+        log.line("aborting: --output is required unless --lint is set");
+        eprintln!("pnass: --output is required unless --lint is set");
+        std::process::exit(1);
+    };
+
+    if let Some(t) = args.title.clone() {
         sub.script_info.title = t;
     }
     fill_script_info_defaults(&mut sub.script_info, wrap_style);
 
+    if timing
+        && let Err(e) = apply_timing(&mut sub, &args, shift_arg.as_deref(), retime_arg.as_deref(), &mut log).await
+    {
+        log.line(&format!("timing failed: {}", e));
+        println!("{}", pn_emit!(protocol = proto, negkey = &neg,
+            schema = [leaf, leaf], data = ["4", e]).unwrap());
+        std::process::exit(1);
+    }
+
+    if let Some(library_path) = args.apply_styles.as_deref().filter(|path| !path.is_empty()) {
+        log.line(&format!("loading style library {}", library_path));
+        let library = load_or_exit(library_path, false, &proto, &neg, &mut log).await;
+        let add = split_list(args.add_styles.as_deref());
+        match apply_styles(&mut sub, library, &add, resample) {
+            Ok(changes) => {
+                log.line(&format!("styles: {:?}", changes));
+                let mut notes: Vec<String> = changes.not_found.iter()
+                    .map(|name| format!("style library has no `{}`", name))
+                    .collect();
+                if !changes.is_empty() {
+                    notes.insert(0, format!("styles: {}", changes.summary()));
+                }
+                for note in notes {
+                    println!("{}", pn_emit!(protocol = proto, negkey = &neg,
+                        schema = [leaf, leaf], data = ["4", note]).unwrap());
+                }
+            }
+            Err(e) => {
+                log.line(&format!("apply styles failed: {}", e));
+                println!("{}", pn_emit!(protocol = proto, negkey = &neg,
+                    schema = [leaf, leaf], data = ["4", e]).unwrap());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(motion_path) = motion.as_deref() {
+        match apply_motion_file(&mut sub, &args, motion_path).await {
+            Ok(note) => {
+                log.line(&note);
+                println!("{}", pn_emit!(protocol = proto, negkey = &neg,
+                    schema = [leaf, leaf], data = ["4", note]).unwrap());
+            }
+            Err(e) => {
+                log.line(&format!("motion failed: {}", e));
+                println!("{}", pn_emit!(protocol = proto, negkey = &neg,
+                    schema = [leaf, leaf], data = ["4", e]).unwrap());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if args.karaoke_templater {
+        let roots: Vec<PathBuf> = args.fonts_dir.iter().map(PathBuf::from).collect();
+        match apply_templates(&mut sub, &roots) {
+            Ok(outcome) => {
+                log.line(&format!("templater: {}", outcome.summary()));
+                for note in std::iter::once(outcome.summary()).chain(outcome.warnings) {
+                    println!("{}", pn_emit!(protocol = proto, negkey = &neg,
+                        schema = [leaf, leaf], data = ["4", note]).unwrap());
+                }
+            }
+            Err(e) => {
+                log.line(&format!("templater failed: {}", e));
+                println!("{}", pn_emit!(protocol = proto, negkey = &neg,
+                    schema = [leaf, leaf], data = ["4", e]).unwrap());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if args.bake_clips {
+        let report = bake_clips(&mut sub);
+        let mut notes = vec![format!(
+            "bake-clips: {} lines cut, {} left empty and removed",
+            report.cut, report.removed
+        )];
+        if !report.skipped.is_empty() {
+            let lines: Vec<String> = report.skipped.iter().map(usize::to_string).collect();
+            notes.push(format!(
+                "bake-clips: clip kept on lines {} (needs \\an7 and \\pos, no \\t, \\move, \\frx/\\fry or style angle)",
+                lines.join(", ")
+            ));
+        }
+        for note in notes {
+            log.line(&note);
+            println!("{}", pn_emit!(protocol = proto, negkey = &neg,
+                schema = [leaf, leaf], data = ["4", note]).unwrap());
+        }
+    }
+
     if let Some(n) = args.set_layer {
         for ev in &mut sub.events {
             ev.layer = n;
@@ -126,7 +386,7 @@ async fn main() {
     }
     if let Some(merge_path) = args.merge.as_deref().or(args.inject.as_deref()) {
         log.line(&format!("loading secondary {}", merge_path));
-        let mut secondary = SubstationAlpha::load(PathBuf::from(merge_path), adv_parsing).await;
+        let mut secondary = load_or_exit(merge_path, adv_parsing, &proto, &neg, &mut log).await;
         log.line(&format!("secondary loaded: {} events", secondary.events.len()));
         fill_script_info_defaults(&mut secondary.script_info, wrap_style);
         if args.inject.is_some() {
@@ -141,7 +401,7 @@ async fn main() {
             apply_injection_timings(&mut secondary, duration);
             log.line(&format!("injection timings applied for {}cs", duration));
         }
-        if let Err(e) = normalize_merge_resolutions(&mut sub, &mut secondary) {
+        if let Err(e) = normalize_merge_resolutions(&mut sub, &mut secondary, resample) {
             log.line(&format!("resolution normalisation failed: {}", e));
             println!("{}", pn_emit!(protocol = proto, negkey = &neg,
                 schema = [leaf, leaf], data = ["4", e]).unwrap());
@@ -200,15 +460,257 @@ async fn main() {
         std::process::exit(1);
     }
 
-    log.line(&format!("writing {} events to {}", sub.events.len(), args.output));
-    if sub.dump_to_file(PathBuf::from(&args.output)).await.is_err() {
+    log.line(&format!("writing {} events to {}", sub.events.len(), output));
+    if let Some(format) = args.export.as_deref() {
+        let options = TextExportOptions { merge_simultaneous: args.merge_simultaneous };
+        let text = match format {
+            "srt" => to_srt(&sub, &options),
+            "vtt" => to_webvtt(&sub, &options),
+            _ => match sub.to_json().and_then(|doc| serde_json::to_string_pretty(&doc).map_err(|e| e.to_string())) {
+                Ok(text) => text,
+                Err(e) => {
+                    log.line(&format!("json export failed: {}", e));
+                    eprintln!("pnass: {}", e);
+                    std::process::exit(1);
+                }
+            },
+        };
+        if tokio::fs::write(&output, text).await.is_err() {
+            log.line("write failed");
+            eprintln!("pnass: failed to write {}", output);
+            std::process::exit(1);
+        }
+        log.line("done");
+        return;
+    }
+    if sub.dump_to_file(PathBuf::from(&output)).await.is_err() {
         log.line("write failed");
-        eprintln!("pnass: failed to write {}", args.output);
+        eprintln!("pnass: failed to write {}", output);
         std::process::exit(1);
     }
     log.line("done");
 }
 
+// Load diagnostics go out as opcode 4 before anything else runs, prefixed with the file name so a
+// merge can tell TL and TS apart; a file that cannot be read at all is reported the same way and
+// ends the run instead of panicking.
+async fn load_or_exit(path: &str, adv_parsing: bool, proto: &Protocol, neg: &str, log: &mut ToolLog) -> SubstationAlpha {
+    let label = std::path::Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string());
+    match SubstationAlpha::load_with_diagnostics(PathBuf::from(path), adv_parsing).await {
+        Ok((sub, diagnostics)) => {
+            for diagnostic in diagnostics {
+                let warning = format!("{} {}", label, diagnostic);
+                log.line(&format!("diagnostic: {}", warning));
+                println!("{}", pn_emit!(protocol = proto, negkey = neg,
+                    schema = [leaf, leaf], data = ["4", warning]).unwrap());
+            }
+            sub
+        }
+        Err(e) => {
+            let message = format!("{}: {}", label, e);
+            log.line(&format!("load failed: {}", message));
+            println!("{}", pn_emit!(protocol = proto, negkey = neg,
+                schema = [leaf, leaf], data = ["4", message]).unwrap());
+            std::process::exit(1);
+        }
+    }
+}
+
+// A missing dictionary or glossary is a warning, not a failure: the other check still runs.
+fn run_spellcheck(
+    sub: &SubstationAlpha,
+    language: Option<&str>,
+    dictionaries: &str,
+    glossary_path: Option<&str>,
+    proto: &Protocol,
+    neg: &str,
+    log: &mut ToolLog,
+) {
+    let mut problems = Vec::new();
+    let dictionary = language.and_then(|language| match Dictionary::load(std::path::Path::new(dictionaries), language) {
+        Ok(dictionary) => {
+            log.line(&format!("spellcheck: {} loaded, {} words", language, dictionary.word_count()));
+            Some(dictionary)
+        }
+        Err(e) => {
+            problems.push(format!("spellcheck: no {} dictionary ({})", language, e));
+            None
+        }
+    });
+    let glossary = match glossary_path.map(std::fs::read_to_string) {
+        Some(Ok(text)) => Glossary::parse(&text),
+        Some(Err(e)) => {
+            problems.push(format!("glossary: cannot read {} ({})", glossary_path.unwrap_or_default(), e));
+            Glossary::default()
+        }
+        None => Glossary::default(),
+    };
+    let report = spellcheck(sub, dictionary.as_ref(), &glossary);
+    log.line(&format!("spellcheck: {} glossary entries, {} lines flagged", glossary.len(), report.len()));
+    for warning in problems.into_iter().chain(report.iter().map(|line| line.to_string())) {
+        println!("{}", pn_emit!(protocol = proto, negkey = neg,
+            schema = [leaf, leaf], data = ["4", warning]).unwrap());
+    }
+}
+
+// --sync-map runs first, then --retime, then --shift, so an offset measured against the target
+// video applies as-is. --tpp runs last: its keyframes belong to the video the output is for.
+async fn apply_timing(
+    sub: &mut SubstationAlpha,
+    args: &Args,
+    shift_arg: Option<&str>,
+    retime_arg: Option<&str>,
+    log: &mut ToolLog,
+) -> Result<(), String> {
+    let filter = EventFilter {
+        styles: split_list(args.only_styles.as_deref()),
+        layers: split_list(args.only_layers.as_deref())
+            .iter()
+            .map(|layer| layer.parse::<u16>().map_err(|_| format!("--only-layers: `{}` is not a layer number", layer)))
+            .collect::<Result<_, _>>()?,
+    };
+    let timecodes = match args.timecodes.as_deref().filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let text = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| format!("failed to read timecodes {}: {}", path, e))?;
+            Some(FrameTimes::parse_timecodes(&text)?)
+        }
+        None => None,
+    };
+    if let Some(path) = args.sync_map.as_deref().filter(|path| !path.is_empty()) {
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| format!("failed to read sync map {}: {}", path, e))?;
+        let segments = parse_sync_map(&text)?;
+        let changed = apply_sync_map(sub, &segments, &filter);
+        log.line(&format!("sync map {}: {} segments, {} events", path, segments.len(), changed));
+    }
+    if let Some(spec) = retime_arg {
+        let (from, to) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("--retime expects FROM:TO frame rates, got `{}`", spec))?;
+        let parse_fps = |value: &str| {
+            value.trim().parse::<f64>()
+                .map_err(|_| format!("--retime: `{}` is not a frame rate", value))
+                .and_then(FrameTimes::constant)
+        };
+        let from = parse_fps(from)?;
+        let to = if to.trim() == "timecodes" {
+            timecodes.clone().ok_or("--retime FROM:timecodes needs --timecodes")?
+        } else {
+            parse_fps(to)?
+        };
+        let changed = retime(sub, &from, &to, &filter);
+        log.line(&format!("retime {}: {} events", spec, changed));
+    }
+    if let Some(offset) = shift_arg {
+        let changed = match parse_offset(offset)? {
+            TimeOffset::Ms(ms) => shift(sub, ms, &filter),
+            TimeOffset::Frames(frames) => {
+                let times = match (&timecodes, args.fps) {
+                    (Some(times), _) => times.clone(),
+                    (None, Some(fps)) => FrameTimes::constant(fps)?,
+                    (None, None) => return Err("frame offsets need --fps or --timecodes".to_string()),
+                };
+                shift_frames(sub, frames, &times, &filter)
+            }
+        };
+        log.line(&format!("shift {}: {} events", offset, changed));
+    }
+    if args.tpp {
+        let defaults = TppOptions::default();
+        let options = TppOptions {
+            lead_in_ms: args.lead_in.unwrap_or(defaults.lead_in_ms),
+            lead_out_ms: args.lead_out.unwrap_or(defaults.lead_out_ms),
+            link_gap_ms: args.link_gap.unwrap_or(defaults.link_gap_ms),
+            link_bias: args.link_bias.unwrap_or(defaults.link_bias),
+            snap_start: parse_window("--kf-start", args.kf_start.as_deref())?.unwrap_or(defaults.snap_start),
+            snap_end: parse_window("--kf-end", args.kf_end.as_deref())?.unwrap_or(defaults.snap_end),
+            skip_signs: defaults.skip_signs,
+        };
+        let keyframes = match args.keyframes.as_deref().filter(|path| !path.is_empty()) {
+            Some(path) => {
+                let text = tokio::fs::read_to_string(path)
+                    .await
+                    .map_err(|e| format!("failed to read keyframes {}: {}", path, e))?;
+                Some(parse_keyframes(&text)?)
+            }
+            None => None,
+        };
+        // Keyframe numbers count frames, so snapping needs the video's frame timing: explicit
+        // flags win over the rate the keyframes file was written with.
+        let times = match (&timecodes, args.fps, keyframes.as_ref().and_then(|k| k.fps)) {
+            (Some(times), _, _) => Some(times.clone()),
+            (None, Some(fps), _) | (None, None, Some(fps)) => Some(FrameTimes::constant(fps)?),
+            (None, None, None) if keyframes.is_some() => {
+                return Err("--keyframes without a frame rate needs --fps or --timecodes".to_string());
+            }
+            (None, None, None) => None,
+        };
+        let snap = keyframes.as_ref().zip(times.as_ref()).map(|(k, times)| (k.frames.as_slice(), times));
+        let report = apply_tpp(sub, &options, snap, &filter)?;
+        log.line(&format!(
+            "tpp: {} leads, {} linked, {} snapped",
+            report.lead, report.linked, report.snapped
+        ));
+    }
+    Ok(())
+}
+
+// Keyframe windows are `BEFORE,AFTER` in frames; empty means the default.
+fn parse_window(flag: &str, value: Option<&str>) -> Result<Option<(i64, i64)>, String> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let parsed = value
+        .split_once(',')
+        .and_then(|(before, after)| Some((before.trim().parse::<i64>().ok()?, after.trim().parse::<i64>().ok()?)));
+    match parsed {
+        Some(window) => Ok(Some(window)),
+        None => Err(format!("{} expects BEFORE,AFTER frame counts, got `{}`", flag, value)),
+    }
+}
+
+// Frame timing comes from --timecodes, then --fps, then the tracking data's own Units Per Second.
+async fn apply_motion_file(sub: &mut SubstationAlpha, args: &Args, path: &str) -> Result<String, String> {
+    let text = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("failed to read motion data {}: {}", path, e))?;
+    let data = parse_motion(&text)?;
+    let times = match (args.timecodes.as_deref().filter(|path| !path.is_empty()), args.fps, data.fps) {
+        (Some(timecodes), _, _) => {
+            let text = tokio::fs::read_to_string(timecodes)
+                .await
+                .map_err(|e| format!("failed to read timecodes {}: {}", timecodes, e))?;
+            FrameTimes::parse_timecodes(&text)?
+        }
+        (None, Some(fps), _) | (None, None, Some(fps)) => FrameTimes::constant(fps)?,
+        (None, None, None) => return Err("--motion data without a frame rate needs --fps or --timecodes".to_string()),
+    };
+    let lines = split_list(args.motion_lines.as_deref())
+        .iter()
+        .map(|line| line.parse::<usize>().map_err(|_| format!("--motion-lines: `{}` is not a line number", line)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if lines.is_empty() {
+        return Err("--motion needs --motion-lines".to_string());
+    }
+    let outcome = apply_motion(sub, &lines, &data, &times, args.motion_origin.unwrap_or(1))?;
+    Ok(format!("motion: {} lines tracked over {} frames into {} lines", outcome.lines, data.frames(), outcome.events))
+}
+
+fn split_list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or("")
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
 fn visible_lines(line: &ASSLine) -> Vec<String> {
     let mut lines: Vec<String> = Vec::new();
     let mut current = String::new();
@@ -330,7 +832,14 @@ fn apply_injection_timings(sub: &mut SubstationAlpha, duration: u64) {
     }
 }
 
-fn normalize_merge_resolutions(primary: &mut SubstationAlpha, secondary: &mut SubstationAlpha) -> Result<(), String> {
+// Same-ratio scripts meet at the larger resolution. A different ratio needs an explicit
+// `--resample` mode, and then the secondary is fitted into the input's frame: the input is the
+// script timed against the release, so its PlayRes is the one that matches the video.
+fn normalize_merge_resolutions(
+    primary: &mut SubstationAlpha,
+    secondary: &mut SubstationAlpha,
+    resample: Option<ResampleMode>,
+) -> Result<(), String> {
     let px = primary.script_info.playresx;
     let py = primary.script_info.playresy;
     let sx = secondary.script_info.playresx;
@@ -340,10 +849,13 @@ fn normalize_merge_resolutions(primary: &mut SubstationAlpha, secondary: &mut Su
         return Ok(());
     }
     if px as u32 * sy as u32 != py as u32 * sx as u32 {
-        return Err(format!(
-            "ASS merge rejected: incompatible PlayRes ratios (input {}x{}, merge {}x{})",
-            px, py, sx, sy
-        ));
+        let Some(mode) = resample else {
+            return Err(format!(
+                "ASS merge rejected: incompatible PlayRes ratios (input {}x{}, merge {}x{}); pick a resample mode",
+                px, py, sx, sy
+            ));
+        };
+        return secondary.resample(px, py, mode);
     }
 
     let primary_area = px as u32 * py as u32;
@@ -425,6 +937,106 @@ fn rename_style_refs_in_override(ov: &mut ASSOverride, mapping: &std::collection
     }
 }
 
+struct ClipBake {
+    cut: usize,
+    removed: usize,
+    skipped: Vec<usize>,
+}
+
+// Cuts every `\an7` drawing line that has a `\clip` (or `\iclip`) down to what the clip shows and
+// drops the tag. `\frz`, `\fax`/`\fay` and `\org` are baked into the shape on the way, so the
+// shadow no longer turns with it; lines that move or use perspective are left alone.
+fn bake_clips(sub: &mut SubstationAlpha) -> ClipBake {
+    let mut report = ClipBake { cut: 0, removed: 0, skipped: Vec::new() };
+    let mut kept = Vec::with_capacity(sub.events.len());
+    for (i, mut ev) in std::mem::take(&mut sub.events).into_iter().enumerate() {
+        let style = sub.v4p_styles.iter().find(|style| style.name == ev.style);
+        match bake_event_clip(&mut ev, style) {
+            None => {}
+            Some(Ok(true)) => report.cut += 1,
+            Some(Ok(false)) => {
+                report.removed += 1;
+                continue;
+            }
+            Some(Err(())) => report.skipped.push(i + 1),
+        }
+        kept.push(ev);
+    }
+    sub.events = kept;
+    report
+}
+
+// None for lines without a clip or a drawing; Ok(false) when nothing of the drawing is left.
+fn bake_event_clip(
+    ev: &mut pandora_toolchain::libkagami::core::Event,
+    style: Option<&pandora_toolchain::libkagami::core::V4pStyle>,
+) -> Option<Result<bool, ()>> {
+    let overrides: Vec<&ASSOverride> = ev.text.data.iter()
+        .filter_map(|item| if let ASSText::Override(ov) = item { Some(ov) } else { None })
+        .collect();
+    let (mask, inverse) = overrides.iter().rev().find_map(|ov| clip_mask(ov))?;
+    if !ev.text.data.iter().any(|item| matches!(item, ASSText::Drawing(_))) {
+        return None;
+    }
+    let moves = overrides.iter().any(|ov| match ov {
+        ASSOverride::TransformI(_) | ASSOverride::TransformII(..) | ASSOverride::TransformIII(..)
+        | ASSOverride::TransformIV(..) | ASSOverride::MoveI(..) | ASSOverride::MoveII(..) => true,
+        ASSOverride::Frx(v) | ASSOverride::Fry(v) => *v != 0.0,
+        _ => false,
+    });
+    let align = overrides.iter().rev()
+        .find_map(|ov| if let ASSOverride::An(a) = ov { Some(*a) } else { None })
+        .or(style.map(|s| s.alignment));
+    let pos = overrides.iter().rev()
+        .find_map(|ov| if let ASSOverride::Pos(x, y) = ov { Some((*x, *y)) } else { None });
+    let angled = style.is_some_and(|s| s.angle != 0.0);
+    let (Some(pos), Some(7), false, false) = (pos, align, moves, angled) else {
+        return Some(Err(()));
+    };
+
+    let mut scale = style.map(|s| (s.scale_x as f32, s.scale_y as f32)).unwrap_or((100.0, 100.0));
+    let mut units = 1.0f32;
+    for ov in &overrides {
+        match ov {
+            ASSOverride::Fscx(v) => scale.0 = *v,
+            ASSOverride::Fscy(v) => scale.1 = *v,
+            ASSOverride::Fsc(v) => scale = (*v, *v),
+            ASSOverride::P(n) if *n > 0 => units = 2f32.powi(*n as i32 - 1),
+            _ => {}
+        }
+    }
+    let (sx, sy) = (scale.0 as f64 / 100.0 / units as f64, scale.1 as f64 / 100.0 / units as f64);
+    if sx == 0.0 || sy == 0.0 {
+        return Some(Err(()));
+    }
+    let placement = AssTransform::from_overrides(overrides.iter().copied(), pos);
+    // libass order: \fscx/\fscy and the \pN scale first, then \pos, then the shear and rotation.
+    // `back` only undoes the first two, since \fscx/\fscy stay on the line and \frz does not.
+    let to_screen = Affine::scale(sx, sy)
+        .then(Affine::translate(pos.0 as f64, pos.1 as f64))
+        .then(placement.affine());
+    let back = Affine::translate(-pos.0 as f64, -pos.1 as f64).then(Affine::scale(1.0 / sx, 1.0 / sy));
+
+    let mut visible = false;
+    for item in &mut ev.text.data {
+        if let ASSText::Drawing(drawing) = item {
+            drawing.transform(&to_screen);
+            let mut cut = if inverse { drawing.difference(&mask) } else { drawing.intersection(&mask) };
+            cut.transform(&back);
+            visible |= !cut.commands.is_empty();
+            *drawing = cut;
+        }
+    }
+    let baked = !placement.is_identity();
+    ev.text.data.retain(|item| match item {
+        ASSText::Override(ov) => clip_mask(ov).is_none()
+            && !(baked && matches!(ov,
+                ASSOverride::Frz(_) | ASSOverride::Fr(_) | ASSOverride::Fax(_) | ASSOverride::Fay(_) | ASSOverride::Org(..))),
+        _ => true,
+    });
+    Some(Ok(visible))
+}
+
 fn set_basic_text_layers(sub: &mut SubstationAlpha, layer: u16) {
     for ev in &mut sub.events {
         if event_has_only_basic_overrides(ev) {
@@ -485,12 +1097,22 @@ fn split_sign_events(sub: &mut SubstationAlpha) -> Option<SubstationAlpha> {
     }
     sub.v4p_styles = tl_styles;
 
+    let sign_extradata = signs.iter().flat_map(|ev| ev.extradata.iter().copied()).collect::<std::collections::HashSet<u32>>();
+    let extradata = sub.extradata.iter()
+        .filter(|entry| sign_extradata.contains(&entry.id))
+        .cloned()
+        .collect();
+
     Some(SubstationAlpha {
         script_info: clone_script_info(&sub.script_info),
         v4p_styles: sign_styles,
         events: signs,
         comments: Vec::new(),
         pandora_meta: PandoraMeta::default(),
+        fonts: sub.fonts.clone(),
+        graphics: sub.graphics.clone(),
+        project_garbage: sub.project_garbage.clone(),
+        extradata,
     })
 }
 
@@ -558,18 +1180,29 @@ fn style_names(sub: &SubstationAlpha) -> std::collections::HashSet<String> {
     set
 }
 
-fn append_sub(dst: &mut SubstationAlpha, src: SubstationAlpha) {
+fn append_sub(dst: &mut SubstationAlpha, mut src: SubstationAlpha) {
+    dst.absorb_sections(&mut src);
     dst.v4p_styles.extend(src.v4p_styles);
     dst.events.extend(src.events);
 }
 
+// A name both scripts use is only a conflict when the definitions differ: TL and TS started from
+// the same template usually share `Default` verbatim, and the TS copy is simply dropped.
 fn prepare_merge_styles(primary: &mut SubstationAlpha, secondary: &mut SubstationAlpha) {
     prune_unused_styles(primary);
     prune_unused_styles(secondary);
-    let overlap: std::collections::HashSet<String> = style_names(primary)
+    let mut overlap: std::collections::HashSet<String> = style_names(primary)
         .intersection(&style_names(secondary))
         .cloned()
         .collect();
+    secondary.v4p_styles.retain(|style| {
+        let shared = overlap.contains(&style.name)
+            && primary.v4p_styles.iter().any(|p| p.name == style.name && p.same_definition(style));
+        if shared {
+            overlap.remove(&style.name);
+        }
+        !shared
+    });
     if !overlap.is_empty() {
         rename_overlapping_styles(secondary, &overlap);
     }
@@ -622,6 +1255,8 @@ mod tests {
     use super::*;
     use pandora_toolchain::libkagami::complex::types::{AssColour, AssTime};
     use pandora_toolchain::libkagami::core::{Event, V4pStyle};
+    use pandora_toolchain::libkagami::sections::ProjectGarbage;
+    use pandora_toolchain::libkagami::drawing::parse::DrawingCommand;
 
     fn style(name: &str) -> V4pStyle {
         V4pStyle {
@@ -665,6 +1300,7 @@ mod tests {
             margin_v: 0,
             effect: String::new(),
             text: ASSLine { current_overrides: Vec::new(), data },
+            extradata: Vec::new(),
         }
     }
 
@@ -691,6 +1327,10 @@ mod tests {
             )],
             comments: Vec::new(),
             pandora_meta: PandoraMeta::default(),
+            fonts: Vec::new(),
+            graphics: Vec::new(),
+            project_garbage: ProjectGarbage::default(),
+            extradata: Vec::new(),
         }
     }
 
@@ -718,6 +1358,10 @@ mod tests {
             )],
             comments: Vec::new(),
             pandora_meta: PandoraMeta::default(),
+            fonts: Vec::new(),
+            graphics: Vec::new(),
+            project_garbage: ProjectGarbage::default(),
+            extradata: Vec::new(),
         };
 
         prune_unused_styles(&mut sub);
@@ -752,6 +1396,10 @@ mod tests {
             )],
             comments: Vec::new(),
             pandora_meta: PandoraMeta::default(),
+            fonts: Vec::new(),
+            graphics: Vec::new(),
+            project_garbage: ProjectGarbage::default(),
+            extradata: Vec::new(),
         };
         let overlap = std::collections::HashSet::from(["Default".to_string()]);
 
@@ -792,6 +1440,10 @@ mod tests {
             events: vec![event("Default", vec![ASSText::RawText("tl".to_string())])],
             comments: Vec::new(),
             pandora_meta: PandoraMeta::default(),
+            fonts: Vec::new(),
+            graphics: Vec::new(),
+            project_garbage: ProjectGarbage::default(),
+            extradata: Vec::new(),
         };
         let mut secondary = SubstationAlpha {
             script_info: ScriptInfo {
@@ -809,6 +1461,10 @@ mod tests {
             events: vec![event("Shared", vec![ASSText::RawText("ts".to_string())])],
             comments: Vec::new(),
             pandora_meta: PandoraMeta::default(),
+            fonts: Vec::new(),
+            graphics: Vec::new(),
+            project_garbage: ProjectGarbage::default(),
+            extradata: Vec::new(),
         };
 
         prepare_merge_styles(&mut primary, &mut secondary);
@@ -821,12 +1477,31 @@ mod tests {
         assert_eq!(secondary.events[0].style, "Shared");
     }
 
+    #[test]
+    fn merge_keeps_one_copy_of_identical_styles_and_renames_the_rest() {
+        let mut primary = sub_with_res(1920, 1080);
+        primary.v4p_styles.push(style("Alt"));
+        primary.events.push(event("Alt", vec![ASSText::RawText("tl".to_string())]));
+        let mut secondary = sub_with_res(1920, 1080);
+        let mut alt = style("Alt");
+        alt.fontsize = 72;
+        secondary.v4p_styles.push(alt);
+        secondary.events.push(event("Alt", vec![ASSText::RawText("ts".to_string())]));
+
+        prepare_merge_styles(&mut primary, &mut secondary);
+
+        assert_eq!(secondary.v4p_styles.len(), 1);
+        assert!(secondary.v4p_styles[0].name.starts_with("pn-"));
+        assert_eq!(secondary.events[0].style, "Default");
+        assert_eq!(secondary.events[1].style, secondary.v4p_styles[0].name);
+    }
+
     #[test]
     fn merge_resolution_scales_secondary_to_primary_when_primary_is_larger() {
         let mut primary = sub_with_res(1920, 1080);
         let mut secondary = sub_with_res(1280, 720);
 
-        normalize_merge_resolutions(&mut primary, &mut secondary).unwrap();
+        normalize_merge_resolutions(&mut primary, &mut secondary, None).unwrap();
 
         assert_eq!(primary.script_info.playresx, 1920);
         assert_eq!(primary.script_info.playresy, 1080);
@@ -843,7 +1518,7 @@ mod tests {
         let mut primary = sub_with_res(640, 480);
         let mut secondary = sub_with_res(1440, 1080);
 
-        normalize_merge_resolutions(&mut primary, &mut secondary).unwrap();
+        normalize_merge_resolutions(&mut primary, &mut secondary, None).unwrap();
 
         assert_eq!(primary.script_info.playresx, 1440);
         assert_eq!(primary.script_info.playresy, 1080);
@@ -860,13 +1535,29 @@ mod tests {
         let mut primary = sub_with_res(1440, 1080);
         let mut secondary = sub_with_res(1920, 1080);
 
-        let err = normalize_merge_resolutions(&mut primary, &mut secondary).unwrap_err();
+        let err = normalize_merge_resolutions(&mut primary, &mut secondary, None).unwrap_err();
 
         assert!(err.contains("incompatible PlayRes ratios"));
         assert_eq!(primary.script_info.playresx, 1440);
         assert_eq!(secondary.script_info.playresx, 1920);
     }
 
+    #[test]
+    fn merge_resolution_resamples_secondary_into_the_input_frame() {
+        let mut primary = sub_with_res(1920, 1080);
+        let mut secondary = sub_with_res(640, 480);
+
+        normalize_merge_resolutions(&mut primary, &mut secondary, Some(ResampleMode::Letterbox)).unwrap();
+
+        assert_eq!(primary.script_info.playresx, 1920);
+        assert_eq!(secondary.script_info.playresx, 1920);
+        assert_eq!(secondary.script_info.playresy, 1080);
+        assert!(matches!(
+            secondary.events[0].text.data[0],
+            ASSText::Override(ASSOverride::Pos(x, y)) if x == 262.5 && y == 45.0
+        ));
+    }
+
     #[test]
     fn injection_timings_use_video_duration_only_for_all_events() {
         let mut sub = sub_with_res(1920, 1080);
@@ -901,4 +1592,85 @@ mod tests {
             vec!["7: leftover # character: visible # marker".to_string()]
         );
     }
+
+    #[test]
+    fn bake_clips_cuts_an7_drawings_and_reports_moving_ones() {
+        let drawing = |shape: &str| ASSText::Drawing(shape.parse().unwrap());
+        let mut sub = sub_with_res(1920, 1080);
+        sub.events = vec![
+            event("Default", vec![
+                ASSText::Override(ASSOverride::An(7)),
+                ASSText::Override(ASSOverride::Pos(100.0, 100.0)),
+                ASSText::Override(ASSOverride::Fscx(200.0)),
+                ASSText::Override(ASSOverride::ClipRect(100.0, 100.0, 200.0, 150.0)),
+                ASSText::Override(ASSOverride::P(1)),
+                drawing("m 0 0 l 100 0 100 100 0 100"),
+            ]),
+            event("Default", vec![
+                ASSText::Override(ASSOverride::An(7)),
+                ASSText::Override(ASSOverride::Pos(0.0, 0.0)),
+                ASSText::Override(ASSOverride::IclipRect(0.0, 0.0, 50.0, 50.0)),
+                ASSText::Override(ASSOverride::P(1)),
+                drawing("m 0 0 l 50 0 50 50 0 50"),
+            ]),
+            event("Default", vec![
+                ASSText::Override(ASSOverride::An(7)),
+                ASSText::Override(ASSOverride::MoveI(0.0, 0.0, 10.0, 10.0)),
+                ASSText::Override(ASSOverride::ClipRect(0.0, 0.0, 5.0, 5.0)),
+                ASSText::Override(ASSOverride::P(1)),
+                drawing("m 0 0 l 50 0 50 50 0 50"),
+            ]),
+        ];
+
+        let report = bake_clips(&mut sub);
+
+        assert_eq!((report.cut, report.removed, report.skipped), (1, 1, vec![3]));
+        assert_eq!(sub.events.len(), 2);
+        assert_eq!(sub.events[0].text.stringify(), "{\\an7\\pos(100,100)\\fscx200\\p1}m 0 0 l 50 0 50 50 0 50");
+    }
+
+    #[test]
+    fn bake_clips_scales_before_rotating_like_libass() {
+        let mut sub = sub_with_res(1920, 1080);
+        sub.events = vec![event("Default", vec![
+            ASSText::Override(ASSOverride::An(7)),
+            ASSText::Override(ASSOverride::Pos(100.0, 100.0)),
+            ASSText::Override(ASSOverride::Fscx(200.0)),
+            ASSText::Override(ASSOverride::Fscy(100.0)),
+            ASSText::Override(ASSOverride::Frz(30.0)),
+            ASSText::Override(ASSOverride::ClipRect(0.0, 0.0, 400.0, 400.0)),
+            ASSText::Override(ASSOverride::P(1)),
+            ASSText::Drawing("m 0 0 l 50 0 50 50 0 50".parse().unwrap()),
+        ])];
+
+        assert_eq!(bake_clips(&mut sub).cut, 1);
+
+        // libass stretches the square to 100x50 first and turns that around \pos, so the corners
+        // land at (100,100), (186.6,50), (211.6,93.3) and (125,143.3). The kept \fscx200 draws
+        // the baked points twice as wide again.
+        let mut corners: Vec<(i32, i32)> = Vec::new();
+        for item in &sub.events[0].text.data {
+            if let ASSText::Drawing(drawing) = item {
+                for command in &drawing.commands {
+                    if let DrawingCommand::Move(x, y) | DrawingCommand::Line(x, y) = command {
+                        let screen = ((100.0 + 2.0 * x) * 10.0, (100.0 + y) * 10.0);
+                        corners.push((screen.0.round() as i32, screen.1.round() as i32));
+                    }
+                }
+            }
+        }
+        corners.sort();
+        corners.dedup();
+        assert_eq!(corners, vec![(1000, 1000), (1250, 1433), (1866, 500), (2116, 933)]);
+        assert!(!sub.events[0].text.stringify().contains("\\frz"));
+    }
+
+    #[test]
+    fn negative_shifts_parse_as_values() {
+        for shift in ["-250ms", "-0:00:01.50", "-24f"] {
+            let args = Args::try_parse_from(["pnass", "--input", "in.ass", "--shift", shift, "--fps", "23.976"]).unwrap();
+            assert_eq!(args.shift.as_deref(), Some(shift));
+            assert_eq!(args.fps, Some(23.976));
+        }
+    }
 }
@@ -472,6 +472,13 @@ const DEFAULT_COMMAND_RANKS: &[(&str, u8)] = &[
     ("merge", 0),
     ("release", 0),
     ("source", 0),
+    ("lint", 0),
+    ("consistency", 0),
+    ("retime", 0),
+    ("styles", 1),
+    ("resync", 0),
+    ("keyframes", 0),
+    ("trace", 0),
     ("get", 0),
     ("job", 0),
     ("!enc", 0),
@@ -765,8 +772,8 @@ fn help_catalog() -> &'static [HelpCommand] {
             section: "repo",
             name: "smartcode",
             summary: "Merge attached repo subtitles, then encode or preview an episode.",
-            usage: "/smartcode do|keep episode:<n> [link] or /smartcode preview episode:<n> [link] [cooldown]",
-            details: "Requires this channel to be attached to an anime repo. `do` reads TL/TS files, uploads the release ASS, then encodes using the source link or SOURCE.md. `keep` runs the same flow and retains the encode locally under a generated or supplied keyword. `preview` performs the merge/upload step, then renders up to three stamp-first, cluster-ranked previews. Cooldown defaults to 90 seconds; set it to 0 to disable cooldown.",
+            usage: "/smartcode do|keep episode:<n> [link] [tpp] [resample] [fontcheck] or /smartcode preview episode:<n> [link] [cooldown] [tpp] [resample] [fontcheck]",
+            details: "Requires this channel to be attached to an anime repo. `do` reads TL/TS files, uploads the release ASS, then encodes using the source link or SOURCE.md. `keep` runs the same flow and retains the encode locally under a generated or supplied keyword. `preview` performs the merge/upload step, then renders up to three stamp-first, cluster-ranked previews. Cooldown defaults to 90 seconds; set it to 0 to disable cooldown. `tpp` runs the timing post-processor over the TL before the merge, snapping to `NN/keyframes.txt` when `/keyframes` has committed one; only the release is affected. `resample` fits a TS whose PlayRes has a different aspect ratio into the TL's (stretch, letterbox, crop, or reset for a TS typeset at the TL's resolution under a stale header); without it such a merge is rejected. `fontcheck` runs the `/fontcheck` coverage check on the merged script and stops before the upload and encode if a font or glyph is missing.",
         },
         HelpCommand {
             section: "repo",
@@ -778,9 +785,9 @@ fn help_catalog() -> &'static [HelpCommand] {
         HelpCommand {
             section: "repo",
             name: "release",
-            summary: "Upload release fonts for an attached episode.",
-            usage: "/release episode:<n>",
-            details: "Requires an attached anime repo and an existing release ASS. Reads the release ASS font list and uploads a font zip to Google Drive: local Drive uses the attached anime folder under fonts/, global Drive uses the default folder.",
+            summary: "Collect and upload release fonts for an attached episode.",
+            usage: "/release episode:<n> [embed_fonts]",
+            details: "Requires an attached anime repo and an existing release ASS. Resolves the face every line is drawn with (styles, `\\fn`, bold and italic) and collects exactly those files from the server and global font directories. They are committed as `NN/fonts.zip` next to the release and uploaded to Google Drive: local Drive uses the attached anime folder under fonts/, global Drive uses the default folder. `embed_fonts` also writes them into the release ASS's [Fonts] section. Fonts that are not installed are listed as warnings.",
         },
         HelpCommand {
             section: "repo",
@@ -789,12 +796,61 @@ fn help_catalog() -> &'static [HelpCommand] {
             usage: "/source episode:<n> link:<source_link>",
             details: "Stores the episode source link in the attached Forgejo repo. Source links can be torrent URLs, magnet links, or Google Drive links.",
         },
+        HelpCommand {
+            section: "repo",
+            name: "lint",
+            summary: "Check an attached episode's TL and TS for QC problems.",
+            usage: "/lint episode:<n> [max_cps]",
+            details: "Requires an attached anime repo. Runs the pnass lint rules (overlaps, reading speed, missing/unused styles, bad durations, off-screen positions, transforms past the line end, duplicate lines) on the repo's TL and TS without changing them. Run it before /smartcode do.",
+        },
+        HelpCommand {
+            section: "repo",
+            name: "consistency",
+            summary: "Compare every episode of the attached anime with each other.",
+            usage: "/consistency",
+            details: "Requires an attached anime repo. Reads every episode's TL, TS and Release and reports style definitions that drifted between episodes, differing PlayRes or YCbCr Matrix, GLOSSARY.md terms spelled another way, and started episodes without a TS or SOURCE.md. The full report is committed as CONSISTENCY.md at the repo root; the reply summarises it.",
+        },
+        HelpCommand {
+            section: "repo",
+            name: "resync",
+            summary: "Move an attached episode's TL and TS to another release of the same episode.",
+            usage: "/resync episode:<n> link:<new source> [reference]",
+            details: "Requires an attached anime repo. Downloads the new release and the one the subtitles are timed to (SOURCE.md, or `reference`), matches their audio, and shifts every line by the offset measured where it starts, so inserted or removed scenes such as a recap or a longer eyecatch are handled. The resynced TL and TS are committed and SOURCE.md is updated to the new link. The reply lists each cut the aligner found.",
+        },
+        HelpCommand {
+            section: "repo",
+            name: "keyframes",
+            summary: "Extract an attached episode's keyframes into the repo.",
+            usage: "/keyframes episode:<n> [link] [tpp]",
+            details: "Requires an attached anime repo. Downloads the release (SOURCE.md unless `link` is given), reads its keyframes plus ffmpeg's scene cuts, and commits them as an Aegisub keyframes file at `NN/keyframes.txt`; the file is also attached to the reply. With `tpp` the episode's TL is run through the timing post-processor (lead-in, lead-out, linking and keyframe snapping) and committed too. `/smartcode tpp:true` uses the same file for the release.",
+        },
+        HelpCommand {
+            section: "repo",
+            name: "trace",
+            summary: "Trace a frame of a job's source video into an ASS drawing.",
+            usage: "/trace time:<h:mm:ss.cc> [crop] [preset] [job_id] [index] [episode]",
+            details: "Reads the frame at `time` from a `/probe` result (`job_id` with `index`) or, without one, from the episode's SOURCE.md, reusing the cached download when there is one. `crop` is `x,y,width,height` in video pixels and limits the trace to that rectangle; `preset` picks the kagami-trace preset (logo_ui, illustration, photo, gradient; default illustration). The drawing is placed where the crop sits on screen, scaled to the episode's PlayRes when `episode` is given and to the video size otherwise, and the `.ass` is attached to the reply.",
+        },
+        HelpCommand {
+            section: "repo",
+            name: "retime",
+            summary: "Shift or convert the timing of an attached episode's TL and TS.",
+            usage: "/retime episode:<n> [shift] [retime] [file] [styles] [fps] [timecodes]",
+            details: "Requires an attached anime repo. `shift` takes `1.5s`, `-250ms`, `+24f` or `-0:00:01.50`; frame offsets use `fps` (default 23.976) or the `timecodes` attachment. `retime` converts between frame rates as `FROM:TO`, e.g. `25:23.976`, or `23.976:timecodes` for a VFR mkv v2 timecodes file. `styles` limits the change to a comma-separated style list. Transforms, moves, fades and karaoke are rescaled with the line, and the result is committed back to the repo.",
+        },
+        HelpCommand {
+            section: "repo",
+            name: "styles",
+            summary: "Keep a server style library and apply it to an attached episode.",
+            usage: "/styles show | /styles import file:<ass> [replace] [resample] | /styles remove names:<a,b> | /styles apply episode:<n> [file] [add] [resample]",
+            details: "The library is `DB/config/<server_id>/styles.ass`. `import` adds or replaces styles by name from any ASS, scaled to the library's PlayRes (the first import sets it); `replace` starts the library over. `apply` runs `pnass --apply-styles` on the episode's TL and/or TS: styles the library also defines are replaced, styles the lines use but the file lacks are added, and `add` adds more by name. Library styles are scaled to each file's PlayRes; another aspect ratio needs `resample`. Only files whose styles changed are committed.",
+        },
         HelpCommand {
             section: "repo",
             name: "job",
             summary: "Upload one episode work file to the attached repo.",
             usage: "/job type:<TL|TLC|TS> episode:<n> subtitle:<subtitle_or_zip> [commit]",
-            details: "Requires a channel attachment. Accepts ASS or any text subtitle ffmpeg can read (.srt, .ssa, .vtt, .sub, .smi, ...), directly or as a root-level zip entry; non-ASS uploads are converted to ASS and flagged as unstyled. Normalizes the result, then uploads it under the selected job type.",
+            details: "Requires a channel attachment. Accepts ASS or a text subtitle (.srt, .vtt, MicroDVD .sub natively; .ssa, .smi, ... through ffmpeg), directly or as a root-level zip entry; non-ASS uploads are converted to ASS and flagged as unstyled. Normalizes the result, then uploads it under the selected job type.",
         },
         HelpCommand {
             section: "repo",
@@ -1002,9 +1058,9 @@ fn help_catalog() -> &'static [HelpCommand] {
         HelpCommand {
             section: "fonts",
             name: "fontcheck",
-            summary: "Count usable unique fonts in the DB fontconfig directories.",
-            usage: "/fontcheck",
-            details: "Scans DB/fontconfig/global and DB/fontconfig/<server_id>, counts font files and extracts unique usable font names from their name tables.",
+            summary: "Count usable fonts, or check an episode's font coverage.",
+            usage: "/fontcheck [episode]",
+            details: "Scans DB/fontconfig/global and DB/fontconfig/<server_id>, counts font files and extracts unique usable font names from their name tables. With `episode`, the attached repo's TL and TS are checked instead: every line's effective font (style, `\\fn`, `\\b`, `\\i`, `\\r`) must be installed and have a glyph for every character it draws. Missing fonts, missing glyphs and faux bold/italic are listed by line.",
         },
         HelpCommand {
             section: "repo",
@@ -2193,6 +2249,33 @@ impl EventHandler for Handler {
                 "source" => {
                     handle_source(&ctx, &command).await;
                 }
+                "lint" => {
+                    handle_lint(&ctx, &command).await;
+                }
+                "consistency" => {
+                    handle_consistency(&ctx, &command).await;
+                }
+                "retime" => {
+                    handle_retime(&ctx, &command).await;
+                }
+                "styles" => {
+                    handle_styles(&ctx, &command).await;
+                }
+                "resync" => {
+                    if let Some(job) = handle_resync(&ctx, &command).await {
+                        self.tx.send(JobClass::Job(job)).await.unwrap();
+                    }
+                }
+                "keyframes" => {
+                    if let Some(job) = handle_keyframes(&ctx, &command).await {
+                        self.tx.send(JobClass::Job(job)).await.unwrap();
+                    }
+                }
+                "trace" => {
+                    if let Some(job) = handle_trace(&ctx, &command).await {
+                        self.tx.send(JobClass::Job(job)).await.unwrap();
+                    }
+                }
                 "get" => {
                     handle_get(&ctx, &command).await;
                 }
@@ -2279,6 +2362,32 @@ impl EventHandler for Handler {
             "keyword",
             "Existing keep keyword; omit for New keyword"
         ).required(false);
+        let resample_option = CreateCommandOption::new(
+            CommandOptionType::String,
+            "resample",
+            "How to fit a TS with a different aspect ratio into the TL"
+        )
+            .required(false)
+            .add_string_choice("Stretch", "stretch")
+            .add_string_choice("Letterbox", "letterbox")
+            .add_string_choice("Crop", "crop")
+            .add_string_choice("Reset header", "reset");
+        let style_resample_option = CreateCommandOption::new(
+            CommandOptionType::String,
+            "resample",
+            "How to fit styles made at a PlayRes with a different aspect ratio"
+        )
+            .required(false)
+            .add_string_choice("Stretch", "stretch")
+            .add_string_choice("Letterbox", "letterbox")
+            .add_string_choice("Crop", "crop")
+            .add_string_choice("Reset header", "reset");
+        let fontcheck_option = CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "fontcheck",
+            "Refuse to encode when a used font or glyph is missing from this server's fonts"
+        )
+            .required(false);
         let mut help_section_option = CreateCommandOption::new(
             CommandOptionType::String,
             "section",
@@ -2605,6 +2714,12 @@ impl EventHandler for Handler {
                             CreateCommandOption::new(CommandOptionType::String, "link", "Source link. Falls back to SOURCE.md if omitted.")
                                 .required(false)
                         )
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::Boolean, "tpp", "Post-process TL timing (leads, linking, keyframe snapping) for the release")
+                                .required(false)
+                        )
+                        .add_sub_option(resample_option.clone())
+                        .add_sub_option(fontcheck_option.clone())
                 )
                 .add_option(
                     CreateCommandOption::new(CommandOptionType::SubCommand, "keep", "Merge, encode, and keep the episode locally")
@@ -2618,6 +2733,12 @@ impl EventHandler for Handler {
                                 .required(false)
                         )
                         .add_sub_option(keyword_option.clone())
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::Boolean, "tpp", "Post-process TL timing (leads, linking, keyframe snapping) for the release")
+                                .required(false)
+                        )
+                        .add_sub_option(resample_option.clone())
+                        .add_sub_option(fontcheck_option.clone())
                 )
                 .add_option(
                     CreateCommandOption::new(CommandOptionType::SubCommand, "preview", "Render 1-3 typeset preview screenshots")
@@ -2636,6 +2757,12 @@ impl EventHandler for Handler {
                                 .min_int_value(0)
                                 .max_int_value(3600)
                         )
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::Boolean, "tpp", "Post-process TL timing (leads, linking, keyframe snapping) for the release")
+                                .required(false)
+                        )
+                        .add_sub_option(resample_option.clone())
+                        .add_sub_option(fontcheck_option.clone())
                 ),
             CreateCommand::new("merge")
                 .description("Merge the channel's attached TL and TS subtitles for an episode and upload the release ASS")
@@ -2649,11 +2776,15 @@ impl EventHandler for Handler {
                         .required(false)
                 ),
             CreateCommand::new("release")
-                .description("Upload release fonts to Google Drive for an existing episode release ASS")
+                .description("Collect an episode release's fonts into fonts.zip and upload it to Google Drive")
                 .add_option(
                     CreateCommandOption::new(CommandOptionType::Integer, "episode", "Episode number (1-based)")
                         .required(true)
                         .min_int_value(1)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Boolean, "embed_fonts", "Also embed the fonts in the release ASS [Fonts] section")
+                        .required(false)
                 ),
             CreateCommand::new("source")
                 .description("Write the SOURCE.md for an episode's folder in the attached repo")
@@ -2666,6 +2797,162 @@ impl EventHandler for Handler {
                     CreateCommandOption::new(CommandOptionType::String, "link", "Source link (torrent URL, magnet link, or Google Drive link)")
                         .required(true)
                 ),
+            CreateCommand::new("lint")
+                .description("Check the attached repo's TL and TS for an episode against the QC lint rules")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "episode", "Episode number (1-based)")
+                        .required(true)
+                        .min_int_value(1)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Number, "max_cps", "Characters-per-second limit (default 25)")
+                        .required(false)
+                        .min_number_value(1.0)
+                        .max_number_value(100.0)
+                ),
+            CreateCommand::new("consistency")
+                .description("Compare every episode in the attached repo and commit CONSISTENCY.md"),
+            CreateCommand::new("retime")
+                .description("Shift or convert the timing of the attached repo's TL and TS for an episode")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "episode", "Episode number (1-based)")
+                        .required(true)
+                        .min_int_value(1)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "shift", "Offset such as 1.5s, -250ms, +24f or -0:00:01.50")
+                        .required(false)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "retime", "Frame rate conversion FROM:TO, e.g. 25:23.976 or 23.976:timecodes")
+                        .required(false)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "file", "Which file to change (default both)")
+                        .required(false)
+                        .add_string_choice("TL", "TL")
+                        .add_string_choice("TS", "TS")
+                        .add_string_choice("Both", "both")
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "styles", "Only change these comma-separated styles")
+                        .required(false)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Number, "fps", "Frame rate for frame offsets (default 23.976)")
+                        .required(false)
+                        .min_number_value(1.0)
+                        .max_number_value(240.0)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Attachment, "timecodes", "mkv v2 timecodes file for VFR video")
+                        .required(false)
+                ),
+            CreateCommand::new("styles")
+                .description("Manage this server's style library and apply it to an attached episode")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "show", "List the styles in the library")
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "import", "Add or replace library styles from an ASS file")
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::Attachment, "file", "ASS file whose styles to import")
+                                .required(true)
+                        )
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::Boolean, "replace", "Start the library over with this file's styles")
+                                .required(false)
+                        )
+                        .add_sub_option(style_resample_option.clone())
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Remove styles from the library")
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::String, "names", "Comma-separated style names")
+                                .required(true)
+                        )
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "apply", "Apply the library to an episode's TL and/or TS")
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::Integer, "episode", "Episode number (1-based)")
+                                .required(true)
+                                .min_int_value(1)
+                        )
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::String, "file", "Which file to change (default both)")
+                                .required(false)
+                                .add_string_choice("TL", "TL")
+                                .add_string_choice("TS", "TS")
+                                .add_string_choice("Both", "both")
+                        )
+                        .add_sub_option(
+                            CreateCommandOption::new(CommandOptionType::String, "add", "Comma-separated library styles to add even if unused")
+                                .required(false)
+                        )
+                        .add_sub_option(style_resample_option.clone())
+                ),
+            CreateCommand::new("resync")
+                .description("Resync the attached repo's TL and TS for an episode to another release by audio")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "episode", "Episode number (1-based)")
+                        .required(true)
+                        .min_int_value(1)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "link", "Torrent URL, magnet link or direct link of the new release")
+                        .required(true)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "reference", "Release the subtitles are timed to. Falls back to SOURCE.md if omitted.")
+                        .required(false)
+                ),
+            CreateCommand::new("keyframes")
+                .description("Extract an episode's keyframes into the attached repo")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "episode", "Episode number (1-based)")
+                        .required(true)
+                        .min_int_value(1)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "link", "Release to read. Falls back to SOURCE.md if omitted.")
+                        .required(false)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Boolean, "tpp", "Also post-process the TL timing against the keyframes and commit it")
+                        .required(false)
+                ),
+            CreateCommand::new("trace")
+                .description("Trace a frame of the source video into an ASS drawing")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "time", "Frame timestamp, e.g. 0:01:23.45")
+                        .required(true)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "crop", "Region to trace as x,y,width,height in video pixels")
+                        .required(false)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "preset", "Trace preset")
+                        .required(false)
+                        .add_string_choice("Logo / UI", "logo_ui")
+                        .add_string_choice("Illustration", "illustration")
+                        .add_string_choice("Photo", "photo")
+                        .add_string_choice("Gradient", "gradient")
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "job_id", "Job ID from /probe result; use with index")
+                        .required(false)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "index", "File index from probe results")
+                        .required(false)
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "episode", "Attached episode whose PlayRes to use; its SOURCE.md is read when job_id is omitted")
+                        .required(false)
+                        .min_int_value(1)
+                ),
             CreateCommand::new("get")
                 .description("Get the download link for an episode's translation or typeset file")
                 .add_option(
@@ -3170,7 +3457,12 @@ impl EventHandler for Handler {
                         .set_autocomplete(true)
                 ),
             CreateCommand::new("fontcheck")
-                .description("Count usable unique fonts in the DB fontconfig directories"),
+                .description("Count usable unique fonts, or check an episode's font coverage")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "episode", "Check this episode's TL and TS against the server's fonts")
+                        .required(false)
+                        .min_int_value(1)
+                ),
             CreateCommand::new("readmebase")
                 .description("Set the base.md for this server (used as the README template when bootstrapping repos)")
                 .add_option(
@@ -1,6 +1,8 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use pandora_toolchain::libkagami::complex::overrides::ASSOverride;
 use pandora_toolchain::libkagami::core::SubstationAlpha;
+use pandora_toolchain::libkagami::diff::diff_scripts;
+use pandora_toolchain::libkagami::fontcheck::check_fonts;
 use pandora_toolchain::libkagami::tags::{ASSText, ASSLine};
 use std::io::{self, Write};
 use std::path::PathBuf;
@@ -10,10 +12,14 @@ use std::path::PathBuf;
     name = "pnkagami",
     version = "0.1.0",
     about = "Show libkagami advanced parsing changes",
-    long_about = None
+    long_about = None,
+    args_conflicts_with_subcommands = true
 )]
 struct Args {
-    input: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    input: Option<String>,
 
     #[arg(long)]
     all: bool,
@@ -22,6 +28,27 @@ struct Args {
     no_pause: bool,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare two scripts event by event (text, timing, style, added and removed lines)
+    Diff {
+        old: String,
+        new: String,
+
+        /// terminal, md or json
+        #[arg(long, default_value = "terminal")]
+        format: String,
+    },
+    /// Check that every font a script uses is installed and has every glyph it draws
+    Fontcheck {
+        input: String,
+
+        /// Font directory to search (repeatable); fonts attached to the script are always used
+        #[arg(long = "fonts-dir")]
+        fonts_dir: Vec<String>,
+    },
+}
+
 struct EventDiff {
     idx: usize,
     style: String,
@@ -32,9 +59,39 @@ struct EventDiff {
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
-    let path = PathBuf::from(&args.input);
-    let raw = SubstationAlpha::load(path.clone(), false).await;
-    let adv = SubstationAlpha::load(path, true).await;
+    match &args.command {
+        Some(Command::Diff { old, new, format }) => {
+            run_diff(old, new, format).await;
+            return;
+        }
+        Some(Command::Fontcheck { input, fonts_dir }) => {
+            run_fontcheck(input, fonts_dir).await;
+            return;
+        }
+        None => {}
+    }
+    let Some(input) = args.input.as_deref() else {
+        eprintln!("pnkagami: an input file, `diff <old> <new>` or `fontcheck <input>` is required");
+        std::process::exit(1);
+    };
+    let path = PathBuf::from(input);
+    let (raw, diagnostics) = match SubstationAlpha::load_with_diagnostics(path.clone(), false).await {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("pnkagami: {}: {}", input, e);
+            std::process::exit(1);
+        }
+    };
+    for diagnostic in &diagnostics {
+        println!("\x1b[33m{}\x1b[0m", diagnostic);
+    }
+    let adv = match SubstationAlpha::load(path, true).await {
+        Ok(sub) => sub,
+        Err(e) => {
+            eprintln!("pnkagami: {}: {}", input, e);
+            std::process::exit(1);
+        }
+    };
 
     let mut diffs = Vec::new();
     for (idx, (before, after)) in raw.events.iter().zip(adv.events.iter()).enumerate() {
@@ -67,6 +124,61 @@ async fn main() {
     }
 }
 
+// Diagnostics go to stderr here so `--format json` stays machine-readable.
+async fn run_diff(old: &str, new: &str, format: &str) {
+    if !matches!(format, "terminal" | "md" | "json") {
+        eprintln!("pnkagami: --format must be terminal, md or json");
+        std::process::exit(1);
+    }
+    let mut scripts = Vec::new();
+    for input in [old, new] {
+        match SubstationAlpha::load_with_diagnostics(PathBuf::from(input), false).await {
+            Ok((sub, diagnostics)) => {
+                for diagnostic in &diagnostics {
+                    eprintln!("{}: {}", input, diagnostic);
+                }
+                scripts.push(sub);
+            }
+            Err(e) => {
+                eprintln!("pnkagami: {}: {}", input, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let diff = diff_scripts(&scripts[0], &scripts[1]);
+    match format {
+        "md" => print!("{}", diff.to_markdown()),
+        "json" => println!("{}", serde_json::to_string_pretty(&diff.to_json()).unwrap()),
+        _ => print!("{}", diff.to_terminal()),
+    }
+}
+
+// Exits 2 when a font or glyph is missing so scripts can gate an encode on it; faux bold/italic
+// is printed but does not fail.
+async fn run_fontcheck(input: &str, fonts_dir: &[String]) {
+    let sub = match SubstationAlpha::load_with_diagnostics(PathBuf::from(input), true).await {
+        Ok((sub, diagnostics)) => {
+            for diagnostic in &diagnostics {
+                eprintln!("{}: {}", input, diagnostic);
+            }
+            sub
+        }
+        Err(e) => {
+            eprintln!("pnkagami: {}: {}", input, e);
+            std::process::exit(1);
+        }
+    };
+    let roots: Vec<PathBuf> = fonts_dir.iter().map(PathBuf::from).collect();
+    let report = check_fonts(&sub, &roots);
+    for message in report.messages() {
+        println!("{}", message);
+    }
+    println!("{}", report.summary());
+    if !report.passed() {
+        std::process::exit(2);
+    }
+}
+
 fn is_drawing_line(line: &ASSLine) -> bool {
     line.data.iter().any(|item| matches!(item, ASSText::Override(ASSOverride::P(1))))
 }
@@ -13,7 +13,11 @@ use pandora_toolchain::{pn_data, pn_emit, pn_schema};
 use pandora_toolchain::lib::mpeg::core::RpbData;
 use pandora_toolchain::lib::logging::tool::ToolLog;
 use pandora_toolchain::lib::mpeg::studio::{studio_ffmpeg_params, write_ffconcat, StudioRenderManifest};
+use pandora_toolchain::lib::mpeg::resync::{AlignOptions, align, decode_envelope, describe_segments};
 use pandora_toolchain::lib::mpeg::subs::{ExtractOutcome, extract_subtitle, ffprobe_subtitle_streams};
+use pandora_toolchain::lib::mpeg::keyframes::extract_keyframes;
+use pandora_toolchain::libkagami::timing::format_sync_map;
+use pandora_toolchain::libkagami::tpp::format_keyframes;
 use pandora_toolchain::lib::protocol::core::{Protocol, Schema, ToolInfo};
 use std::str::FromStr;
 use clap::Parser;
@@ -58,6 +62,19 @@ struct Args {
     #[arg(long)]
     extractsubs: bool,
 
+    /// Align the audio of --subinput (the old source) against --input (the new one) and write
+    /// the resulting sync map to --output.
+    #[arg(long)]
+    syncmap: bool,
+
+    /// Write the keyframes of --input to --output as an Aegisub keyframes file.
+    #[arg(long)]
+    keyframes: bool,
+
+    /// With --keyframes, also add scene cuts scoring above this (0..1, ffmpeg's scene score).
+    #[arg(long)]
+    scene_threshold: Option<f64>,
+
     #[arg(long)]
     legacyconcat: bool,
 
@@ -140,9 +157,10 @@ async fn main() {
         args.input, args.output, args.ass, args.lang, args.intro_dir, args.candidate.len()
     ));
     log.line(&format!(
-        "mode gpu={} x264={} pseudolossless={} veryslow={} dummy={} concat={} legacyconcat={} joinconcat={} joinass={} studio={} extractsubs={}",
+        "mode gpu={} x264={} pseudolossless={} veryslow={} dummy={} concat={} legacyconcat={} joinconcat={} joinass={} studio={} extractsubs={} syncmap={} keyframes={}",
         args.gpu, args.x264, args.pseudolossless, args.veryslow, args.dummy,
-        args.concat, args.legacyconcat, args.joinconcat, args.joinass, args.studio, args.extractsubs
+        args.concat, args.legacyconcat, args.joinconcat, args.joinass, args.studio, args.extractsubs, args.syncmap,
+        args.keyframes
     ));
     let mut proto = Protocol::new(vec![1]);
     let neg = proto.request(ToolInfo { tool: match args.negotiator {
@@ -213,6 +231,87 @@ async fn main() {
         return;
     }
 
+    // Like extraction, alignment only reads audio and answers on its own. Each segment of the
+    // map is also sent as an opcode 4 line so the caller can report the edits it found.
+    if args.syncmap {
+        let Some(reference) = args.subinput.as_deref().filter(|path| !path.is_empty()) else {
+            eprintln!("[pnmpeg] --syncmap needs --subinput (the source the subtitles were timed to)");
+            emit_extract_failure(&proto, &neg);
+            std::process::exit(1);
+        };
+        let decoded = log.step("decode reference audio", || decode_envelope(Path::new(reference)))
+            .and_then(|reference| {
+                let target = log.step("decode target audio", || decode_envelope(Path::new(&args.input)))?;
+                log.step("align", || align(&reference, &target, &AlignOptions::default()))
+            });
+        let segments = match decoded {
+            Ok(segments) => segments,
+            Err(e) => {
+                log.line(&format!("syncmap failed: {}", e));
+                println!("{}", pn_emit!(protocol = proto, negkey = &neg, schema = [leaf, leaf], data = ["4", e]).unwrap());
+                emit_extract_failure(&proto, &neg);
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = tokio::fs::write(&args.output, format_sync_map(&segments)).await {
+            eprintln!("[pnmpeg] sync map write failed: {e}");
+            emit_extract_failure(&proto, &neg);
+            std::process::exit(1);
+        }
+        for line in describe_segments(&segments) {
+            println!("{}", pn_emit!(protocol = proto, negkey = &neg, schema = [leaf, leaf], data = ["4", line]).unwrap());
+        }
+        println!(
+            "{}",
+            pn_emit!(
+                protocol = proto,
+                negkey = &neg,
+                schema = [leaf, leaf],
+                data = ["1", "DONE"]
+            )
+            .unwrap()
+        );
+        return;
+    }
+
+    // Keyframe extraction reads packet flags (and, with --scene-threshold, decodes the video for
+    // the scene detector); the opcode 4 line is a one-line summary for the caller to show.
+    if args.keyframes {
+        let extracted = match log.step("extract keyframes", || extract_keyframes(Path::new(&args.input), args.scene_threshold)) {
+            Ok(extracted) => extracted,
+            Err(e) => {
+                log.line(&format!("keyframes failed: {}", e));
+                println!("{}", pn_emit!(protocol = proto, negkey = &neg, schema = [leaf, leaf], data = ["4", e]).unwrap());
+                emit_extract_failure(&proto, &neg);
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = tokio::fs::write(&args.output, format_keyframes(&extracted.frames, extracted.fps)).await {
+            eprintln!("[pnmpeg] keyframes write failed: {e}");
+            emit_extract_failure(&proto, &neg);
+            std::process::exit(1);
+        }
+        let mut summary = format!("{} keyframes", extracted.frames.len());
+        if extracted.scene_cuts > 0 {
+            summary.push_str(&format!(" ({} from scene detection)", extracted.scene_cuts));
+        }
+        if let Some(fps) = extracted.fps {
+            summary.push_str(&format!(" at {:.3} fps", fps));
+        }
+        println!("{}", pn_emit!(protocol = proto, negkey = &neg, schema = [leaf, leaf], data = ["4", summary]).unwrap());
+        println!(
+            "{}",
+            pn_emit!(
+                protocol = proto,
+                negkey = &neg,
+                schema = [leaf, leaf],
+                data = ["1", "DONE"]
+            )
+            .unwrap()
+        );
+        return;
+    }
+
     if args.studio {
         let manifest_bytes = match tokio::fs::read(&args.input).await {
             Ok(bytes) => bytes,
@@ -0,0 +1,59 @@
+use super::*;
+use pandora_toolchain::lib::git::check_repo_consistency;
+
+pub async fn handle_consistency(ctx: &Context, command: &serenity::all::CommandInteraction) {
+    let server_id = match command_server_id(ctx, command, "/consistency").await {
+        Some(id) => id,
+        None => return,
+    };
+    let (_meta, owner_repo, repo_url) = match attached_repo(ctx, command, server_id, None).await {
+        Some(t) => t,
+        None => return,
+    };
+    let mut response_msg = match working_response(ctx, command, "Comparing episodes…").await {
+        Some(m) => m,
+        None => return,
+    };
+
+    let outcome = match check_repo_consistency(server_id, command.channel_id.get()).await {
+        Ok(o) => o,
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new().content(format!("Error: {}", e))).await;
+            return;
+        }
+    };
+    let report = &outcome.report;
+    let counts = [
+        report.styles.len(),
+        report.headers.len(),
+        report.glossary.len(),
+        report.missing_ts.len(),
+        report.missing_source.len(),
+    ].map(|n| n.to_string());
+    let lines: Vec<String> = report.summary_lines().into_iter()
+        .chain(outcome.skipped.iter().map(|skipped| format!("Skipped {}", skipped)))
+        .collect();
+    let embed = info_embed(command, COMMAND_CONSISTENCY_REPORT)
+        .description(command_format(command, CONSISTENCY_SUMMARY, &counts))
+        .field(
+            command_message(command, FIELD_REPO),
+            format!("[{}]({})", owner_repo, repo_url),
+            true,
+        )
+        .field(
+            command_message(command, FIELD_EPISODES),
+            format!("`{}` / `{}`", report.started, report.episodes),
+            true,
+        )
+        .field(
+            command_message(command, FIELD_FILE),
+            format!("`{}`", outcome.report_path),
+            false,
+        )
+        .field(
+            command_message(command, FIELD_WARNINGS),
+            format_warnings_field(&lines, command),
+            false,
+        );
+    edit_response_embed(ctx, &mut response_msg, embed).await;
+}
@@ -10,6 +10,13 @@ pub async fn handle_fontcheck(
         Some(id) => id,
         None => return,
     };
+    if option_i64(command, "episode").is_some() {
+        let Some(episode) = positive_u32_option(ctx, command, "episode").await else {
+            return;
+        };
+        check_episode_fonts(ctx, command, server_id, episode).await;
+        return;
+    }
 
     let mut response_msg = match working_response(ctx, command, "Counting fonts...").await {
         Some(m) => m,
@@ -78,3 +85,85 @@ fn count_fonts(dir: &Path) -> FontCount {
     }
     FontCount { files: files.len(), names }
 }
+
+// Runs the libkagami coverage check on the episode's TL and TS against the same directories the
+// encoder stages fonts from.
+async fn check_episode_fonts(
+    ctx: &Context,
+    command: &serenity::all::CommandInteraction,
+    server_id: u64,
+    episode: u32,
+) {
+    let (meta, owner_repo, repo_url) = match attached_repo(ctx, command, server_id, Some(episode)).await {
+        Some(t) => t,
+        None => return,
+    };
+    let (forgejo_base, api_key) = match forgejo_config(ctx, command, server_id).await {
+        Some(t) => t,
+        None => return,
+    };
+    let mut response_msg = match working_response(ctx, command, "Checking font coverage...").await {
+        Some(m) => m,
+        None => return,
+    };
+    let fg = match Forgejo::new(forgejo_base, api_key) {
+        Ok(f) => f,
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new()
+                .content(format!("Forgejo init failed: {}", e))).await;
+            return;
+        }
+    };
+
+    let safe_name = meta.name.clone().unwrap_or_default().replace('/', "-");
+    let folder = pad2(episode);
+    let mut reports: Vec<(String, FontReport)> = Vec::new();
+    for kind in ["TL", "TS"] {
+        let ass_path = format!("{}/{} - {} - E{:02}.ass", folder, kind, safe_name, episode);
+        let (bytes, path) = match read_repo_ass(&fg, &owner_repo, &ass_path).await {
+            Ok(Some(found)) => found,
+            Ok(None) => continue,
+            Err(e) => {
+                let _ = response_msg.edit(ctx, EditMessage::new()
+                    .content(format!("Failed to fetch {}: {}", kind, e))).await;
+                return;
+            }
+        };
+        match server_font_coverage(server_id, bytes).await {
+            Ok(report) => reports.push((path, report)),
+            Err(e) => {
+                let _ = response_msg.edit(ctx, EditMessage::new()
+                    .content(format!("`{}`: {}", path, e))).await;
+                return;
+            }
+        }
+    }
+    if reports.is_empty() {
+        let _ = response_msg.edit(ctx, EditMessage::new()
+            .content(format!("No TL or TS file found in `{}/` for episode {}.", folder, episode))).await;
+        return;
+    }
+
+    let total = |count: fn(&FontReport) -> usize| reports.iter().map(|(_, r)| count(r)).sum::<usize>().to_string();
+    let mut embed = info_embed(command, COMMAND_FONT_COVERAGE)
+        .description(command_format(command, FONT_COVERAGE_SUMMARY, &[
+            total(|r| r.faces_used),
+            total(|r| r.missing_fonts.len()),
+            total(|r| r.faux_styles.len()),
+            total(|r| r.missing_glyphs.len()),
+        ]))
+        .field(
+            command_message(command, FIELD_REPO),
+            format!("[{}]({})", owner_repo, repo_url),
+            true,
+        )
+        .field(
+            command_message(command, FIELD_EPISODE),
+            format!("`{}`", episode),
+            true,
+        );
+    for (path, report) in reports {
+        embed = embed.field(format!("`{}`", path), format_warnings_field(&report.messages(), command), false);
+    }
+    edit_response_embed(ctx, &mut response_msg, embed).await;
+}
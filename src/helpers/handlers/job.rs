@@ -1,4 +1,8 @@
 use super::*;
+use pandora_toolchain::libkagami::core::SubstationAlpha;
+use pandora_toolchain::libkagami::diff::{diff_scripts, ScriptDiff};
+use pandora_toolchain::libkagami::spellcheck::{DICTIONARIES_DIR, GLOSSARY_FILE};
+use serenity::builder::CreateAttachment;
 
 pub async fn handle_job(ctx: &Context, command: &serenity::all::CommandInteraction) {
     let job_kind = match option_str(command, "type").and_then(parse_job_kind)
@@ -109,9 +113,9 @@ pub async fn handle_job(ctx: &Context, command: &serenity::all::CommandInteracti
     let mut warnings: Vec<String> = Vec::new();
     let ass_bytes = match ensure_ass(&source_name, &source_bytes).await {
         Ok(converted) => {
-            if let Some(warning) = converted.warning {
+            if !converted.warnings.is_empty() {
                 println!("[job] id={} converted={}", job_id, source_name);
-                warnings.push(warning);
+                warnings.extend(converted.warnings);
             }
             converted.bytes
         }
@@ -128,6 +132,27 @@ pub async fn handle_job(ctx: &Context, command: &serenity::all::CommandInteracti
     }
     println!("[job] id={} input_ass_bytes={}", job_id, ass_bytes.len());
 
+    let fg = match Forgejo::new(forgejo_base, api_key) {
+        Ok(f) => f,
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new()
+                .content(format!("Forgejo init failed: {}", e))).await;
+            return;
+        }
+    };
+    // Translations are spellchecked and held to the repo glossary; typesetting is left alone.
+    let (spellcheck_lang, glossary_path) = if matches!(job_kind, JobKind::TS) {
+        (String::new(), String::new())
+    } else {
+        let glossary_path = match fetch_glossary(&fg, &owner_repo, &job_dir).await {
+            Ok(path) => path.unwrap_or_default(),
+            Err(e) => {
+                warnings.push(format!("Glossary skipped: {}", e));
+                String::new()
+            }
+        };
+        (spellcheck_language(), glossary_path)
+    };
     let title = if name.is_empty() { owner.clone() } else { format!("{} - {}", owner, name) };
     let wrap_style = server_wrap_style(server_id);
     let pnass_path = match get_pandora_env().get(PNASS) {
@@ -147,6 +172,8 @@ pub async fn handle_job(ctx: &Context, command: &serenity::all::CommandInteracti
             ("OUTPUT", PathValue::from(output_path.clone())),
             ("TITLE", PathValue::from(title)),
             ("WRAPSTYLE", PathValue::from(wrap_style)),
+            ("SPELLCHECK", PathValue::from(spellcheck_lang)),
+            ("GLOSSARY", PathValue::from(glossary_path)),
         ]),
         job_id,
         &mut proto,
@@ -161,7 +188,7 @@ pub async fn handle_job(ctx: &Context, command: &serenity::all::CommandInteracti
     ).await;
     if !matches!(result, ToolResult::Success) {
         let _ = response_msg.edit(ctx, EditMessage::new()
-            .content(format!("Failed to standardise ASS with pnass (warnings so far: {}).", warnings.len()))).await;
+            .content(tool_failure_message("Failed to standardise ASS with pnass", &warnings))).await;
         return;
     }
     let output_bytes = match tokio::fs::read(&output_path).await {
@@ -189,13 +216,17 @@ pub async fn handle_job(ctx: &Context, command: &serenity::all::CommandInteracti
     let folder = pad2(episode);
     let repo_path = format!("{}/{}", folder, file_name);
 
-    let fg = match Forgejo::new(forgejo_base, api_key) {
-        Ok(f) => f,
-        Err(e) => {
-            let _ = response_msg.edit(ctx, EditMessage::new()
-                .content(format!("Forgejo init failed: {}", e))).await;
-            return;
+    // An edit pass is reviewed against the TL it replaces, so that revision is read before the upload.
+    let previous = if matches!(job_kind, JobKind::TLC) {
+        match read_repo_ass(&fg, &owner_repo, &repo_path).await {
+            Ok(previous) => previous.map(|(bytes, _)| bytes),
+            Err(e) => {
+                warnings.push(format!("Could not read the previous TL for the diff: {}", e));
+                None
+            }
         }
+    } else {
+        None
     };
     match upsert_repo_ass(&fg, &owner_repo, &repo_path, &output_bytes, &commit_msg).await {
         Ok(uploaded_path) => {
@@ -205,7 +236,14 @@ pub async fn handle_job(ctx: &Context, command: &serenity::all::CommandInteracti
                 JobKind::TLC => "TLC",
                 JobKind::TS => "TS",
             };
-            let embed = success_embed(command, COMMAND_JOB_COMPLETE)
+            let diff = previous.as_deref().and_then(|previous| match tlc_diff(previous, &output_bytes) {
+                Ok(diff) => Some(diff),
+                Err(e) => {
+                    warnings.push(format!("TLC diff skipped: {}", e));
+                    None
+                }
+            });
+            let mut embed = success_embed(command, COMMAND_JOB_COMPLETE)
                 .description(format!("**{}** • {} `{:02}`", kind, command_message(command, FIELD_EPISODE), episode))
                 .field(
                     command_message(command, FIELD_REPO),
@@ -232,7 +270,16 @@ pub async fn handle_job(ctx: &Context, command: &serenity::all::CommandInteracti
                     format_warnings_field(&warnings, command),
                     false,
                 );
-            edit_response_embed(ctx, &mut response_msg, embed).await;
+            let Some(diff) = diff else {
+                edit_response_embed(ctx, &mut response_msg, embed).await;
+                return;
+            };
+            embed = embed.field(command_message(command, FIELD_CHANGES), diff.summary(), false);
+            let diff_name = format!("TLC diff - E{:02}.md", episode);
+            let _ = response_msg.edit(ctx, EditMessage::new()
+                .content("")
+                .embed(embed)
+                .new_attachment(CreateAttachment::bytes(diff.to_markdown().into_bytes(), diff_name))).await;
         }
         Err(e) => {
             let _ = response_msg.edit(ctx, EditMessage::new()
@@ -240,3 +287,31 @@ pub async fn handle_job(ctx: &Context, command: &serenity::all::CommandInteracti
         }
     }
 }
+
+fn tlc_diff(previous: &[u8], edited: &[u8]) -> Result<ScriptDiff, String> {
+    let (old, _) = SubstationAlpha::from_bytes(previous, false).map_err(|e| format!("previous TL: {}", e))?;
+    let (new, _) = SubstationAlpha::from_bytes(edited, false).map_err(|e| format!("edited TL: {}", e))?;
+    Ok(diff_scripts(&old, &new))
+}
+
+// Turkish is the only language subbed for so far; a server without the dictionary runs the glossary
+// check alone.
+const SPELLCHECK_LANGUAGE: &str = "tr_TR";
+
+fn spellcheck_language() -> String {
+    let aff = PathBuf::from(DICTIONARIES_DIR).join(format!("{}.aff", SPELLCHECK_LANGUAGE));
+    if aff.is_file() { SPELLCHECK_LANGUAGE.to_string() } else { String::new() }
+}
+
+// The repo's glossary, written into the job dir for pnass. None when the repo has no glossary.
+async fn fetch_glossary(fg: &Forgejo, owner_repo: &str, job_dir: &str) -> Result<Option<String>, String> {
+    let Some((b64, _)) = fg.get_file_content(owner_repo, GLOSSARY_FILE).await? else {
+        return Ok(None);
+    };
+    let path = format!("{}/glossary.md", job_dir);
+    let bytes = base64_decode_bytes(&b64).map_err(|e| format!("failed to decode {}: {}", GLOSSARY_FILE, e))?;
+    tokio::fs::write(&path, bytes)
+        .await
+        .map_err(|e| format!("cannot write {}: {}", path, e))?;
+    Ok(Some(path))
+}
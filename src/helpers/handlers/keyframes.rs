@@ -0,0 +1,58 @@
+use super::*;
+use pandora_toolchain::lib::git::episode_source;
+use pandora_toolchain::pnworker::core::KeyframesRequest;
+
+// `/keyframes` extracts from the release the subtitles are timed to, so `link` falls back to
+// SOURCE.md; a different release would put every keyframe a few frames off.
+pub async fn handle_keyframes(
+    ctx: &Context,
+    command: &serenity::all::CommandInteraction,
+) -> Option<Job> {
+    let episode = positive_u32_option(ctx, command, "episode").await?;
+    let server_id = command_server_id(ctx, command, "/keyframes").await?;
+    attached_repo(ctx, command, server_id, Some(episode)).await?;
+    forgejo_config(ctx, command, server_id).await?;
+
+    let link = match option_trimmed(command, "link") {
+        Some(link) => link,
+        None => match episode_source(server_id, command.channel_id.get(), episode).await {
+            Ok(Some(link)) => link,
+            Ok(None) => {
+                command_error(
+                    ctx,
+                    command,
+                    format!("Error: no `{}/SOURCE.md` to read the release from; pass `link` or run `/source` first.", pad2(episode)),
+                )
+                .await;
+                return None;
+            }
+            Err(e) => {
+                command_error(ctx, command, format!("Error: {}", e)).await;
+                return None;
+            }
+        },
+    };
+
+    let response_msg = working_response(ctx, command, "...").await?;
+    response_msg.react(ctx, '❌').await.ok();
+
+    let mut job = Job::new(
+        command.user.id.get(),
+        command.channel_id.get(),
+        response_msg.id.get(),
+        JobType::Keyframes,
+        response_msg.id.get(),
+        nyaaise(&link),
+        Vec::new(),
+        ctx.clone(),
+        response_msg,
+        read_lang(command.guild_id),
+        Some(server_id),
+    );
+    job.display_link = Some(display_source_link(&link));
+    job.keyframes = Some(KeyframesRequest {
+        episode,
+        tpp: option_bool(command, "tpp").unwrap_or(false),
+    });
+    Some(job)
+}
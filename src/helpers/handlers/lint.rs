@@ -0,0 +1,82 @@
+use super::*;
+use pandora_toolchain::lib::git::{lint_episode, LintRow};
+use pandora_toolchain::libkagami::lint::DEFAULT_MAX_CPS;
+
+pub async fn handle_lint(ctx: &Context, command: &serenity::all::CommandInteraction) {
+    let episode = match positive_u32_option(ctx, command, "episode").await {
+        Some(n) => n,
+        None => return,
+    };
+    let max_cps = match option_f64(command, "max_cps") {
+        Some(v) if (1.0..=100.0).contains(&v) => v as f32,
+        Some(_) => {
+            command_error(ctx, command, "Error: `max_cps` must be between 1 and 100.").await;
+            return;
+        }
+        None => DEFAULT_MAX_CPS,
+    };
+    let server_id = match command_server_id(ctx, command, "/lint").await {
+        Some(id) => id,
+        None => return,
+    };
+    let (_meta, owner_repo, repo_url) = match attached_repo(ctx, command, server_id, Some(episode)).await {
+        Some(t) => t,
+        None => return,
+    };
+    let mut response_msg = match working_response(ctx, command, "Linting…").await {
+        Some(m) => m,
+        None => return,
+    };
+
+    let outcome = match lint_episode(server_id, command.channel_id.get(), episode, max_cps).await {
+        Ok(o) => o,
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new().content(format!("Error: {}", e))).await;
+            return;
+        }
+    };
+
+    let count = |severity: &str| outcome.files.iter()
+        .flat_map(|file| &file.findings)
+        .filter(|row| row.severity == severity)
+        .count()
+        .to_string();
+    let mut embed = info_embed(command, COMMAND_LINT_REPORT)
+        .description(command_format(command, LINT_SUMMARY, &[count("error"), count("warning"), count("info")]))
+        .field(
+            command_message(command, FIELD_REPO),
+            format!("[{}]({})", owner_repo, repo_url),
+            true,
+        )
+        .field(
+            command_message(command, FIELD_EPISODE),
+            format!("`{}`", episode),
+            true,
+        );
+    for file in outcome.files {
+        let mut findings = file.findings;
+        // Errors first: the field is cut at the embed limit and the tail is only counted.
+        findings.sort_by_key(|row| severity_rank(&row.severity));
+        let lines: Vec<String> = file.diagnostics.into_iter()
+            .chain(findings.iter().map(format_lint_row))
+            .collect();
+        embed = embed.field(format!("`{}`", file.path), format_warnings_field(&lines, command), false);
+    }
+    edit_response_embed(ctx, &mut response_msg, embed).await;
+}
+
+fn format_lint_row(row: &LintRow) -> String {
+    let event = match row.event {
+        Some(n) => format!("event {}: ", n),
+        None => String::new(),
+    };
+    format!("[{}] {}: {}{}", row.severity, row.code, event, row.message)
+}
+
+fn severity_rank(severity: &str) -> u8 {
+    match severity {
+        "error" => 0,
+        "warning" => 1,
+        _ => 2,
+    }
+}
@@ -1,15 +1,23 @@
 use super::*;
+use pandora_toolchain::lib::git::{commit_release_fonts, fontconfig_roots, run_tpp, tool_failure_message};
 
 mod message;
 mod probe;
 mod batch;
 mod subs;
+mod resync;
+mod keyframes;
+mod trace;
 mod backup;
 mod smartcode;
 mod cfont;
 mod merge;
 mod release;
 mod source;
+mod lint;
+mod consistency;
+mod retime;
+mod styles;
 mod get;
 mod init;
 mod attach;
@@ -53,12 +61,19 @@ pub use self::message::handle_message;
 pub use self::probe::{handle_probe, handle_probe_component};
 pub use self::batch::{handle_batch, handle_batch_component};
 pub use self::subs::handle_subs;
+pub use self::resync::handle_resync;
+pub use self::keyframes::handle_keyframes;
+pub use self::trace::handle_trace;
 pub use self::backup::handle_backup;
 pub use self::smartcode::{handle_smartcode, handle_smartcode_preview};
 pub use self::cfont::{handle_cfont, handle_cfont_autocomplete, refresh_font_name_choices, resolve_preview_watermark_font_path, warm_font_name_cache};
 pub use self::merge::handle_merge;
 pub use self::release::handle_release;
 pub use self::source::handle_source;
+pub use self::lint::handle_lint;
+pub use self::consistency::handle_consistency;
+pub use self::retime::handle_retime;
+pub use self::styles::handle_styles;
 pub use self::get::handle_get;
 pub use self::init::handle_init;
 pub use self::attach::handle_attach;
@@ -99,6 +114,7 @@ pub use self::catlogs::handle_catlogs;
 pub use self::refreshcache::handle_refreshcache;
 
 use pandora_toolchain::pnworker::messages::*;
+use pandora_toolchain::libkagami::fontcheck::{check_ass_fonts, FontReport};
 use pandora_toolchain::pnworker::util::IntrosConfig;
 use pandora_toolchain::lib::env::standard::ENV_PATH;
 use pandora_toolchain::lumiere_broker::{
@@ -340,7 +356,7 @@ async fn smartcode_merge_upload(
         ).await;
         if !matches!(split_result, ToolResult::Success) {
             let _ = response_msg.edit(ctx, EditMessage::new()
-                .content(format!("ASS sign split failed (warnings so far: {}).", warnings.len()))).await;
+                .content(tool_failure_message("ASS sign split failed", &warnings))).await;
             return None;
         }
         if tokio::fs::metadata(&ts_local).await.is_ok() {
@@ -380,6 +396,17 @@ async fn smartcode_merge_upload(
         }
     }
 
+    // `tpp` post-processes the work copy only, so the repo TL keeps the timer's own timing.
+    if option_bool(command, "tpp").unwrap_or(false) {
+        match run_tpp(&fg, &owner_repo, &folder, &pnass_path, &tl_local, None, job_id).await {
+            Ok(note) => warnings.extend(note),
+            Err(e) => {
+                let _ = response_msg.edit(ctx, EditMessage::new().content(e)).await;
+                return None;
+            }
+        }
+    }
+
     let spec: &[CliParam] = if ts_bytes_opt.is_some() { PNASS_MERGE } else { PNASS_MERGE_TL_ONLY };
     let mut paths: HashMap<&str, PathValue> = HashMap::from([
         ("INPUT",  PathValue::from(tl_local.display().to_string())),
@@ -388,6 +415,7 @@ async fn smartcode_merge_upload(
     ]);
     if ts_bytes_opt.is_some() {
         paths.insert("MERGE", PathValue::from(ts_local.display().to_string()));
+        paths.insert("RESAMPLE", PathValue::from(option_trimmed(command, "resample").unwrap_or_default()));
     }
 
     let mut proto = Protocol::new(vec![1]);
@@ -408,7 +436,7 @@ async fn smartcode_merge_upload(
     ).await;
     if !matches!(result, ToolResult::Success) {
         let _ = response_msg.edit(ctx, EditMessage::new()
-            .content(format!("ASS merge failed (warnings so far: {}).", warnings.len()))).await;
+            .content(tool_failure_message("ASS merge failed", &warnings))).await;
         return None;
     }
 
@@ -425,6 +453,27 @@ async fn smartcode_merge_upload(
             .content("ASS merge produced no dialogue lines; release upload was skipped.")).await;
         return None;
     }
+    // `fontcheck` refuses the encode while a font or glyph would fall back; faux bold/italic only warns.
+    if option_bool(command, "fontcheck").unwrap_or(false) {
+        match server_font_coverage(server_id, merged_bytes.clone()).await {
+            Ok(report) if report.passed() => warnings.extend(report.messages()),
+            Ok(report) => {
+                println!("[{}] font check failed: {}", log_prefix, report.summary());
+                let mut content = format!("Font check failed ({}); nothing was uploaded or encoded.", report.summary());
+                for message in report.messages().iter().take(8) {
+                    let line: String = message.chars().take(200).collect();
+                    content.push_str(&format!("\n• {}", line));
+                }
+                let _ = response_msg.edit(ctx, EditMessage::new().content(content)).await;
+                return None;
+            }
+            Err(e) => {
+                let _ = response_msg.edit(ctx, EditMessage::new()
+                    .content(format!("Font check failed: {}", e))).await;
+                return None;
+            }
+        }
+    }
 
     let release_path = format!("{}/Release - {} - E{:02}.ass", folder, safe_name, episode);
     let release_commit = "Smartcode merge".to_string();
@@ -442,6 +491,15 @@ async fn smartcode_merge_upload(
         }
     };
 
+    // The encode does not depend on fonts.zip, so a failed commit is only a warning.
+    match commit_release_fonts(&fg, &owner_repo, server_id, &release_path, &merged_bytes, false).await {
+        Ok(fonts) => {
+            println!("[{}] fonts.zip files={} missing={}", log_prefix, fonts.files.len(), fonts.missing.len());
+            warnings.extend(fonts.warnings());
+        }
+        Err(e) => warnings.push(e),
+    }
+
     let source_path = format!("{}/SOURCE.md", folder);
     if link_opt.is_none() {
         println!("[{}] source from {} (skipping rewrite)", log_prefix, source_path);
@@ -919,50 +977,12 @@ async fn remove_gitkeep_for_path(fg: &Forgejo, owner_repo: &str, path: &str) {
     }
 }
 
-async fn zip_files(paths: &[PathBuf]) -> Result<Vec<u8>, String> {
-    let mut out: Vec<u8> = Vec::new();
-    {
-        let mut writer = async_zip::base::write::ZipFileWriter::new(&mut out);
-        let mut used: HashSet<String> = HashSet::new();
-        for path in paths {
-            let data = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
-            let name = path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("font")
-                .to_string();
-            if !used.insert(name.clone()) {
-                continue;
-            }
-            let entry = async_zip::ZipEntryBuilder::new(name.into(), async_zip::Compression::Deflate);
-            writer.write_entry_whole(entry, &data).await.map_err(|e| e.to_string())?;
-        }
-        writer.close().await.map_err(|e| e.to_string())?;
-    }
-    Ok(out)
-}
-
-async fn build_fonts_zip(owner_repo: &str, server_id: u64, font_names: &[String]) -> Result<Option<Vec<u8>>, String> {
-    let roots = vec![
-        PathBuf::from("DB").join("fontconfig").join(server_id.to_string()),
-        PathBuf::from("DB").join("fontconfig").join("global"),
-    ];
-    let font_files = {
-        let names = font_names.to_vec();
-        tokio::task::spawn_blocking(move || find_fonts_with_roots(&names, &roots))
-            .await
-            .map_err(|e| e.to_string())?
-    };
-    println!("[fonts] owner_repo={} requested={} found={}", owner_repo, font_names.len(), font_files.len());
-    for name in font_names {
-        println!("[fonts] requested={}", name);
-    }
-    for path in &font_files {
-        println!("[fonts] found={}", path.display());
-    }
-    if font_files.is_empty() {
-        return Ok(None);
-    }
-    Ok(Some(zip_files(&font_files).await?))
+// Font coverage of a script against the directories the encoder stages fonts from.
+async fn server_font_coverage(server_id: u64, bytes: Vec<u8>) -> Result<FontReport, String> {
+    let roots = fontconfig_roots(server_id);
+    tokio::task::spawn_blocking(move || check_ass_fonts(&bytes, &roots))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 async fn upload_release_fonts_to_drive(
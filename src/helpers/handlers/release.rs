@@ -48,39 +48,32 @@ pub async fn handle_release(ctx: &Context, command: &serenity::all::CommandInter
         }
     };
 
+    let embed_fonts = option_bool(command, "embed_fonts").unwrap_or(false);
+    let fonts = match commit_release_fonts(&fg, &owner_repo, server_id, &release_path, &release_bytes, embed_fonts).await {
+        Ok(f) => f,
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new()
+                .content(format!("Release fonts failed for `{}`: {}", folder, e))).await;
+            return;
+        }
+    };
+    println!("[release] owner_repo={} requested={} files={} missing={} embedded={}",
+        owner_repo, fonts.requested, fonts.files.len(), fonts.missing.len(), fonts.embedded);
+
     let work_dir = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
         Ok(d) => std::env::temp_dir().join(format!("pandora_release_{}", d.as_nanos())),
         Err(_) => std::env::temp_dir().join(format!("pandora_release_{}", response_msg.id.get())),
     };
-    let local_ass = work_dir.join("release.ass");
     if let Err(e) = tokio::fs::create_dir_all(&work_dir).await {
         let _ = response_msg.edit(ctx, EditMessage::new()
             .content(format!("Failed to create work dir: {}", e))).await;
         return;
     }
-    if let Err(e) = tokio::fs::write(&local_ass, &release_bytes).await {
-        let _ = response_msg.edit(ctx, EditMessage::new()
-            .content(format!("Failed to write release ASS: {}", e))).await;
-        let _ = tokio::fs::remove_dir_all(&work_dir).await;
-        return;
-    }
-
-    let release_sub = SubstationAlpha::load(local_ass, true).await;
-    let font_names = release_sub.font_names();
-    let fonts_zip = match build_fonts_zip(&owner_repo, server_id, &font_names).await {
-        Ok(p) => p,
-        Err(e) => {
-            let _ = response_msg.edit(ctx, EditMessage::new()
-                .content(format!("fonts.zip build failed for `{}`: {}", folder, e))).await;
-            let _ = tokio::fs::remove_dir_all(&work_dir).await;
-            return;
-        }
-    };
-    let fonts_field = match fonts_zip {
-        Some(zip) => {
+    let fonts_field = match (&fonts.zip, &fonts.zip_path) {
+        (Some(zip), Some(repo_zip_path)) => {
             let zip_name = format!("Fonts - {} - E{:02}.zip", safe_name, episode);
             let zip_path = work_dir.join(&zip_name);
-            if let Err(e) = tokio::fs::write(&zip_path, &zip).await {
+            if let Err(e) = tokio::fs::write(&zip_path, zip).await {
                 let _ = response_msg.edit(ctx, EditMessage::new()
                     .content(format!("Failed to write fonts zip: {}", e))).await;
                 let _ = tokio::fs::remove_dir_all(&work_dir).await;
@@ -88,10 +81,13 @@ pub async fn handle_release(ctx: &Context, command: &serenity::all::CommandInter
             }
             match upload_release_fonts_to_drive(server_id, &safe_name, &zip_path, &zip_name, &work_dir).await {
                 Ok(upload) => format!(
-                    "{}\nFolder: `{}`\nFile: `{}`",
+                    "{}\nFolder: `{}`\nFile: `{}`\nRepo: `{}` ({} file(s){})",
                     upload.link,
                     upload.folder_label(),
                     zip_name,
+                    repo_zip_path,
+                    fonts.files.len(),
+                    if fonts.embedded > 0 { format!(", {} embedded in the release", fonts.embedded) } else { String::new() },
                 ),
                 Err(e) => {
                     let _ = response_msg.edit(ctx, EditMessage::new()
@@ -101,13 +97,15 @@ pub async fn handle_release(ctx: &Context, command: &serenity::all::CommandInter
                 }
             }
         }
-        None => "No matching local fonts found".to_string(),
+        _ => "No matching local fonts found".to_string(),
     };
 
-    let anisub_field = anisub_release_upload(&meta, &owner_repo, &safe_name, episode, &release_bytes).await;
+    // AniSub gets the same bytes the repo now holds, [Fonts] included.
+    let published = fonts.release.as_deref().unwrap_or(&release_bytes);
+    let anisub_field = anisub_release_upload(&meta, &owner_repo, &safe_name, episode, published).await;
     let _ = tokio::fs::remove_dir_all(&work_dir).await;
 
-    let embed = success_embed(command, COMMAND_RELEASE_COMPLETE)
+    let mut embed = success_embed(command, COMMAND_RELEASE_COMPLETE)
         .field(
             command_message(command, FIELD_REPO),
             format!("`{}`", owner_repo),
@@ -121,10 +119,13 @@ pub async fn handle_release(ctx: &Context, command: &serenity::all::CommandInter
         .field(command_message(command, FIELD_FONTS), fonts_field, false)
         .field(
             command_message(command, FIELD_REQUESTED),
-            format!("`{}`", font_names.len()),
+            format!("`{}`", fonts.requested),
             true,
         )
         .field("AniSub", anisub_field, false);
+    if !fonts.missing.is_empty() {
+        embed = embed.field(command_message(command, FIELD_WARNINGS), format_warnings_field(&fonts.warnings(), command), false);
+    }
     edit_response_embed(ctx, &mut response_msg, embed).await;
 }
 
@@ -0,0 +1,69 @@
+use super::*;
+use pandora_toolchain::lib::git::episode_source;
+use pandora_toolchain::pnworker::core::ResyncRequest;
+
+// `/resync` moves an episode's TL/TS from the release they were timed against to another one. The
+// old release defaults to SOURCE.md because that is what the subtitles were last checked against;
+// `reference` only exists for repos whose SOURCE.md was never written or is already wrong.
+pub async fn handle_resync(
+    ctx: &Context,
+    command: &serenity::all::CommandInteraction,
+) -> Option<Job> {
+    let episode = positive_u32_option(ctx, command, "episode").await?;
+    let Some(link) = option_trimmed(command, "link") else {
+        command_error(ctx, command, "Error: `link` is required.").await;
+        return None;
+    };
+    let server_id = command_server_id(ctx, command, "/resync").await?;
+    attached_repo(ctx, command, server_id, Some(episode)).await?;
+    forgejo_config(ctx, command, server_id).await?;
+
+    let reference = match option_trimmed(command, "reference") {
+        Some(reference) => reference,
+        None => match episode_source(server_id, command.channel_id.get(), episode).await {
+            Ok(Some(reference)) => reference,
+            Ok(None) => {
+                command_error(
+                    ctx,
+                    command,
+                    format!("Error: no `{}/SOURCE.md` to resync from; pass `reference` or run `/source` first.", pad2(episode)),
+                )
+                .await;
+                return None;
+            }
+            Err(e) => {
+                command_error(ctx, command, format!("Error: {}", e)).await;
+                return None;
+            }
+        },
+    };
+    if source_link(&reference) == source_link(&link) {
+        command_error(ctx, command, "Error: the new link is the source the subtitles are already timed to.").await;
+        return None;
+    }
+
+    let response_msg = working_response(ctx, command, "...").await?;
+    response_msg.react(ctx, '❌').await.ok();
+
+    let mut job = Job::new(
+        command.user.id.get(),
+        command.channel_id.get(),
+        response_msg.id.get(),
+        JobType::Resync,
+        response_msg.id.get(),
+        nyaaise(&link),
+        Vec::new(),
+        ctx.clone(),
+        response_msg,
+        read_lang(command.guild_id),
+        Some(server_id),
+    );
+    job.display_link = Some(display_source_link(&link));
+    job.resync = Some(ResyncRequest {
+        episode,
+        reference,
+        target: link,
+        target_ready: false,
+    });
+    Some(job)
+}
@@ -0,0 +1,226 @@
+use super::*;
+use pandora_toolchain::libkagami::timing::parse_offset;
+
+pub async fn handle_retime(ctx: &Context, command: &serenity::all::CommandInteraction) {
+    let episode = match positive_u32_option(ctx, command, "episode").await {
+        Some(n) => n,
+        None => return,
+    };
+    let shift = option_trimmed(command, "shift").unwrap_or_default();
+    let retime = option_trimmed(command, "retime").unwrap_or_default();
+    if shift.is_empty() && retime.is_empty() {
+        command_error(ctx, command, "Error: give `shift`, `retime`, or both.").await;
+        return;
+    }
+    if !shift.is_empty() && let Err(e) = parse_offset(&shift) {
+        command_error(ctx, command, &format!("Error: `shift`: {}", e)).await;
+        return;
+    }
+    if !retime.is_empty() && !retime.contains(':') {
+        command_error(ctx, command, "Error: `retime` must be `FROM:TO`, e.g. `25:23.976` or `23.976:timecodes`.").await;
+        return;
+    }
+    let kinds: &[&str] = match option_str(command, "file").unwrap_or("both") {
+        "TL" => &["TL"],
+        "TS" => &["TS"],
+        _ => &["TL", "TS"],
+    };
+    let fps = option_f64(command, "fps").unwrap_or(23.976);
+    let styles = option_trimmed(command, "styles").unwrap_or_default();
+    let timecodes = option_attachment(command, "timecodes");
+    if retime.ends_with("timecodes") && timecodes.is_none() {
+        command_error(ctx, command, "Error: `retime` to `timecodes` needs a `timecodes` attachment.").await;
+        return;
+    }
+    let server_id = match command_server_id(ctx, command, "/retime").await {
+        Some(id) => id,
+        None => return,
+    };
+    let (meta, owner_repo, repo_url) = match attached_repo(ctx, command, server_id, Some(episode)).await {
+        Some(t) => t,
+        None => return,
+    };
+    let (forgejo_base, api_key) = match forgejo_config(ctx, command, server_id).await {
+        Some(t) => t,
+        None => return,
+    };
+    let mut response_msg = match working_response(ctx, command, "Retiming…").await {
+        Some(m) => m,
+        None => return,
+    };
+
+    let fg = match Forgejo::new(forgejo_base, api_key) {
+        Ok(f) => f,
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new()
+                .content(format!("Forgejo init failed: {}", e))).await;
+            return;
+        }
+    };
+    let pnass_path = match get_pandora_env().get(PNASS) {
+        Some(p) if !p.is_empty() => p.clone(),
+        _ => {
+            let _ = response_msg.edit(ctx, EditMessage::new()
+                .content("Error: PNASS binary path is not set in DB/config/global/environment/env.pandora.")).await;
+            return;
+        }
+    };
+
+    let job_id = response_msg.id.get();
+    let work_dir = std::env::temp_dir().join(format!("pandora_retime_{}", job_id));
+    if let Err(e) = tokio::fs::create_dir_all(&work_dir).await {
+        let _ = response_msg.edit(ctx, EditMessage::new()
+            .content(format!("Failed to create work dir: {}", e))).await;
+        return;
+    }
+    let timecodes_path = match timecodes {
+        Some(attachment) => {
+            let path = work_dir.join("timecodes.txt");
+            let written = match attachment.download().await {
+                Ok(bytes) => tokio::fs::write(&path, bytes).await.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = written {
+                let _ = tokio::fs::remove_dir_all(&work_dir).await;
+                let _ = response_msg.edit(ctx, EditMessage::new()
+                    .content(format!("Failed to download timecodes: {}", e))).await;
+                return;
+            }
+            path.display().to_string()
+        }
+        None => String::new(),
+    };
+
+    let params = RetimeParams { shift: &shift, retime: &retime, fps, timecodes: &timecodes_path, styles: &styles };
+    let commit_message = params.commit_summary();
+    let safe_name = meta.name.clone().unwrap_or_default().replace('/', "-");
+    let folder = pad2(episode);
+    let mut updated: Vec<(String, Vec<String>)> = Vec::new();
+    let mut failure: Option<String> = None;
+    for kind in kinds {
+        let ass_path = format!("{}/{} - {} - E{:02}.ass", folder, kind, safe_name, episode);
+        let bytes = match read_repo_ass(&fg, &owner_repo, &ass_path).await {
+            Ok(Some((bytes, _))) => bytes,
+            Ok(None) => continue,
+            Err(e) => {
+                failure = Some(format!("Failed to fetch {}: {}", kind, e));
+                break;
+            }
+        };
+        let input = work_dir.join(format!("{}.ass", kind.to_lowercase()));
+        let output = work_dir.join(format!("{}.retimed.ass", kind.to_lowercase()));
+        let (retimed, warnings) = match retime_repo_ass(&pnass_path, &input, &output, &bytes, &params, job_id).await {
+            Ok(r) => r,
+            Err(e) => {
+                failure = Some(format!("`{}`: {}", ass_path, e));
+                break;
+            }
+        };
+        match upsert_repo_ass(&fg, &owner_repo, &ass_path, &retimed, &format!("{} {}", kind, commit_message)).await {
+            Ok(uploaded) => updated.push((uploaded, warnings)),
+            Err(e) => {
+                failure = Some(format!("Failed to upload {}: {}", kind, e));
+                break;
+            }
+        }
+    }
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    if let Some(e) = failure {
+        let _ = response_msg.edit(ctx, EditMessage::new().content(e)).await;
+        return;
+    }
+    if updated.is_empty() {
+        let _ = response_msg.edit(ctx, EditMessage::new()
+            .content(format!("No TL or TS file found in `{}/` for episode {}.", folder, episode))).await;
+        return;
+    }
+
+    let mut embed = success_embed(command, COMMAND_RETIMED)
+        .description(format!("`{}`", commit_message))
+        .field(
+            command_message(command, FIELD_REPO),
+            format!("[{}]({})", owner_repo, repo_url),
+            true,
+        )
+        .field(
+            command_message(command, FIELD_EPISODE),
+            format!("`{}`", episode),
+            true,
+        );
+    for (path, warnings) in updated {
+        let value = if warnings.is_empty() {
+            command_message(command, RETIME_FILE_UPDATED)
+        } else {
+            format_warnings_field(&warnings, command)
+        };
+        embed = embed.field(format!("`{}`", path), value, false);
+    }
+    edit_response_embed(ctx, &mut response_msg, embed).await;
+}
+
+struct RetimeParams<'a> {
+    shift: &'a str,
+    retime: &'a str,
+    fps: f64,
+    timecodes: &'a str,
+    styles: &'a str,
+}
+
+impl RetimeParams<'_> {
+    // "Retime: 25:23.976, shift +1.5s (Default, Alt)" — prefixed with the file kind per commit.
+    fn commit_summary(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        if !self.retime.is_empty() {
+            parts.push(self.retime.to_string());
+        }
+        if !self.shift.is_empty() {
+            parts.push(format!("shift {}", self.shift));
+        }
+        let mut summary = format!("retime: {}", parts.join(", "));
+        if !self.styles.is_empty() {
+            summary.push_str(&format!(" ({})", self.styles));
+        }
+        summary
+    }
+}
+
+async fn retime_repo_ass(
+    pnass_path: &str,
+    input: &Path,
+    output: &Path,
+    bytes: &[u8],
+    params: &RetimeParams<'_>,
+    job_id: u64,
+) -> Result<(Vec<u8>, Vec<String>), String> {
+    tokio::fs::write(input, bytes).await.map_err(|e| format!("failed to write work copy: {}", e))?;
+    let mut warnings: Vec<String> = Vec::new();
+    let mut proto = Protocol::new(vec![1]);
+    let result = run_tool(
+        pnass_path,
+        PNASS_RETIME,
+        &HashMap::from([
+            ("INPUT", PathValue::from(input.display().to_string())),
+            ("OUTPUT", PathValue::from(output.display().to_string())),
+            ("SHIFT", PathValue::from(params.shift.to_string())),
+            ("RETIME", PathValue::from(params.retime.to_string())),
+            ("FPS", PathValue::from(params.fps.to_string())),
+            ("TIMECODES", PathValue::from(params.timecodes.to_string())),
+            ("STYLES", PathValue::from(params.styles.to_string())),
+        ]),
+        job_id,
+        &mut proto,
+        |data| {
+            if data.get(0).and_then(|v| v.as_str()) == Some("4")
+                && let Some(line) = data.get(1).and_then(|v| v.as_str())
+            {
+                warnings.push(line.to_string());
+            }
+            None
+        },
+    ).await;
+    if !matches!(result, ToolResult::Success) {
+        return Err(tool_failure_message("retime failed", &warnings));
+    }
+    let retimed = tokio::fs::read(output).await.map_err(|e| format!("failed to read retimed ASS: {}", e))?;
+    Ok((retimed, warnings))
+}
@@ -174,7 +174,7 @@ async fn load_preview_ass(job_id: u64, kind: &str, bytes: &[u8]) -> Result<Subst
         .map_err(|e| e.to_string())?;
     let script = SubstationAlpha::load(path.clone(), true).await;
     let _ = tokio::fs::remove_file(path).await;
-    Ok(script)
+    script.map_err(|e| e.to_string())
 }
 
 fn preview_temp_ass_path(job_id: u64, kind: &str) -> PathBuf {
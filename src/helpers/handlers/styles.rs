@@ -0,0 +1,156 @@
+use super::*;
+use pandora_toolchain::lib::git::{apply_episode_styles, read_style_library, update_style_library, StyleLibrary};
+
+pub async fn handle_styles(ctx: &Context, command: &serenity::all::CommandInteraction) {
+    let server_id = match command_server_id(ctx, command, "/styles").await {
+        Some(id) => id,
+        None => return,
+    };
+    match subcommand_options(command).map(|(name, _)| name).unwrap_or("show") {
+        "show" => show_styles(ctx, command, server_id).await,
+        "import" => import_styles(ctx, command, server_id).await,
+        "remove" => remove_styles(ctx, command, server_id).await,
+        "apply" => apply_styles(ctx, command, server_id).await,
+        other => command_error(ctx, command, format!("Unknown styles subcommand `{}`.", other)).await,
+    }
+}
+
+async fn show_styles(ctx: &Context, command: &serenity::all::CommandInteraction, server_id: u64) {
+    match read_style_library(server_id).await {
+        Ok(library) => {
+            let embed = library_embed(info_embed(command, COMMAND_STYLE_LIBRARY), command, &library);
+            command.create_response(ctx, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().embed(embed)
+            )).await.ok();
+        }
+        Err(e) => command_error(ctx, command, format!("Error: {}", e)).await,
+    }
+}
+
+async fn import_styles(ctx: &Context, command: &serenity::all::CommandInteraction, server_id: u64) {
+    let Some(attachment) = option_attachment(command, "file") else {
+        command_error(ctx, command, "Error: `file` attachment is required.").await;
+        return;
+    };
+    let replace = option_bool(command, "replace").unwrap_or(false);
+    let resample = option_trimmed(command, "resample");
+    let mut response_msg = match working_response(ctx, command, "Working...").await {
+        Some(m) => m,
+        None => return,
+    };
+    let bytes = match attachment.download().await {
+        Ok(b) => b,
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new()
+                .content(format!("Failed to download attachment: {}", e))).await;
+            return;
+        }
+    };
+    match update_style_library(server_id, Some(&bytes), replace, &[], resample.as_deref()).await {
+        Ok(library) => {
+            let embed = success_embed(command, COMMAND_UPDATED)
+                .description(format!("Imported `{}` into `styles.ass`.", attachment.filename));
+            edit_response_embed(ctx, &mut response_msg, library_embed(embed, command, &library)).await;
+        }
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new()
+                .content(format!("Style import failed: {}", e))).await;
+        }
+    }
+}
+
+async fn remove_styles(ctx: &Context, command: &serenity::all::CommandInteraction, server_id: u64) {
+    let names: Vec<String> = option_str(command, "names")
+        .unwrap_or("")
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    if names.is_empty() {
+        command_error(ctx, command, "Error: `names` must list at least one style.").await;
+        return;
+    }
+    match update_style_library(server_id, None, false, &names, None).await {
+        Ok(library) if library.removed.is_empty() => {
+            command_error(ctx, command, "Error: none of those styles are in the library.").await;
+        }
+        Ok(library) => {
+            let embed = success_embed(command, COMMAND_UPDATED)
+                .description(format!("Removed {}.", quoted_list(&library.removed)));
+            command.create_response(ctx, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().embed(library_embed(embed, command, &library))
+            )).await.ok();
+        }
+        Err(e) => command_error(ctx, command, format!("Error: {}", e)).await,
+    }
+}
+
+async fn apply_styles(ctx: &Context, command: &serenity::all::CommandInteraction, server_id: u64) {
+    let episode = match positive_u32_option(ctx, command, "episode").await {
+        Some(n) => n,
+        None => return,
+    };
+    let kinds: &[&str] = match option_str(command, "file").unwrap_or("both") {
+        "TL" => &["TL"],
+        "TS" => &["TS"],
+        _ => &["TL", "TS"],
+    };
+    let add = option_trimmed(command, "add").unwrap_or_default();
+    let resample = option_trimmed(command, "resample").unwrap_or_default();
+    let mut response_msg = match working_response(ctx, command, "Applying styles…").await {
+        Some(m) => m,
+        None => return,
+    };
+
+    let outcome = match apply_episode_styles(server_id, command.channel_id.get(), episode, kinds, &add, &resample).await {
+        Ok(o) => o,
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new().content(format!("Error: {}", e))).await;
+            return;
+        }
+    };
+    let mut embed = success_embed(command, COMMAND_STYLES_APPLIED)
+        .field(
+            command_message(command, FIELD_REPO),
+            format!("`{}`", outcome.owner_repo),
+            true,
+        )
+        .field(
+            command_message(command, FIELD_EPISODE),
+            format!("`{}`", episode),
+            true,
+        );
+    for file in outcome.files {
+        let value = if file.changed {
+            format_warnings_field(&file.notes, command)
+        } else if file.notes.is_empty() {
+            command_message(command, STYLES_FILE_UNCHANGED)
+        } else {
+            format!("{}\n{}", command_message(command, STYLES_FILE_UNCHANGED), format_warnings_field(&file.notes, command))
+        };
+        embed = embed.field(format!("`{}`", file.path), value, false);
+    }
+    edit_response_embed(ctx, &mut response_msg, embed).await;
+}
+
+fn library_embed(embed: CreateEmbed, command: &serenity::all::CommandInteraction, library: &StyleLibrary) -> CreateEmbed {
+    let mut value = command_format(command, STYLE_LIBRARY_SUMMARY, &[
+        library.styles.len().to_string(),
+        library.playresx.to_string(),
+        library.playresy.to_string(),
+    ]);
+    if !library.styles.is_empty() {
+        value.push('\n');
+        value.push_str(&format_warnings_field(&library.styles, command));
+    }
+    let mut embed = embed.field(command_message(command, FIELD_STYLES), value, false);
+    let changes = library.changes.summary();
+    if !changes.is_empty() {
+        embed = embed.field(command_message(command, FIELD_CHANGES), changes, false);
+    }
+    embed
+}
+
+fn quoted_list(names: &[String]) -> String {
+    names.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(", ")
+}
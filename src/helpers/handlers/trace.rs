@@ -0,0 +1,131 @@
+use super::*;
+use pandora_toolchain::kagami_trace::TracePreset;
+use pandora_toolchain::lib::git::episode_source;
+use pandora_toolchain::lib::mpeg::clip::FrameCrop;
+use pandora_toolchain::libkagami::complex::types::AssTime;
+use pandora_toolchain::pnworker::core::TraceRequest;
+
+// `/trace` reads the frame from the video the subtitles are timed to: a `/probe` result when the
+// release is a pack, otherwise the episode's SOURCE.md. `episode` is what places the drawing in
+// the script's PlayRes, so it is worth passing even alongside `job_id`.
+pub async fn handle_trace(
+    ctx: &Context,
+    command: &serenity::all::CommandInteraction,
+) -> Option<Job> {
+    let Some(at) = option_trimmed(command, "time").and_then(|time| time.parse::<AssTime>().ok()) else {
+        command_error(ctx, command, "Error: `time` must be an ASS timestamp like `0:01:23.45`.").await;
+        return None;
+    };
+    let crop = match option_trimmed(command, "crop") {
+        Some(raw) => match raw.parse::<FrameCrop>() {
+            Ok(crop) => Some(crop),
+            Err(e) => {
+                command_error(ctx, command, format!("Error: `crop`: {}", e)).await;
+                return None;
+            }
+        },
+        None => None,
+    };
+    let preset = match option_str(command, "preset").unwrap_or("illustration") {
+        "logo_ui" => TracePreset::LogoUi,
+        "photo" => TracePreset::Photo,
+        "gradient" => TracePreset::Gradient,
+        _ => TracePreset::Illustration,
+    };
+    let probe_job_id = option_str(command, "job_id").map(str::trim).filter(|id| !id.is_empty());
+    let episode = match option_i64(command, "episode") {
+        Some(_) => Some(positive_u32_option(ctx, command, "episode").await?),
+        None => None,
+    };
+    if probe_job_id.is_none() && episode.is_none() {
+        command_error(ctx, command, "Error: pass `job_id` with `index`, or an `episode` with a SOURCE.md.").await;
+        return None;
+    }
+    let server_id = command_server_id(ctx, command, "/trace").await?;
+    if let Some(episode) = episode {
+        attached_repo(ctx, command, server_id, Some(episode)).await?;
+        forgejo_config(ctx, command, server_id).await?;
+    }
+
+    let (source, probe_job_id, file_index) = match (probe_job_id, episode) {
+        (Some(raw), _) => {
+            let Ok(probe_job_id) = raw.parse::<u64>() else {
+                command_error(ctx, command, "Error: job_id must be a number").await;
+                return None;
+            };
+            let index = match option_i64(command, "index") {
+                Some(index) if index >= 0 => index as u64,
+                _ => {
+                    command_error(ctx, command, "Error: `index` is required with `job_id`.").await;
+                    return None;
+                }
+            };
+            let db = match JobDb::new().await {
+                Ok(db) => db,
+                Err(e) => {
+                    command_error(ctx, command, format!("Error: failed to open job DB: {}", e)).await;
+                    return None;
+                }
+            };
+            let source = match db.get_job(probe_job_id).await {
+                Ok(Some(row)) => row.link,
+                Ok(None) => {
+                    command_error(ctx, command, "Error: probe job was not found.").await;
+                    return None;
+                }
+                Err(e) => {
+                    command_error(ctx, command, format!("Error: failed to read probe job: {}", e)).await;
+                    return None;
+                }
+            };
+            (source, Some(probe_job_id), Some(index))
+        }
+        (None, Some(episode)) => match episode_source(server_id, command.channel_id.get(), episode).await {
+            Ok(Some(link)) => (link, None, None),
+            Ok(None) => {
+                command_error(
+                    ctx,
+                    command,
+                    format!("Error: no `{}/SOURCE.md` to read the frame from; pass `job_id` or run `/source` first.", pad2(episode)),
+                )
+                .await;
+                return None;
+            }
+            Err(e) => {
+                command_error(ctx, command, format!("Error: {}", e)).await;
+                return None;
+            }
+        },
+        (None, None) => return None,
+    };
+
+    let response_msg = working_response(ctx, command, "...").await?;
+    response_msg.react(ctx, '❌').await.ok();
+
+    let mut job = Job::new(
+        command.user.id.get(),
+        command.channel_id.get(),
+        response_msg.id.get(),
+        JobType::Trace,
+        response_msg.id.get(),
+        nyaaise(&source),
+        Vec::new(),
+        ctx.clone(),
+        response_msg,
+        read_lang(command.guild_id),
+        Some(server_id),
+    );
+    job.display_link = Some(match file_index {
+        Some(index) => format!("{} • file #{}", display_source_link(&source), index),
+        None => display_source_link(&source),
+    });
+    job.probe_job_id = probe_job_id;
+    job.probe_file_index = file_index;
+    job.trace = Some(TraceRequest {
+        at_centiseconds: at.total_centiseconds(),
+        crop,
+        preset,
+        episode,
+    });
+    Some(job)
+}
@@ -122,12 +122,12 @@ pub async fn handle_ts_message(ctx: &Context, msg: &Message, parts: &[&str]) {
         (attachment.filename.clone(), attachment_bytes)
     };
 
-    let mut conversion_warning: Option<String> = None;
+    let mut conversion_warnings: Vec<String> = Vec::new();
     let ass_bytes = match ensure_ass(&source_name, &source_bytes).await {
         Ok(converted) => {
-            if let Some(warning) = converted.warning {
+            if !converted.warnings.is_empty() {
                 println!("[ts] id={} converted={}", job_id, source_name);
-                conversion_warning = Some(warning);
+                conversion_warnings = converted.warnings;
             }
             converted.bytes
         }
@@ -152,7 +152,14 @@ pub async fn handle_ts_message(ctx: &Context, msg: &Message, parts: &[&str]) {
 
     let anime_name = meta.name.clone().unwrap_or_default();
     let title = if anime_name.is_empty() { owner.clone() } else { format!("{} - {}", owner, anime_name) };
-    let mut sub = SubstationAlpha::load(PathBuf::from(&output_path), false).await;
+    let mut sub = match SubstationAlpha::load(PathBuf::from(&output_path), false).await {
+        Ok(sub) => sub,
+        Err(e) => {
+            let _ = response_msg.edit(ctx, EditMessage::new()
+                .content(format!("Failed to load ASS: {}", e))).await;
+            return;
+        }
+    };
     sub.script_info.title = title;
     if sub.dump_to_file(PathBuf::from(&output_path)).await.is_err() {
         let _ = response_msg.edit(ctx, EditMessage::new()
@@ -186,8 +193,8 @@ pub async fn handle_ts_message(ctx: &Context, msg: &Message, parts: &[&str]) {
                 .field("Repo", format!("`{}`", owner_repo), true)
                 .field("File", format!("`{}`", uploaded_path), true)
                 .field("Job", format!("`{}`", job_id), true);
-            if let Some(warning) = &conversion_warning {
-                embed = embed.field("Warnings", warning, false);
+            if !conversion_warnings.is_empty() {
+                embed = embed.field("Warnings", conversion_warnings.join("\n"), false);
             }
             let _ = response_msg.edit(ctx, EditMessage::new().content("").embed(embed)).await;
         }
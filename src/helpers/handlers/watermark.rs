@@ -41,6 +41,13 @@ pub async fn handle_touchwatermark(ctx: &Context, command: &serenity::all::Comma
     }
     let script = SubstationAlpha::load(temp.clone(), true).await;
     tokio::fs::remove_file(&temp).await.ok();
+    let script = match script {
+        Ok(script) => script,
+        Err(e) => {
+            command_error(ctx, command, format!("Error: watermark could not be read: {}", e)).await;
+            return;
+        }
+    };
     if script.events.is_empty() {
         command_error(
             ctx,
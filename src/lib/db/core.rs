@@ -457,6 +457,9 @@ pub fn job_type_label(job_type: i64) -> &'static str {
         15 => "StudioPreview",
         16 => "Batch",
         17 => "Subs",
+        18 => "Resync",
+        19 => "Keyframes",
+        20 => "Trace",
         _ => "Unknown",
     }
 }
@@ -6,7 +6,16 @@ use crate::lib::env::standard::PNASS;
 use crate::lib::http::forgejo::core::{base64_encode, base64_encode_bytes, Forgejo};
 use crate::lib::http::mal::core::{fetch_anime, AnimeKind, AnimeMeta};
 use crate::lib::protocol::core::Protocol;
-use crate::pnworker::tools::{PNASS_MERGE, PNASS_MERGE_TL_ONLY, PNASS_SPLIT_SIGNS};
+use crate::libkagami::consistency::{check_consistency, ConsistencyReport, EpisodeFiles, ScriptFile};
+use crate::libkagami::core::{append_fonts, ResampleMode, SubstationAlpha};
+use crate::libkagami::fontcheck::check_ass_fonts;
+use crate::libkagami::fontcollect::{collect_fonts, CollectedFont};
+use crate::libkagami::motion::parse_motion;
+use crate::libkagami::spellcheck::{Glossary, GLOSSARY_FILE};
+use crate::libkagami::styles::{import_styles, library_text, remove_styles, StyleChanges};
+use crate::pnworker::tools::{
+    PNASS_APPLY_STYLES, PNASS_LINT, PNASS_MERGE, PNASS_MERGE_TL_ONLY, PNASS_MOTION, PNASS_SPLIT_SIGNS, PNASS_SYNCMAP, PNASS_TPP,
+};
 use crate::pnworker::util::{run_tool, CliParam, PathValue, ToolResult};
 
 pub struct Credits {
@@ -319,6 +328,9 @@ pub async fn smartcode_merge(
     channel_id: u64,
     episode: u32,
     link_opt: Option<String>,
+    tpp: bool,
+    resample: Option<String>,
+    fontcheck: bool,
 ) -> Result<SmartMergeResult, String> {
     let meta = read_channel_meta(server_id, channel_id);
     if meta.mal_id.is_none() {
@@ -350,20 +362,8 @@ pub async fn smartcode_merge(
 
     let link = match link_opt {
         Some(ref l) => l.clone(),
-        None => {
-            let source_md_path = format!("{}/SOURCE.md", folder);
-            let b64 = fg.get_file_content(&owner_repo, &source_md_path).await?
-                .ok_or_else(|| format!("`link` was not provided and no {} exists in the repo to read it from.", source_md_path))?
-                .0;
-            let bytes = base64_decode_bytes(&b64).map_err(|e| format!("failed to decode {}: {}", source_md_path, e))?;
-            let text = String::from_utf8(bytes).map_err(|e| format!("{} is not valid UTF-8: {}", source_md_path, e))?;
-            text.lines()
-                .map(str::trim)
-                .find(|l| !l.is_empty() && !l.starts_with(';'))
-                .map(|l| l.trim_start_matches('#').trim().to_string())
-                .filter(|s| !s.is_empty())
-                .ok_or_else(|| format!("{} does not contain a parseable source link.", source_md_path))?
-        }
+        None => read_source_link(&fg, &owner_repo, &folder).await?
+            .ok_or_else(|| format!("`link` was not provided and no {}/SOURCE.md exists in the repo to read it from.", folder))?,
     };
 
     let pnass_path = match get_pandora_env().get(PNASS) {
@@ -378,7 +378,7 @@ pub async fn smartcode_merge(
 
     let result = smartcode_merge_inner(
         &fg, &owner_repo, &tl_path, &ts_path, &folder, &safe_name, episode,
-        tl_bytes, &mut ts_bytes_opt, &link, link_opt.is_some(), &pnass_path, &wrap_style, job_id, &work_dir,
+        tl_bytes, &mut ts_bytes_opt, &link, link_opt.is_some(), &pnass_path, &wrap_style, tpp, resample.as_deref(), server_id, fontcheck, job_id, &work_dir,
     ).await;
     let _ = tokio::fs::remove_dir_all(&work_dir).await;
 
@@ -427,6 +427,10 @@ async fn smartcode_merge_inner(
     link_from_arg: bool,
     pnass_path: &str,
     wrap_style: &str,
+    tpp: bool,
+    resample: Option<&str>,
+    server_id: u64,
+    fontcheck: bool,
     job_id: u64,
     work_dir: &Path,
 ) -> Result<(Vec<u8>, String, String, Vec<String>), String> {
@@ -462,7 +466,7 @@ async fn smartcode_merge_inner(
             },
         ).await;
         if !matches!(split_result, ToolResult::Success) {
-            return Err(format!("ASS sign split failed (warnings so far: {}).", warnings.len()));
+            return Err(tool_failure_message("ASS sign split failed", &warnings));
         }
         if tokio::fs::metadata(&ts_local).await.is_ok() {
             let split_tl_bytes = tokio::fs::read(&split_tl_local).await
@@ -478,6 +482,12 @@ async fn smartcode_merge_inner(
         }
     }
 
+    // Post-processing only touches the work copy: the repo TL keeps the timer's own timing and
+    // only the release carries the leads and snaps.
+    if tpp && let Some(note) = run_tpp(fg, owner_repo, folder, pnass_path, &tl_local, None, job_id).await? {
+        warnings.push(note);
+    }
+
     let spec: &[CliParam] = if ts_bytes_opt.is_some() { PNASS_MERGE } else { PNASS_MERGE_TL_ONLY };
     let mut paths: HashMap<&str, PathValue> = HashMap::from([
         ("INPUT",  PathValue::from(tl_local.display().to_string())),
@@ -486,6 +496,7 @@ async fn smartcode_merge_inner(
     ]);
     if ts_bytes_opt.is_some() {
         paths.insert("MERGE", PathValue::from(ts_local.display().to_string()));
+        paths.insert("RESAMPLE", PathValue::from(resample.unwrap_or_default().to_string()));
     }
 
     let mut proto = Protocol::new(vec![1]);
@@ -505,7 +516,7 @@ async fn smartcode_merge_inner(
         },
     ).await;
     if !matches!(result, ToolResult::Success) {
-        return Err(format!("ASS merge failed (warnings so far: {}).", warnings.len()));
+        return Err(tool_failure_message("ASS merge failed", &warnings));
     }
 
     let merged_bytes = tokio::fs::read(&merged_local).await
@@ -513,10 +524,25 @@ async fn smartcode_merge_inner(
     if !ass_has_dialogue(&merged_bytes) {
         return Err("ASS merge produced no dialogue lines; release upload was skipped.".to_string());
     }
+    if fontcheck {
+        let (bytes, roots) = (merged_bytes.clone(), fontconfig_roots(server_id));
+        let report = tokio::task::spawn_blocking(move || check_ass_fonts(&bytes, &roots))
+            .await
+            .map_err(|e| format!("font check failed: {}", e))??;
+        if !report.passed() {
+            return Err(tool_failure_message(&format!("font check failed ({})", report.summary()), &report.messages()));
+        }
+        warnings.extend(report.messages());
+    }
 
     let release_path = format!("{}/Release - {} - E{:02}.ass", folder, safe_name, episode);
     let uploaded_release_path = upsert_repo_ass(fg, owner_repo, &release_path, &merged_bytes, "Smartcode merge").await
         .map_err(|e| format!("merged ASS upload to {} failed: {}", release_path, e))?;
+    // The encode does not depend on fonts.zip, so a failed commit is only a warning.
+    match commit_release_fonts(fg, owner_repo, server_id, &release_path, &merged_bytes, false).await {
+        Ok(fonts) => warnings.extend(fonts.warnings()),
+        Err(e) => warnings.push(e),
+    }
 
     let source_path = format!("{}/SOURCE.md", folder);
     if link_from_arg {
@@ -530,6 +556,950 @@ async fn smartcode_merge_inner(
     Ok((merged_bytes, uploaded_release_path, source_path, warnings))
 }
 
+#[derive(serde::Serialize)]
+pub struct LintRow {
+    pub severity: String,
+    pub code: String,
+    pub event: Option<usize>,
+    pub message: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct FileLint {
+    pub path: String,
+    pub diagnostics: Vec<String>,
+    pub findings: Vec<LintRow>,
+}
+
+pub struct LintOutcome {
+    pub owner_repo: String,
+    pub files: Vec<FileLint>,
+}
+
+// Lints the episode's TL and TS as they are in the repo, before any merge touches them. pnass
+// reports findings as structured opcode 4 rows and load diagnostics as plain ones.
+pub async fn lint_episode(
+    server_id: u64,
+    channel_id: u64,
+    episode: u32,
+    max_cps: f32,
+) -> Result<LintOutcome, String> {
+    let meta = read_channel_meta(server_id, channel_id);
+    if meta.mal_id.is_none() {
+        return Err("this channel is not attached to an anime. Run /init or /attach first.".to_string());
+    }
+    let max_ep = meta.episode_count.unwrap_or(0);
+    if episode < 1 || episode > max_ep {
+        return Err(format!("`episode` must be between 1 and {}.", max_ep));
+    }
+    let repo_url = meta.repo_url.clone().filter(|s| !s.is_empty())
+        .ok_or_else(|| "this channel has no repo URL configured.".to_string())?;
+    let (owner, repo) = parse_repo_url(&repo_url).map_err(|e| format!("bad repo URL in meta: {}", e))?;
+    let owner_repo = format!("{}/{}", owner, repo);
+    let safe_name = meta.name.clone().unwrap_or_default().replace('/', "-");
+
+    let (forgejo_base, api_key) = forgejo_config(server_id).await?;
+    let fg = Forgejo::new(forgejo_base, api_key).map_err(|e| format!("Forgejo init failed: {}", e))?;
+    let pnass_path = match get_pandora_env().get(PNASS) {
+        Some(p) if !p.is_empty() => p.clone(),
+        _ => return Err("PNASS binary path is not set in DB/config/global/environment/env.pandora.".to_string()),
+    };
+
+    let folder = pad2(episode);
+    let job_id = nano_id();
+    let work_dir = std::env::temp_dir().join(format!("pandora_lint_{}", job_id));
+    tokio::fs::create_dir_all(&work_dir).await.map_err(|e| format!("failed to create work dir: {}", e))?;
+
+    let mut files = Vec::new();
+    let mut result = Ok(());
+    for kind in ["TL", "TS"] {
+        let ass_path = format!("{}/{} - {} - E{:02}.ass", folder, kind, safe_name, episode);
+        let (bytes, path) = match read_repo_ass(&fg, &owner_repo, &ass_path).await {
+            Ok(Some(found)) => found,
+            Ok(None) => continue,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        let local = work_dir.join(format!("{}.ass", kind.to_lowercase()));
+        match lint_ass_file(&pnass_path, &local, &bytes, max_cps, job_id).await {
+            Ok((diagnostics, findings)) => files.push(FileLint { path, diagnostics, findings }),
+            Err(e) => {
+                result = Err(format!("{}: {}", path, e));
+                break;
+            }
+        }
+    }
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    result?;
+
+    if files.is_empty() {
+        return Err(format!("no TL or TS file found in {}/ for episode {}.", folder, episode));
+    }
+    Ok(LintOutcome { owner_repo, files })
+}
+
+async fn lint_ass_file(
+    pnass_path: &str,
+    local: &Path,
+    bytes: &[u8],
+    max_cps: f32,
+    job_id: u64,
+) -> Result<(Vec<String>, Vec<LintRow>), String> {
+    tokio::fs::write(local, bytes).await.map_err(|e| format!("failed to write work copy: {}", e))?;
+    let mut diagnostics: Vec<String> = Vec::new();
+    let mut findings: Vec<LintRow> = Vec::new();
+    let mut proto = Protocol::new(vec![1]);
+    let result = run_tool(
+        pnass_path,
+        PNASS_LINT,
+        &HashMap::from([
+            ("INPUT", PathValue::from(local.display().to_string())),
+            ("MAXCPS", PathValue::from(max_cps.to_string())),
+        ]),
+        job_id,
+        &mut proto,
+        |data| {
+            if data.get(0).and_then(|v| v.as_str()) != Some("4") {
+                return None;
+            }
+            if let Some(line) = data.get(1).and_then(|v| v.as_str()) {
+                diagnostics.push(line.to_string());
+            } else if let Some(row) = data.get(1).and_then(|v| v.as_multi()) {
+                let field = |index: usize| {
+                    row.get(index).and_then(|v| v.as_str()).unwrap_or("").to_string()
+                };
+                findings.push(LintRow {
+                    severity: field(0),
+                    code: field(1),
+                    event: field(2).parse().ok(),
+                    message: field(3),
+                });
+            }
+            None
+        },
+    ).await;
+    if !matches!(result, ToolResult::Success) {
+        return Err(tool_failure_message("lint failed", &diagnostics));
+    }
+    Ok((diagnostics, findings))
+}
+
+const CONSISTENCY_REPORT_PATH: &str = "CONSISTENCY.md";
+
+pub struct ConsistencyOutcome {
+    pub owner_repo: String,
+    pub report_path: String,
+    pub report: ConsistencyReport,
+    pub skipped: Vec<String>,
+}
+
+// Compares every episode's TL, TS and Release in the repo with each other, against the repo's
+// GLOSSARY.md, and commits the result as CONSISTENCY.md at the repo root. A file that can't be
+// read or isn't UTF-8 is listed as skipped instead of failing the whole report.
+pub async fn check_repo_consistency(server_id: u64, channel_id: u64) -> Result<ConsistencyOutcome, String> {
+    let meta = read_channel_meta(server_id, channel_id);
+    if meta.mal_id.is_none() {
+        return Err("this channel is not attached to an anime. Run /init or /attach first.".to_string());
+    }
+    let repo_url = meta.repo_url.clone().filter(|s| !s.is_empty())
+        .ok_or_else(|| "this channel has no repo URL configured.".to_string())?;
+    let (owner, repo) = parse_repo_url(&repo_url).map_err(|e| format!("bad repo URL in meta: {}", e))?;
+    let owner_repo = format!("{}/{}", owner, repo);
+    let safe_name = meta.name.clone().unwrap_or_default().replace('/', "-");
+    let (forgejo_base, api_key) = forgejo_config(server_id).await?;
+    let fg = Forgejo::new(forgejo_base, api_key).map_err(|e| format!("Forgejo init failed: {}", e))?;
+
+    let glossary = match fg.get_file_content(&owner_repo, GLOSSARY_FILE).await? {
+        Some((b64, _)) => {
+            let bytes = base64_decode_bytes(&b64).map_err(|e| format!("failed to decode {}: {}", GLOSSARY_FILE, e))?;
+            Glossary::parse(&String::from_utf8_lossy(&bytes))
+        }
+        None => Glossary::default(),
+    };
+
+    let mut episodes = Vec::new();
+    let mut skipped = Vec::new();
+    for episode in 1..=meta.episode_count.unwrap_or(0) {
+        let folder = pad2(episode);
+        // One listing per folder instead of four content lookups for episodes nobody has started.
+        // Only a missing folder means "not started"; any other failure would commit a wrong report.
+        let entries = fg.list_contents_if_exists(&owner_repo, &folder).await
+            .map_err(|e| format!("failed to list {}/: {}", folder, e))?
+            .unwrap_or_default();
+        let mut files = Vec::new();
+        for kind in ["TL", "TS", "Release"] {
+            let name = format!("{} - {} - E{:02}.ass", kind, safe_name, episode);
+            if !entries.iter().any(|entry| *entry == name || *entry == format!("{}.zip", name)) {
+                continue;
+            }
+            let ass_path = format!("{}/{}", folder, name);
+            match read_repo_ass(&fg, &owner_repo, &ass_path).await {
+                Ok(Some((bytes, path))) => match SubstationAlpha::from_bytes(&bytes, false) {
+                    Ok((sub, _)) => files.push(ScriptFile { kind: kind.to_string(), sub }),
+                    Err(e) => skipped.push(format!("{}: {}", path, e)),
+                },
+                Ok(None) => {}
+                Err(e) => skipped.push(format!("{}: {}", ass_path, e)),
+            }
+        }
+        let has_source = entries.iter().any(|entry| entry == "SOURCE.md");
+        episodes.push(EpisodeFiles { episode, files, has_source });
+    }
+
+    let report = check_consistency(&episodes, &glossary);
+    let mut markdown = report.to_markdown();
+    if !skipped.is_empty() {
+        markdown.push_str("\n## Skipped files\n\n");
+        for line in &skipped {
+            markdown.push_str(&format!("- {}\n", line));
+        }
+    }
+    fg.upsert_file(&owner_repo, CONSISTENCY_REPORT_PATH, &base64_encode(&markdown), "Consistency report").await
+        .map_err(|e| format!("report upload to {} failed: {}", CONSISTENCY_REPORT_PATH, e))?;
+    Ok(ConsistencyOutcome { owner_repo, report_path: CONSISTENCY_REPORT_PATH.to_string(), report, skipped })
+}
+
+pub struct ResyncOutcome {
+    pub owner_repo: String,
+    pub files: Vec<String>,
+    pub source_path: String,
+}
+
+// The source link an episode's TL/TS were timed against, as recorded in its SOURCE.md.
+pub async fn episode_source(server_id: u64, channel_id: u64, episode: u32) -> Result<Option<String>, String> {
+    let meta = read_channel_meta(server_id, channel_id);
+    let repo_url = meta.repo_url.clone().filter(|s| !s.is_empty())
+        .ok_or_else(|| "this channel has no repo URL configured.".to_string())?;
+    let (owner, repo) = parse_repo_url(&repo_url).map_err(|e| format!("bad repo URL in meta: {}", e))?;
+    let owner_repo = format!("{}/{}", owner, repo);
+    let (forgejo_base, api_key) = forgejo_config(server_id).await?;
+    let fg = Forgejo::new(forgejo_base, api_key).map_err(|e| format!("Forgejo init failed: {}", e))?;
+    read_source_link(&fg, &owner_repo, &pad2(episode)).await
+}
+
+// The PlayRes of an episode's TS, or of its TL while there is no TS yet, for placing traced signs.
+// `None` when neither file exists or neither sets a PlayRes.
+pub async fn episode_play_res(server_id: u64, channel_id: u64, episode: u32) -> Result<Option<(u16, u16)>, String> {
+    let meta = read_channel_meta(server_id, channel_id);
+    let repo_url = meta.repo_url.clone().filter(|s| !s.is_empty())
+        .ok_or_else(|| "this channel has no repo URL configured.".to_string())?;
+    let (owner, repo) = parse_repo_url(&repo_url).map_err(|e| format!("bad repo URL in meta: {}", e))?;
+    let owner_repo = format!("{}/{}", owner, repo);
+    let safe_name = meta.name.clone().unwrap_or_default().replace('/', "-");
+    let (forgejo_base, api_key) = forgejo_config(server_id).await?;
+    let fg = Forgejo::new(forgejo_base, api_key).map_err(|e| format!("Forgejo init failed: {}", e))?;
+    for kind in ["TS", "TL"] {
+        let ass_path = format!("{}/{} - {} - E{:02}.ass", pad2(episode), kind, safe_name, episode);
+        let Some((bytes, path)) = read_repo_ass(&fg, &owner_repo, &ass_path).await? else {
+            continue;
+        };
+        let (sub, _) = SubstationAlpha::from_bytes(&bytes, false).map_err(|e| format!("{}: {}", path, e))?;
+        let info = sub.script_info;
+        if info.playresx > 0 && info.playresy > 0 {
+            return Ok(Some((info.playresx, info.playresy)));
+        }
+    }
+    Ok(None)
+}
+
+// Applies a pnmpeg sync map to the episode's TL and TS, commits both, then points SOURCE.md at the
+// release they now match. Nothing is committed unless every file converted.
+pub async fn resync_episode(
+    server_id: u64,
+    channel_id: u64,
+    episode: u32,
+    sync_map: &Path,
+    new_link: &str,
+) -> Result<ResyncOutcome, String> {
+    let meta = read_channel_meta(server_id, channel_id);
+    if meta.mal_id.is_none() {
+        return Err("this channel is not attached to an anime. Run /init or /attach first.".to_string());
+    }
+    let repo_url = meta.repo_url.clone().filter(|s| !s.is_empty())
+        .ok_or_else(|| "this channel has no repo URL configured.".to_string())?;
+    let (owner, repo) = parse_repo_url(&repo_url).map_err(|e| format!("bad repo URL in meta: {}", e))?;
+    let owner_repo = format!("{}/{}", owner, repo);
+    let safe_name = meta.name.clone().unwrap_or_default().replace('/', "-");
+
+    let (forgejo_base, api_key) = forgejo_config(server_id).await?;
+    let fg = Forgejo::new(forgejo_base, api_key).map_err(|e| format!("Forgejo init failed: {}", e))?;
+    let pnass_path = match get_pandora_env().get(PNASS) {
+        Some(p) if !p.is_empty() => p.clone(),
+        _ => return Err("PNASS binary path is not set in DB/config/global/environment/env.pandora.".to_string()),
+    };
+
+    let folder = pad2(episode);
+    let job_id = nano_id();
+    let work_dir = std::env::temp_dir().join(format!("pandora_resync_{}", job_id));
+    tokio::fs::create_dir_all(&work_dir).await.map_err(|e| format!("failed to create work dir: {}", e))?;
+
+    let mut resynced: Vec<(String, String, Vec<u8>)> = Vec::new();
+    let mut result = Ok(());
+    for kind in ["TL", "TS"] {
+        let ass_path = format!("{}/{} - {} - E{:02}.ass", folder, kind, safe_name, episode);
+        let bytes = match read_repo_ass(&fg, &owner_repo, &ass_path).await {
+            Ok(Some((bytes, _))) => bytes,
+            Ok(None) => continue,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        let input = work_dir.join(format!("{}.ass", kind.to_lowercase()));
+        let output = work_dir.join(format!("{}.resynced.ass", kind.to_lowercase()));
+        match resync_ass_file(&pnass_path, &input, &output, &bytes, sync_map, job_id).await {
+            Ok(out) => resynced.push((kind.to_string(), ass_path, out)),
+            Err(e) => {
+                result = Err(format!("{}: {}", ass_path, e));
+                break;
+            }
+        }
+    }
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    result?;
+    if resynced.is_empty() {
+        return Err(format!("no TL or TS file found in {}/ for episode {}.", folder, episode));
+    }
+
+    let mut files = Vec::new();
+    for (kind, ass_path, bytes) in resynced {
+        let uploaded = upsert_repo_ass(&fg, &owner_repo, &ass_path, &bytes, &format!("{} resync to new source", kind)).await
+            .map_err(|e| format!("failed to upload {}: {}", kind, e))?;
+        files.push(uploaded);
+    }
+    let source = set_source(server_id, channel_id, episode, new_link).await?;
+    Ok(ResyncOutcome { owner_repo, files, source_path: source.path })
+}
+
+async fn resync_ass_file(
+    pnass_path: &str,
+    input: &Path,
+    output: &Path,
+    bytes: &[u8],
+    sync_map: &Path,
+    job_id: u64,
+) -> Result<Vec<u8>, String> {
+    tokio::fs::write(input, bytes).await.map_err(|e| format!("failed to write work copy: {}", e))?;
+    let mut warnings: Vec<String> = Vec::new();
+    let mut proto = Protocol::new(vec![1]);
+    let result = run_tool(
+        pnass_path,
+        PNASS_SYNCMAP,
+        &HashMap::from([
+            ("INPUT", PathValue::from(input.display().to_string())),
+            ("OUTPUT", PathValue::from(output.display().to_string())),
+            ("MAP", PathValue::from(sync_map.display().to_string())),
+        ]),
+        job_id,
+        &mut proto,
+        |data| {
+            if data.get(0).and_then(|v| v.as_str()) == Some("4")
+                && let Some(line) = data.get(1).and_then(|v| v.as_str())
+            {
+                warnings.push(line.to_string());
+            }
+            None
+        },
+    ).await;
+    if !matches!(result, ToolResult::Success) {
+        return Err(tool_failure_message("resync failed", &warnings));
+    }
+    tokio::fs::read(output).await.map_err(|e| format!("failed to read resynced ASS: {}", e))
+}
+
+// `requested` counts faces (family + bold/italic) the release draws text with; `files` are the
+// zip entries that serve them.
+pub struct ReleaseFonts {
+    pub zip_path: Option<String>,
+    pub zip: Option<Vec<u8>>,
+    pub requested: usize,
+    pub files: Vec<String>,
+    pub missing: Vec<String>,
+    pub embedded: usize,
+    // The release as committed when fonts were embedded into it, for uploads that must match.
+    pub release: Option<Vec<u8>>,
+}
+
+impl ReleaseFonts {
+    // One line per font no installed file could be found for, for the callers' warning lists.
+    pub fn warnings(&self) -> Vec<String> {
+        self.missing.iter()
+            .map(|face| format!("fonts.zip: no installed font for `{}`", face))
+            .collect()
+    }
+}
+
+// Collects the fonts a release ASS draws with from the server and global fontconfig directories
+// and commits them as `fonts.zip` in the release's folder. With `embed` the release itself is
+// rewritten with the same files in its [Fonts] section. Nothing is committed when no font is found.
+pub async fn commit_release_fonts(
+    fg: &Forgejo,
+    owner_repo: &str,
+    server_id: u64,
+    release_path: &str,
+    release_bytes: &[u8],
+    embed: bool,
+) -> Result<ReleaseFonts, String> {
+    let roots = fontconfig_roots(server_id);
+    let bytes = release_bytes.to_vec();
+    let collection = tokio::task::spawn_blocking(move || {
+        let (sub, _) = SubstationAlpha::from_bytes(&bytes, true).map_err(|e| e.to_string())?;
+        Ok::<_, String>(collect_fonts(&sub, &roots))
+    })
+        .await
+        .map_err(|e| format!("font collection failed: {}", e))??;
+
+    let mut out = ReleaseFonts {
+        zip_path: None,
+        zip: None,
+        requested: collection.fonts.iter().map(|font| font.faces.len()).sum::<usize>() + collection.missing.len(),
+        files: collection.fonts.iter().map(|font| font.file_name.clone()).collect(),
+        missing: collection.missing.clone(),
+        embedded: 0,
+        release: None,
+    };
+    if collection.is_empty() {
+        return Ok(out);
+    }
+    let zip = zip_fonts(&collection.fonts).await.map_err(|e| format!("fonts.zip build failed: {}", e))?;
+    let zip_path = match release_path.rsplit_once('/') {
+        Some((folder, _)) => format!("{}/fonts.zip", folder),
+        None => "fonts.zip".to_string(),
+    };
+    fg.upsert_file(owner_repo, &zip_path, &base64_encode_bytes(&zip), "Release fonts").await
+        .map_err(|e| format!("fonts.zip upload to {} failed: {}", zip_path, e))?;
+    remove_gitkeep_for_path(fg, owner_repo, &zip_path).await;
+
+    if embed {
+        // The new attachments are spliced into the original text, so every other line of the
+        // release, comments and section order included, is committed as it was.
+        let embed_failed = |e: String| {
+            format!("{} was committed, but embedding fonts into {} failed: {}", zip_path, release_path, e)
+        };
+        let (mut sub, _) = SubstationAlpha::from_bytes(release_bytes, false).map_err(|e| embed_failed(e.to_string()))?;
+        let attached = sub.fonts.len();
+        out.embedded = collection.embed_into(&mut sub);
+        if out.embedded > 0 {
+            let text = String::from_utf8_lossy(release_bytes);
+            let embedded = append_fonts(&text, &sub.fonts[attached..]).into_bytes();
+            upsert_repo_ass(fg, owner_repo, release_path, &embedded, "Embed release fonts").await.map_err(embed_failed)?;
+            out.release = Some(embedded);
+        }
+    }
+    out.zip_path = Some(zip_path);
+    out.zip = Some(zip);
+    Ok(out)
+}
+
+// The directories the encoder stages fonts from, server first.
+pub fn fontconfig_roots(server_id: u64) -> Vec<PathBuf> {
+    vec![
+        PathBuf::from("DB").join("fontconfig").join(server_id.to_string()),
+        PathBuf::from("DB").join("fontconfig").join("global"),
+    ]
+}
+
+async fn zip_fonts(fonts: &[CollectedFont]) -> Result<Vec<u8>, String> {
+    let mut out: Vec<u8> = Vec::new();
+    {
+        let mut writer = async_zip::base::write::ZipFileWriter::new(&mut out);
+        for font in fonts {
+            let entry = async_zip::ZipEntryBuilder::new(font.file_name.clone().into(), async_zip::Compression::Deflate);
+            writer.write_entry_whole(entry, &font.data).await.map_err(|e| e.to_string())?;
+        }
+        writer.close().await.map_err(|e| e.to_string())?;
+    }
+    Ok(out)
+}
+
+pub struct KeyframesOutcome {
+    pub owner_repo: String,
+    pub keyframes_path: String,
+    pub tl_path: Option<String>,
+}
+
+// Commits an extracted keyframes file as NN/keyframes.txt, where /smartcode's post-processor looks
+// for it. With `tpp` the episode's TL is post-processed against the same file and committed too.
+pub async fn commit_keyframes(
+    server_id: u64,
+    channel_id: u64,
+    episode: u32,
+    keyframes: &Path,
+    tpp: bool,
+) -> Result<KeyframesOutcome, String> {
+    let meta = read_channel_meta(server_id, channel_id);
+    if meta.mal_id.is_none() {
+        return Err("this channel is not attached to an anime. Run /init or /attach first.".to_string());
+    }
+    let repo_url = meta.repo_url.clone().filter(|s| !s.is_empty())
+        .ok_or_else(|| "this channel has no repo URL configured.".to_string())?;
+    let (owner, repo) = parse_repo_url(&repo_url).map_err(|e| format!("bad repo URL in meta: {}", e))?;
+    let owner_repo = format!("{}/{}", owner, repo);
+    let safe_name = meta.name.clone().unwrap_or_default().replace('/', "-");
+    let (forgejo_base, api_key) = forgejo_config(server_id).await?;
+    let fg = Forgejo::new(forgejo_base, api_key).map_err(|e| format!("Forgejo init failed: {}", e))?;
+
+    let folder = pad2(episode);
+    let text = tokio::fs::read_to_string(keyframes).await
+        .map_err(|e| format!("failed to read keyframes: {}", e))?;
+    let keyframes_path = format!("{}/keyframes.txt", folder);
+
+    // The TL is post-processed before anything is committed, so a failed run leaves the repo as it was.
+    let processed = if tpp {
+        let pnass_path = match get_pandora_env().get(PNASS) {
+            Some(p) if !p.is_empty() => p.clone(),
+            _ => return Err("PNASS binary path is not set in DB/config/global/environment/env.pandora.".to_string()),
+        };
+        let path = format!("{}/TL - {} - E{:02}.ass", folder, safe_name, episode);
+        let bytes = read_repo_ass(&fg, &owner_repo, &path).await?
+            .map(|(bytes, _)| bytes)
+            .ok_or_else(|| format!("TL file not found at {} or {}.zip.", path, path))?;
+        let job_id = nano_id();
+        let work_dir = std::env::temp_dir().join(format!("pandora_tpp_{}", job_id));
+        tokio::fs::create_dir_all(&work_dir).await.map_err(|e| format!("failed to create work dir: {}", e))?;
+        let input = work_dir.join("tl.ass");
+        let result = async {
+            tokio::fs::write(&input, &bytes).await.map_err(|e| format!("failed to write work copy: {}", e))?;
+            run_tpp(&fg, &owner_repo, &folder, &pnass_path, &input, Some(keyframes), job_id).await?;
+            tokio::fs::read(&input).await.map_err(|e| format!("failed to read post-processed TL: {}", e))
+        }.await;
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+        Some((path, result?))
+    } else {
+        None
+    };
+
+    fg.upsert_file(&owner_repo, &keyframes_path, &base64_encode(&text), "Keyframes").await
+        .map_err(|e| format!("keyframes upload to {} failed: {}", keyframes_path, e))?;
+    let mut tl_path = None;
+    if let Some((path, bytes)) = processed {
+        tl_path = Some(upsert_repo_ass(&fg, &owner_repo, &path, &bytes, "TL timing post-process").await
+            .map_err(|e| format!("failed to upload TL: {}", e))?);
+    }
+    remove_gitkeep_for_path(&fg, &owner_repo, &keyframes_path).await;
+    Ok(KeyframesOutcome { owner_repo, keyframes_path, tl_path })
+}
+
+// Runs the pnass timing post-processor over `ass` in place. Keyframes come from `keyframes` when
+// the caller extracted them, otherwise from the episode folder's keyframes.txt; with neither the
+// lines still get their leads and links, and the returned note says snapping was skipped.
+pub async fn run_tpp(
+    fg: &Forgejo,
+    owner_repo: &str,
+    folder: &str,
+    pnass_path: &str,
+    ass: &Path,
+    keyframes: Option<&Path>,
+    job_id: u64,
+) -> Result<Option<String>, String> {
+    let mut note = None;
+    let keyframes = match keyframes {
+        Some(path) => path.display().to_string(),
+        None => {
+            let repo_path = format!("{}/keyframes.txt", folder);
+            match fg.get_file_content(owner_repo, &repo_path).await? {
+                Some((b64, _)) => {
+                    let bytes = base64_decode_bytes(&b64).map_err(|e| format!("failed to decode {}: {}", repo_path, e))?;
+                    let local = ass.with_file_name("keyframes.txt");
+                    tokio::fs::write(&local, bytes).await.map_err(|e| format!("failed to write keyframes: {}", e))?;
+                    local.display().to_string()
+                }
+                None => {
+                    note = Some(format!("No {} in the repo; timing was post-processed without keyframe snapping.", repo_path));
+                    String::new()
+                }
+            }
+        }
+    };
+    let output = ass.with_extension("tpp.ass");
+    let mut warnings: Vec<String> = Vec::new();
+    let mut proto = Protocol::new(vec![1]);
+    let result = run_tool(
+        pnass_path,
+        PNASS_TPP,
+        &HashMap::from([
+            ("INPUT", PathValue::from(ass.display().to_string())),
+            ("OUTPUT", PathValue::from(output.display().to_string())),
+            ("KEYFRAMES", PathValue::from(keyframes)),
+        ]),
+        job_id,
+        &mut proto,
+        |data| {
+            if data.get(0).and_then(|v| v.as_str()) == Some("4")
+                && let Some(line) = data.get(1).and_then(|v| v.as_str())
+            {
+                warnings.push(line.to_string());
+            }
+            None
+        },
+    ).await;
+    if !matches!(result, ToolResult::Success) {
+        return Err(tool_failure_message("timing post-process failed", &warnings));
+    }
+    tokio::fs::rename(&output, ass).await.map_err(|e| format!("failed to replace {}: {}", ass.display(), e))?;
+    Ok(note)
+}
+
+// The server's style library as stored at DB/config/<server_id>/styles.ass, plus what the last
+// update changed.
+pub struct StyleLibrary {
+    pub playresx: u16,
+    pub playresy: u16,
+    pub styles: Vec<String>,
+    pub changes: StyleChanges,
+    pub removed: Vec<String>,
+}
+
+// `DB/config/<server>/styles.ass`, the library `/styles` and the API read and write.
+pub fn style_library_path(server_id: u64) -> PathBuf {
+    PathBuf::from("DB").join("config").join(server_id.to_string()).join("styles.ass")
+}
+
+async fn load_style_library(server_id: u64) -> Result<SubstationAlpha, String> {
+    match tokio::fs::read(style_library_path(server_id)).await {
+        Ok(bytes) => SubstationAlpha::from_bytes(&bytes, false)
+            .map(|(library, _)| library)
+            .map_err(|e| format!("styles.ass: {}", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SubstationAlpha::parse("", false).0),
+        Err(e) => Err(format!("failed to read styles.ass: {}", e)),
+    }
+}
+
+pub async fn read_style_library(server_id: u64) -> Result<StyleLibrary, String> {
+    let library = load_style_library(server_id).await?;
+    Ok(StyleLibrary {
+        playresx: library.script_info.playresx,
+        playresy: library.script_info.playresy,
+        styles: library.v4p_styles.iter().map(|style| style.name.clone()).collect(),
+        changes: StyleChanges::default(),
+        removed: Vec::new(),
+    })
+}
+
+// Imports the styles of `import` (any ASS; only its header and styles are kept) into the library,
+// or in place of it with `replace`, then drops the `remove` names. The import is scaled to the
+// library's PlayRes; another aspect ratio needs `resample`.
+pub async fn update_style_library(
+    server_id: u64,
+    import: Option<&[u8]>,
+    replace: bool,
+    remove: &[String],
+    resample: Option<&str>,
+) -> Result<StyleLibrary, String> {
+    let resample = resample
+        .filter(|mode| !mode.trim().is_empty())
+        .map(str::parse::<ResampleMode>)
+        .transpose()?;
+    let mut library = if replace {
+        SubstationAlpha::parse("", false).0
+    } else {
+        load_style_library(server_id).await?
+    };
+    let mut changes = StyleChanges::default();
+    if let Some(bytes) = import {
+        let (source, _) = SubstationAlpha::from_bytes(bytes, false).map_err(|e| e.to_string())?;
+        if source.v4p_styles.is_empty() {
+            return Err("the file has no [V4+ Styles] entries".to_string());
+        }
+        changes = import_styles(&mut library, source, resample)?;
+    }
+    let removed = remove_styles(&mut library, remove);
+
+    let path = style_library_path(server_id);
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await.map_err(|e| format!("failed to create config dir: {}", e))?;
+    }
+    tokio::fs::write(&path, library_text(&library)).await.map_err(|e| format!("failed to write styles.ass: {}", e))?;
+    Ok(StyleLibrary {
+        playresx: library.script_info.playresx,
+        playresy: library.script_info.playresy,
+        styles: library.v4p_styles.iter().map(|style| style.name.clone()).collect(),
+        changes,
+        removed,
+    })
+}
+
+#[derive(serde::Serialize)]
+pub struct StyledFile {
+    pub path: String,
+    pub changed: bool,
+    pub notes: Vec<String>,
+}
+
+pub struct StylesOutcome {
+    pub owner_repo: String,
+    pub files: Vec<StyledFile>,
+}
+
+// Runs `pnass --apply-styles` with the server library over the episode's TL and/or TS and commits
+// each file whose styles actually changed as `<kind> styles: <summary>`.
+pub async fn apply_episode_styles(
+    server_id: u64,
+    channel_id: u64,
+    episode: u32,
+    kinds: &[&str],
+    add: &str,
+    resample: &str,
+) -> Result<StylesOutcome, String> {
+    let meta = read_channel_meta(server_id, channel_id);
+    if meta.mal_id.is_none() {
+        return Err("this channel is not attached to an anime. Run /init or /attach first.".to_string());
+    }
+    let max_ep = meta.episode_count.unwrap_or(0);
+    if episode < 1 || episode > max_ep {
+        return Err(format!("`episode` must be between 1 and {}.", max_ep));
+    }
+    let library_path = style_library_path(server_id);
+    if tokio::fs::metadata(&library_path).await.is_err() {
+        return Err("this server has no style library yet. Import one with /styles import.".to_string());
+    }
+    let repo_url = meta.repo_url.clone().filter(|s| !s.is_empty())
+        .ok_or_else(|| "this channel has no repo URL configured.".to_string())?;
+    let (owner, repo) = parse_repo_url(&repo_url).map_err(|e| format!("bad repo URL in meta: {}", e))?;
+    let owner_repo = format!("{}/{}", owner, repo);
+    let safe_name = meta.name.clone().unwrap_or_default().replace('/', "-");
+
+    let (forgejo_base, api_key) = forgejo_config(server_id).await?;
+    let fg = Forgejo::new(forgejo_base, api_key).map_err(|e| format!("Forgejo init failed: {}", e))?;
+    let pnass_path = match get_pandora_env().get(PNASS) {
+        Some(p) if !p.is_empty() => p.clone(),
+        _ => return Err("PNASS binary path is not set in DB/config/global/environment/env.pandora.".to_string()),
+    };
+
+    let folder = pad2(episode);
+    let job_id = nano_id();
+    let work_dir = std::env::temp_dir().join(format!("pandora_styles_{}", job_id));
+    tokio::fs::create_dir_all(&work_dir).await.map_err(|e| format!("failed to create work dir: {}", e))?;
+
+    let mut files = Vec::new();
+    let mut result = Ok(());
+    for kind in kinds {
+        let ass_path = format!("{}/{} - {} - E{:02}.ass", folder, kind, safe_name, episode);
+        let bytes = match read_repo_ass(&fg, &owner_repo, &ass_path).await {
+            Ok(Some((bytes, _))) => bytes,
+            Ok(None) => continue,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        let input = work_dir.join(format!("{}.ass", kind.to_lowercase()));
+        let output = work_dir.join(format!("{}.styled.ass", kind.to_lowercase()));
+        let (styled, notes) = match apply_styles_file(&pnass_path, &input, &output, &bytes, &library_path, add, resample, job_id).await {
+            Ok(r) => r,
+            Err(e) => {
+                result = Err(format!("{}: {}", ass_path, e));
+                break;
+            }
+        };
+        let summary = notes.iter().find_map(|note| note.strip_prefix("styles: ")).map(str::to_string);
+        let path = match &summary {
+            Some(summary) => match upsert_repo_ass(&fg, &owner_repo, &ass_path, &styled, &format!("{} styles: {}", kind, summary)).await {
+                Ok(uploaded) => uploaded,
+                Err(e) => {
+                    result = Err(format!("failed to upload {}: {}", kind, e));
+                    break;
+                }
+            },
+            None => ass_path,
+        };
+        files.push(StyledFile { path, changed: summary.is_some(), notes });
+    }
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    result?;
+
+    if files.is_empty() {
+        return Err(format!("no TL or TS file found in {}/ for episode {}.", folder, episode));
+    }
+    Ok(StylesOutcome { owner_repo, files })
+}
+
+// Only the style notes come back; the line-length warnings pnass prints on every run are not
+// what the caller asked about.
+#[allow(clippy::too_many_arguments)]
+async fn apply_styles_file(
+    pnass_path: &str,
+    input: &Path,
+    output: &Path,
+    bytes: &[u8],
+    library: &Path,
+    add: &str,
+    resample: &str,
+    job_id: u64,
+) -> Result<(Vec<u8>, Vec<String>), String> {
+    tokio::fs::write(input, bytes).await.map_err(|e| format!("failed to write work copy: {}", e))?;
+    let mut warnings: Vec<String> = Vec::new();
+    let mut proto = Protocol::new(vec![1]);
+    let result = run_tool(
+        pnass_path,
+        PNASS_APPLY_STYLES,
+        &HashMap::from([
+            ("INPUT", PathValue::from(input.display().to_string())),
+            ("OUTPUT", PathValue::from(output.display().to_string())),
+            ("LIBRARY", PathValue::from(library.display().to_string())),
+            ("ADD", PathValue::from(add.to_string())),
+            ("RESAMPLE", PathValue::from(resample.to_string())),
+        ]),
+        job_id,
+        &mut proto,
+        |data| {
+            if data.get(0).and_then(|v| v.as_str()) == Some("4")
+                && let Some(line) = data.get(1).and_then(|v| v.as_str())
+            {
+                warnings.push(line.to_string());
+            }
+            None
+        },
+    ).await;
+    if !matches!(result, ToolResult::Success) {
+        return Err(tool_failure_message("applying styles failed", &warnings));
+    }
+    let styled = tokio::fs::read(output).await.map_err(|e| format!("failed to read styled ASS: {}", e))?;
+    warnings.retain(|note| note.starts_with("styles: ") || note.starts_with("style library has no "));
+    Ok((styled, warnings))
+}
+
+pub struct MotionApplied {
+    pub owner_repo: String,
+    pub path: String,
+    pub summary: String,
+}
+
+// Runs `pnass --motion` with pasted tracking data over lines of the episode's TS (or TL) and commits
+// the frame-split result as `<kind> motion: <summary>`. The data's own frame rate is used unless
+// `fps` is given.
+#[allow(clippy::too_many_arguments)]
+pub async fn apply_episode_motion(
+    server_id: u64,
+    channel_id: u64,
+    episode: u32,
+    kind: &str,
+    data: &str,
+    lines: &[usize],
+    origin: usize,
+    fps: Option<f64>,
+) -> Result<MotionApplied, String> {
+    let meta = read_channel_meta(server_id, channel_id);
+    if meta.mal_id.is_none() {
+        return Err("this channel is not attached to an anime. Run /init or /attach first.".to_string());
+    }
+    let max_ep = meta.episode_count.unwrap_or(0);
+    if episode < 1 || episode > max_ep {
+        return Err(format!("`episode` must be between 1 and {}.", max_ep));
+    }
+    let tracking = parse_motion(data)?;
+    let fps = fps.or(tracking.fps)
+        .ok_or_else(|| "the tracking data has no Units Per Second; give `fps`.".to_string())?;
+    let repo_url = meta.repo_url.clone().filter(|s| !s.is_empty())
+        .ok_or_else(|| "this channel has no repo URL configured.".to_string())?;
+    let (owner, repo) = parse_repo_url(&repo_url).map_err(|e| format!("bad repo URL in meta: {}", e))?;
+    let owner_repo = format!("{}/{}", owner, repo);
+    let safe_name = meta.name.clone().unwrap_or_default().replace('/', "-");
+
+    let (forgejo_base, api_key) = forgejo_config(server_id).await?;
+    let fg = Forgejo::new(forgejo_base, api_key).map_err(|e| format!("Forgejo init failed: {}", e))?;
+    let pnass_path = match get_pandora_env().get(PNASS) {
+        Some(p) if !p.is_empty() => p.clone(),
+        _ => return Err("PNASS binary path is not set in DB/config/global/environment/env.pandora.".to_string()),
+    };
+
+    let folder = pad2(episode);
+    let ass_path = format!("{}/{} - {} - E{:02}.ass", folder, kind, safe_name, episode);
+    let bytes = read_repo_ass(&fg, &owner_repo, &ass_path).await?
+        .map(|(bytes, _)| bytes)
+        .ok_or_else(|| format!("{} file not found at {} or {}.zip.", kind, ass_path, ass_path))?;
+    let job_id = nano_id();
+    let work_dir = std::env::temp_dir().join(format!("pandora_motion_{}", job_id));
+    tokio::fs::create_dir_all(&work_dir).await.map_err(|e| format!("failed to create work dir: {}", e))?;
+    let input = work_dir.join(format!("{}.ass", kind.to_lowercase()));
+    let output = work_dir.join(format!("{}.motion.ass", kind.to_lowercase()));
+    let motion = work_dir.join("motion.txt");
+    let lines = lines.iter().map(usize::to_string).collect::<Vec<_>>().join(",");
+    let result = async {
+        tokio::fs::write(&input, &bytes).await.map_err(|e| format!("failed to write work copy: {}", e))?;
+        tokio::fs::write(&motion, data).await.map_err(|e| format!("failed to write tracking data: {}", e))?;
+        let mut warnings: Vec<String> = Vec::new();
+        let mut proto = Protocol::new(vec![1]);
+        let result = run_tool(
+            &pnass_path,
+            PNASS_MOTION,
+            &HashMap::from([
+                ("INPUT", PathValue::from(input.display().to_string())),
+                ("OUTPUT", PathValue::from(output.display().to_string())),
+                ("MOTION", PathValue::from(motion.display().to_string())),
+                ("LINES", PathValue::from(lines.clone())),
+                ("ORIGIN", PathValue::from(origin.to_string())),
+                ("FPS", PathValue::from(fps.to_string())),
+            ]),
+            job_id,
+            &mut proto,
+            |data| {
+                if data.get(0).and_then(|v| v.as_str()) == Some("4")
+                    && let Some(line) = data.get(1).and_then(|v| v.as_str())
+                {
+                    warnings.push(line.to_string());
+                }
+                None
+            },
+        ).await;
+        if !matches!(result, ToolResult::Success) {
+            return Err(tool_failure_message("motion tracking failed", &warnings));
+        }
+        let tracked = tokio::fs::read(&output).await.map_err(|e| format!("failed to read tracked ASS: {}", e))?;
+        let summary = warnings.iter()
+            .find_map(|note| note.strip_prefix("motion: "))
+            .unwrap_or("applied")
+            .to_string();
+        let path = upsert_repo_ass(&fg, &owner_repo, &ass_path, &tracked, &format!("{} motion: {}", kind, summary)).await
+            .map_err(|e| format!("failed to upload {}: {}", kind, e))?;
+        Ok(MotionApplied { owner_repo: owner_repo.clone(), path, summary })
+    }.await;
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    result
+}
+
+async fn read_source_link(fg: &Forgejo, owner_repo: &str, folder: &str) -> Result<Option<String>, String> {
+    let source_md_path = format!("{}/SOURCE.md", folder);
+    let Some((b64, _)) = fg.get_file_content(owner_repo, &source_md_path).await? else {
+        return Ok(None);
+    };
+    let bytes = base64_decode_bytes(&b64).map_err(|e| format!("failed to decode {}: {}", source_md_path, e))?;
+    let text = String::from_utf8(bytes).map_err(|e| format!("{} is not valid UTF-8: {}", source_md_path, e))?;
+    text.lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with(';'))
+        .map(|l| l.trim_start_matches('#').trim().to_string())
+        .filter(|s| !s.is_empty())
+        .map(Some)
+        .ok_or_else(|| format!("{} does not contain a parseable source link.", source_md_path))
+}
+
+// Tool failures used to report only how many warnings had been collected. pnass explains a failed
+// load as its last opcode 4 line, so the tail of the warning list is the reason. The pndc handlers
+// share this copy.
+pub fn tool_failure_message(summary: &str, warnings: &[String]) -> String {
+    const SHOWN: usize = 5;
+    const MAX_CHARS: usize = 300;
+    if warnings.is_empty() {
+        return format!("{}.", summary);
+    }
+    let mut out = format!("{}:", summary);
+    let skipped = warnings.len().saturating_sub(SHOWN);
+    if skipped > 0 {
+        out.push_str(&format!("\n({} earlier warnings not shown)", skipped));
+    }
+    for warning in &warnings[skipped..] {
+        let mut line: String = warning.chars().take(MAX_CHARS).collect();
+        if line.len() < warning.len() {
+            line.push('…');
+        }
+        out.push_str(&format!("\n• {}", line));
+    }
+    out
+}
+
 fn nano_id() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
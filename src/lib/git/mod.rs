@@ -3,7 +3,11 @@ pub mod core;
 pub const README_BASE_GUIDE: &str = include_str!("readme_guide.md");
 
 pub use core::{
-    attach_repo, destruct_repo, detach_channel, init_repo, list_attachments, set_source,
-    smartcode_merge, Attachment, Credits, DestructOutcome, DetachOutcome, RepoOutcome,
-    SmartMergeResult, SourceOutcome,
+    apply_episode_motion, apply_episode_styles, attach_repo, check_repo_consistency, commit_keyframes, commit_release_fonts,
+    destruct_repo, detach_channel, episode_play_res, episode_source, fontconfig_roots, init_repo, lint_episode, list_attachments,
+    read_style_library, resync_episode, run_tpp, set_source, smartcode_merge, style_library_path, tool_failure_message,
+    update_style_library,
+    Attachment, ConsistencyOutcome, Credits, DestructOutcome, DetachOutcome, FileLint, KeyframesOutcome, LintOutcome,
+    LintRow, MotionApplied, ReleaseFonts, RepoOutcome, ResyncOutcome, SmartMergeResult, SourceOutcome,
+    StyleLibrary, StyledFile, StylesOutcome,
 };
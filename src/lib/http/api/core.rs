@@ -20,9 +20,11 @@ use crate::pnworker::batch::batch_job_for_token;
 use crate::lib::http::acix::{AnimeCix, MediaType, MixedUpload};
 use crate::lib::db::core::{JobDb, JobStatus};
 use crate::lib::git::{
-    attach_repo, destruct_repo, detach_channel, init_repo, list_attachments, set_source,
-    smartcode_merge, Credits, RepoOutcome,
+    apply_episode_motion, apply_episode_styles, attach_repo, destruct_repo, detach_channel, init_repo, lint_episode,
+    list_attachments, read_style_library, set_source, smartcode_merge, style_library_path, update_style_library, Credits, RepoOutcome,
+    StyleLibrary,
 };
+use crate::libkagami::lint::DEFAULT_MAX_CPS;
 use crate::lib::p2p::nyaaise::nyaaise;
 use crate::lib::p2p::nyaaise::TorrentType;
 use crate::lib::env::core::get_pandora_env;
@@ -180,16 +182,39 @@ pub async fn serve(tx: Sender<JobClass>, port: u16) -> Result<(), Box<dyn std::e
         .route("/git/detach", post(git_detach))
         .route("/git/destruct", post(git_destruct))
         .route("/git/smartcode", post(git_smartcode))
+        .route("/git/lint", post(git_lint))
+        .route("/git/styles", get(git_styles).post(git_styles_set))
+        .route("/git/styles/apply", post(git_styles_apply))
+        .route("/git/motion", post(git_motion))
         .route("/gitsync", post(gitsync))
         .route("/acix/search", post(acix_search))
         .route("/acix/tmdb", post(acix_tmdb))
         .route("/acix/translators", get(acix_translators))
         .route("/acix/publish", post(acix_publish))
+        .route(
+            "/subs/parse",
+            post(super::subs::parse).layer(DefaultBodyLimit::max(super::subs::SUBS_REQUEST_LIMIT)),
+        )
+        .route(
+            "/subs/render",
+            post(super::subs::render).layer(DefaultBodyLimit::max(super::subs::SUBS_REQUEST_LIMIT)),
+        )
         .route(
             "/trace",
             post(super::trace::run_trace)
                 .layer(DefaultBodyLimit::max(crate::kagami_trace::MAX_ENCODED_BYTES)),
         )
+        .route(
+            "/trace/animation",
+            post(super::trace::run_animation_trace)
+                .layer(DefaultBodyLimit::max(crate::kagami_trace::MAX_ENCODED_BYTES)),
+        )
+        .route(
+            "/trace/svg",
+            post(super::trace::run_svg_import)
+                .layer(DefaultBodyLimit::max(crate::kagami_trace::MAX_ENCODED_BYTES)),
+        )
+        .route("/trace/clip", post(super::trace::run_clip_trace))
         .route(
             "/trace/ass",
             post(super::trace::export_ass)
@@ -1040,13 +1065,19 @@ struct GitSmartcodeReq {
     keep: bool,
     #[serde(default)]
     keyword: Option<String>,
+    #[serde(default)]
+    tpp: bool,
+    #[serde(default)]
+    resample: Option<String>,
+    #[serde(default)]
+    fontcheck: bool,
 }
 
 async fn git_smartcode(State(st): State<AppState>, Extension(auth): Extension<ApiAuth>, Json(req): Json<GitSmartcodeReq>) -> Response {
     let server_id = match require_local(&auth) { Ok(id) => id, Err(r) => return r };
     let channel_id = match parse_channel_id(&req.channel_id) { Ok(c) => c, Err(r) => return r };
     let link_opt = req.link.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
-    let merge = match smartcode_merge(server_id, channel_id, req.episode, link_opt).await {
+    let merge = match smartcode_merge(server_id, channel_id, req.episode, link_opt, req.tpp, req.resample, req.fontcheck).await {
         Ok(m) => m,
         Err(e) => return (StatusCode::BAD_GATEWAY, e).into_response(),
     };
@@ -1092,6 +1123,157 @@ async fn git_smartcode(State(st): State<AppState>, Extension(auth): Extension<Ap
     }))).into_response()
 }
 
+#[derive(Deserialize)]
+struct GitLintReq {
+    channel_id: String,
+    episode: u32,
+    #[serde(default)]
+    max_cps: Option<f32>,
+}
+
+async fn git_lint(Extension(auth): Extension<ApiAuth>, Json(req): Json<GitLintReq>) -> Response {
+    let server_id = match require_local(&auth) { Ok(id) => id, Err(r) => return r };
+    let channel_id = match parse_channel_id(&req.channel_id) { Ok(c) => c, Err(r) => return r };
+    let max_cps = req.max_cps.unwrap_or(DEFAULT_MAX_CPS);
+    if !(1.0..=100.0).contains(&max_cps) {
+        return (StatusCode::BAD_REQUEST, "max_cps must be between 1 and 100").into_response();
+    }
+    match lint_episode(server_id, channel_id, req.episode, max_cps).await {
+        Ok(out) => (StatusCode::OK, Json(json!({
+            "owner_repo": out.owner_repo,
+            "max_cps": max_cps,
+            "files": out.files,
+        }))).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
+fn style_library_json(library: StyleLibrary, content: String) -> serde_json::Value {
+    json!({
+        "playresx": library.playresx,
+        "playresy": library.playresy,
+        "styles": library.styles,
+        "replaced": library.changes.replaced,
+        "added": library.changes.added,
+        "unchanged": library.changes.unchanged,
+        "removed": library.removed,
+        "content": content,
+    })
+}
+
+async fn git_styles(Extension(auth): Extension<ApiAuth>) -> Response {
+    let server_id = match require_local(&auth) { Ok(id) => id, Err(r) => return r };
+    match read_style_library(server_id).await {
+        Ok(library) => {
+            let content = tokio::fs::read_to_string(style_library_path(server_id)).await.unwrap_or_default();
+            Json(style_library_json(library, content)).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct GitStylesReq {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    replace: bool,
+    #[serde(default)]
+    remove: Vec<String>,
+    #[serde(default)]
+    resample: Option<String>,
+}
+
+async fn git_styles_set(Extension(auth): Extension<ApiAuth>, Json(req): Json<GitStylesReq>) -> Response {
+    let server_id = match require_local(&auth) { Ok(id) => id, Err(r) => return r };
+    let content = req.content.as_deref().filter(|content| !content.trim().is_empty());
+    if content.is_none() && req.remove.is_empty() {
+        return (StatusCode::BAD_REQUEST, "give content to import, remove, or both").into_response();
+    }
+    // `replace` starts the library over from `content`; without one it would only empty it.
+    if req.replace && content.is_none() {
+        return (StatusCode::BAD_REQUEST, "replace needs content to replace the library with").into_response();
+    }
+    let import = content.map(str::as_bytes);
+    match update_style_library(server_id, import, req.replace, &req.remove, req.resample.as_deref()).await {
+        Ok(library) => {
+            let content = tokio::fs::read_to_string(style_library_path(server_id)).await.unwrap_or_default();
+            Json(style_library_json(library, content)).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct GitStylesApplyReq {
+    channel_id: String,
+    episode: u32,
+    #[serde(default)]
+    file: Option<String>,
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    resample: Option<String>,
+}
+
+async fn git_styles_apply(Extension(auth): Extension<ApiAuth>, Json(req): Json<GitStylesApplyReq>) -> Response {
+    let server_id = match require_local(&auth) { Ok(id) => id, Err(r) => return r };
+    let channel_id = match parse_channel_id(&req.channel_id) { Ok(c) => c, Err(r) => return r };
+    let kinds: &[&str] = match req.file.as_deref().unwrap_or("both") {
+        "TL" => &["TL"],
+        "TS" => &["TS"],
+        "both" => &["TL", "TS"],
+        _ => return (StatusCode::BAD_REQUEST, "file must be TL, TS or both").into_response(),
+    };
+    let add = req.add.join(",");
+    let resample = req.resample.unwrap_or_default();
+    match apply_episode_styles(server_id, channel_id, req.episode, kinds, &add, &resample).await {
+        Ok(out) => (StatusCode::OK, Json(json!({
+            "owner_repo": out.owner_repo,
+            "files": out.files,
+        }))).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct GitMotionReq {
+    channel_id: String,
+    episode: u32,
+    #[serde(default)]
+    file: Option<String>,
+    data: String,
+    lines: Vec<usize>,
+    #[serde(default)]
+    origin: Option<usize>,
+    #[serde(default)]
+    fps: Option<f64>,
+}
+
+async fn git_motion(Extension(auth): Extension<ApiAuth>, Json(req): Json<GitMotionReq>) -> Response {
+    let server_id = match require_local(&auth) { Ok(id) => id, Err(r) => return r };
+    let channel_id = match parse_channel_id(&req.channel_id) { Ok(c) => c, Err(r) => return r };
+    let kind = match req.file.as_deref().unwrap_or("TS") {
+        kind @ ("TL" | "TS") => kind,
+        _ => return (StatusCode::BAD_REQUEST, "file must be TL or TS").into_response(),
+    };
+    if req.lines.is_empty() || req.lines.contains(&0) {
+        return (StatusCode::BAD_REQUEST, "lines must list 1-based line numbers").into_response();
+    }
+    if req.fps.is_some_and(|fps| !(1.0..=300.0).contains(&fps)) {
+        return (StatusCode::BAD_REQUEST, "fps must be between 1 and 300").into_response();
+    }
+    let origin = req.origin.unwrap_or(1);
+    match apply_episode_motion(server_id, channel_id, req.episode, kind, &req.data, &req.lines, origin, req.fps).await {
+        Ok(out) => (StatusCode::OK, Json(json!({
+            "owner_repo": out.owner_repo,
+            "path": out.path,
+            "summary": out.summary,
+        }))).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
 pub(super) async fn submit(st: &AppState, job: Job) -> Response {
     submit_with_progress(st, job, None).await
 }
@@ -1,6 +1,7 @@
 pub mod core;
 mod logs;
 mod studio;
+mod subs;
 mod token;
 mod workers;
 pub mod trace;
@@ -0,0 +1,56 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::libkagami::core::SubstationAlpha;
+
+// Scripts with embedded fonts run well past the general API body limit.
+pub(super) const SUBS_REQUEST_LIMIT: usize = 64 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub(super) struct ParseReq {
+    ass: String,
+    // Off keeps every line as raw text, which is faster when only the header and timings matter.
+    adv_parsing: Option<bool>,
+}
+
+// ASS text in, the versioned JSON document out, with the load diagnostics next to it.
+pub(super) async fn parse(Json(req): Json<ParseReq>) -> Response {
+    let adv_parsing = req.adv_parsing.unwrap_or(true);
+    let converted = tokio::task::spawn_blocking(move || {
+        let (sub, diagnostics) = SubstationAlpha::parse(&req.ass, adv_parsing);
+        let mut document = sub.to_json()?;
+        document["diagnostics"] = diagnostics
+            .iter()
+            .map(|d| json!({ "line": d.line, "message": d.message }))
+            .collect();
+        Ok::<_, String>(document)
+    })
+    .await;
+    match converted {
+        Ok(Ok(document)) => (StatusCode::OK, Json(document)).into_response(),
+        Ok(Err(error)) => error_response(StatusCode::BAD_REQUEST, error),
+        Err(error) => error_response(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
+    }
+}
+
+// The JSON document in (as `/subs/parse` returns it, diagnostics optional), ASS text out.
+pub(super) async fn render(Json(document): Json<serde_json::Value>) -> Response {
+    let converted = tokio::task::spawn_blocking(move || {
+        SubstationAlpha::from_json(document).map(|sub| sub.stringify())
+    })
+    .await;
+    match converted {
+        Ok(Ok(ass)) => (StatusCode::OK, Json(json!({ "ass": ass }))).into_response(),
+        Ok(Err(error)) => error_response(StatusCode::BAD_REQUEST, error),
+        Err(error) => error_response(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
+    }
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
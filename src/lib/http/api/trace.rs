@@ -5,16 +5,22 @@ use async_zip::{Compression, ZipEntryBuilder};
 use axum::{
     Json, Router,
     body::Bytes,
-    extract::{DefaultBodyLimit, Query},
+    extract::{DefaultBodyLimit, Extension, Query, State},
     http::{HeaderValue, StatusCode, header},
     response::{Html, IntoResponse, Response},
     routing::{get, post},
 };
 use serde::{Deserialize, Serialize};
 
-use crate::kagami_trace::{MAX_ENCODED_BYTES, Trace, TraceOptions, TracePreset, trace_image};
+use crate::kagami_trace::{
+    MAX_ENCODED_BYTES, SvgImportOptions, Trace, TraceError, TraceFrame, TraceOptions, TracePreset,
+    import_svg, trace_animation, trace_image, trace_rgba_frames,
+};
+use crate::lib::mpeg::clip::{extract_clip_frames, find_source_video};
 use crate::libkagami::complex::types::AssTime;
-use crate::libkagami::tracing::{TraceAssOptions, trace_to_ass};
+use crate::libkagami::tracing::{TraceAssMode, TraceAssOptions, trace_frames_to_ass, trace_to_ass};
+
+use super::core::{ApiAuth, AppState};
 
 pub(super) const ASS_REQUEST_LIMIT: usize = 64 * 1024 * 1024;
 const DEFAULT_ASS_DURATION_CENTISECONDS: u64 = 500;
@@ -34,14 +40,50 @@ pub(super) struct TraceQuery {
     alpha_threshold: Option<u8>,
     max_dimension: Option<u32>,
     svg_seam_overlap: Option<f32>,
+    outline_width: Option<f32>,
+    gradient_step: Option<u8>,
+    gradient_bands: Option<u16>,
+    flatten_tolerance: Option<f32>,
 }
 
 #[derive(Deserialize)]
 pub(super) struct AssRequest {
-    trace: Trace,
+    trace: Option<Trace>,
+    frames: Option<Vec<TraceFrame>>,
+    fps: Option<f64>,
     filename: Option<String>,
+    start_centiseconds: Option<u64>,
     duration_centiseconds: Option<u64>,
     seam_overlap: Option<f32>,
+    mode: Option<TraceAssMode>,
+    gradient_band: Option<f32>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ClipRequest {
+    job_id: u64,
+    start_centiseconds: u64,
+    end_centiseconds: u64,
+}
+
+// Query settings that are applied around the trace rather than passed into it.
+struct TraceSettings {
+    options: TraceOptions,
+    svg_seam_overlap: f32,
+    outline_width: Option<f32>,
+    gradient_step: Option<u8>,
+}
+
+impl TraceSettings {
+    // Outlines are folded first so a ramp's edge band isn't mistaken for an outline afterwards.
+    fn post_process(&self, trace: &mut Trace) {
+        if let Some(width) = self.outline_width {
+            trace.fold_outlines(width);
+        }
+        if let Some(step) = self.gradient_step {
+            trace.detect_gradients(step);
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -51,6 +93,17 @@ struct TraceResponse {
     elapsed_ms: u128,
 }
 
+// `svg` previews the first frame. `fps` is only known for clips; a GIF or APNG carries frame
+// delays but no video rate, so the caller supplies one when exporting ASS.
+#[derive(Serialize)]
+struct AnimationResponse {
+    frames: Vec<TraceFrame>,
+    fps: Option<f64>,
+    start_centiseconds: u64,
+    svg: String,
+    elapsed_ms: u128,
+}
+
 #[derive(Serialize)]
 struct ErrorResponse {
     error: String,
@@ -64,6 +117,14 @@ pub fn standalone_router() -> Router {
             "/api/trace",
             post(run_trace).layer(DefaultBodyLimit::max(MAX_ENCODED_BYTES)),
         )
+        .route(
+            "/api/svg",
+            post(run_svg_import).layer(DefaultBodyLimit::max(MAX_ENCODED_BYTES)),
+        )
+        .route(
+            "/api/animation",
+            post(run_animation_trace).layer(DefaultBodyLimit::max(MAX_ENCODED_BYTES)),
+        )
         .route(
             "/api/ass",
             post(export_ass).layer(DefaultBodyLimit::max(ASS_REQUEST_LIMIT)),
@@ -86,63 +147,165 @@ async fn health() -> &'static str {
 }
 
 pub(super) async fn run_trace(Query(query): Query<TraceQuery>, bytes: Bytes) -> Response {
-    let preset = query.preset;
-    let mut options = preset.map(TraceOptions::for_preset).unwrap_or_default();
-    if let Some(value) = query.color_count {
-        options.color_count = value;
-    }
-    if let Some(value) = query.preserve_gradients {
-        options.preserve_gradients = value;
-    }
-    if let Some(value) = query.color_smoothing {
-        options.color_smoothing = value;
-    }
-    if let Some(value) = query.path_simplify {
-        options.path_simplify = value;
-    }
-    if let Some(value) = query.curve_fit {
-        options.curve_fit = value;
-    }
-    if let Some(value) = query.corner_threshold {
-        options.corner_threshold = value;
-    }
-    if let Some(value) = query.min_area {
-        options.min_area = value;
-    }
-    if let Some(value) = query.alpha_threshold {
-        options.alpha_threshold = value;
-    }
-    if let Some(value) = query.max_dimension {
-        options.max_dimension = value;
+    let settings = match trace_options(&query) {
+        Ok(settings) => settings,
+        Err(response) => return response,
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        let started = Instant::now();
+        let trace = trace_image(&bytes, &settings.options)?;
+        Ok::<_, TraceError>(trace_response(trace, &settings, started))
+    })
+    .await;
+    json_result(result.map(|result| result.map_err(|error| error.to_string())))
+}
+
+// An SVG's own shapes in the trace model. Only the output query fields and `gradient_bands` /
+// `flatten_tolerance` apply; the raster tracing options are ignored.
+pub(super) async fn run_svg_import(Query(query): Query<TraceQuery>, bytes: Bytes) -> Response {
+    let settings = match trace_options(&query) {
+        Ok(settings) => settings,
+        Err(response) => return response,
+    };
+    let defaults = SvgImportOptions::default();
+    let options = SvgImportOptions {
+        gradient_bands: query.gradient_bands.unwrap_or(defaults.gradient_bands),
+        flatten_tolerance: query
+            .flatten_tolerance
+            .unwrap_or(defaults.flatten_tolerance),
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        let started = Instant::now();
+        let trace = import_svg(&bytes, &options)?;
+        Ok::<_, TraceError>(trace_response(trace, &settings, started))
+    })
+    .await;
+    json_result(result.map(|result| result.map_err(|error| error.to_string())))
+}
+
+fn trace_response(mut trace: Trace, settings: &TraceSettings, started: Instant) -> TraceResponse {
+    settings.post_process(&mut trace);
+    let svg = trace.to_svg_with_seam_overlap(settings.svg_seam_overlap);
+    TraceResponse {
+        trace,
+        svg,
+        elapsed_ms: started.elapsed().as_millis(),
     }
-    let svg_seam_overlap = query.svg_seam_overlap.unwrap_or_else(|| match preset {
-        Some(TracePreset::LogoUi) => 0.0,
-        Some(TracePreset::Illustration) => 0.25,
-        Some(TracePreset::Photo) => 0.5,
-        Some(TracePreset::Gradient) => 0.25,
-        None if options.preserve_gradients => 0.25,
-        None if options.curve_fit > 0.0 => 0.25,
-        None => 0.0,
-    });
-    if !svg_seam_overlap.is_finite() || !(0.0..=4.0).contains(&svg_seam_overlap) {
+}
+
+// Every frame of an animated GIF or APNG, traced against one shared palette.
+pub(super) async fn run_animation_trace(Query(query): Query<TraceQuery>, bytes: Bytes) -> Response {
+    let settings = match trace_options(&query) {
+        Ok(settings) => settings,
+        Err(response) => return response,
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        let started = Instant::now();
+        let frames = trace_animation(&bytes, &settings.options)?;
+        Ok::<_, TraceError>(animation_response(frames, None, 0, &settings, started))
+    })
+    .await;
+    json_result(result.map(|result| result.map_err(|error| error.to_string())))
+}
+
+// A range of a job's source video, decoded by ffmpeg at the video's resolution and traced like
+// an animation. The job directory has to still be on disk.
+pub(super) async fn run_clip_trace(
+    State(st): State<AppState>,
+    Extension(auth): Extension<ApiAuth>,
+    Query(query): Query<TraceQuery>,
+    Json(request): Json<ClipRequest>,
+) -> Response {
+    let settings = match trace_options(&query) {
+        Ok(settings) => settings,
+        Err(response) => return response,
+    };
+    if request.end_centiseconds <= request.start_centiseconds
+        || request.end_centiseconds > MAX_ASS_CENTISECONDS
+    {
         return error_response(
             StatusCode::BAD_REQUEST,
-            "SVG seam overlap must be finite and between 0 and 4".to_string(),
+            "clip end must be after its start and within ASS time".to_string(),
         );
     }
+    let job = match st.db.get_job(request.job_id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "no such job".to_string()),
+        Err(error) => {
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, error.to_string());
+        }
+    };
+    // A local token only reaches the source videos of its own server's jobs.
+    if let Some(server_id) = auth.local_server_id
+        && job.server_id != Some(server_id as i64)
+    {
+        return error_response(StatusCode::FORBIDDEN, "cannot trace a job from another server".to_string());
+    }
 
     let result = tokio::task::spawn_blocking(move || {
         let started = Instant::now();
-        let trace = trace_image(&bytes, &options)?;
-        let svg = trace.to_svg_with_seam_overlap(svg_seam_overlap);
-        Ok::<_, crate::kagami_trace::TraceError>(TraceResponse {
-            trace,
-            svg,
-            elapsed_ms: started.elapsed().as_millis(),
-        })
+        let source = find_source_video(std::path::Path::new(&job.directory))
+            .ok_or_else(|| "the job's source video is no longer on disk".to_string())?;
+        let clip = extract_clip_frames(
+            &source,
+            request.start_centiseconds * 10,
+            request.end_centiseconds * 10,
+        )?;
+        let pixels: Vec<&[u8]> = clip.frames.iter().map(Vec::as_slice).collect();
+        let traces = trace_rgba_frames(clip.width, clip.height, &pixels, &settings.options)
+            .map_err(|error| error.to_string())?;
+        let frames = traces
+            .into_iter()
+            .zip(clip.durations_ms())
+            .map(|(trace, duration_ms)| TraceFrame { duration_ms, trace })
+            .collect();
+        Ok::<_, String>(animation_response(
+            frames,
+            Some(clip.fps),
+            request.start_centiseconds,
+            &settings,
+            started,
+        ))
     })
     .await;
+    json_result(result)
+}
+
+// Outlines and gradients are found frame by frame, so a frame whose band merged with something
+// else simply keeps it as a layer.
+fn animation_response(
+    mut frames: Vec<TraceFrame>,
+    fps: Option<f64>,
+    start_centiseconds: u64,
+    settings: &TraceSettings,
+    started: Instant,
+) -> AnimationResponse {
+    for frame in &mut frames {
+        settings.post_process(&mut frame.trace);
+    }
+    let svg = frames
+        .first()
+        .map(|frame| {
+            frame
+                .trace
+                .to_svg_with_seam_overlap(settings.svg_seam_overlap)
+        })
+        .unwrap_or_default();
+    AnimationResponse {
+        frames,
+        fps,
+        start_centiseconds,
+        svg,
+        elapsed_ms: started.elapsed().as_millis(),
+    }
+}
 
+fn json_result<T: Serialize>(
+    result: Result<Result<T, String>, tokio::task::JoinError>,
+) -> Response {
     match result {
         Ok(Ok(response)) => (
             StatusCode::OK,
@@ -150,16 +313,17 @@ pub(super) async fn run_trace(Query(query): Query<TraceQuery>, bytes: Bytes) ->
             Json(response),
         )
             .into_response(),
-        Ok(Err(error)) => error_response(StatusCode::BAD_REQUEST, error.to_string()),
+        Ok(Err(error)) => error_response(StatusCode::BAD_REQUEST, error),
         Err(error) => error_response(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
     }
 }
 
 pub(super) async fn export_ass(Json(request): Json<AssRequest>) -> Response {
+    let start = request.start_centiseconds.unwrap_or(0);
     let duration = request
         .duration_centiseconds
         .unwrap_or(DEFAULT_ASS_DURATION_CENTISECONDS);
-    if duration == 0 || duration > MAX_ASS_CENTISECONDS {
+    if duration == 0 || start.saturating_add(duration) > MAX_ASS_CENTISECONDS {
         return error_response(
             StatusCode::BAD_REQUEST,
             format!("ASS duration must be between 1 and {MAX_ASS_CENTISECONDS} centiseconds"),
@@ -173,15 +337,28 @@ pub(super) async fn export_ass(Json(request): Json<AssRequest>) -> Response {
     let archive_name = format!("{stem}.zip");
     let title = stem.clone();
     let converted = tokio::task::spawn_blocking(move || {
-        let ass = trace_to_ass(
-            &request.trace,
-            &TraceAssOptions {
-                title,
-                end: AssTime::from_centiseconds(duration),
-                seam_overlap,
-                ..TraceAssOptions::default()
-            },
-        )?;
+        let options = TraceAssOptions {
+            title,
+            start: AssTime::from_centiseconds(start),
+            end: AssTime::from_centiseconds(start + duration),
+            seam_overlap,
+            mode: request.mode.unwrap_or_default(),
+            gradient_band: request
+                .gradient_band
+                .unwrap_or_else(|| TraceAssOptions::default().gradient_band),
+            ..TraceAssOptions::default()
+        };
+        // Animations end with their last frame, so `duration_centiseconds` only times stills.
+        let ass = match (&request.trace, &request.frames) {
+            (Some(trace), None) => trace_to_ass(trace, &options)?,
+            (None, Some(frames)) => {
+                let fps = request
+                    .fps
+                    .ok_or_else(|| "animated ASS export needs the video fps".to_string())?;
+                trace_frames_to_ass(frames, fps, &options)?
+            }
+            _ => return Err("send either a trace or animation frames".to_string()),
+        };
         Ok::<String, String>(ass.stringify())
     })
     .await;
@@ -213,6 +390,66 @@ pub(super) async fn export_ass(Json(request): Json<AssRequest>) -> Response {
     response
 }
 
+fn trace_options(query: &TraceQuery) -> Result<TraceSettings, Response> {
+    let preset = query.preset;
+    let mut options = preset.map(TraceOptions::for_preset).unwrap_or_default();
+    if let Some(value) = query.color_count {
+        options.color_count = value;
+    }
+    if let Some(value) = query.preserve_gradients {
+        options.preserve_gradients = value;
+    }
+    if let Some(value) = query.color_smoothing {
+        options.color_smoothing = value;
+    }
+    if let Some(value) = query.path_simplify {
+        options.path_simplify = value;
+    }
+    if let Some(value) = query.curve_fit {
+        options.curve_fit = value;
+    }
+    if let Some(value) = query.corner_threshold {
+        options.corner_threshold = value;
+    }
+    if let Some(value) = query.min_area {
+        options.min_area = value;
+    }
+    if let Some(value) = query.alpha_threshold {
+        options.alpha_threshold = value;
+    }
+    if let Some(value) = query.max_dimension {
+        options.max_dimension = value;
+    }
+    let svg_seam_overlap = query.svg_seam_overlap.unwrap_or_else(|| match preset {
+        Some(TracePreset::LogoUi) => 0.0,
+        Some(TracePreset::Illustration) => 0.25,
+        Some(TracePreset::Photo) => 0.5,
+        Some(TracePreset::Gradient) => 0.25,
+        None if options.preserve_gradients => 0.25,
+        None if options.curve_fit > 0.0 => 0.25,
+        None => 0.0,
+    });
+    if !svg_seam_overlap.is_finite() || !(0.0..=4.0).contains(&svg_seam_overlap) {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "SVG seam overlap must be finite and between 0 and 4".to_string(),
+        ));
+    }
+    let outline_width = query.outline_width.filter(|width| *width > 0.0);
+    if outline_width.is_some_and(|width| !width.is_finite() || width > 16.0) {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "outline width must be finite and at most 16".to_string(),
+        ));
+    }
+    Ok(TraceSettings {
+        options,
+        svg_seam_overlap,
+        outline_width,
+        gradient_step: query.gradient_step.filter(|step| *step > 0),
+    })
+}
+
 async fn zip_ass(filename: &str, ass: &[u8]) -> Result<Vec<u8>, String> {
     let mut writer = ZipFileWriter::new(Vec::new());
     let entry = ZipEntryBuilder::new(filename.to_string().into(), Compression::Deflate);
@@ -90,20 +90,29 @@ impl Forgejo {
     }
 
     pub async fn list_contents(&self, owner_repo: &str, path: &str) -> Result<Vec<String>, String> {
+        self.list_contents_if_exists(owner_repo, path).await?
+            .ok_or_else(|| format!("list_contents failed: 404 Not Found ({})", path))
+    }
+
+    // Like list_contents, but a missing path is `None` instead of an error.
+    pub async fn list_contents_if_exists(&self, owner_repo: &str, path: &str) -> Result<Option<Vec<String>>, String> {
         let url = contents_url(self.provider, &self.host, owner_repo, path)?;
         let resp = self.client.get(url.clone())
             .bearer_auth(&self.token)
             .send().await
             .map_err(|e| e.to_string())?;
-        if !resp.status().is_success() {
-            let s = resp.status();
+        let status = resp.status();
+        if status.as_u16() == 404 {
+            return Ok(None);
+        }
+        if !status.is_success() {
             let text = resp.text().await.unwrap_or_default();
-            return Err(format!("list_contents failed: {} {}", s, text));
+            return Err(format!("list_contents failed: {} {}", status, text));
         }
         let json: Value = resp.json().await.map_err(|e| e.to_string())?;
         let arr = match json.as_array() {
             Some(a) => a,
-            None => return Ok(Vec::new()),
+            None => return Ok(Some(Vec::new())),
         };
         let mut names: Vec<String> = Vec::new();
         for entry in arr {
@@ -113,7 +122,7 @@ impl Forgejo {
                 names.push(name);
             }
         }
-        Ok(names)
+        Ok(Some(names))
     }
 
     pub async fn create_file(&self, owner_repo: &str, path: &str, content_b64: &str, message: &str) -> Result<(), String> {
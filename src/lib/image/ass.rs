@@ -0,0 +1,187 @@
+use super::core::{Canvas, Color, ImageError, ImageResult};
+use crate::libkagami::core::SubstationAlpha;
+use crate::libkagami::drawing::parse::{Drawing, DrawingCommand};
+use crate::libkagami::render::{EventRender, Renderer};
+use ab_glyph::FontRef;
+use resvg::tiny_skia;
+use std::path::PathBuf;
+
+// Paints what `libkagami::render` lays out, so previews don't need a source video and an ffmpeg
+// burn. Script coordinates are stretched over the whole canvas.
+pub struct AssPreview<'s> {
+    renderer: Renderer<'s>,
+}
+
+impl<'s> AssPreview<'s> {
+    // Text in fonts that are neither under `font_roots` nor attached falls back to the embedded
+    // Liberation Mono so the line still shows up where it should.
+    pub fn new(sub: &'s SubstationAlpha, font_roots: &[PathBuf], video_width: u32) -> AssPreview<'s> {
+        let fallback = FontRef::try_from_slice(include_bytes!("testdata/LiberationMono-Regular.ttf")).ok();
+        AssPreview { renderer: Renderer::new(sub, font_roots, fallback, video_width as f32) }
+    }
+
+    // Everything on screen at `time_ms` on a fresh canvas. Use `Color::TRANSPARENT` for an overlay.
+    pub fn frame(&self, time_ms: f64, width: u32, height: u32, background: Color) -> ImageResult<Canvas> {
+        let mut canvas = Canvas::new(width, height, background)?;
+        self.draw_frame(&mut canvas, time_ms)?;
+        Ok(canvas)
+    }
+
+    pub fn draw_frame(&self, canvas: &mut Canvas, time_ms: f64) -> ImageResult<()> {
+        for event in self.renderer.frame(time_ms) {
+            canvas.draw_ass_event(&event, self.renderer.play_res())?;
+        }
+        Ok(())
+    }
+
+    // One event on its own; nothing is drawn when it isn't on screen at `time_ms`.
+    pub fn draw_event(&self, canvas: &mut Canvas, index: usize, time_ms: f64) -> ImageResult<bool> {
+        let Some(event) = self.renderer.event(index, time_ms) else {
+            return Ok(false);
+        };
+        canvas.draw_ass_event(&event, self.renderer.play_res())?;
+        Ok(true)
+    }
+}
+
+impl Canvas {
+    // Each run of shapes sharing a pass and colour is drawn opaque onto a scratch layer first and
+    // then composited at its alpha, so overlapping glyphs and a border under its own fill don't
+    // stack their transparency. libass composites its bitmaps the same way.
+    pub fn draw_ass_event(&mut self, event: &EventRender, play_res: (f32, f32)) -> ImageResult<()> {
+        let (width, height) = (self.width(), self.height());
+        if play_res.0 <= 0.0 || play_res.1 <= 0.0 {
+            return Err(ImageError::Dimensions("script resolution must be non-zero".to_string()));
+        }
+        let transform = tiny_skia::Transform::from_scale(width as f32 / play_res.0, height as f32 / play_res.1);
+        let clip = match &event.clip {
+            Some((shape, inverse)) => Some(clip_mask(shape, *inverse, width, height, transform)?),
+            None => None,
+        };
+        let mut layer = tiny_skia::Pixmap::new(width, height)
+            .ok_or_else(|| ImageError::Dimensions(format!("cannot allocate {}x{} ass layer", width, height)))?;
+        let mut opaque = tiny_skia::Paint::default();
+        opaque.anti_alias = true;
+
+        let mut shapes = event.shapes.iter().peekable();
+        while let Some(first) = shapes.next() {
+            let mut group = vec![first];
+            while let Some(next) = shapes.next_if(|next| next.pass == first.pass && next.rgba == first.rgba) {
+                group.push(next);
+            }
+            let [r, g, b, a] = first.rgba;
+            if a == 0 {
+                continue;
+            }
+
+            layer.fill(tiny_skia::Color::TRANSPARENT);
+            opaque.set_color_rgba8(r, g, b, 255);
+            for shape in group {
+                let Some(path) = skia_path(&shape.shape) else {
+                    continue;
+                };
+                layer.fill_path(&path, &opaque, tiny_skia::FillRule::Winding, transform, None);
+                if shape.stroke > 0.0 {
+                    let stroke = tiny_skia::Stroke {
+                        width: shape.stroke * 2.0,
+                        line_join: tiny_skia::LineJoin::Round,
+                        line_cap: tiny_skia::LineCap::Round,
+                        ..tiny_skia::Stroke::default()
+                    };
+                    layer.stroke_path(&path, &opaque, &stroke, transform, None);
+                }
+            }
+
+            let paint = tiny_skia::PixmapPaint {
+                opacity: a as f32 / 255.0,
+                blend_mode: tiny_skia::BlendMode::SourceOver,
+                ..tiny_skia::PixmapPaint::default()
+            };
+            self.pixmap_mut().draw_pixmap(0, 0, layer.as_ref(), &paint, tiny_skia::Transform::identity(), clip.as_ref());
+        }
+        Ok(())
+    }
+}
+
+// Contours are closed before the next `m`, as libass closes them, so borders go all the way round.
+fn skia_path(drawing: &Drawing) -> Option<tiny_skia::Path> {
+    let mut builder = tiny_skia::PathBuilder::new();
+    let mut open = false;
+    for command in drawing.to_cubics().commands {
+        match command {
+            DrawingCommand::Move(x, y) | DrawingCommand::MoveN(x, y) => {
+                if open {
+                    builder.close();
+                }
+                builder.move_to(x, y);
+                open = false;
+            }
+            DrawingCommand::Line(x, y) => {
+                builder.line_to(x, y);
+                open = true;
+            }
+            DrawingCommand::CubicBezier(x1, y1, x2, y2, x, y) => {
+                builder.cubic_to(x1, y1, x2, y2, x, y);
+                open = true;
+            }
+            _ => {}
+        }
+    }
+    if open {
+        builder.close();
+    }
+    builder.finish()
+}
+
+fn clip_mask(shape: &Drawing, inverse: bool, width: u32, height: u32, transform: tiny_skia::Transform) -> ImageResult<tiny_skia::Mask> {
+    let mut mask = tiny_skia::Mask::new(width, height)
+        .ok_or_else(|| ImageError::Dimensions(format!("cannot allocate {}x{} clip mask", width, height)))?;
+    if let Some(path) = skia_path(shape) {
+        mask.fill_path(&path, tiny_skia::FillRule::Winding, true, transform);
+    }
+    if inverse {
+        for coverage in mask.data_mut() {
+            *coverage = 255 - *coverage;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "[Script Info]\nPlayResX: 320\nPlayResY: 180\nScaledBorderAndShadow: yes\n\n[V4+ Styles]\n\
+        Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+        Style: Default,Arial,40,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,0,0,7,0,0,0,1\n\n\
+        [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+        Dialogue: 0,0:00:00.00,0:00:02.00,Default,,0,0,0,,{\\pos(10,10)\\c&H0000FF&\\clip(0,0,40,180)\\p1}m 0 0 l 60 0 60 20 0 20\n\
+        Dialogue: 1,0:00:01.00,0:00:02.00,Default,,0,0,0,,{\\pos(100,100)\\1a&H80&\\p1}m 0 0 l 20 0 20 20 0 20\n";
+
+    #[test]
+    fn draws_clipped_drawings_scaled_to_the_canvas() {
+        let (sub, _) = SubstationAlpha::parse(SCRIPT, true);
+        let preview = AssPreview::new(&sub, &[], 640);
+        let canvas = preview.frame(500.0, 640, 360, Color::BLACK).unwrap();
+
+        assert_eq!(canvas.pixel_rgba(40, 40), Some(Color { r: 255, g: 0, b: 0, a: 255 }));
+        // Past the clip's right edge at x = 40 script pixels.
+        assert_eq!(canvas.pixel_rgba(100, 40), Some(Color::BLACK));
+        // The second line isn't on screen yet.
+        assert_eq!(canvas.pixel_rgba(220, 220), Some(Color::BLACK));
+    }
+
+    #[test]
+    fn draws_alpha_over_a_transparent_canvas() {
+        let (sub, _) = SubstationAlpha::parse(SCRIPT, true);
+        let preview = AssPreview::new(&sub, &[], 640);
+        let mut canvas = Canvas::new(320, 180, Color::TRANSPARENT).unwrap();
+
+        assert!(!preview.draw_event(&mut canvas, 1, 500.0).unwrap());
+        assert!(preview.draw_event(&mut canvas, 1, 1500.0).unwrap());
+        let pixel = canvas.pixel_rgba(110, 110).unwrap();
+        assert_eq!((pixel.r, pixel.g, pixel.b), (255, 255, 255));
+        assert!((126..=128).contains(&pixel.a), "{:?}", pixel);
+        assert_eq!(canvas.pixel_rgba(5, 170), Some(Color::TRANSPARENT));
+    }
+}
@@ -1,8 +1,10 @@
+pub mod ass;
 pub mod core;
 pub mod svg;
 pub mod text;
 pub mod timeline;
 
+pub use self::ass::AssPreview;
 pub use self::core::{Canvas, Color, ImageError, ImageResult, Ratio};
 pub use self::svg::{FitMode, Placement, SvgImage};
 pub use self::text::{Align, Font, TextBounds, TextOptions};
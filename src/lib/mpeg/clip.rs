@@ -0,0 +1,218 @@
+use crate::kagami_trace::{MAX_ANIMATION_FRAMES, MAX_ANIMATION_PIXELS};
+use crate::lib::bin::resolve_runtime_binary;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const VIDEO_EXTENSIONS: &[&str] = &["mkv", "mp4", "m2ts", "ts", "webm", "avi", "mov"];
+
+// Decoded RGBA frames of a stretch of a video's first stream. They stay at the video's own
+// resolution so traced coordinates line up with a script whose PlayRes matches the video.
+pub struct ClipFrames {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64,
+    pub frames: Vec<Vec<u8>>,
+}
+
+impl ClipFrames {
+    // Per-frame display times in whole milliseconds, rounded against the running total so a long
+    // clip doesn't drift off the frame grid.
+    pub fn durations_ms(&self) -> Vec<u32> {
+        let at = |frame: usize| (frame as f64 * 1000.0 / self.fps).round() as u32;
+        (0..self.frames.len()).map(|frame| at(frame + 1) - at(frame)).collect()
+    }
+}
+
+pub fn extract_clip_frames(path: &Path, start_ms: u64, end_ms: u64) -> Result<ClipFrames, String> {
+    if end_ms <= start_ms {
+        return Err("clip end must be after its start".to_string());
+    }
+    let (width, height, fps) = probe_video(path)?;
+    let count = ((end_ms - start_ms) as f64 * fps / 1000.0).round().max(1.0) as usize;
+    if count > MAX_ANIMATION_FRAMES || width as u64 * height as u64 * count as u64 > MAX_ANIMATION_PIXELS {
+        return Err(format!("a {}-frame {}x{} clip is over the tracing limit; pick a shorter range", count, width, height));
+    }
+
+    let output = Command::new(resolve_runtime_binary("ffmpeg"))
+        .args(["-hide_banner", "-nostdin", "-ss"])
+        .arg(format!("{:.3}", start_ms as f64 / 1000.0))
+        .arg("-i")
+        .arg(path)
+        .args(["-map", "0:v:0", "-an", "-sn", "-frames:v"])
+        .arg(count.to_string())
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+        .output()
+        .map_err(|e| format!("ffmpeg failed to start: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().last().unwrap_or("ffmpeg could not decode the clip").trim().to_string());
+    }
+    let frame_bytes = width as usize * height as usize * 4;
+    let frames: Vec<Vec<u8>> = output.stdout.chunks_exact(frame_bytes).map(<[u8]>::to_vec).collect();
+    if frames.is_empty() {
+        return Err("ffmpeg decoded no frames in that range".to_string());
+    }
+    Ok(ClipFrames { width, height, fps, frames })
+}
+
+// A rectangle of the video frame in video pixels, written `x,y,width,height`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameCrop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl std::str::FromStr for FrameCrop {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<u32> = s
+            .split(',')
+            .map(|part| part.trim().parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("crop `{}` must be four whole numbers `x,y,width,height`", s))?;
+        let [x, y, width, height] = values[..] else {
+            return Err(format!("crop `{}` must be four whole numbers `x,y,width,height`", s));
+        };
+        if width == 0 || height == 0 {
+            return Err("crop width and height must be positive".to_string());
+        }
+        Ok(FrameCrop { x, y, width, height })
+    }
+}
+
+// One decoded frame, cropped when asked, with the full frame's size so the crop can be placed back
+// into script coordinates.
+pub struct StillFrame {
+    pub width: u32,
+    pub height: u32,
+    pub video_width: u32,
+    pub video_height: u32,
+    pub pixels: Vec<u8>,
+}
+
+pub fn extract_frame(path: &Path, at_ms: u64, crop: Option<FrameCrop>) -> Result<StillFrame, String> {
+    let (video_width, video_height, _) = probe_video(path)?;
+    let crop = crop.unwrap_or(FrameCrop { x: 0, y: 0, width: video_width, height: video_height });
+    if crop.x as u64 + crop.width as u64 > video_width as u64 || crop.y as u64 + crop.height as u64 > video_height as u64 {
+        return Err(format!(
+            "crop {}x{} at {},{} does not fit the {}x{} video",
+            crop.width, crop.height, crop.x, crop.y, video_width, video_height
+        ));
+    }
+
+    let output = Command::new(resolve_runtime_binary("ffmpeg"))
+        .args(["-hide_banner", "-nostdin", "-ss"])
+        .arg(format!("{:.3}", at_ms as f64 / 1000.0))
+        .arg("-i")
+        .arg(path)
+        .args(["-map", "0:v:0", "-an", "-sn", "-vf"])
+        .arg(format!("crop={}:{}:{}:{}", crop.width, crop.height, crop.x, crop.y))
+        .args(["-frames:v", "1", "-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+        .output()
+        .map_err(|e| format!("ffmpeg failed to start: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().last().unwrap_or("ffmpeg could not decode the frame").trim().to_string());
+    }
+    let frame_bytes = crop.width as usize * crop.height as usize * 4;
+    if output.stdout.len() < frame_bytes {
+        return Err("ffmpeg decoded no frame at that time".to_string());
+    }
+    let mut pixels = output.stdout;
+    pixels.truncate(frame_bytes);
+    Ok(StillFrame { width: crop.width, height: crop.height, video_width, video_height, pixels })
+}
+
+// The largest video file under a job directory, which is the episode itself rather than an
+// extra or a sample.
+pub fn find_source_video(dir: &Path) -> Option<PathBuf> {
+    let mut best: Option<(u64, PathBuf)> = None;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let video = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+            if video && best.as_ref().is_none_or(|(size, _)| meta.len() > *size) {
+                best = Some((meta.len(), path));
+            }
+        }
+    }
+    best.map(|(_, path)| path)
+}
+
+fn probe_video(path: &Path) -> Result<(u32, u32, f64), String> {
+    let output = Command::new(resolve_runtime_binary("ffprobe"))
+        .args(["-v", "error", "-select_streams", "v:0", "-show_entries", "stream=width,height,r_frame_rate,avg_frame_rate", "-of", "default=noprint_wrappers=1"])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("ffprobe failed to start: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().last().unwrap_or("ffprobe could not read the video").trim().to_string());
+    }
+    parse_stream_info(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| format!("{} has no video stream with a known size and frame rate", path.display()))
+}
+
+// `key=value` lines from ffprobe. avg_frame_rate is `0/0` on some Matroska files, so
+// r_frame_rate backs it up.
+fn parse_stream_info(text: &str) -> Option<(u32, u32, f64)> {
+    let field = |key: &str| {
+        text.lines()
+            .filter_map(|line| line.trim().split_once('='))
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.trim())
+    };
+    let rate = |value: &str| {
+        let (num, den) = value.split_once('/').unwrap_or((value, "1"));
+        let rate = num.parse::<f64>().ok()? / den.parse::<f64>().ok()?;
+        (rate.is_finite() && rate > 0.0).then_some(rate)
+    };
+    let width = field("width")?.parse().ok().filter(|w| *w > 0)?;
+    let height = field("height")?.parse().ok().filter(|h| *h > 0)?;
+    let fps = field("avg_frame_rate").and_then(rate).or_else(|| field("r_frame_rate").and_then(rate))?;
+    Some((width, height, fps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_info_falls_back_to_the_real_frame_rate() {
+        let text = "width=1920\nheight=1080\nr_frame_rate=24000/1001\navg_frame_rate=0/0\n";
+        let (width, height, fps) = parse_stream_info(text).unwrap();
+        assert_eq!((width, height), (1920, 1080));
+        assert!((fps - 23.976).abs() < 0.001);
+        assert!(parse_stream_info("width=1920\n").is_none());
+
+        let clip = ClipFrames { width: 1, height: 1, fps, frames: vec![Vec::new(); 24] };
+        let durations = clip.durations_ms();
+        assert_eq!(&durations[..3], &[42, 41, 42]);
+        assert_eq!(durations.iter().sum::<u32>(), 1001);
+    }
+
+    #[test]
+    fn crops_parse_from_four_numbers() {
+        let crop: FrameCrop = " 120, 40,640,360".parse().unwrap();
+        assert_eq!(crop, FrameCrop { x: 120, y: 40, width: 640, height: 360 });
+        assert!("120,40,640".parse::<FrameCrop>().is_err());
+        assert!("0,0,0,360".parse::<FrameCrop>().is_err());
+        assert!("-1,0,10,10".parse::<FrameCrop>().is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::lib::bin::resolve_runtime_binary;
+use std::path::Path;
+use std::process::Command;
+
+// Keyframes of the first video stream, as frame numbers counted in presentation order, plus the
+// stream's average frame rate. `scene_threshold` additionally runs ffmpeg's scene detector and
+// merges its cuts in — encoders with a long GOP or open-GOP settings miss cuts that a typesetter
+// still wants lines to snap to. Values around 0.3 catch hard cuts without picking up pans.
+pub struct ExtractedKeyframes {
+    pub frames: Vec<i64>,
+    pub fps: Option<f64>,
+    pub scene_cuts: usize,
+}
+
+pub fn extract_keyframes(path: &Path, scene_threshold: Option<f64>) -> Result<ExtractedKeyframes, String> {
+    let output = Command::new(resolve_runtime_binary("ffprobe"))
+        .args(["-v", "error", "-select_streams", "v:0", "-show_entries", "packet=pts_time,flags", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("ffprobe failed to start: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().last().unwrap_or("ffprobe could not read the video").trim().to_string());
+    }
+    let packets = parse_packets(&String::from_utf8_lossy(&output.stdout));
+    if packets.is_empty() {
+        return Err(format!("{} has no video packets", path.display()));
+    }
+    let fps = average_fps(&packets);
+    let mut frames: Vec<i64> = packets
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, key))| *key)
+        .map(|(frame, _)| frame as i64)
+        .collect();
+
+    let mut scene_cuts = 0;
+    if let Some(threshold) = scene_threshold {
+        let pts: Vec<f64> = packets.iter().map(|(pts, _)| *pts).collect();
+        for time in detect_scene_cuts(path, threshold)? {
+            let frame = nearest_frame(&pts, time);
+            if let Err(at) = frames.binary_search(&frame) {
+                frames.insert(at, frame);
+                scene_cuts += 1;
+            }
+        }
+    }
+    // Aegisub treats frame 0 as a keyframe whether or not the file lists it.
+    if frames.first() != Some(&0) {
+        frames.insert(0, 0);
+    }
+    Ok(ExtractedKeyframes { frames, fps, scene_cuts })
+}
+
+fn detect_scene_cuts(path: &Path, threshold: f64) -> Result<Vec<f64>, String> {
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!("scene threshold {} is outside 0..1", threshold));
+    }
+    let filter = format!("select='gt(scene,{})',showinfo", threshold);
+    let output = Command::new(resolve_runtime_binary("ffmpeg"))
+        .args(["-hide_banner", "-nostdin", "-i"])
+        .arg(path)
+        .args(["-map", "0:v:0", "-an", "-sn", "-vf", &filter, "-f", "null", "-"])
+        .output()
+        .map_err(|e| format!("ffmpeg failed to start: {e}"))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(stderr.lines().last().unwrap_or("ffmpeg scene detection failed").trim().to_string());
+    }
+    Ok(parse_showinfo(&stderr))
+}
+
+// `pts_time,flags` rows in decode order, sorted into presentation order. Packets without a
+// timestamp are dropped; B-frame reordering is why the sort is needed at all.
+fn parse_packets(text: &str) -> Vec<(f64, bool)> {
+    let mut packets: Vec<(f64, bool)> = text
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().split(',');
+            let pts = fields.next()?.trim().parse::<f64>().ok()?;
+            let flags = fields.next().unwrap_or("");
+            Some((pts, flags.contains('K')))
+        })
+        .collect();
+    packets.sort_by(|a, b| a.0.total_cmp(&b.0));
+    packets
+}
+
+fn average_fps(packets: &[(f64, bool)]) -> Option<f64> {
+    let (first, last) = (packets.first()?.0, packets.last()?.0);
+    let span = last - first;
+    (packets.len() > 1 && span > 0.0).then(|| (packets.len() - 1) as f64 / span)
+}
+
+fn parse_showinfo(stderr: &str) -> Vec<f64> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("Parsed_showinfo"))
+        .filter_map(|line| {
+            let rest = &line[line.find("pts_time:")? + "pts_time:".len()..];
+            rest.split_whitespace().next()?.parse().ok()
+        })
+        .collect()
+}
+
+fn nearest_frame(pts: &[f64], time: f64) -> i64 {
+    let at = pts.partition_point(|p| *p < time);
+    if at == 0 {
+        return 0;
+    }
+    if at == pts.len() || time - pts[at - 1] < pts[at] - time {
+        (at - 1) as i64
+    } else {
+        at as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_are_reordered_and_keyframes_counted_in_display_order() {
+        let csv = "0.000000,K__\n0.125000,___\n0.041667,___\n0.083333,___\nN/A,___\n0.166667,K__\n";
+        let packets = parse_packets(csv);
+        let keys: Vec<i64> = packets.iter().enumerate().filter(|(_, p)| p.1).map(|(i, _)| i as i64).collect();
+        assert_eq!(keys, vec![0, 4]);
+        let fps = average_fps(&packets).unwrap();
+        assert!((fps - 24.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn scene_cuts_map_to_the_closest_frame() {
+        let stderr = "[Parsed_showinfo_1 @ 0x1] n:   0 pts:  12012 pts_time:0.5005  duration:1001\n\
+                      frame=  100 fps=0.0 q=-0.0\n\
+                      [Parsed_showinfo_1 @ 0x1] n:   1 pts:  48048 pts_time:2.002 duration:1001\n";
+        let times = parse_showinfo(stderr);
+        assert_eq!(times, vec![0.5005, 2.002]);
+        let pts: Vec<f64> = (0..100).map(|i| i as f64 * 1001.0 / 24000.0).collect();
+        assert_eq!(nearest_frame(&pts, times[0]), 12);
+        assert_eq!(nearest_frame(&pts, times[1]), 48);
+        assert_eq!(nearest_frame(&pts, 99.0), 99);
+    }
+}
@@ -4,3 +4,6 @@ pub mod subs;
 pub mod preset;
 pub mod preview;
 pub mod studio;
+pub mod resync;
+pub mod keyframes;
+pub mod clip;
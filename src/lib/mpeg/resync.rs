@@ -0,0 +1,335 @@
+use crate::lib::bin::resolve_runtime_binary;
+use crate::libkagami::timing::SyncSegment;
+use std::path::Path;
+use std::process::Command;
+
+// Envelope frames per second. 10 ms is finer than a subtitle frame and keeps a 24 minute
+// episode at ~144k values, which brute-force correlation gets through in well under a second.
+pub const ENVELOPE_RATE: usize = 100;
+const FRAME_MS: i64 = 1000 / ENVELOPE_RATE as i64;
+const DECODE_RATE: usize = 8000;
+// The coarse pass searches every lag at 10 frames per step, then the fine pass looks this many
+// frames either side of the coarse winner.
+const COARSE_STEP: usize = 10;
+const FINE_RADIUS: i64 = 15;
+
+#[derive(Clone, Debug)]
+pub struct AlignOptions {
+    // Length of the reference chunk matched on its own. Shorter chunks place a cut more
+    // precisely before refinement but match less reliably on quiet scenes.
+    pub chunk_ms: i64,
+    // Furthest a chunk may have moved between the two sources.
+    pub max_offset_ms: i64,
+    // Normalised correlation a chunk needs before its offset is trusted.
+    pub min_correlation: f32,
+    // Offsets closer than this count as the same segment.
+    pub tolerance_ms: i64,
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        Self {
+            chunk_ms: 15_000,
+            max_offset_ms: 600_000,
+            min_correlation: 0.5,
+            tolerance_ms: 40,
+        }
+    }
+}
+
+// Decodes the first Japanese audio track of `path` (the first track when none is tagged) to a
+// mono log-energy envelope at ENVELOPE_RATE. Both sources go through the same path, so the
+// envelopes line up even when one release is AAC and the other FLAC.
+pub fn decode_envelope(path: &Path) -> Result<Vec<f32>, String> {
+    let pcm = match decode_pcm(path, "0:a:m:language:jpn") {
+        Ok(pcm) if !pcm.is_empty() => pcm,
+        _ => decode_pcm(path, "0:a:0")?,
+    };
+    if pcm.is_empty() {
+        return Err(format!("{} has no decodable audio", path.display()));
+    }
+    let window = DECODE_RATE / ENVELOPE_RATE;
+    Ok(pcm
+        .chunks(window)
+        .map(|samples| {
+            let energy = samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32;
+            10.0 * (energy + 1e-10).log10()
+        })
+        .collect())
+}
+
+fn decode_pcm(path: &Path, map: &str) -> Result<Vec<f32>, String> {
+    let output = Command::new(resolve_runtime_binary("ffmpeg"))
+        .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-i"])
+        .arg(path)
+        .args(["-map", map, "-vn", "-sn", "-ac", "1", "-ar", &DECODE_RATE.to_string(), "-f", "s16le", "pipe:1"])
+        .output()
+        .map_err(|e| format!("ffmpeg failed to start: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().last().unwrap_or("ffmpeg could not decode the audio").trim().to_string());
+    }
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / 32768.0)
+        .collect())
+}
+
+// Finds where each part of `reference` (the audio the subtitles were timed against) ended up in
+// `target`. The reference is cut into chunks that are matched independently, so an inserted or
+// removed block — a commercial break, a longer OP, a recap — shows up as a change of offset
+// between neighbouring chunks; each change is then placed to the frame by trying every cut
+// position between the two chunks. Returns segments covering the whole reference.
+pub fn align(reference: &[f32], target: &[f32], options: &AlignOptions) -> Result<Vec<SyncSegment>, String> {
+    let reference = standardize(reference);
+    let target = standardize(target);
+    let chunk = ((options.chunk_ms / FRAME_MS) as usize).max(COARSE_STEP * 10);
+    if reference.len() < chunk || target.len() < chunk {
+        return Err("audio is too short to align".to_string());
+    }
+    let max_lag = options.max_offset_ms / FRAME_MS;
+    let coarse_reference = downsample(&reference);
+    let coarse_target = downsample(&target);
+
+    let mut offsets: Vec<Option<i64>> = Vec::new();
+    for start in (0..reference.len()).step_by(chunk) {
+        let end = (start + chunk).min(reference.len());
+        if end - start < chunk / 2 {
+            // A short tail is matched with its predecessor's offset.
+            offsets.push(None);
+            continue;
+        }
+        offsets.push(match_chunk(
+            &reference, &target, &coarse_reference, &coarse_target, start, end, max_lag, options.min_correlation,
+        ));
+    }
+    let Some(first) = offsets.iter().flatten().next().copied() else {
+        return Err("the two audio tracks do not match anywhere; is the new link the same episode?".to_string());
+    };
+    let tolerance = (options.tolerance_ms / FRAME_MS).max(1);
+    let mut filled: Vec<i64> = Vec::with_capacity(offsets.len());
+    let mut last = first;
+    for offset in &offsets {
+        last = offset.unwrap_or(last);
+        filled.push(last);
+    }
+    // A lone chunk that disagrees with two neighbours that agree with each other is a bad match,
+    // not a two-way cut: real edits move every later chunk.
+    for i in 1..filled.len().saturating_sub(1) {
+        if (filled[i - 1] - filled[i + 1]).abs() <= tolerance && (filled[i] - filled[i - 1]).abs() > tolerance {
+            filled[i] = filled[i - 1];
+        }
+    }
+
+    let mut segments: Vec<SyncSegment> = Vec::new();
+    let mut segment_start = 0usize;
+    let mut segment_offset = filled[0];
+    for (i, offset) in filled.iter().copied().enumerate().skip(1) {
+        if (offset - segment_offset).abs() <= tolerance {
+            continue;
+        }
+        let window_start = (i - 1) * chunk;
+        let window_end = ((i + 1) * chunk).min(reference.len());
+        let cut = place_cut(&reference, &target, window_start.max(segment_start), window_end, segment_offset, offset);
+        segments.push(SyncSegment {
+            start_ms: segment_start as i64 * FRAME_MS,
+            end_ms: cut as i64 * FRAME_MS,
+            offset_ms: segment_offset * FRAME_MS,
+        });
+        segment_start = cut;
+        segment_offset = offset;
+    }
+    segments.push(SyncSegment {
+        start_ms: segment_start as i64 * FRAME_MS,
+        end_ms: reference.len() as i64 * FRAME_MS,
+        offset_ms: segment_offset * FRAME_MS,
+    });
+    Ok(segments)
+}
+
+// One line per segment for the job embed: where it starts, how far it moved, and what changed
+// relative to the segment before it.
+pub fn describe_segments(segments: &[SyncSegment]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut previous: Option<i64> = None;
+    for segment in segments {
+        let change = match previous {
+            None => String::new(),
+            Some(prev) if segment.offset_ms > prev => {
+                format!(" — {} inserted in the new source", format_seconds(segment.offset_ms - prev))
+            }
+            Some(prev) => format!(" — {} removed from the new source", format_seconds(prev - segment.offset_ms)),
+        };
+        lines.push(format!(
+            "{}: {:+.2}s{}",
+            format_clock(segment.start_ms),
+            segment.offset_ms as f64 / 1000.0,
+            change
+        ));
+        previous = Some(segment.offset_ms);
+    }
+    lines
+}
+
+fn format_seconds(ms: i64) -> String {
+    format!("{:.2}s", ms as f64 / 1000.0)
+}
+
+fn format_clock(ms: i64) -> String {
+    let cs = ms.max(0) / 10;
+    format!("{}:{:02}:{:02}.{:02}", cs / 360_000, cs / 6000 % 60, cs / 100 % 60, cs % 100)
+}
+
+// Zero mean, unit variance over the whole track, so a louder release does not look like a
+// different one and squared differences in place_cut are comparable between sources.
+fn standardize(values: &[f32]) -> Vec<f32> {
+    if values.is_empty() {
+        return Vec::new();
+    }
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len() as f32;
+    let scale = if variance > 1e-12 { variance.sqrt() } else { 1.0 };
+    values.iter().map(|v| (v - mean) / scale).collect()
+}
+
+fn downsample(values: &[f32]) -> Vec<f32> {
+    values
+        .chunks(COARSE_STEP)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect()
+}
+
+// Pearson correlation of `a` against `b` starting at `lag` frames into `b`; None when the window
+// runs off either end or the reference window is flat (silence correlates with anything).
+fn correlation(a: &[f32], b: &[f32], lag: i64) -> Option<f32> {
+    let start = usize::try_from(lag).ok()?;
+    let b = b.get(start..start + a.len())?;
+    let n = a.len() as f32;
+    let (mean_a, mean_b) = (a.iter().sum::<f32>() / n, b.iter().sum::<f32>() / n);
+    let (mut cov, mut var_a, mut var_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        let (dx, dy) = (x - mean_a, y - mean_b);
+        cov += dx * dy;
+        var_a += dx * dx;
+        var_b += dy * dy;
+    }
+    if var_a < 1e-6 * n || var_b < 1e-6 * n {
+        return None;
+    }
+    Some(cov / (var_a.sqrt() * var_b.sqrt()))
+}
+
+// Offset in frames of reference[start..end] inside target, searched at 1/COARSE_STEP resolution
+// over the whole allowed range and then refined around the best coarse lag.
+#[allow(clippy::too_many_arguments)]
+fn match_chunk(
+    reference: &[f32],
+    target: &[f32],
+    coarse_reference: &[f32],
+    coarse_target: &[f32],
+    start: usize,
+    end: usize,
+    max_lag: i64,
+    min_correlation: f32,
+) -> Option<i64> {
+    let step = COARSE_STEP as i64;
+    let coarse_start = start / COARSE_STEP;
+    let coarse_window = &coarse_reference[coarse_start..(end / COARSE_STEP).min(coarse_reference.len())];
+    let coarse_max = max_lag / step;
+    let mut best: Option<(i64, f32)> = None;
+    for lag in -coarse_max..=coarse_max {
+        if let Some(score) = correlation(coarse_window, coarse_target, coarse_start as i64 + lag)
+            && best.is_none_or(|(_, best_score)| score > best_score)
+        {
+            best = Some((lag, score));
+        }
+    }
+    let (coarse_lag, _) = best?;
+    let window = &reference[start..end];
+    let mut fine: Option<(i64, f32)> = None;
+    for lag in coarse_lag * step - FINE_RADIUS..=coarse_lag * step + FINE_RADIUS {
+        if let Some(score) = correlation(window, target, start as i64 + lag)
+            && fine.is_none_or(|(_, best_score)| score > best_score)
+        {
+            fine = Some((lag, score));
+        }
+    }
+    fine.filter(|(_, score)| *score >= min_correlation).map(|(lag, _)| lag)
+}
+
+// The frame in [from, to) where the reference stops matching target at `before` and starts
+// matching it at `after`, found by minimising the squared error of the two-piece fit.
+fn place_cut(reference: &[f32], target: &[f32], from: usize, to: usize, before: i64, after: i64) -> usize {
+    let error = |frame: usize, offset: i64| {
+        let value = reference[frame];
+        match usize::try_from(frame as i64 + offset).ok().and_then(|i| target.get(i)) {
+            Some(other) => (value - other).powi(2),
+            None => value.powi(2) + 1.0,
+        }
+    };
+    // error_before[i] sums frames from..from+i at `before`; error_after sums the rest at `after`.
+    let mut error_before = vec![0.0f32; to - from + 1];
+    for (i, frame) in (from..to).enumerate() {
+        error_before[i + 1] = error_before[i] + error(frame, before);
+    }
+    let mut error_after = vec![0.0f32; to - from + 1];
+    for (i, frame) in (from..to).enumerate().rev() {
+        error_after[i] = error_after[i + 1] + error(frame, after);
+    }
+    (0..=to - from)
+        .min_by(|&a, &b| {
+            (error_before[a] + error_after[a]).total_cmp(&(error_before[b] + error_after[b]))
+        })
+        .map_or(from, |i| from + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic stand-in for programme audio: a noisy envelope with no long repeats.
+    fn programme(frames: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed;
+        (0..frames)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                -40.0 + 20.0 * (state >> 16) as f32 / 65_536.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_a_constant_delay() {
+        let reference = programme(30_000, 7);
+        let mut target = programme(250, 99);
+        target.extend_from_slice(&reference);
+        let segments = align(&reference, &target, &AlignOptions::default()).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].offset_ms, 2500);
+        assert_eq!(segments[0].start_ms, 0);
+    }
+
+    #[test]
+    fn places_an_inserted_break_to_the_frame() {
+        let reference = programme(40_000, 3);
+        // Ninety seconds of something else spliced in at 2:30.00 of the old timeline.
+        let mut target = reference[..15_000].to_vec();
+        target.extend(programme(9_000, 1234));
+        target.extend_from_slice(&reference[15_000..]);
+        let segments = align(&reference, &target, &AlignOptions::default()).unwrap();
+        assert_eq!(segments.len(), 2, "{:?}", segments);
+        assert_eq!(segments[0].offset_ms, 0);
+        assert_eq!(segments[1].start_ms, 150_000);
+        assert_eq!(segments[1].offset_ms, 90_000);
+        let lines = describe_segments(&segments);
+        assert_eq!(lines[1], "0:02:30.00: +90.00s — 90.00s inserted in the new source");
+    }
+
+    #[test]
+    fn unrelated_audio_is_an_error() {
+        let reference = programme(20_000, 5);
+        let target = programme(20_000, 6);
+        assert!(align(&reference, &target, &AlignOptions::default()).is_err());
+    }
+}
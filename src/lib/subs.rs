@@ -1,13 +1,17 @@
+use std::borrow::Cow;
 use std::path::PathBuf;
 
+use encoding_rs::{Encoding, SHIFT_JIS, UTF_16BE, UTF_16LE, WINDOWS_1252, WINDOWS_1254};
 use regex::Regex;
 
 use crate::lib::bin::resolve_runtime_binary;
+use crate::libkagami::textsubs::{is_microdvd, parse_microdvd, parse_srt, parse_webvtt};
 
-// Text subtitle formats ffmpeg can demux, mapped from the uploaded extension to the
-// extension the temp file gets before ffmpeg probes it (several demuxers key off it).
-// `.sub` is ambiguous — MicroDVD text or a binary VobSub payload — so it is resolved
-// by sniffing the bytes, not by this table.
+// Text subtitle formats we accept, mapped from the uploaded extension to a canonical one.
+// SRT, WebVTT and MicroDVD are read natively by libkagami::textsubs; the rest keep going
+// through ffmpeg, and the canonical extension is the one the temp file gets before ffmpeg
+// probes it (several demuxers key off it). `.sub` is ambiguous — MicroDVD text or a binary
+// VobSub payload — so it is resolved by sniffing the bytes, not by this table.
 const CONVERTIBLE: &[(&str, &str)] = &[
     ("srt", "srt"),
     ("ssa", "ssa"),
@@ -39,10 +43,14 @@ pub enum SubtitleInput {
     Unsupported,
 }
 
+// Bytes of the letters only Turkish uses in Windows-1254 / ISO-8859-9 (Ğ İ Ş ğ ı ş). Windows-1252
+// puts Icelandic letters there, which our sources do not contain.
+const TURKISH_BYTES: &[u8] = &[0xD0, 0xDD, 0xDE, 0xF0, 0xFD, 0xFE];
+
 #[derive(Debug)]
 pub struct ConvertedSubtitle {
     pub bytes: Vec<u8>,
-    pub warning: Option<String>,
+    pub warnings: Vec<String>,
 }
 
 // Decides what an uploaded subtitle is from its extension, falling back to sniffing
@@ -96,7 +104,8 @@ pub fn sniff_subtitle(bytes: &[u8]) -> SubtitleInput {
     if bytes.starts_with(b"PG") {
         return SubtitleInput::ImageBased("PGS");
     }
-    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]).to_lowercase();
+    let text = subtitle_to_utf8(bytes).map(|(text, _)| text).unwrap_or(Cow::Borrowed(bytes));
+    let head = String::from_utf8_lossy(&text[..text.len().min(4096)]).to_lowercase();
     let first_line = head.lines().find(|line| !line.trim().is_empty()).unwrap_or("").trim().to_string();
     if head.contains("[v4 styles]") {
         SubtitleInput::Convertible("ssa")
@@ -118,8 +127,8 @@ pub fn sniff_subtitle(bytes: &[u8]) -> SubtitleInput {
 }
 
 // Normalises any accepted subtitle upload into ASS bytes. ASS input is passed through
-// untouched; everything else goes through ffmpeg, which produces an unstyled script,
-// hence the warning the callers surface to the user.
+// untouched; everything else is converted into an unstyled script, hence the warning
+// the callers surface to the user.
 pub async fn ensure_ass(filename: &str, bytes: &[u8]) -> Result<ConvertedSubtitle, String> {
     let kind = classify_subtitle(filename, bytes);
     convert_classified(kind, &format!("`{}`", filename), bytes).await
@@ -131,37 +140,117 @@ pub async fn ensure_ass_bytes(bytes: &[u8]) -> Result<ConvertedSubtitle, String>
     convert_classified(sniff_subtitle(bytes), "the attached subtitle", bytes).await
 }
 
+// Text encoding of a subtitle upload, for files that are not UTF-8. A BOM settles it; otherwise
+// the checks only cover what our sources actually use: UTF-16 without a BOM, Shift-JIS for
+// Japanese releases, the Turkish code pages, and Windows-1252 for everything else.
+fn detect_legacy_encoding(bytes: &[u8]) -> (&'static Encoding, &'static str) {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return (encoding, encoding.name());
+    }
+    let head = &bytes[..bytes.len().min(4096)];
+    let pairs = head.len() / 2;
+    let zeros = |parity: usize| head.iter().skip(parity).step_by(2).filter(|b| **b == 0).count();
+    if pairs > 0 && zeros(1) * 3 > pairs && zeros(0) * 10 < pairs {
+        return (UTF_16LE, "UTF-16LE");
+    }
+    if pairs > 0 && zeros(0) * 3 > pairs && zeros(1) * 10 < pairs {
+        return (UTF_16BE, "UTF-16BE");
+    }
+    // Most Windows-1254 text also decodes as Shift-JIS, into random kanji. Real Japanese is
+    // full of kana, so that decides it.
+    let (japanese, had_errors) = SHIFT_JIS.decode_without_bom_handling(bytes);
+    if !had_errors {
+        let non_ascii = japanese.chars().filter(|c| !c.is_ascii()).count();
+        let kana = japanese.chars().filter(|c| ('\u{3040}'..='\u{30ff}').contains(c)).count();
+        if non_ascii > 0 && kana * 5 >= non_ascii {
+            return (SHIFT_JIS, "Shift-JIS");
+        }
+    }
+    if bytes.iter().any(|b| TURKISH_BYTES.contains(b)) {
+        // The two Turkish code pages only differ in 0x80-0x9F, which ISO-8859-9 leaves to
+        // control codes; encoding_rs decodes both through the Windows-1254 table.
+        let label = if bytes.iter().any(|b| (0x80..=0x9F).contains(b)) { "Windows-1254" } else { "ISO-8859-9" };
+        return (WINDOWS_1254, label);
+    }
+    (WINDOWS_1252, "Windows-1252")
+}
+
+// Returns the upload as UTF-8 plus the name of the encoding it was transcoded from. UTF-8 input
+// comes back borrowed with no name. None means the bytes do not decode cleanly in the detected
+// encoding, or decode to control characters no subtitle contains, which is what binary junk or
+// a wrong guess looks like.
+pub fn subtitle_to_utf8(bytes: &[u8]) -> Option<(Cow<'_, [u8]>, Option<&'static str>)> {
+    if std::str::from_utf8(bytes).is_ok() {
+        return Some((Cow::Borrowed(bytes), None));
+    }
+    let (encoding, name) = detect_legacy_encoding(bytes);
+    let (text, _, had_errors) = encoding.decode(bytes);
+    if had_errors || text.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        return None;
+    }
+    Some((Cow::Owned(text.into_owned().into_bytes()), Some(name)))
+}
+
 async fn convert_classified(kind: SubtitleInput, label: &str, bytes: &[u8]) -> Result<ConvertedSubtitle, String> {
-    match kind {
-        SubtitleInput::Ass => Ok(ConvertedSubtitle {
-            bytes: bytes.to_vec(),
-            warning: None,
-        }),
-        SubtitleInput::ImageBased(format) => Err(format!(
+    let demux_ext = match kind {
+        SubtitleInput::Ass => None,
+        SubtitleInput::Convertible(demux_ext) => Some(demux_ext),
+        SubtitleInput::ImageBased(format) => return Err(format!(
             "{} is {} — image-based subtitles cannot be converted to ASS (they would need OCR).",
             label, format
         )),
-        SubtitleInput::Unsupported => Err(format!(
-            "unsupported subtitle file type ({}). Use .ass, or a text subtitle ffmpeg can read (.srt, .ssa, .vtt, .sub, .smi, .lrc, .mpl2, .jss, .stl, .pjs, .rt, .aqt).",
+        SubtitleInput::Unsupported => return Err(format!(
+            "unsupported subtitle file type ({}). Use .ass, or a supported text subtitle (.srt, .ssa, .vtt, .sub, .smi, .lrc, .mpl2, .jss, .stl, .pjs, .rt, .aqt).",
             label
         )),
-        SubtitleInput::Convertible(demux_ext) => {
-            if std::str::from_utf8(bytes).is_err() {
-                return Err(format!(
-                    "{} is not valid UTF-8. Re-save it as UTF-8 and upload it again.",
-                    label
-                ));
-            }
-            let converted = convert_to_ass(bytes, demux_ext).await?;
-            Ok(ConvertedSubtitle {
-                bytes: converted,
-                warning: Some(format!(
-                    "{} was converted to ASS by ffmpeg. Converted scripts carry no styling — the result uses ffmpeg's Default Arial 16 style at 384x288.",
-                    label
-                )),
-            })
-        }
+    };
+    let Some((utf8, encoding)) = subtitle_to_utf8(bytes) else {
+        return Err(format!(
+            "{} is not valid UTF-8 and its encoding could not be detected. Re-save it as UTF-8 and upload it again.",
+            label
+        ));
+    };
+    let mut warnings = Vec::new();
+    if let Some(encoding) = encoding {
+        warnings.push(format!(
+            "{} was {} text and was converted to UTF-8. Check accented and non-Latin characters before release.",
+            label, encoding
+        ));
     }
+    let Some(demux_ext) = demux_ext else {
+        return Ok(ConvertedSubtitle {
+            bytes: utf8.into_owned(),
+            warnings,
+        });
+    };
+    let text = std::str::from_utf8(&utf8).map_err(|e| e.to_string())?;
+    // SubViewer also uses `.sub`; only MicroDVD content is read natively.
+    let native = match demux_ext {
+        "srt" => Some(parse_srt(text)),
+        "vtt" => Some(parse_webvtt(text)),
+        "sub" if is_microdvd(text) => Some(parse_microdvd(text, None)),
+        _ => None,
+    };
+    if let Some(parsed) = native {
+        let sub = parsed.map_err(|e| format!("subtitle conversion failed: {}", e))?;
+        warnings.push(format!(
+            "{} was converted to ASS. Converted scripts carry no styling — the result uses a single Default Arial 60 style at 1920x1080.",
+            label
+        ));
+        return Ok(ConvertedSubtitle {
+            bytes: sub.stringify().into_bytes(),
+            warnings,
+        });
+    }
+    let converted = convert_to_ass(&utf8, demux_ext).await?;
+    warnings.push(format!(
+        "{} was converted to ASS by ffmpeg. Converted scripts carry no styling — the result uses ffmpeg's Default Arial 16 style at 384x288.",
+        label
+    ));
+    Ok(ConvertedSubtitle {
+        bytes: converted,
+        warnings,
+    })
 }
 
 // Runs the input through ffmpeg's ASS muxer in a scratch directory.
@@ -293,27 +382,30 @@ mod tests {
         let raw = b"[Script Info]\nScriptType: v4.00+\n";
         let out = ensure_ass("x.ass", raw).await.unwrap();
         assert_eq!(out.bytes, raw.to_vec());
-        assert!(out.warning.is_none());
+        assert!(out.warnings.is_empty());
     }
 
-    // Skipped when the machine running the tests has no ffmpeg; pndc always has one.
     #[tokio::test]
     async fn srt_becomes_playable_ass() {
-        if tokio::process::Command::new(resolve_runtime_binary("ffmpeg"))
-            .arg("-version")
-            .output()
-            .await
-            .is_err()
-        {
-            return;
-        }
         let srt = b"1\n00:00:01,000 --> 00:00:03,500\nHello <i>world</i>\n\n";
         let out = ensure_ass("x.srt", srt).await.unwrap();
         let text = String::from_utf8(out.bytes).unwrap();
         assert!(text.contains("[Script Info]"), "{}", text);
         assert!(text.contains("0:00:01.00,0:00:03.50"), "{}", text);
         assert!(text.contains("Hello {\\i1}world{\\i0}"), "{}", text);
-        assert!(out.warning.is_some());
+        assert!(text.contains("PlayResX: 1920"), "{}", text);
+        assert_eq!(out.warnings.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn microdvd_and_webvtt_convert_without_ffmpeg() {
+        let out = ensure_ass("x.sub", b"{1}{1}25\n{25}{50}{y:i}Hello|there\n").await.unwrap();
+        let text = String::from_utf8(out.bytes).unwrap();
+        assert!(text.contains("0:00:01.00,0:00:02.00"), "{}", text);
+        assert!(text.contains("{\\i1}Hello{\\i0}\\Nthere"), "{}", text);
+
+        let out = ensure_ass_bytes(b"WEBVTT\n\n00:01.000 --> 00:02.000\nhi\n").await.unwrap();
+        assert!(String::from_utf8(out.bytes).unwrap().contains(",Default,,0,0,0,,hi"));
     }
 
     #[tokio::test]
@@ -321,4 +413,38 @@ mod tests {
         let err = ensure_ass("x.srt", &[0xff, 0xfe, 0x00]).await.unwrap_err();
         assert!(err.contains("not valid UTF-8"), "{}", err);
     }
+
+    #[tokio::test]
+    async fn legacy_encodings_are_transcoded_and_reported() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,000\nŞimdi ığdır\n\n";
+        let (turkish, _, _) = WINDOWS_1254.encode(srt);
+        let out = ensure_ass("x.srt", &turkish).await.unwrap();
+        assert!(String::from_utf8(out.bytes).unwrap().contains("Şimdi ığdır"));
+        assert!(out.warnings[0].contains("ISO-8859-9"), "{:?}", out.warnings);
+
+        let (windows, _, _) = WINDOWS_1254.encode("[Script Info]\nTitle: “Şu”\n");
+        let out = ensure_ass("x.ass", &windows).await.unwrap();
+        assert_eq!(String::from_utf8(out.bytes).unwrap(), "[Script Info]\nTitle: “Şu”\n");
+        assert!(out.warnings[0].contains("Windows-1254"), "{:?}", out.warnings);
+
+        let (japanese, _, _) = SHIFT_JIS.encode("1\n00:00:01,000 --> 00:00:02,000\nこんにちは、世界\n\n");
+        let out = ensure_ass_bytes(&japanese).await.unwrap();
+        assert!(String::from_utf8(out.bytes).unwrap().contains("こんにちは、世界"));
+        assert!(out.warnings[0].contains("Shift-JIS"), "{:?}", out.warnings);
+    }
+
+    #[tokio::test]
+    async fn utf16_is_sniffed_with_or_without_bom() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,000\nçay\n\n";
+        let mut with_bom = vec![0xFF, 0xFE];
+        with_bom.extend(srt.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        let out = ensure_ass_bytes(&with_bom).await.unwrap();
+        assert!(String::from_utf8(out.bytes).unwrap().contains("çay"));
+        assert!(out.warnings[0].contains("UTF-16LE"), "{:?}", out.warnings);
+
+        let big_endian: Vec<u8> = srt.encode_utf16().flat_map(|unit| unit.to_be_bytes()).collect();
+        let out = ensure_ass("x.srt", &big_endian).await.unwrap();
+        assert!(String::from_utf8(out.bytes).unwrap().contains("çay"));
+        assert!(out.warnings[0].contains("UTF-16BE"), "{:?}", out.warnings);
+    }
 }
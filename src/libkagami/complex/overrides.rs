@@ -1,4 +1,5 @@
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "tag", content = "args")]
 pub enum ASSOverride {
     BlockText(String),
     Bold(bool),
@@ -82,6 +82,33 @@ impl std::fmt::Display for AssTime {
     }
 }
 
+// Both go to JSON the way they are written in a script, `0:01:02.03` and `&H00FFFFFF`.
+impl serde::Serialize for AssTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for AssTime {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = <String as serde::Deserialize>::deserialize(deserializer)?;
+        text.parse().map_err(|_| serde::de::Error::custom(format!("invalid ASS time {:?}", text)))
+    }
+}
+
+impl serde::Serialize for AssColour {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for AssColour {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = <String as serde::Deserialize>::deserialize(deserializer)?;
+        text.parse().map_err(|_| serde::de::Error::custom(format!("invalid ASS colour {:?}", text)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,269 @@
+// Cross-episode comparison of an anime's scripts: style definitions that drifted, script headers
+// that disagree, glossary terms spelled differently from one episode to the next, and episodes
+// missing their TS or SOURCE.md. Reading the files is the caller's job (`lib::git` pulls them from
+// the repo); this only compares what it is given.
+
+use std::collections::BTreeMap;
+
+use crate::libkagami::core::SubstationAlpha;
+use crate::libkagami::spellcheck::{spellcheck, Glossary, SpellIssue};
+
+pub struct ScriptFile {
+    pub kind: String, // TL, TS or Release
+    pub sub: SubstationAlpha,
+}
+
+pub struct EpisodeFiles {
+    pub episode: u32,
+    pub files: Vec<ScriptFile>,
+    pub has_source: bool,
+}
+
+// One value seen across the repo and where, e.g. a style definition and the files that use it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variant {
+    pub value: String,
+    pub places: Vec<String>,
+}
+
+// A style, header field or glossary term with more than one variant. The most used variant comes
+// first; for glossary terms the variants are the off-glossary spellings only.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Drift {
+    pub name: String,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConsistencyReport {
+    pub episodes: usize,
+    pub started: usize, // episodes with at least one file
+    pub files: usize,
+    pub styles: Vec<Drift>,
+    pub headers: Vec<Drift>,
+    pub glossary: Vec<Drift>,
+    pub missing_ts: Vec<u32>,
+    pub missing_source: Vec<u32>,
+}
+
+impl ConsistencyReport {
+    pub fn is_clean(&self) -> bool {
+        self.styles.is_empty()
+            && self.headers.is_empty()
+            && self.glossary.is_empty()
+            && self.missing_ts.is_empty()
+            && self.missing_source.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} styles drifted, {} header mismatches, {} glossary terms, {} missing TS, {} missing SOURCE.md",
+            self.styles.len(),
+            self.headers.len(),
+            self.glossary.len(),
+            self.missing_ts.len(),
+            self.missing_source.len()
+        )
+    }
+
+    // One line per finding naming only the odd ones out, for chat; the Markdown has everything.
+    pub fn summary_lines(&self) -> Vec<String> {
+        let minority = |drift: &Drift| -> Vec<String> {
+            drift.variants.iter().skip(1).flat_map(|variant| variant.places.iter().cloned()).collect()
+        };
+        let mut out = Vec::new();
+        for drift in &self.styles {
+            out.push(format!("Style `{}` differs in {}", drift.name, minority(drift).join(", ")));
+        }
+        for drift in &self.headers {
+            for variant in drift.variants.iter().skip(1) {
+                out.push(format!("{} `{}` in {}", drift.name, variant.value, variant.places.join(", ")));
+            }
+        }
+        for drift in &self.glossary {
+            for variant in &drift.variants {
+                out.push(format!("{} → {} ({}×)", variant.value, drift.name, variant.places.len()));
+            }
+        }
+        for (name, episodes) in [("TS", &self.missing_ts), ("SOURCE.md", &self.missing_source)] {
+            if !episodes.is_empty() {
+                let episodes: Vec<String> = episodes.iter().map(|n| format!("E{:02}", n)).collect();
+                out.push(format!("Missing {}: {}", name, episodes.join(", ")));
+            }
+        }
+        out
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# Consistency report\n\n{} of {} episodes started, {} files checked.\n\n**{}**\n",
+            self.started,
+            self.episodes,
+            self.files,
+            self.summary()
+        );
+        if self.is_clean() {
+            out.push_str("\nNo inconsistencies found.\n");
+            return out;
+        }
+        for (title, drifts) in [("Styles", &self.styles), ("Script headers", &self.headers), ("Glossary", &self.glossary)] {
+            if drifts.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("\n## {}\n", title));
+            for drift in drifts {
+                out.push_str(&format!("\n### {}\n", drift.name));
+                for variant in &drift.variants {
+                    out.push_str(&format!("- `{}` — {}\n", variant.value, variant.places.join(", ")));
+                }
+            }
+        }
+        if !self.missing_ts.is_empty() || !self.missing_source.is_empty() {
+            out.push_str("\n## Missing files\n\n");
+            for (name, episodes) in [("TS", &self.missing_ts), ("SOURCE.md", &self.missing_source)] {
+                if !episodes.is_empty() {
+                    let episodes: Vec<String> = episodes.iter().map(|n| format!("E{:02}", n)).collect();
+                    out.push_str(&format!("- {}: {}\n", name, episodes.join(", ")));
+                }
+            }
+        }
+        out
+    }
+}
+
+// Episodes with no file at all haven't been started and are only counted. Release files take part
+// in the style and header checks (they are what ships) but not the glossary one, which would
+// otherwise report every TL line twice.
+pub fn check_consistency(episodes: &[EpisodeFiles], glossary: &Glossary) -> ConsistencyReport {
+    let mut styles: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    let mut headers: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    let mut terms: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    let mut report = ConsistencyReport { episodes: episodes.len(), ..ConsistencyReport::default() };
+
+    for episode in episodes {
+        if episode.files.is_empty() {
+            continue;
+        }
+        report.started += 1;
+        if !episode.files.iter().any(|file| file.kind == "TS") {
+            report.missing_ts.push(episode.episode);
+        }
+        if !episode.has_source {
+            report.missing_source.push(episode.episode);
+        }
+        for file in &episode.files {
+            report.files += 1;
+            let place = format!("E{:02} {}", episode.episode, file.kind);
+            for style in &file.sub.v4p_styles {
+                let line = style.stringify();
+                let definition = line.trim_end().split_once(',').map(|(_, rest)| rest).unwrap_or("").to_string();
+                styles.entry(style.name.clone()).or_default().entry(definition).or_default().push(place.clone());
+            }
+            let info = &file.sub.script_info;
+            let matrix = if info.ycbcr_matrix.trim().is_empty() { "(none)".to_string() } else { info.ycbcr_matrix.clone() };
+            for (field, value) in [("PlayRes", format!("{}x{}", info.playresx, info.playresy)), ("YCbCr Matrix", matrix)] {
+                headers.entry(field.to_string()).or_default().entry(value).or_default().push(place.clone());
+            }
+            if file.kind == "Release" || glossary.is_empty() {
+                continue;
+            }
+            for line in spellcheck(&file.sub, None, glossary) {
+                for issue in line.issues {
+                    if let SpellIssue::Glossary { found, preferred } = issue {
+                        let at = format!("{} line {}", place, line.event);
+                        terms.entry(preferred).or_default().entry(found).or_default().push(at);
+                    }
+                }
+            }
+        }
+    }
+
+    report.styles = drifts(styles, 2);
+    report.headers = drifts(headers, 2);
+    report.glossary = drifts(terms, 1);
+    report
+}
+
+// Names with at least `min` distinct values, most used value first.
+fn drifts(seen: BTreeMap<String, BTreeMap<String, Vec<String>>>, min: usize) -> Vec<Drift> {
+    seen.into_iter()
+        .filter(|(_, values)| values.len() >= min)
+        .map(|(name, values)| {
+            let mut variants: Vec<Variant> = values.into_iter().map(|(value, places)| Variant { value, places }).collect();
+            variants.sort_by_key(|variant| std::cmp::Reverse(variant.places.len()));
+            Drift { name, variants }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(res: &str, default: &str, lines: &[&str]) -> SubstationAlpha {
+        let events: String = lines
+            .iter()
+            .map(|text| format!("Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{}\n", text))
+            .collect();
+        let text = format!(
+            "[Script Info]\nScriptType: v4.00+\n{}\nYCbCr Matrix: TV.709\n\n[V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+            Style: Default,{},&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,20,20,20,1\n\n\
+            [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n{}",
+            res, default, events
+        );
+        SubstationAlpha::parse(&text, false).0
+    }
+
+    fn file(kind: &str, sub: SubstationAlpha) -> ScriptFile {
+        ScriptFile { kind: kind.to_string(), sub }
+    }
+
+    #[test]
+    fn reports_drift_against_the_other_episodes() {
+        let hd = "PlayResX: 1920\nPlayResY: 1080";
+        let episodes = vec![
+            EpisodeFiles {
+                episode: 1,
+                files: vec![file("TL", script(hd, "Arial,60", &["Rimuru-sama!"])), file("TS", script(hd, "Arial,60", &[]))],
+                has_source: true,
+            },
+            EpisodeFiles {
+                episode: 2,
+                files: vec![file("TL", script(hd, "Arial,60", &["Rimuru sama?", "Rimuru-sama."]))],
+                has_source: false,
+            },
+            EpisodeFiles {
+                episode: 3,
+                files: vec![file("TL", script("PlayResX: 1280\nPlayResY: 720", "Arial,48", &[])), file("TS", script(hd, "Arial,60", &[]))],
+                has_source: true,
+            },
+            EpisodeFiles { episode: 4, files: vec![], has_source: false },
+        ];
+        let glossary = Glossary::parse("- Rimuru-sama = Rimuru sama\n");
+
+        let report = check_consistency(&episodes, &glossary);
+        assert_eq!((report.episodes, report.started, report.files), (4, 3, 5));
+        assert_eq!(report.styles.len(), 1);
+        assert_eq!(report.styles[0].variants[1].places, vec!["E03 TL"]);
+        assert_eq!(report.headers.len(), 1);
+        assert_eq!(report.headers[0].variants[1], Variant { value: "1280x720".to_string(), places: vec!["E03 TL".to_string()] });
+        assert_eq!(report.glossary, vec![Drift {
+            name: "Rimuru-sama".to_string(),
+            variants: vec![Variant { value: "Rimuru sama".to_string(), places: vec!["E02 TL line 1".to_string()] }],
+        }]);
+        assert_eq!(report.missing_ts, vec![2]);
+        assert_eq!(report.missing_source, vec![2]);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("### PlayRes\n- `1920x1080` — E01 TL, E01 TS, E02 TL, E03 TS\n- `1280x720` — E03 TL\n"), "{}", markdown);
+        assert!(markdown.contains("- TS: E02\n- SOURCE.md: E02\n"), "{}", markdown);
+        assert_eq!(report.summary_lines(), vec![
+            "Style `Default` differs in E03 TL",
+            "PlayRes `1280x720` in E03 TL",
+            "Rimuru sama → Rimuru-sama (1×)",
+            "Missing TS: E02",
+            "Missing SOURCE.md: E02",
+        ]);
+    }
+}
@@ -1,11 +1,16 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
 use crate::libkagami::complex::overrides::ASSOverride;
 use crate::libkagami::drawing::parse::Drawing;
 use crate::libkagami::tags::{ASSLine, ASSText};
 use crate::libkagami::complex::types::{AssColour, AssTime};
+use crate::libkagami::sections::{
+    format_extradata_refs, parse_extradata_line, split_extradata_refs, uudecode, EmbeddedFile,
+    ExtradataEntry, ProjectGarbage,
+};
 use tokio::{fs::File, io::{AsyncReadExt, AsyncWriteExt}};
 
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct ScriptInfo {
     pub title: String,
     pub script_type: String,
@@ -41,6 +46,7 @@ impl ScriptInfo {
     }
 }
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct V4pStyle {
     pub name: String,
     pub fontname: String,
@@ -74,6 +80,12 @@ impl V4pStyle {
             self.shadow, self.alignment, self.margin_l, self.margin_r, self.margin_v, self.encoding,
         )
     }
+    // Everything but the name, compared as written to the file so float noise below the printed
+    // precision does not make two copies of a style differ.
+    pub fn same_definition(&self, other: &V4pStyle) -> bool {
+        let body = |style: &V4pStyle| style.stringify().split_once(',').map(|(_, rest)| rest.to_string());
+        body(self) == body(other)
+    }
     pub fn to_overrides(&self) -> Vec<ASSOverride> {
         vec![
             ASSOverride::Bold(self.bold),
@@ -97,7 +109,7 @@ impl V4pStyle {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct Event {
     pub layer: u16,
     pub start: AssTime,
@@ -109,14 +121,24 @@ pub struct Event {
     pub margin_v: u16,
     pub effect: String,     // kfx
     pub text: ASSLine,      // raw text including override tags
+    pub extradata: Vec<u32>, // [Aegisub Extradata] ids, written back as a leading {=1=2} block
 }
 
 impl Event {
     pub fn stringify(&self) -> String {
-        format!("Dialogue: {},{},{},{},{},{},{},{},{},{}\n",
-            self.layer, self.start, self.end, self.style, self.name,
+        self.stringify_as("Dialogue")
+    }
+
+    // Comment lines share the Dialogue format; only the line type differs.
+    pub fn stringify_comment(&self) -> String {
+        self.stringify_as("Comment")
+    }
+
+    fn stringify_as(&self, kind: &str) -> String {
+        format!("{}: {},{},{},{},{},{},{},{},{},{}{}\n",
+            kind, self.layer, self.start, self.end, self.style, self.name,
             self.margin_l, self.margin_r, self.margin_v, self.effect,
-            self.text.stringify(),
+            format_extradata_refs(&self.extradata), self.text.stringify(),
         )
     }
 
@@ -149,87 +171,262 @@ impl Event {
     }
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct PandoraMeta {
     pub stamps: Vec<Stamp>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Stamp {
     pub start: AssTime,
     pub end: AssTime,
     pub note: String,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    FieldCount,
+    BadColour,
+    BadTimestamp,
+    UnknownSection,
+    DuplicateStyle,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadDiagnostic {
+    pub line: usize,
+    pub kind: DiagnosticKind,
+    pub message: String,
+}
+
+impl LoadDiagnostic {
+    fn new(line: usize, kind: DiagnosticKind, message: String) -> Self {
+        Self { line, kind, message }
+    }
+}
+
+impl std::fmt::Display for LoadDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    InvalidUtf8 { line: usize },
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "cannot read subtitle: {}", e),
+            LoadError::InvalidUtf8 { line } => write!(f, "subtitle is not valid UTF-8 (line {})", line),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct SubstationAlpha {
     pub script_info: ScriptInfo,
     pub v4p_styles: Vec<V4pStyle>,
     pub events: Vec<Event>,
     pub comments: Vec<Event>,
     pub pandora_meta: PandoraMeta,
+    pub fonts: Vec<EmbeddedFile>,
+    pub graphics: Vec<EmbeddedFile>,
+    pub project_garbage: ProjectGarbage,
+    pub extradata: Vec<ExtradataEntry>,
+}
+
+// How `SubstationAlpha::resample` fits a script into a PlayRes of a different shape, after
+// Aegisub's resampler. Stretch scales each axis on its own and hands the horizontal squash to
+// \fscx and the style ScaleX so glyphs follow the frame (1440x1080 anamorphic to 1920x1080).
+// Letterbox keeps the picture's ratio and pads it centred (4:3 signs in a 16:9 frame), Crop fills
+// the target and cuts the overhang. Reset only rewrites the header, for scripts that were typeset
+// at the target resolution but still carry another PlayRes — usually Aegisub's 640x480 default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResampleMode {
+    Stretch,
+    Letterbox,
+    Crop,
+    Reset,
+}
+
+impl std::str::FromStr for ResampleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "stretch" => Ok(Self::Stretch),
+            "letterbox" | "borders" => Ok(Self::Letterbox),
+            "crop" => Ok(Self::Crop),
+            "reset" => Ok(Self::Reset),
+            other => Err(format!("unknown resample mode `{}` (stretch, letterbox, crop or reset)", other)),
+        }
+    }
 }
 
 impl SubstationAlpha {
     pub fn scale(&mut self, target_x: u16, target_y: u16) -> Result<(), String> {
         let source_x = self.script_info.playresx;
         let source_y = self.script_info.playresy;
-        if source_x == 0 || source_y == 0 {
-            return Err("source PlayRes must be non-zero".to_string());
-        }
-        if target_x == 0 || target_y == 0 {
-            return Err("target PlayRes must be non-zero".to_string());
-        }
         if source_x as u32 * target_y as u32 != source_y as u32 * target_x as u32 {
             return Err(format!(
                 "incompatible aspect ratio: {}x{} cannot scale to {}x{}",
                 source_x, source_y, target_x, target_y
             ));
         }
+        self.resample(target_x, target_y, ResampleMode::Stretch)
+    }
 
-        let sx = target_x as f32 / source_x as f32;
-        let sy = target_y as f32 / source_y as f32;
-        let layout_sx = sx;
-        let layout_sy = sy;
+    pub fn resample(&mut self, target_x: u16, target_y: u16, mode: ResampleMode) -> Result<(), String> {
+        let source_x = self.script_info.playresx;
+        let source_y = self.script_info.playresy;
+        if source_x == 0 || source_y == 0 {
+            return Err("source PlayRes must be non-zero".to_string());
+        }
+        if target_x == 0 || target_y == 0 {
+            return Err("target PlayRes must be non-zero".to_string());
+        }
+
+        let (src_x, src_y) = (source_x as f32, source_y as f32);
+        let (dst_x, dst_y) = (target_x as f32, target_y as f32);
+        let (sx, sy) = match mode {
+            ResampleMode::Stretch | ResampleMode::Reset => (dst_x / src_x, dst_y / src_y),
+            ResampleMode::Letterbox => {
+                let s = (dst_x / src_x).min(dst_y / src_y);
+                (s, s)
+            }
+            ResampleMode::Crop => {
+                let s = (dst_x / src_x).max(dst_y / src_y);
+                (s, s)
+            }
+        };
+        // Border added on each side in target pixels; negative when cropping.
+        let ox = (dst_x - src_x * sx) / 2.0;
+        let oy = (dst_y - src_y * sy) / 2.0;
+        let stretch = if (sx / sy - 1.0).abs() < 0.0001 { 1.0 } else { sx / sy };
+        let r = Resample {
+            sx,
+            sy,
+            ox,
+            oy,
+            stretch,
+            layout_sx: dst_x / src_x,
+            layout_sy: dst_y / src_y,
+            scaled_border_and_shadow: self.script_info.scaled_border_and_shadow,
+        };
 
         self.script_info.playresx = target_x;
         self.script_info.playresy = target_y;
         if self.script_info.layout_res_x != 0 {
-            self.script_info.layout_res_x = scale_u16(self.script_info.layout_res_x, sx);
+            self.script_info.layout_res_x = scale_u16(self.script_info.layout_res_x, r.layout_sx);
         }
         if self.script_info.layout_res_y != 0 {
-            self.script_info.layout_res_y = scale_u16(self.script_info.layout_res_y, sy);
+            self.script_info.layout_res_y = scale_u16(self.script_info.layout_res_y, r.layout_sy);
+        }
+        if mode == ResampleMode::Reset {
+            return Ok(());
         }
 
         for style in &mut self.v4p_styles {
             style.fontsize = scale_u16(style.fontsize, sy);
+            style.scale_x = scale_u16(style.scale_x, stretch);
             style.spacing *= sx;
-            style.outline *= border_scalar(self.script_info.scaled_border_and_shadow, sx, layout_sx);
-            style.shadow *= border_scalar(self.script_info.scaled_border_and_shadow, sx, layout_sx);
-            style.margin_l = scale_u16(style.margin_l, sx);
-            style.margin_r = scale_u16(style.margin_r, sx);
-            style.margin_v = scale_u16(style.margin_v, sy);
+            style.outline *= r.border_y();
+            style.shadow *= r.border_y();
+            style.margin_l = scale_u16_offset(style.margin_l, sx, ox);
+            style.margin_r = scale_u16_offset(style.margin_r, sx, ox);
+            style.margin_v = scale_u16_offset(style.margin_v, sy, oy);
         }
 
         for event in &mut self.events {
-            event.margin_l = scale_u16(event.margin_l, sx);
-            event.margin_r = scale_u16(event.margin_r, sx);
-            event.margin_v = scale_u16(event.margin_v, sy);
-            event.effect = scale_effect(&event.effect, sx, sy);
-            scale_line(&mut event.text, sx, sy, layout_sx, layout_sy, self.script_info.scaled_border_and_shadow);
+            // Zero defers to the style margin, which already carries the padding.
+            if event.margin_l != 0 {
+                event.margin_l = scale_u16_offset(event.margin_l, sx, ox);
+            }
+            if event.margin_r != 0 {
+                event.margin_r = scale_u16_offset(event.margin_r, sx, ox);
+            }
+            if event.margin_v != 0 {
+                event.margin_v = scale_u16_offset(event.margin_v, sy, oy);
+            }
+            event.effect = scale_effect(&event.effect, &r);
+            scale_line(&mut event.text, &r);
+        }
+
+        for entry in &mut self.extradata {
+            if entry.key != "_aegi_perspective_ambient_plane" {
+                continue;
+            }
+            if let Some(plane) = entry.value_str().and_then(|v| scale_perspective_plane(v, &r)) {
+                entry.value = plane.into_bytes();
+            }
         }
 
         Ok(())
     }
 
+    // Moves the attached sections of `other` into `self` ahead of appending its events: fonts and
+    // graphics are deduplicated by name, the primary's project state wins, and `other`'s extradata
+    // ids are shifted past ours so the `{=N}` references on its events keep pointing at their data.
+    pub fn absorb_sections(&mut self, other: &mut SubstationAlpha) {
+        for font in other.fonts.drain(..) {
+            if !self.fonts.iter().any(|existing| existing.name == font.name) {
+                self.fonts.push(font);
+            }
+        }
+        for graphic in other.graphics.drain(..) {
+            if !self.graphics.iter().any(|existing| existing.name == graphic.name) {
+                self.graphics.push(graphic);
+            }
+        }
+        if self.project_garbage.is_empty() {
+            self.project_garbage = std::mem::take(&mut other.project_garbage);
+        }
+        let offset = self.extradata.iter().map(|entry| entry.id).max().unwrap_or(0);
+        for event in other.events.iter_mut().chain(other.comments.iter_mut()) {
+            for id in &mut event.extradata {
+                *id += offset;
+            }
+        }
+        for mut entry in other.extradata.drain(..) {
+            entry.id += offset;
+            self.extradata.push(entry);
+        }
+    }
+
     /// Loads an ASS file from path. If adv_parsing is true, lib also parses Override Tags, and optimises no-op ones.
     /// If adv_parsing is false, entire text is an ASSLine vector with a single ASSText::RawText
-    pub async fn load(path: PathBuf, adv_parsing: bool) -> Self {
-        let mut buf = String::new();
-        match File::open(path).await {
-            Ok(mut f) => { f.read_to_string(&mut buf).await.unwrap(); }
-            Err(_) => unimplemented!("file open err")
+    pub async fn load(path: PathBuf, adv_parsing: bool) -> Result<Self, LoadError> {
+        Self::load_with_diagnostics(path, adv_parsing).await.map(|(sub, _)| sub)
+    }
+
+    pub async fn load_with_diagnostics(path: PathBuf, adv_parsing: bool) -> Result<(Self, Vec<LoadDiagnostic>), LoadError> {
+        let mut bytes = Vec::new();
+        let mut file = File::open(path).await.map_err(LoadError::Io)?;
+        file.read_to_end(&mut bytes).await.map_err(LoadError::Io)?;
+        Self::from_bytes(&bytes, adv_parsing)
+    }
+
+    pub fn from_bytes(bytes: &[u8], adv_parsing: bool) -> Result<(Self, Vec<LoadDiagnostic>), LoadError> {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Ok(Self::parse(text, adv_parsing)),
+            Err(e) => {
+                let line = bytes[..e.valid_up_to()].iter().filter(|b| **b == b'\n').count() + 1;
+                Err(LoadError::InvalidUtf8 { line })
+            }
         }
-        let buf = buf.trim_start_matches('\u{FEFF}');
+    }
+
+    // Parsing itself never fails: anything malformed is skipped or defaulted exactly as before, and
+    // reported as a line-numbered diagnostic so callers can tell the user what was dropped.
+    pub fn parse(text: &str, adv_parsing: bool) -> (Self, Vec<LoadDiagnostic>) {
+        let buf = text.trim_start_matches('\u{FEFF}');
+        let mut diagnostics = Vec::new();
         let mut title = String::new();
         let mut script_type = String::new();
         let mut wrap_style = 0u8;
@@ -239,20 +436,36 @@ impl SubstationAlpha {
         let mut playresy = 0u16;
         let mut layout_res_x = 0u16;
         let mut layout_res_y = 0u16;
-        let mut v4p_styles = Vec::new();
+        let mut v4p_styles: Vec<V4pStyle> = Vec::new();
+        let mut style_lines: HashMap<String, usize> = HashMap::new();
         let mut events = Vec::new();
         let mut comments = Vec::new();
         let mut pandora_meta = PandoraMeta::default();
+        let mut fonts: Vec<(String, String)> = Vec::new();
+        let mut graphics: Vec<(String, String)> = Vec::new();
+        let mut project_garbage = ProjectGarbage::default();
+        let mut extradata = Vec::new();
 
         let mut section = "";
 
-        for line in buf.lines() {
+        for (index, line) in buf.lines().enumerate() {
+            let line_no = index + 1;
             let line = line.trim();
-            if line.starts_with('[') {
+            // uuencoded attachment data may legitimately start with `[` or `;`, so inside [Fonts]
+            // and [Graphics] only a known section header ends the attachment.
+            let in_attachment = matches!(section, "[Fonts]" | "[Graphics]");
+            if line.starts_with('[') && (!in_attachment || is_known_section(line)) {
                 section = line;
+                if !is_parsed_section(line) {
+                    diagnostics.push(LoadDiagnostic::new(
+                        line_no,
+                        DiagnosticKind::UnknownSection,
+                        format!("unknown section {}; its lines were ignored", line),
+                    ));
+                }
                 continue;
             }
-            if line.starts_with(';') || line.is_empty() {
+            if line.is_empty() || (line.starts_with(';') && !in_attachment) {
                 continue;
             }
 
@@ -275,44 +488,28 @@ impl SubstationAlpha {
                 }
                 "[V4+ Styles]" => {
                     if let Some(data) = line.strip_prefix("Style:") {
-                        let f: Vec<&str> = data.trim().splitn(24, ',').collect();
-                        if f.len() < 23 { continue; }
-                        v4p_styles.push(V4pStyle {
-                            name:         f[0].to_string(),
-                            fontname:     f[1].to_string(),
-                            fontsize:     f[2].parse().unwrap_or(0),
-                            colours: [
-                                f[3].parse().unwrap_or(AssColour::opaque_white()),
-                                f[4].parse().unwrap_or(AssColour::opaque_white()),
-                                f[5].parse().unwrap_or(AssColour::transparent()),
-                                f[6].parse().unwrap_or(AssColour::transparent()),
-                            ],
-                            bold:          f[7] == "-1",
-                            italic:        f[8] == "-1",
-                            underline:     f[9] == "-1",
-                            strikeout:     f[10] == "-1",
-                            scale_x:       f[11].parse().unwrap_or(100),
-                            scale_y:       f[12].parse().unwrap_or(100),
-                            spacing:       f[13].parse().unwrap_or(0.0),
-                            angle:         f[14].parse().unwrap_or(0.0),
-                            border_style:  f[15].parse().unwrap_or(1),
-                            outline:       f[16].parse().unwrap_or(0.0),
-                            shadow:        f[17].parse().unwrap_or(0.0),
-                            alignment:     f[18].parse().unwrap_or(2),
-                            margin_l:      f[19].parse().unwrap_or(0),
-                            margin_r:      f[20].parse().unwrap_or(0),
-                            margin_v:      f[21].parse().unwrap_or(0),
-                            encoding:      f[22].parse().unwrap_or(1),
-                        });
+                        let Some(style) = parse_style(data, line_no, &mut diagnostics) else {
+                            continue;
+                        };
+                        if let Some(first) = style_lines.get(&style.name) {
+                            diagnostics.push(LoadDiagnostic::new(
+                                line_no,
+                                DiagnosticKind::DuplicateStyle,
+                                format!("duplicate style `{}` (first defined on line {})", style.name, first),
+                            ));
+                        } else {
+                            style_lines.insert(style.name.clone(), line_no);
+                        }
+                        v4p_styles.push(style);
                     }
                 }
                 "[Events]" => {
                     if let Some(data) = line.strip_prefix("Dialogue:") {
-                        if let Some(event) = parse_event(data, adv_parsing, &v4p_styles) {
+                        if let Some(event) = parse_event(data, adv_parsing, &v4p_styles, "Dialogue", line_no, &mut diagnostics) {
                             events.push(event);
                         }
                     } else if let Some(data) = line.strip_prefix("Comment:") {
-                        if let Some(event) = parse_event(data, false, &v4p_styles) {
+                        if let Some(event) = parse_event(data, false, &v4p_styles, "Comment", line_no, &mut diagnostics) {
                             comments.push(event);
                         }
                     }
@@ -323,9 +520,19 @@ impl SubstationAlpha {
                         let (Some(start), Some(end), Some(note)) =
                             (fields.next(), fields.next(), fields.next())
                         else {
+                            diagnostics.push(LoadDiagnostic::new(
+                                line_no,
+                                DiagnosticKind::FieldCount,
+                                "Stamp needs start, end and note; skipped".to_string(),
+                            ));
                             continue;
                         };
                         let (Ok(start), Ok(end)) = (start.trim().parse(), end.trim().parse()) else {
+                            diagnostics.push(LoadDiagnostic::new(
+                                line_no,
+                                DiagnosticKind::BadTimestamp,
+                                format!("Stamp has a bad timestamp `{},{}`; skipped", start.trim(), end.trim()),
+                            ));
                             continue;
                         };
                         pandora_meta.stamps.push(Stamp {
@@ -335,6 +542,28 @@ impl SubstationAlpha {
                         });
                     }
                 }
+                "[Fonts]" | "[Graphics]" => {
+                    let (files, header) = if section == "[Fonts]" {
+                        (&mut fonts, "fontname:")
+                    } else {
+                        (&mut graphics, "filename:")
+                    };
+                    if let Some(name) = line.strip_prefix(header) {
+                        files.push((name.trim().to_string(), String::new()));
+                    } else if let Some((_, data)) = files.last_mut() {
+                        data.push_str(line);
+                    }
+                }
+                "[Aegisub Project Garbage]" => {
+                    if let Some((key, val)) = line.split_once(':') {
+                        project_garbage.entries.push((key.trim().to_string(), val.trim().to_string()));
+                    }
+                }
+                "[Aegisub Extradata]" => {
+                    if let Some(entry) = line.strip_prefix("Data:").and_then(parse_extradata_line) {
+                        extradata.push(entry);
+                    }
+                }
                 _ => {}
             }
         }
@@ -352,7 +581,7 @@ impl SubstationAlpha {
         });
         pandora_meta.stamps.sort_by_key(|stamp| stamp.start.total_centiseconds());
 
-        Self {
+        let sub = Self {
             script_info: ScriptInfo {
                 title,
                 script_type,
@@ -368,7 +597,12 @@ impl SubstationAlpha {
             events,
             comments,
             pandora_meta,
-        }
+            fonts: fonts.into_iter().map(|(name, data)| EmbeddedFile { name, data: uudecode(&data) }).collect(),
+            graphics: graphics.into_iter().map(|(name, data)| EmbeddedFile { name, data: uudecode(&data) }).collect(),
+            project_garbage,
+            extradata,
+        };
+        (sub, diagnostics)
     }
     pub fn add_style(&mut self, style: V4pStyle) {
         self.v4p_styles.push(style);
@@ -391,18 +625,47 @@ impl SubstationAlpha {
         // Script Info
         sevent.push_str("[Script Info]\n");
         sevent.push_str(&self.script_info.stringify());
+        if !self.project_garbage.is_empty() {
+            sevent.push_str("\n[Aegisub Project Garbage]\n");
+            sevent.push_str(&self.project_garbage.stringify());
+        }
         // Styles
         sevent.push_str("\n[V4+ Styles]\n");
         sevent.push_str("Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n");
         for i in &self.v4p_styles {
             sevent.push_str(&i.stringify());
         }
+        // Attachments
+        if !self.fonts.is_empty() {
+            sevent.push_str("\n[Fonts]\n");
+            for font in &self.fonts {
+                sevent.push_str(&font.stringify("fontname"));
+            }
+        }
+        if !self.graphics.is_empty() {
+            sevent.push_str("\n[Graphics]\n");
+            for graphic in &self.graphics {
+                sevent.push_str(&graphic.stringify("filename"));
+            }
+        }
         // Events
         sevent.push_str("\n[Events]\n");
         sevent.push_str("Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n");
+        // Comments are kept apart from the dialogue, so they are written first, in load order:
+        // templater `template`/`code` lines expect to lead the script anyway. Stamps are already
+        // written to [Pandora Meta].
+        for i in self.comments.iter().filter(|event| !event.is_stamp()) {
+            sevent.push_str(&i.stringify_comment());
+        }
         for i in &self.events {
             sevent.push_str(&i.stringify());
         }
+        if !self.extradata.is_empty() {
+            sevent.push_str("\n[Aegisub Extradata]\n");
+            for entry in &self.extradata {
+                sevent.push_str(&entry.stringify());
+            }
+        }
         if !self.pandora_meta.stamps.is_empty() {
             sevent.push_str("\n[Pandora Meta]\n");
             for stamp in &self.pandora_meta.stamps {
@@ -430,24 +693,108 @@ impl SubstationAlpha {
     }
 }
 
-fn parse_event(data: &str, adv_parsing: bool, v4p_styles: &[V4pStyle]) -> Option<Event> {
+fn parse_style(data: &str, line: usize, diagnostics: &mut Vec<LoadDiagnostic>) -> Option<V4pStyle> {
+    let f: Vec<&str> = data.trim().splitn(24, ',').collect();
+    if f.len() != 23 {
+        let found = data.split(',').count();
+        diagnostics.push(LoadDiagnostic::new(
+            line,
+            DiagnosticKind::FieldCount,
+            if f.len() < 23 {
+                format!("Style has {} fields, expected 23; skipped", found)
+            } else {
+                format!("Style has {} fields, expected 23; extra fields were ignored", found)
+            },
+        ));
+        if f.len() < 23 {
+            return None;
+        }
+    }
+    let mut colour = |index: usize, fallback: AssColour| match f[index].trim().parse() {
+        Ok(colour) => colour,
+        Err(_) => {
+            diagnostics.push(LoadDiagnostic::new(
+                line,
+                DiagnosticKind::BadColour,
+                format!("style `{}` has a bad colour `{}`; using {}", f[0], f[index], fallback),
+            ));
+            fallback
+        }
+    };
+    let colours = [
+        colour(3, AssColour::opaque_white()),
+        colour(4, AssColour::opaque_white()),
+        colour(5, AssColour::transparent()),
+        colour(6, AssColour::transparent()),
+    ];
+    Some(V4pStyle {
+        name:         f[0].to_string(),
+        fontname:     f[1].to_string(),
+        fontsize:     f[2].parse().unwrap_or(0),
+        colours,
+        bold:          f[7] == "-1",
+        italic:        f[8] == "-1",
+        underline:     f[9] == "-1",
+        strikeout:     f[10] == "-1",
+        scale_x:       f[11].parse().unwrap_or(100),
+        scale_y:       f[12].parse().unwrap_or(100),
+        spacing:       f[13].parse().unwrap_or(0.0),
+        angle:         f[14].parse().unwrap_or(0.0),
+        border_style:  f[15].parse().unwrap_or(1),
+        outline:       f[16].parse().unwrap_or(0.0),
+        shadow:        f[17].parse().unwrap_or(0.0),
+        alignment:     f[18].parse().unwrap_or(2),
+        margin_l:      f[19].parse().unwrap_or(0),
+        margin_r:      f[20].parse().unwrap_or(0),
+        margin_v:      f[21].parse().unwrap_or(0),
+        encoding:      f[22].parse().unwrap_or(1),
+    })
+}
+
+fn parse_event(
+    data: &str,
+    adv_parsing: bool,
+    v4p_styles: &[V4pStyle],
+    kind: &str,
+    line: usize,
+    diagnostics: &mut Vec<LoadDiagnostic>,
+) -> Option<Event> {
     let f: Vec<&str> = data.trim().splitn(10, ',').collect();
     if f.len() < 10 {
+        diagnostics.push(LoadDiagnostic::new(
+            line,
+            DiagnosticKind::FieldCount,
+            format!("{} has {} fields, expected 10; skipped", kind, f.len()),
+        ));
         return None;
     }
+    let mut timestamp = |index: usize, which: &str| match f[index].trim().parse() {
+        Ok(time) => time,
+        Err(_) => {
+            diagnostics.push(LoadDiagnostic::new(
+                line,
+                DiagnosticKind::BadTimestamp,
+                format!("{} has a bad {} timestamp `{}`; using 0:00:00.00", kind, which, f[index]),
+            ));
+            AssTime { hours: 0, minutes: 0, seconds: 0, centiseconds: 0 }
+        }
+    };
+    let start = timestamp(1, "start");
+    let end = timestamp(2, "end");
+    let (extradata, body) = split_extradata_refs(f[9]);
     let text = if adv_parsing {
         let style_overrides = v4p_styles.iter()
             .find(|s| s.name == f[3])
             .map(|s| s.to_overrides())
             .unwrap_or_default();
-        ASSLine::from_str_store(f[9], style_overrides)
+        ASSLine::from_str_store(body, style_overrides)
     } else {
-        ASSLine { current_overrides: vec![], data: vec![ASSText::RawText(f[9].into())] }
+        ASSLine { current_overrides: vec![], data: vec![ASSText::RawText(body.into())] }
     };
     Some(Event {
         layer:    f[0].parse().unwrap_or(0),
-        start:    f[1].parse().unwrap_or(AssTime { hours: 0, minutes: 0, seconds: 0, centiseconds: 0 }),
-        end:      f[2].parse().unwrap_or(AssTime { hours: 0, minutes: 0, seconds: 0, centiseconds: 0 }),
+        start,
+        end,
         style:    f[3].to_string(),
         name:     f[4].to_string(),
         margin_l: f[5].parse().unwrap_or(0),
@@ -455,15 +802,167 @@ fn parse_event(data: &str, adv_parsing: bool, v4p_styles: &[V4pStyle]) -> Option
         margin_v: f[7].parse().unwrap_or(0),
         effect:   f[8].to_string(),
         text,
+        extradata,
     })
 }
 
-fn border_scalar(scaled_border_and_shadow: bool, screen_scale: f32, layout_scale: f32) -> f32 {
-    if scaled_border_and_shadow { screen_scale } else { layout_scale }
+fn is_parsed_section(line: &str) -> bool {
+    line != "[V4 Styles]" && is_known_section(line)
+}
+
+fn is_known_section(line: &str) -> bool {
+    matches!(
+        line,
+        "[Script Info]"
+            | "[V4+ Styles]"
+            | "[V4 Styles]"
+            | "[Events]"
+            | "[Fonts]"
+            | "[Graphics]"
+            | "[Aegisub Project Garbage]"
+            | "[Aegisub Extradata]"
+            | "[Pandora Meta]"
+    )
+}
+
+// Writes `fonts` into the script text without touching any other byte: they go after the last
+// attachment of an existing [Fonts] section, or into a new one ahead of [Graphics] or [Events],
+// where `stringify` puts it. Section headers are recognised the way `parse` does.
+pub fn append_fonts(script: &str, fonts: &[EmbeddedFile]) -> String {
+    let newline = if script.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut block = String::new();
+    for font in fonts {
+        block.push_str(&font.stringify("fontname").replace('\n', newline));
+    }
+    if block.is_empty() {
+        return script.to_string();
+    }
+
+    let mut section = "";
+    let mut fonts_end = None;
+    let mut next_section = None;
+    let mut offset = 0;
+    for raw in script.split_inclusive('\n') {
+        let line = raw.trim().trim_start_matches('\u{FEFF}');
+        let in_attachment = matches!(section, "[Fonts]" | "[Graphics]");
+        if line.starts_with('[') && (!in_attachment || is_known_section(line)) {
+            section = line;
+            if section == "[Fonts]" {
+                fonts_end = Some(offset + raw.len());
+            } else if fonts_end.is_none() && next_section.is_none() && matches!(section, "[Graphics]" | "[Events]") {
+                next_section = Some(offset);
+            }
+        } else if section == "[Fonts]" && !line.is_empty() {
+            fonts_end = Some(offset + raw.len());
+        }
+        offset += raw.len();
+    }
+
+    let mut out = String::with_capacity(script.len() + block.len() + 16);
+    match (fonts_end, next_section) {
+        (Some(at), _) => {
+            out.push_str(&script[..at]);
+            if !out.ends_with('\n') {
+                out.push_str(newline);
+            }
+            out.push_str(&block);
+            out.push_str(&script[at..]);
+        }
+        (None, Some(at)) => {
+            out.push_str(&script[..at]);
+            out.push_str("[Fonts]");
+            out.push_str(newline);
+            out.push_str(&block);
+            out.push_str(newline);
+            out.push_str(&script[at..]);
+        }
+        (None, None) => {
+            out.push_str(script);
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push_str(newline);
+            }
+            out.push_str(newline);
+            out.push_str("[Fonts]");
+            out.push_str(newline);
+            out.push_str(&block);
+        }
+    }
+    out
+}
+
+// `_aegi_perspective_ambient_plane` stores the perspective tool's quad as `x;y|x;y|x;y|x;y` in
+// script coordinates, so it has to follow a PlayRes rescale like \pos does.
+fn scale_perspective_plane(value: &str, r: &Resample) -> Option<String> {
+    let mut points = Vec::new();
+    for point in value.split('|') {
+        let (x, y) = point.split_once(';')?;
+        let x: f32 = x.trim().parse().ok()?;
+        let y: f32 = y.trim().parse().ok()?;
+        points.push(format!("{};{}", format_scaled(r.x(x)), format_scaled(r.y(y))));
+    }
+    Some(points.join("|"))
+}
+
+// A resample as the tags see it: script coordinates are scaled per axis and then shifted by the
+// border (ox, oy) the mode added around the old frame. `stretch` is the part of a non-uniform
+// scale that glyphs and \p drawings take through \fscx rather than through their own
+// coordinates. Without ScaledBorderAndShadow, border and shadow widths are in LayoutRes pixels
+// and follow that instead of PlayRes.
+struct Resample {
+    sx: f32,
+    sy: f32,
+    ox: f32,
+    oy: f32,
+    stretch: f32,
+    layout_sx: f32,
+    layout_sy: f32,
+    scaled_border_and_shadow: bool,
+}
+
+impl Resample {
+    fn x(&self, x: f32) -> f32 {
+        x * self.sx + self.ox
+    }
+
+    fn y(&self, y: f32) -> f32 {
+        y * self.sy + self.oy
+    }
+
+    fn border_x(&self) -> f32 {
+        if self.scaled_border_and_shadow { self.sx } else { self.layout_sx }
+    }
+
+    fn border_y(&self) -> f32 {
+        if self.scaled_border_and_shadow { self.sy } else { self.layout_sy }
+    }
+
+    fn blur(&self) -> f32 {
+        if (self.layout_sx - self.layout_sy).abs() < 0.0001 {
+            self.layout_sy
+        } else {
+            (self.layout_sx + self.layout_sy) / 2.0
+        }
+    }
+
+    // Vector clips are in script coordinates, scaled by 2^(scale-1) like the drawing itself.
+    fn clip(&self, s: &str, clip_scale: i32) -> String {
+        let mut drawing: Drawing = s.parse().unwrap();
+        if drawing.commands.is_empty() {
+            return s.to_string();
+        }
+        let factor = 2f32.powi(clip_scale.max(1) - 1);
+        drawing.scale(self.sx, self.sy);
+        drawing.translate(self.ox * factor, self.oy * factor);
+        drawing.stringify()
+    }
 }
 
 fn scale_u16(v: u16, scale: f32) -> u16 {
-    let scaled = (v as f32 * scale).round();
+    scale_u16_offset(v, scale, 0.0)
+}
+
+fn scale_u16_offset(v: u16, scale: f32, offset: f32) -> u16 {
+    let scaled = (v as f32 * scale + offset).round();
     if !scaled.is_finite() || scaled <= 0.0 {
         0
     } else if scaled >= u16::MAX as f32 {
@@ -473,89 +972,84 @@ fn scale_u16(v: u16, scale: f32) -> u16 {
     }
 }
 
-fn scale_line(line: &mut ASSLine, sx: f32, sy: f32, layout_sx: f32, layout_sy: f32, scaled_border_and_shadow: bool) {
+fn scale_line(line: &mut ASSLine, r: &Resample) {
     let mut drawing = 0;
 
     for ov in &mut line.current_overrides {
-        scale_override(ov, sx, sy, layout_sx, layout_sy, scaled_border_and_shadow);
+        scale_override(ov, r);
     }
 
     for item in &mut line.data {
         match item {
             ASSText::Override(ov) => {
-                scale_override(ov, sx, sy, layout_sx, layout_sy, scaled_border_and_shadow);
+                scale_override(ov, r);
                 if let ASSOverride::P(v) = ov {
                     drawing = *v;
                 }
             }
             ASSText::RawText(text) if drawing > 0 => {
-                *text = scale_drawing_text(text, sx, sy);
+                *text = scale_drawing_text(text, r.sx / r.stretch, r.sy);
             }
             ASSText::Drawing(drawing) => {
-                drawing.scale(sx, sy);
+                drawing.scale(r.sx / r.stretch, r.sy);
             }
             ASSText::RawText(_) => {}
         }
     }
 }
 
-fn scale_override(ov: &mut ASSOverride, sx: f32, sy: f32, layout_sx: f32, layout_sy: f32, scaled_border_and_shadow: bool) {
-    let border_x = border_scalar(scaled_border_and_shadow, sx, layout_sx);
-    let border_y = border_scalar(scaled_border_and_shadow, sy, layout_sy);
+fn scale_override(ov: &mut ASSOverride, r: &Resample) {
     match ov {
-        ASSOverride::Bord(v) | ASSOverride::Shad(v) => *v *= border_y,
-        ASSOverride::Xbord(v) | ASSOverride::Xshad(v) => *v *= border_x,
-        ASSOverride::Ybord(v) | ASSOverride::Yshad(v) => *v *= border_y,
-        ASSOverride::Fs(v) => *v *= sy,
-        ASSOverride::Fsp(v) => *v *= sx,
-        ASSOverride::Blur(v) => *v *= layout_y_scalar(layout_sx, layout_sy),
+        ASSOverride::Bord(v) | ASSOverride::Shad(v) => *v *= r.border_y(),
+        ASSOverride::Xbord(v) | ASSOverride::Xshad(v) => *v *= r.border_x(),
+        ASSOverride::Ybord(v) | ASSOverride::Yshad(v) => *v *= r.border_y(),
+        ASSOverride::Fs(v) => *v *= r.sy,
+        ASSOverride::Fsp(v) => *v *= r.sx,
+        ASSOverride::Fscx(v) => *v *= r.stretch,
+        ASSOverride::Blur(v) => *v *= r.blur(),
         ASSOverride::Pos(x, y) | ASSOverride::Org(x, y) => {
-            *x *= sx;
-            *y *= sy;
+            *x = r.x(*x);
+            *y = r.y(*y);
         }
         ASSOverride::ClipRect(x0, y0, x1, y1) | ASSOverride::IclipRect(x0, y0, x1, y1) => {
-            *x0 *= sx;
-            *x1 *= sx;
-            *y0 *= sy;
-            *y1 *= sy;
+            *x0 = r.x(*x0);
+            *x1 = r.x(*x1);
+            *y0 = r.y(*y0);
+            *y1 = r.y(*y1);
         }
         ASSOverride::ClipI(d) | ASSOverride::IclipI(d) => {
-            *d = scale_drawing_text(d, sx, sy);
+            *d = r.clip(d, 1);
         }
-        ASSOverride::ClipII(_, d) | ASSOverride::IclipII(_, d) => {
-            *d = scale_drawing_text(d, sx, sy);
+        ASSOverride::ClipII(scale, d) | ASSOverride::IclipII(scale, d) => {
+            *d = r.clip(d, *scale as i32);
         }
         ASSOverride::MoveI(x0, y0, x1, y1) => {
-            *x0 *= sx;
-            *x1 *= sx;
-            *y0 *= sy;
-            *y1 *= sy;
+            *x0 = r.x(*x0);
+            *x1 = r.x(*x1);
+            *y0 = r.y(*y0);
+            *y1 = r.y(*y1);
         }
         ASSOverride::MoveII(x0, y0, x1, y1, _, _) => {
-            *x0 *= sx;
-            *x1 *= sx;
-            *y0 *= sy;
-            *y1 *= sy;
-        }
-        ASSOverride::Pbo(v) => *v *= sy,
-        ASSOverride::TransformI(tags) => scale_overrides(tags, sx, sy, layout_sx, layout_sy, scaled_border_and_shadow),
-        ASSOverride::TransformII(_, tags) => scale_overrides(tags, sx, sy, layout_sx, layout_sy, scaled_border_and_shadow),
-        ASSOverride::TransformIII(_, _, tags) => scale_overrides(tags, sx, sy, layout_sx, layout_sy, scaled_border_and_shadow),
-        ASSOverride::TransformIV(_, _, _, tags) => scale_overrides(tags, sx, sy, layout_sx, layout_sy, scaled_border_and_shadow),
+            *x0 = r.x(*x0);
+            *x1 = r.x(*x1);
+            *y0 = r.y(*y0);
+            *y1 = r.y(*y1);
+        }
+        ASSOverride::Pbo(v) => *v *= r.sy,
+        ASSOverride::TransformI(tags) => scale_overrides(tags, r),
+        ASSOverride::TransformII(_, tags) => scale_overrides(tags, r),
+        ASSOverride::TransformIII(_, _, tags) => scale_overrides(tags, r),
+        ASSOverride::TransformIV(_, _, _, tags) => scale_overrides(tags, r),
         _ => {}
     }
 }
 
-fn scale_overrides(tags: &mut [ASSOverride], sx: f32, sy: f32, layout_sx: f32, layout_sy: f32, scaled_border_and_shadow: bool) {
+fn scale_overrides(tags: &mut [ASSOverride], r: &Resample) {
     for tag in tags {
-        scale_override(tag, sx, sy, layout_sx, layout_sy, scaled_border_and_shadow);
+        scale_override(tag, r);
     }
 }
 
-fn layout_y_scalar(layout_sx: f32, layout_sy: f32) -> f32 {
-    if (layout_sx - layout_sy).abs() < 0.0001 { layout_sy } else { (layout_sx + layout_sy) / 2.0 }
-}
-
 fn scale_drawing_text(s: &str, sx: f32, sy: f32) -> String {
     let mut drawing: Drawing = s.parse().unwrap();
     if drawing.commands.is_empty() {
@@ -582,20 +1076,21 @@ fn format_scaled(v: f32) -> String {
     }
 }
 
-fn scale_effect(effect: &str, sx: f32, sy: f32) -> String {
+fn scale_effect(effect: &str, r: &Resample) -> String {
     let mut parts: Vec<String> = effect.split(';').map(|p| p.to_string()).collect();
     let Some(name) = parts.first().map(|p| p.as_str()) else {
         return effect.to_string();
     };
 
     if name == "Banner" {
-        scale_effect_part_inverse(&mut parts, 1, sx);
-        scale_effect_part(&mut parts, 3, sx);
+        scale_effect_part_inverse(&mut parts, 1, r.sx);
+        scale_effect_part(&mut parts, 3, r.sx);
     } else if name == "Scroll up" || name == "Scroll down" {
-        scale_effect_part(&mut parts, 1, sy);
-        scale_effect_part(&mut parts, 2, sy);
-        scale_effect_part_inverse(&mut parts, 3, sy);
-        scale_effect_part(&mut parts, 4, sy);
+        // The two scroll bounds are screen positions; delay and fade height are distances.
+        scale_effect_position(&mut parts, 1, r.sy, r.oy);
+        scale_effect_position(&mut parts, 2, r.sy, r.oy);
+        scale_effect_part_inverse(&mut parts, 3, r.sy);
+        scale_effect_part(&mut parts, 4, r.sy);
     }
 
     parts.join(";")
@@ -609,6 +1104,14 @@ fn scale_effect_part(parts: &mut [String], index: usize, scale: f32) {
     }
 }
 
+fn scale_effect_position(parts: &mut [String], index: usize, scale: f32, offset: f32) {
+    if let Some(part) = parts.get_mut(index)
+        && let Ok(v) = part.trim().parse::<f32>()
+    {
+        *part = format_scaled(v * scale + offset);
+    }
+}
+
 fn scale_effect_part_inverse(parts: &mut [String], index: usize, scale: f32) {
     if let Some(part) = parts.get_mut(index) {
         if let Ok(v) = part.trim().parse::<f32>() {
@@ -876,25 +1379,31 @@ pub fn collect_font_files(dir: &Path, out: &mut Vec<PathBuf>) {
 
 pub fn font_file_names(path: &Path) -> Result<Vec<String>, ()> {
     let data = std::fs::read(path).map_err(|_| ())?;
+    let mut out = Vec::new();
+    for offset in sfnt_face_offsets(&data)? {
+        out.extend(sfnt_names(&data, offset).unwrap_or_default());
+    }
+    Ok(out)
+}
+
+// Offset of every face's table directory; a plain TTF/OTF has one at 0, a TTC lists them in its
+// header in face-index order.
+pub(crate) fn sfnt_face_offsets(data: &[u8]) -> Result<Vec<usize>, ()> {
     let mut offsets = Vec::new();
     if data.get(0..4) == Some(b"ttcf") {
-        let count = read_u32(&data, 8).ok_or(())? as usize;
+        let count = read_u32(data, 8).ok_or(())? as usize;
         for i in 0..count {
-            if let Some(offset) = read_u32(&data, 12 + i * 4) {
+            if let Some(offset) = read_u32(data, 12 + i * 4) {
                 offsets.push(offset as usize);
             }
         }
     } else {
         offsets.push(0);
     }
-    let mut out = Vec::new();
-    for offset in offsets {
-        out.extend(sfnt_names(&data, offset).unwrap_or_default());
-    }
-    Ok(out)
+    Ok(offsets)
 }
 
-fn sfnt_names(data: &[u8], base: usize) -> Result<Vec<String>, ()> {
+pub(crate) fn sfnt_names(data: &[u8], base: usize) -> Result<Vec<String>, ()> {
     let num_tables = read_u16(data, base + 4).ok_or(())? as usize;
     let mut name_offset = None;
     for i in 0..num_tables {
@@ -947,11 +1456,11 @@ fn decode_name_string(platform: u16, raw: &[u8]) -> Option<String> {
     }
 }
 
-fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
     Some(u16::from_be_bytes([*data.get(offset)?, *data.get(offset + 1)?]))
 }
 
-fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
     Some(u32::from_be_bytes([
         *data.get(offset)?,
         *data.get(offset + 1)?,
@@ -1017,9 +1526,14 @@ mod tests {
                 margin_v: 7,
                 effect: String::new(),
                 text: text.parse().unwrap(),
+                extradata: Vec::new(),
             }],
             comments: Vec::new(),
             pandora_meta: PandoraMeta::default(),
+            fonts: Vec::new(),
+            graphics: Vec::new(),
+            project_garbage: ProjectGarbage::default(),
+            extradata: Vec::new(),
         }
     }
 
@@ -1039,7 +1553,7 @@ mod tests {
     async fn load_ass(name: &str, contents: &str, adv_parsing: bool) -> (SubstationAlpha, PathBuf) {
         let path = temp_ass_path(name);
         std::fs::write(&path, contents).unwrap();
-        let sub = SubstationAlpha::load(path.clone(), adv_parsing).await;
+        let sub = SubstationAlpha::load(path.clone(), adv_parsing).await.unwrap();
         (sub, path)
     }
 
@@ -1212,8 +1726,70 @@ mod tests {
         assert_eq!(scroll.events[0].effect, "Scroll up;20;200;20;40");
     }
 
+    #[test]
+    fn resample_stretch_moves_the_squash_into_fscx() {
+        let mut sub = test_sub(r"{\pos(100,200)\fscx80\fscy90\bord2\clip(m 0 0 l 10 10)}text{\p1}m 0 0 l 10 10");
+
+        sub.resample(960, 480, ResampleMode::Stretch).unwrap();
+
+        assert_eq!(sub.script_info.playresx, 960);
+        assert_eq!(sub.script_info.layout_res_x, 960);
+        assert_eq!(sub.v4p_styles[0].scale_x, 150);
+        assert_eq!(sub.v4p_styles[0].scale_y, 100);
+        assert_eq!(sub.v4p_styles[0].fontsize, 20);
+
+        let out = sub.events[0].text.stringify();
+        assert!(out.contains(r"\pos(150,200)"));
+        assert!(out.contains(r"\fscx120"));
+        assert!(out.contains(r"\fscy90"));
+        assert!(out.contains(r"\bord2"));
+        assert!(out.contains(r"\clip(m 0 0 l 15 10)"));
+        assert!(out.ends_with("m 0 0 l 10 10"));
+    }
+
+    #[test]
+    fn resample_letterbox_centres_the_old_frame() {
+        let mut sub = test_sub(r"{\pos(0,0)\move(640,480,320,240)\clip(0,0,640,480)\fs10}text");
+        let mode: ResampleMode = "borders".parse().unwrap();
+
+        sub.resample(1920, 1080, mode).unwrap();
+
+        assert_eq!(sub.v4p_styles[0].scale_x, 100);
+        assert_eq!(sub.v4p_styles[0].fontsize, 45);
+        let out = sub.events[0].text.stringify();
+        assert!(out.contains(r"\pos(240,0)"));
+        assert!(out.contains(r"\move(1680,1080,960,540)"));
+        assert!(out.contains(r"\clip(240,0,1680,1080)"));
+        assert!(out.contains(r"\fs22.5"));
+    }
+
+    #[test]
+    fn resample_crop_cuts_the_overhang() {
+        let mut sub = test_sub(r"{\pos(320,60)\clip(m 0 60 l 640 420)}text");
+
+        sub.resample(1920, 1080, ResampleMode::Crop).unwrap();
+
+        let out = sub.events[0].text.stringify();
+        assert!(out.contains(r"\pos(960,0)"));
+        assert!(out.contains(r"\clip(m 0 0 l 1920 1080)"));
+        assert_eq!(sub.v4p_styles[0].fontsize, 60);
+    }
+
+    #[test]
+    fn resample_reset_only_rewrites_the_header() {
+        let mut sub = test_sub(r"{\pos(100,200)}text");
+
+        sub.resample(1920, 1080, ResampleMode::Reset).unwrap();
+
+        assert_eq!(sub.script_info.playresx, 1920);
+        assert_eq!(sub.script_info.layout_res_y, 1080);
+        assert_eq!(sub.v4p_styles[0].fontsize, 20);
+        assert!(sub.events[0].text.stringify().contains(r"\pos(100,200)"));
+        assert!("sideways".parse::<ResampleMode>().is_err());
+    }
+
     #[tokio::test]
-    async fn comments_are_parsed_separately_and_never_stringified() {
+    async fn comments_are_parsed_separately_and_written_back() {
         let source = r#"[Script Info]
 ScriptType: v4.00+
 
@@ -1228,7 +1804,13 @@ Comment: 0,0:00:03.00,0:00:04.00,Default,note,0,0,0,,{*garbage}commented
         assert_eq!(sub.comments.len(), 1);
         assert_eq!(sub.comments[0].raw_text(), "{*garbage}commented");
         assert!(matches!(sub.comments[0].text.data.as_slice(), [ASSText::RawText(_)]));
-        assert!(!sub.stringify().contains("Comment:"));
+        let output = sub.stringify();
+        assert!(output.contains(
+            "Comment: 0,0:00:03.00,0:00:04.00,Default,note,0,0,0,,{*garbage}commented\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,spoken\n"
+        ), "{}", output);
+        let (reloaded, _) = SubstationAlpha::parse(&output, true);
+        assert_eq!(reloaded.comments.len(), 1);
+        assert_eq!(reloaded.stringify(), output);
 
         let _ = std::fs::remove_file(path);
     }
@@ -1266,7 +1848,7 @@ Comment: 0,0:01:02.00,0:01:04.00,Default,,0,0,0,STAMP,second
         let output = sub.stringify();
         let output_path = temp_ass_path("stamp-roundtrip-output");
         std::fs::write(&output_path, &output).unwrap();
-        let reloaded = SubstationAlpha::load(output_path.clone(), true).await;
+        let reloaded = SubstationAlpha::load(output_path.clone(), true).await.unwrap();
 
         assert!(output.contains("[Pandora Meta]"));
         assert!(!output.contains("Comment:"));
@@ -1301,6 +1883,149 @@ Stamp: 0:00:10.00,0:00:12.00,durable note
         let _ = std::fs::remove_file(path);
     }
 
+    #[tokio::test]
+    async fn attachments_and_aegisub_sections_round_trip() {
+        let font = crate::libkagami::sections::uuencode(&(0u8..=200).collect::<Vec<u8>>());
+        let font_lines = font.as_bytes()
+            .chunks(80)
+            .map(|line| String::from_utf8(line.to_vec()).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let source = format!(r#"[Script Info]
+ScriptType: v4.00+
+PlayResX: 640
+PlayResY: 480
+
+[Aegisub Project Garbage]
+Audio File: ../ep01.mkv
+Video File: ../ep01.mkv
+Keyframes File: ep01_keyframes.txt
+
+[Fonts]
+fontname: test_0.ttf
+{}
+
+[Graphics]
+filename: logo.png
+;[!!
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{{=1}}{{\pos(10,20)}}sign
+
+[Aegisub Extradata]
+Data: 1,_aegi_perspective_ambient_plane,e10;20#7C30;40#7C50;60#7C70;80
+"#, font_lines);
+        let (mut sub, path) = load_ass("attachments", &source, true).await;
+
+        assert_eq!(sub.fonts.len(), 1);
+        assert_eq!(sub.fonts[0].name, "test_0.ttf");
+        assert_eq!(sub.fonts[0].data, (0u8..=200).collect::<Vec<u8>>());
+        assert_eq!(sub.graphics[0].name, "logo.png");
+        assert_eq!(sub.graphics[0].data.len(), 3);
+        assert_eq!(sub.project_garbage.keyframes_file(), Some("ep01_keyframes.txt"));
+        assert_eq!(sub.events[0].extradata, vec![1]);
+        assert_eq!(sub.extradata[0].value_str(), Some("10;20|30;40|50;60|70;80"));
+
+        let output = sub.stringify();
+        assert!(output.contains("[Aegisub Project Garbage]\nAudio File: ../ep01.mkv\n"));
+        assert!(output.contains(&format!("fontname: test_0.ttf\n{}\n", font_lines)));
+        assert!(output.contains("filename: logo.png\n;[!!\n"));
+        assert!(output.contains(r"{=1}{\pos(10,20)}sign"));
+        assert!(output.contains("Data: 1,_aegi_perspective_ambient_plane,e10;20#7C30;40#7C50;60#7C70;80\n"));
+
+        sub.scale(1280, 960).unwrap();
+        assert_eq!(sub.extradata[0].value_str(), Some("20;40|60;80|100;120|140;160"));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn append_fonts_only_adds_attachment_lines() {
+        let font = EmbeddedFile { name: "new_0.ttf".to_string(), data: vec![1, 2, 3] };
+        let attached = font.stringify("fontname");
+        let events = "[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nComment: 0,0:00:00.00,0:00:00.00,Default,,0,0,0,,note\n";
+
+        let bare = format!("[Script Info]\nPlayResX: 640\n\n[V4+ Styles]\n\n{}", events);
+        assert_eq!(
+            append_fonts(&bare, std::slice::from_ref(&font)),
+            format!("[Script Info]\nPlayResX: 640\n\n[V4+ Styles]\n\n[Fonts]\n{}\n{}", attached, events),
+        );
+
+        let existing = format!("[Fonts]\r\nfontname: old_0.ttf\r\n;[!!\r\n\r\n{}", events.replace('\n', "\r\n"));
+        assert_eq!(
+            append_fonts(&existing, std::slice::from_ref(&font)),
+            format!("[Fonts]\r\nfontname: old_0.ttf\r\n;[!!\r\n{}\r\n{}", attached.replace('\n', "\r\n"), events.replace('\n', "\r\n")),
+        );
+        let (sub, _) = SubstationAlpha::parse(&append_fonts(&existing, &[font]), false);
+        assert_eq!(sub.fonts.len(), 2);
+        assert_eq!(sub.fonts[1].data, vec![1, 2, 3]);
+        assert_eq!(sub.comments.len(), 1);
+    }
+
+    #[test]
+    fn parse_reports_line_numbered_diagnostics() {
+        let source = "\u{FEFF}[Script Info]
+ScriptType: v4.00+
+
+[V4+ Styles]
+Style: Default,Arial,20,&H00FFFFFF,&H00FFFFFF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,10,10,10,1
+Style: Default,Arial,20,&H00FFFFFF,nope,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,10,10,10,1
+Style: Short,Arial,20
+
+[Events]
+Dialogue: 0,0:00:01.00,1:2,Default,,0,0,0,,bad end
+Dialogue: 0,0:00:01.00
+
+[Custom Stuff]
+Key: value
+";
+        let (sub, diagnostics) = SubstationAlpha::parse(source, true);
+
+        let found: Vec<(usize, DiagnosticKind)> = diagnostics.iter().map(|d| (d.line, d.kind)).collect();
+        assert_eq!(found, vec![
+            (6, DiagnosticKind::BadColour),
+            (6, DiagnosticKind::DuplicateStyle),
+            (7, DiagnosticKind::FieldCount),
+            (10, DiagnosticKind::BadTimestamp),
+            (11, DiagnosticKind::FieldCount),
+            (13, DiagnosticKind::UnknownSection),
+        ]);
+        assert_eq!(diagnostics[1].to_string(), "line 6: duplicate style `Default` (first defined on line 5)");
+        assert_eq!(sub.v4p_styles.len(), 2);
+        assert_eq!(sub.events.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn load_reports_missing_and_non_utf8_files_as_errors() {
+        let path = temp_ass_path("missing");
+        assert!(matches!(SubstationAlpha::load(path, true).await, Err(LoadError::Io(_))));
+
+        let err = SubstationAlpha::from_bytes(b"[Script Info]\nTitle: \xff\n", true).err().unwrap();
+        assert!(matches!(err, LoadError::InvalidUtf8 { line: 2 }));
+    }
+
+    #[test]
+    fn absorb_sections_renumbers_extradata_and_keeps_primary_project() {
+        let mut primary = test_sub("tl");
+        primary.project_garbage.set("Video File", "tl.mkv");
+        primary.extradata.push(parse_extradata_line("3,a,ex").unwrap());
+        primary.fonts.push(EmbeddedFile { name: "shared.ttf".to_string(), data: vec![1] });
+        let mut secondary = test_sub("ts");
+        secondary.project_garbage.set("Video File", "ts.mkv");
+        secondary.extradata.push(parse_extradata_line("1,b,ey").unwrap());
+        secondary.events[0].extradata = vec![1];
+        secondary.fonts.push(EmbeddedFile { name: "shared.ttf".to_string(), data: vec![2] });
+        secondary.fonts.push(EmbeddedFile { name: "ts.ttf".to_string(), data: vec![3] });
+
+        primary.absorb_sections(&mut secondary);
+
+        assert_eq!(primary.project_garbage.video_file(), Some("tl.mkv"));
+        assert_eq!(primary.extradata.iter().map(|entry| entry.id).collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(secondary.events[0].extradata, vec![4]);
+        assert_eq!(primary.fonts.iter().map(|font| font.data[0]).collect::<Vec<_>>(), vec![1, 3]);
+    }
+
     #[test]
     fn cached_normalized_font_names_normalizes_names() {
         let path = temp_font_path("normalize");
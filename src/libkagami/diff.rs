@@ -0,0 +1,460 @@
+// Event-level comparison of two revisions of a script, for TLC review: which lines the editor
+// reworded, retimed, restyled, added or dropped. Events are paired by time overlap and style rather
+// than by position, because an edit pass routinely splits, merges and reorders lines.
+
+use std::collections::HashMap;
+use crate::libkagami::complex::types::AssTime;
+use crate::libkagami::core::{Event, SubstationAlpha};
+
+// Lines whose text and style are unchanged still pair when retimed this far without overlapping.
+const MOVED_LINE_WINDOW_CS: i64 = 500;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WordEdit {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+impl WordEdit {
+    pub fn op(&self) -> &'static str {
+        match self {
+            WordEdit::Same(_) => "same",
+            WordEdit::Removed(_) => "removed",
+            WordEdit::Added(_) => "added",
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            WordEdit::Same(t) | WordEdit::Removed(t) | WordEdit::Added(t) => t,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffSide {
+    pub index: usize, // 1-based dialogue number, as lint and pnkagami report it
+    pub start: AssTime,
+    pub end: AssTime,
+    pub style: String,
+    pub text: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct EventDiff {
+    pub old: Option<DiffSide>,
+    pub new: Option<DiffSide>,
+    pub words: Vec<WordEdit>, // empty unless both sides exist and the text differs
+}
+
+impl EventDiff {
+    pub fn kind(&self) -> &'static str {
+        match (&self.old, &self.new) {
+            (Some(_), Some(_)) => "changed",
+            (None, _) => "added",
+            (_, None) => "removed",
+        }
+    }
+
+    pub fn timing_changed(&self) -> bool {
+        matches!((&self.old, &self.new), (Some(o), Some(n)) if o.start != n.start || o.end != n.end)
+    }
+
+    pub fn style_changed(&self) -> bool {
+        matches!((&self.old, &self.new), (Some(o), Some(n)) if o.style != n.style)
+    }
+
+    pub fn text_changed(&self) -> bool {
+        !self.words.is_empty()
+    }
+
+    fn anchor(&self) -> (u64, usize) {
+        let side = self.new.as_ref().or(self.old.as_ref()).unwrap();
+        (side.start.total_centiseconds(), side.index)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ScriptDiff {
+    pub events: Vec<EventDiff>,
+    pub unchanged: usize,
+}
+
+impl ScriptDiff {
+    pub fn count(&self, kind: &str) -> usize {
+        self.events.iter().filter(|e| e.kind() == kind).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} changed, {} added, {} removed, {} unchanged",
+            self.count("changed"),
+            self.count("added"),
+            self.count("removed"),
+            self.unchanged
+        )
+    }
+
+    pub fn to_terminal(&self) -> String {
+        let mut out = String::new();
+        for event in &self.events {
+            out.push_str(&format!("\x1b[1;36m{}\x1b[0m\n", heading(event)));
+            if event.timing_changed() {
+                out.push_str(&format!("  timing {}\n", timing_change(event)));
+            }
+            if event.style_changed() {
+                out.push_str(&format!("  style  {} -> {}\n", event.old.as_ref().unwrap().style, event.new.as_ref().unwrap().style));
+            }
+            match (&event.old, &event.new) {
+                (Some(_), Some(_)) if event.text_changed() => {
+                    let text = render_words(&event.words, |w| match w {
+                        WordEdit::Same(t) => t.clone(),
+                        WordEdit::Removed(t) => format!("\x1b[31m[-{}-]\x1b[0m", t),
+                        WordEdit::Added(t) => format!("\x1b[32m{{+{}+}}\x1b[0m", t),
+                    });
+                    out.push_str(&format!("  text   {}\n", text));
+                }
+                (Some(old), None) => out.push_str(&format!("\x1b[31m- {}\x1b[0m\n", old.text)),
+                (None, Some(new)) => out.push_str(&format!("\x1b[32m+ {}\x1b[0m\n", new.text)),
+                _ => {}
+            }
+        }
+        out.push_str(&self.summary());
+        out.push('\n');
+        out
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("**{}**\n", self.summary());
+        for event in &self.events {
+            out.push_str(&format!("\n### {}\n", heading(event)));
+            if event.timing_changed() {
+                out.push_str(&format!("- Timing: {}\n", timing_change(event)));
+            }
+            if event.style_changed() {
+                out.push_str(&format!(
+                    "- Style: `{}` → `{}`\n",
+                    event.old.as_ref().unwrap().style,
+                    event.new.as_ref().unwrap().style
+                ));
+            }
+            match (&event.old, &event.new) {
+                (Some(_), Some(_)) if event.text_changed() => {
+                    let text = render_words(&event.words, |w| match w {
+                        WordEdit::Same(t) => escape_markdown(t),
+                        WordEdit::Removed(t) => format!("~~{}~~", escape_markdown(t)),
+                        WordEdit::Added(t) => format!("**{}**", escape_markdown(t)),
+                    });
+                    out.push_str(&format!("- Text: {}\n", text));
+                }
+                (Some(side), None) | (None, Some(side)) => {
+                    out.push_str(&format!("- `{}`: {}\n", side.style, escape_markdown(&side.text)));
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let side = |s: &Option<DiffSide>| match s {
+            Some(s) => serde_json::json!({
+                "index": s.index,
+                "start": s.start.to_string(),
+                "end": s.end.to_string(),
+                "style": s.style,
+                "text": s.text,
+            }),
+            None => serde_json::Value::Null,
+        };
+        let events: Vec<serde_json::Value> = self
+            .events
+            .iter()
+            .map(|e| {
+                serde_json::json!({
+                    "kind": e.kind(),
+                    "old": side(&e.old),
+                    "new": side(&e.new),
+                    "timing_changed": e.timing_changed(),
+                    "style_changed": e.style_changed(),
+                    "words": e.words.iter().map(|w| serde_json::json!({ "op": w.op(), "text": w.text() })).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "changed": self.count("changed"),
+            "added": self.count("added"),
+            "removed": self.count("removed"),
+            "unchanged": self.unchanged,
+            "events": events,
+        })
+    }
+}
+
+pub fn diff_scripts(old: &SubstationAlpha, new: &SubstationAlpha) -> ScriptDiff {
+    let old_sides: Vec<DiffSide> = old.events.iter().enumerate().map(|(i, e)| side(i, e)).collect();
+    let new_sides: Vec<DiffSide> = new.events.iter().enumerate().map(|(i, e)| side(i, e)).collect();
+    let mut old_taken = vec![false; old_sides.len()];
+    let mut new_taken = vec![false; new_sides.len()];
+    let mut diff = ScriptDiff::default();
+
+    // Identical lines first, so a repeated "Yes." never pairs with the wrong copy.
+    let mut identical: HashMap<(u64, u64, &str, &str), Vec<usize>> = HashMap::new();
+    for (i, s) in old_sides.iter().enumerate().rev() {
+        identical.entry(exact_key(s)).or_default().push(i);
+    }
+    for (j, s) in new_sides.iter().enumerate() {
+        if let Some(i) = identical.get_mut(&exact_key(s)).and_then(|c| c.pop()) {
+            old_taken[i] = true;
+            new_taken[j] = true;
+            diff.unchanged += 1;
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for (i, o) in old_sides.iter().enumerate().filter(|(i, _)| !old_taken[*i]) {
+        for (j, n) in new_sides.iter().enumerate().filter(|(j, _)| !new_taken[*j]) {
+            if let Some(score) = pair_score(o, n) {
+                candidates.push((score, i, j));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+    for (_, i, j) in candidates {
+        if old_taken[i] || new_taken[j] {
+            continue;
+        }
+        old_taken[i] = true;
+        new_taken[j] = true;
+        let (o, n) = (&old_sides[i], &new_sides[j]);
+        let words = if o.text == n.text { Vec::new() } else { diff_words(&o.text, &n.text) };
+        diff.events.push(EventDiff { old: Some(o.clone()), new: Some(n.clone()), words });
+    }
+
+    for (o, _) in old_sides.iter().zip(&old_taken).filter(|(_, taken)| !**taken) {
+        diff.events.push(EventDiff { old: Some(o.clone()), new: None, words: Vec::new() });
+    }
+    for (n, _) in new_sides.iter().zip(&new_taken).filter(|(_, taken)| !**taken) {
+        diff.events.push(EventDiff { old: None, new: Some(n.clone()), words: Vec::new() });
+    }
+    diff.events.sort_by_key(|e| e.anchor());
+    diff
+}
+
+// Word-level edit script between two event texts. Override blocks and \N/\n/\h are tokens of their
+// own, so a TLC that only adds an {\i1} shows up as that block and not as a rewritten word.
+pub fn diff_words(old: &str, new: &str) -> Vec<WordEdit> {
+    let a = tokenize(old);
+    let b = tokenize(new);
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut edits = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push(WordEdit::Same(a[i].clone()));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(WordEdit::Removed(a[i].clone()));
+            i += 1;
+        } else {
+            edits.push(WordEdit::Added(b[j].clone()));
+            j += 1;
+        }
+    }
+    edits
+}
+
+fn side(index: usize, event: &Event) -> DiffSide {
+    DiffSide {
+        index: index + 1,
+        start: event.start,
+        end: event.end,
+        style: event.style.clone(),
+        text: event.text.stringify(),
+    }
+}
+
+fn exact_key(side: &DiffSide) -> (u64, u64, &str, &str) {
+    (side.start.total_centiseconds(), side.end.total_centiseconds(), &side.style, &side.text)
+}
+
+// Overlap share of the two lines' combined span, plus a bonus for keeping the style and for shared
+// words. Lines that do not overlap only pair when nothing but their timing moved.
+fn pair_score(old: &DiffSide, new: &DiffSide) -> Option<f64> {
+    let (os, oe) = (old.start.total_centiseconds() as i64, old.end.total_centiseconds() as i64);
+    let (ns, ne) = (new.start.total_centiseconds() as i64, new.end.total_centiseconds() as i64);
+    let overlap = oe.min(ne) - os.max(ns);
+    let union = (oe.max(ne) - os.min(ns)).max(1);
+    let same_style = old.style == new.style;
+    if overlap <= 0 {
+        let moved = same_style && old.text == new.text && (os - ns).abs() <= MOVED_LINE_WINDOW_CS;
+        return moved.then_some(0.5);
+    }
+    let style_bonus = if same_style { 0.5 } else { 0.0 };
+    Some(overlap as f64 / union as f64 + style_bonus + 0.5 * word_similarity(&old.text, &new.text))
+}
+
+fn word_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (tokenize(a), tokenize(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let same = common_tokens(&a, &b);
+    2.0 * same as f64 / (a.len() + b.len()) as f64
+}
+
+fn common_tokens(a: &[String], b: &[String]) -> usize {
+    let mut row = vec![0usize; b.len() + 1];
+    for x in a {
+        let mut diag = 0;
+        for (j, y) in b.iter().enumerate() {
+            let up = row[j + 1];
+            row[j + 1] = if x == y { diag + 1 } else { up.max(row[j]) };
+            diag = up;
+        }
+    }
+    row[b.len()]
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '{' {
+            push_word(&mut tokens, &mut word);
+            let mut block = String::from('{');
+            for b in chars.by_ref() {
+                block.push(b);
+                if b == '}' {
+                    break;
+                }
+            }
+            tokens.push(block);
+        } else if c == '\\' && matches!(chars.peek(), Some('N' | 'n' | 'h')) {
+            push_word(&mut tokens, &mut word);
+            tokens.push(format!("\\{}", chars.next().unwrap()));
+        } else if c.is_whitespace() {
+            push_word(&mut tokens, &mut word);
+        } else {
+            word.push(c);
+        }
+    }
+    push_word(&mut tokens, &mut word);
+    tokens
+}
+
+fn push_word(tokens: &mut Vec<String>, word: &mut String) {
+    if !word.is_empty() {
+        tokens.push(std::mem::take(word));
+    }
+}
+
+fn heading(event: &EventDiff) -> String {
+    match (&event.old, &event.new) {
+        (Some(o), Some(n)) => format!("{} · event {} → {}", n.start, o.index, n.index),
+        (Some(o), None) => format!("{} · removed event {}", o.start, o.index),
+        (None, Some(n)) => format!("{} · added event {}", n.start, n.index),
+        (None, None) => String::new(),
+    }
+}
+
+fn timing_change(event: &EventDiff) -> String {
+    let (o, n) = (event.old.as_ref().unwrap(), event.new.as_ref().unwrap());
+    format!("{}–{} → {}–{}", o.start, o.end, n.start, n.end)
+}
+
+// Tokens are rejoined with single spaces; the override blocks and line breaks that were glued to
+// their words read fine either way.
+fn render_words(words: &[WordEdit], paint: impl Fn(&WordEdit) -> String) -> String {
+    words.iter().map(paint).collect::<Vec<_>>().join(" ")
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '_' | '~' | '`' | '#' | '|') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(lines: &[(&str, &str, &str, &str)]) -> SubstationAlpha {
+        let mut text = String::from(
+            "[Script Info]\nScriptType: v4.00+\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+        );
+        for (start, end, style, line) in lines {
+            text.push_str(&format!("Dialogue: 0,{},{},{},,0,0,0,,{}\n", start, end, style, line));
+        }
+        SubstationAlpha::parse(&text, false).0
+    }
+
+    #[test]
+    fn words_keep_override_blocks_and_breaks_as_tokens() {
+        let edits = diff_words(r"Hello world\Nagain", r"{\i1}Hello there\Nagain");
+        assert_eq!(
+            edits,
+            vec![
+                WordEdit::Added(r"{\i1}".to_string()),
+                WordEdit::Same("Hello".to_string()),
+                WordEdit::Removed("world".to_string()),
+                WordEdit::Added("there".to_string()),
+                WordEdit::Same(r"\N".to_string()),
+                WordEdit::Same("again".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_pair_by_overlap_and_style_not_position() {
+        let old = script(&[
+            ("0:00:01.00", "0:00:03.00", "Default", "Yes."),
+            ("0:00:04.00", "0:00:06.00", "Default", "I never said that"),
+            ("0:00:07.00", "0:00:08.00", "Default", "Gone line"),
+            ("0:00:20.00", "0:00:21.00", "Sign", "Cafe"),
+        ]);
+        let new = script(&[
+            ("0:00:00.50", "0:00:01.00", "Default", "Wait."),
+            ("0:00:01.00", "0:00:03.00", "Default", "Yes."),
+            ("0:00:04.00", "0:00:06.50", "Italics", "I never said it"),
+            ("0:00:22.00", "0:00:23.00", "Sign", "Cafe"),
+        ]);
+
+        let diff = diff_scripts(&old, &new);
+
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.summary(), "2 changed, 1 added, 1 removed, 1 unchanged");
+        let kinds: Vec<&str> = diff.events.iter().map(|e| e.kind()).collect();
+        assert_eq!(kinds, vec!["added", "changed", "removed", "changed"]);
+
+        let edited = &diff.events[1];
+        assert_eq!(edited.old.as_ref().unwrap().index, 2);
+        assert_eq!(edited.new.as_ref().unwrap().index, 3);
+        assert!(edited.timing_changed() && edited.style_changed() && edited.text_changed());
+
+        let moved = &diff.events[3];
+        assert!(moved.timing_changed() && !moved.text_changed());
+
+        let json = diff.to_json();
+        assert_eq!(json["added"], 1);
+        assert_eq!(json["events"][1]["words"][3]["op"], "removed");
+        assert!(diff.to_markdown().contains("I never said ~~that~~ **it**"));
+    }
+}
@@ -0,0 +1,627 @@
+// Geometry on `\p` drawings and vector `\clip` masks: bounding boxes, affine transforms (including
+// the ones `\org`, `\frz` and `\fax`/`\fay` describe), B-spline to cubic conversion, flattening and
+// boolean operations. Shapes are filled with the non-zero rule, as libass fills them, and boolean
+// results come back as line-only contours with holes wound against their outlines.
+
+use std::collections::HashMap;
+use crate::libkagami::complex::overrides::ASSOverride;
+use crate::libkagami::drawing::parse::{Drawing, DrawingCommand};
+
+// Boolean operations snap split points to this grid (in pixels) so shared vertices match exactly.
+const GRID: f64 = 1024.0;
+// How far either side of an edge the inside test samples.
+const SIDE: f64 = 1e-3;
+// Flattening tolerance used when a caller has no opinion, in pixels.
+pub const DEFAULT_TOLERANCE: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+// x' = a*x + b*y + c, y' = d*x + e*y + f
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Affine {
+    pub fn identity() -> Self {
+        Self { a: 1.0, b: 0.0, c: 0.0, d: 0.0, e: 1.0, f: 0.0 }
+    }
+
+    pub fn translate(dx: f64, dy: f64) -> Self {
+        Self { c: dx, f: dy, ..Self::identity() }
+    }
+
+    pub fn scale(sx: f64, sy: f64) -> Self {
+        Self { a: sx, e: sy, ..Self::identity() }
+    }
+
+    // `\frz` sense: positive angles turn counterclockwise on screen (y grows downwards).
+    pub fn rotate(degrees: f64, origin: (f64, f64)) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let (ox, oy) = origin;
+        Self {
+            a: cos,
+            b: sin,
+            c: ox - ox * cos - oy * sin,
+            d: -sin,
+            e: cos,
+            f: oy + ox * sin - oy * cos,
+        }
+    }
+
+    // `\fax` / `\fay`: x moves by `fax` per pixel below `origin`, y by `fay` per pixel right of it.
+    pub fn shear(fax: f64, fay: f64, origin: (f64, f64)) -> Self {
+        Self { a: 1.0, b: fax, c: -fax * origin.1, d: fay, e: 1.0, f: -fay * origin.0 }
+    }
+
+    // `self` first, then `next`.
+    pub fn then(self, next: Affine) -> Affine {
+        Affine {
+            a: next.a * self.a + next.b * self.d,
+            b: next.a * self.b + next.b * self.e,
+            c: next.a * self.c + next.b * self.f + next.c,
+            d: next.d * self.a + next.e * self.d,
+            e: next.d * self.b + next.e * self.e,
+            f: next.d * self.c + next.e * self.f + next.f,
+        }
+    }
+
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let (x, y) = (x as f64, y as f64);
+        ((self.a * x + self.b * y + self.c) as f32, (self.d * x + self.e * y + self.f) as f32)
+    }
+}
+
+// The placement tags of a line that move its drawing: libass shears around the `\pos` and then
+// rotates around `\org`, which defaults to the `\pos`. `\frx`/`\fry` are perspective and not
+// affine, so they are not covered.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AssTransform {
+    pub pos: (f32, f32),
+    pub org: (f32, f32),
+    pub frz: f32,
+    pub fax: f32,
+    pub fay: f32,
+}
+
+impl AssTransform {
+    // The last of each tag wins, as in libass; tags inside `\t` are not looked at.
+    pub fn from_overrides<'a>(overrides: impl IntoIterator<Item = &'a ASSOverride>, pos: (f32, f32)) -> Self {
+        let mut transform = Self { pos, org: pos, frz: 0.0, fax: 0.0, fay: 0.0 };
+        let mut org = None;
+        for ov in overrides {
+            match ov {
+                ASSOverride::Org(x, y) => org = Some((*x, *y)),
+                ASSOverride::Frz(v) | ASSOverride::Fr(v) => transform.frz = *v,
+                ASSOverride::Fax(v) => transform.fax = *v,
+                ASSOverride::Fay(v) => transform.fay = *v,
+                _ => {}
+            }
+        }
+        transform.org = org.unwrap_or(pos);
+        transform
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.frz == 0.0 && self.fax == 0.0 && self.fay == 0.0
+    }
+
+    pub fn affine(&self) -> Affine {
+        let pos = (self.pos.0 as f64, self.pos.1 as f64);
+        let org = (self.org.0 as f64, self.org.1 as f64);
+        Affine::shear(self.fax as f64, self.fay as f64, pos).then(Affine::rotate(self.frz as f64, org))
+    }
+}
+
+fn map_points(command: &mut DrawingCommand, f: &impl Fn(f32, f32) -> (f32, f32)) {
+    match command {
+        DrawingCommand::Move(x, y)
+        | DrawingCommand::MoveN(x, y)
+        | DrawingCommand::Line(x, y)
+        | DrawingCommand::ExtendBSpline(x, y) => (*x, *y) = f(*x, *y),
+        DrawingCommand::CubicBezier(x0, y0, x1, y1, x2, y2)
+        | DrawingCommand::CubicBSpline(x0, y0, x1, y1, x2, y2) => {
+            (*x0, *y0) = f(*x0, *y0);
+            (*x1, *y1) = f(*x1, *y1);
+            (*x2, *y2) = f(*x2, *y2);
+        }
+        DrawingCommand::CloseBSpline | DrawingCommand::Invalid => {}
+    }
+}
+
+type Point = (f64, f64);
+
+impl Drawing {
+    // Control points move with the transform, so curves and splines stay exact.
+    pub fn transform(&mut self, affine: &Affine) {
+        for command in &mut self.commands {
+            map_points(command, &|x, y| affine.apply(x, y));
+        }
+    }
+
+    pub fn rotate(&mut self, degrees: f32, origin: (f32, f32)) {
+        self.transform(&Affine::rotate(degrees as f64, (origin.0 as f64, origin.1 as f64)));
+    }
+
+    pub fn shear(&mut self, fax: f32, fay: f32, origin: (f32, f32)) {
+        self.transform(&Affine::shear(fax as f64, fay as f64, (origin.0 as f64, origin.1 as f64)));
+    }
+
+    // Replaces `s`/`p`/`c` B-splines with the `b` curves libass draws for them. A spline's first
+    // control point is the pen position and each further point adds one uniform cubic segment;
+    // `c` wraps around to the first three points. Like libass, a spline right after `m` starts the
+    // contour where the curve starts, and one after other segments bends out of the pen.
+    pub fn to_cubics(&self) -> Drawing {
+        let mut out: Vec<DrawingCommand> = Vec::with_capacity(self.commands.len());
+        let mut pen = (0.0f32, 0.0f32);
+        let mut started = false;
+        let mut spline: Vec<(f32, f32)> = Vec::new();
+        let push_segment = |out: &mut Vec<DrawingCommand>, started: &mut bool, p: &[(f32, f32)]| {
+            let at = |w: [f32; 4]| {
+                (
+                    (w[0] * p[0].0 + w[1] * p[1].0 + w[2] * p[2].0 + w[3] * p[3].0) / 6.0,
+                    (w[0] * p[0].1 + w[1] * p[1].1 + w[2] * p[2].1 + w[3] * p[3].1) / 6.0,
+                )
+            };
+            if !*started
+                && let Some(DrawingCommand::Move(x, y) | DrawingCommand::MoveN(x, y)) = out.last_mut()
+            {
+                (*x, *y) = at([1.0, 4.0, 1.0, 0.0]);
+            }
+            let (c1, c2, end) = (at([0.0, 4.0, 2.0, 0.0]), at([0.0, 2.0, 4.0, 0.0]), at([0.0, 1.0, 4.0, 1.0]));
+            out.push(DrawingCommand::CubicBezier(c1.0, c1.1, c2.0, c2.1, end.0, end.1));
+            *started = true;
+        };
+        for command in &self.commands {
+            match *command {
+                DrawingCommand::CubicBSpline(x0, y0, x1, y1, x2, y2) => {
+                    spline = vec![pen, (x0, y0), (x1, y1), (x2, y2)];
+                    push_segment(&mut out, &mut started, &spline);
+                }
+                DrawingCommand::ExtendBSpline(x, y) if !spline.is_empty() => {
+                    spline.push((x, y));
+                    push_segment(&mut out, &mut started, &spline[spline.len() - 4..]);
+                }
+                DrawingCommand::CloseBSpline => {
+                    let n = spline.len();
+                    for i in 0..n.min(3) {
+                        let window: Vec<(f32, f32)> = (0..4).map(|k| spline[(n - 3 + i + k) % n]).collect();
+                        push_segment(&mut out, &mut started, &window);
+                    }
+                    spline.clear();
+                }
+                // A `p` with no spline to extend is drawn as a line.
+                DrawingCommand::ExtendBSpline(x, y) | DrawingCommand::Line(x, y) => {
+                    spline.clear();
+                    out.push(DrawingCommand::Line(x, y));
+                    (pen, started) = ((x, y), true);
+                }
+                DrawingCommand::CubicBezier(_, _, _, _, x, y) => {
+                    spline.clear();
+                    out.push(command.clone());
+                    (pen, started) = ((x, y), true);
+                }
+                DrawingCommand::Move(x, y) | DrawingCommand::MoveN(x, y) => {
+                    spline.clear();
+                    out.push(command.clone());
+                    (pen, started) = ((x, y), false);
+                }
+                DrawingCommand::Invalid => {}
+            }
+            if let Some(DrawingCommand::CubicBezier(_, _, _, _, x, y)) = out.last()
+                && !spline.is_empty()
+            {
+                pen = (*x, *y);
+            }
+        }
+        Drawing { commands: out }
+    }
+
+    // Polylines, one per `m`/`n` contour, with curves split until they stay within `tolerance`
+    // pixels of the true curve. Every contour is implicitly closed, as it is when filled.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Vec<(f32, f32)>> {
+        flatten_f64(self, tolerance as f64)
+            .into_iter()
+            .map(|contour| contour.into_iter().map(|(x, y)| (x as f32, y as f32)).collect())
+            .collect()
+    }
+
+    pub fn bounding_box(&self) -> Option<Rect> {
+        let mut points = flatten_f64(self, DEFAULT_TOLERANCE as f64 / 4.0).into_iter().flatten();
+        let first = points.next()?;
+        let (mut x1, mut y1, mut x2, mut y2) = (first.0, first.1, first.0, first.1);
+        for (x, y) in points {
+            (x1, y1, x2, y2) = (x1.min(x), y1.min(y), x2.max(x), y2.max(y));
+        }
+        Some(Rect { x1: x1 as f32, y1: y1 as f32, x2: x2 as f32, y2: y2 as f32 })
+    }
+
+    // Sum of the contours' signed areas: the covered area for shapes whose contours don't
+    // overlap, such as boolean results.
+    pub fn area(&self) -> f32 {
+        flatten_f64(self, DEFAULT_TOLERANCE as f64).iter().map(|c| signed_area(c)).sum::<f64>().abs() as f32
+    }
+
+    pub fn from_contours(contours: &[Vec<(f32, f32)>]) -> Drawing {
+        let mut commands = Vec::new();
+        for contour in contours.iter().filter(|contour| contour.len() >= 2) {
+            commands.push(DrawingCommand::Move(contour[0].0, contour[0].1));
+            commands.extend(contour[1..].iter().map(|&(x, y)| DrawingCommand::Line(x, y)));
+        }
+        Drawing { commands }
+    }
+
+    pub fn from_rect(rect: Rect) -> Drawing {
+        let Rect { x1, y1, x2, y2 } = rect;
+        Drawing::from_contours(&[vec![(x1, y1), (x2, y1), (x2, y2), (x1, y2)]])
+    }
+
+    // The rectangle this drawing outlines, if it is a single axis-aligned one.
+    pub fn as_rect(&self) -> Option<Rect> {
+        let contours = flatten_f64(self, DEFAULT_TOLERANCE as f64);
+        let [contour] = contours.as_slice() else {
+            return None;
+        };
+        let corners = simplify(contour.clone());
+        if corners.len() != 4 {
+            return None;
+        }
+        let axis_aligned = (0..4).all(|i| {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            a.0 == b.0 || a.1 == b.1
+        });
+        if !axis_aligned {
+            return None;
+        }
+        self.bounding_box()
+    }
+
+    pub fn union(&self, other: &Drawing) -> Drawing {
+        boolean(self, other, |a, b| a || b)
+    }
+
+    pub fn difference(&self, other: &Drawing) -> Drawing {
+        boolean(self, other, |a, b| a && !b)
+    }
+
+    pub fn intersection(&self, other: &Drawing) -> Drawing {
+        boolean(self, other, |a, b| a && b)
+    }
+}
+
+// A `\clip` / `\iclip` as a drawing in script coordinates, with whether it is inverse. Vector clips
+// with a scale are brought down by 2^(scale-1) like `\p` drawings.
+pub fn clip_mask(ov: &ASSOverride) -> Option<(Drawing, bool)> {
+    let vector = |scale: f32, shape: &str| {
+        let mut drawing: Drawing = shape.parse().unwrap_or(Drawing { commands: Vec::new() });
+        if scale > 1.0 {
+            let factor = 1.0 / 2f32.powf(scale - 1.0);
+            drawing.scale(factor, factor);
+        }
+        drawing
+    };
+    let rect = |x1: f32, y1: f32, x2: f32, y2: f32| {
+        Drawing::from_rect(Rect { x1: x1.min(x2), y1: y1.min(y2), x2: x1.max(x2), y2: y1.max(y2) })
+    };
+    match ov {
+        ASSOverride::ClipI(shape) => Some((vector(1.0, shape), false)),
+        ASSOverride::ClipII(scale, shape) => Some((vector(*scale, shape), false)),
+        ASSOverride::ClipRect(x1, y1, x2, y2) => Some((rect(*x1, *y1, *x2, *y2), false)),
+        ASSOverride::IclipI(shape) => Some((vector(1.0, shape), true)),
+        ASSOverride::IclipII(scale, shape) => Some((vector(*scale, shape), true)),
+        ASSOverride::IclipRect(x1, y1, x2, y2) => Some((rect(*x1, *y1, *x2, *y2), true)),
+        _ => None,
+    }
+}
+
+// The clip tag for a mask: the rectangle form when the mask is one, a vector clip otherwise.
+pub fn clip_override(mask: &Drawing, inverse: bool) -> ASSOverride {
+    match (mask.as_rect(), inverse) {
+        (Some(r), false) => ASSOverride::ClipRect(r.x1, r.y1, r.x2, r.y2),
+        (Some(r), true) => ASSOverride::IclipRect(r.x1, r.y1, r.x2, r.y2),
+        (None, false) => ASSOverride::ClipI(mask.stringify()),
+        (None, true) => ASSOverride::IclipI(mask.stringify()),
+    }
+}
+
+fn flatten_f64(drawing: &Drawing, tolerance: f64) -> Vec<Vec<Point>> {
+    let mut contours: Vec<Vec<Point>> = Vec::new();
+    let mut current: Vec<Point> = Vec::new();
+    let tolerance = tolerance.max(1e-3);
+    for command in drawing.to_cubics().commands {
+        match command {
+            DrawingCommand::Move(x, y) | DrawingCommand::MoveN(x, y) => {
+                if !current.is_empty() {
+                    contours.push(std::mem::take(&mut current));
+                }
+                current.push((x as f64, y as f64));
+            }
+            DrawingCommand::Line(x, y) => {
+                if current.is_empty() {
+                    current.push((0.0, 0.0));
+                }
+                current.push((x as f64, y as f64));
+            }
+            DrawingCommand::CubicBezier(x0, y0, x1, y1, x2, y2) => {
+                if current.is_empty() {
+                    current.push((0.0, 0.0));
+                }
+                let start = *current.last().unwrap();
+                let p = [start, (x0 as f64, y0 as f64), (x1 as f64, y1 as f64), (x2 as f64, y2 as f64)];
+                flatten_cubic(p, tolerance, 0, &mut current);
+            }
+            _ => {}
+        }
+    }
+    if !current.is_empty() {
+        contours.push(current);
+    }
+    contours
+}
+
+fn flatten_cubic(p: [Point; 4], tolerance: f64, depth: u32, out: &mut Vec<Point>) {
+    let flat = distance_to_line(p[1], p[0], p[3]).max(distance_to_line(p[2], p[0], p[3]));
+    if depth >= 16 || flat <= tolerance {
+        out.push(p[3]);
+        return;
+    }
+    let mid = |a: Point, b: Point| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    let (p01, p12, p23) = (mid(p[0], p[1]), mid(p[1], p[2]), mid(p[2], p[3]));
+    let (p012, p123) = (mid(p01, p12), mid(p12, p23));
+    let centre = mid(p012, p123);
+    flatten_cubic([p[0], p01, p012, centre], tolerance, depth + 1, out);
+    flatten_cubic([centre, p123, p23, p[3]], tolerance, depth + 1, out);
+}
+
+fn distance_to_line(p: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length < 1e-12 {
+        return ((p.0 - a.0).powi(2) + (p.1 - a.1).powi(2)).sqrt();
+    }
+    ((p.0 - a.0) * dy - (p.1 - a.1) * dx).abs() / length
+}
+
+fn signed_area(contour: &[Point]) -> f64 {
+    let n = contour.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (contour[i], contour[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f64>()
+        / 2.0
+}
+
+fn cross(a: Point, b: Point) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn snap(p: Point) -> (i64, i64) {
+    ((p.0 * GRID).round() as i64, (p.1 * GRID).round() as i64)
+}
+
+fn unsnap(key: (i64, i64)) -> Point {
+    (key.0 as f64 / GRID, key.1 as f64 / GRID)
+}
+
+// Drops repeated points and vertices in the middle of a straight run.
+fn simplify(mut contour: Vec<Point>) -> Vec<Point> {
+    contour.dedup();
+    while contour.len() > 1 && contour.first() == contour.last() {
+        contour.pop();
+    }
+    let mut changed = true;
+    while changed && contour.len() > 2 {
+        changed = false;
+        let n = contour.len();
+        for i in 0..n {
+            let (a, b, c) = (contour[(i + n - 1) % n], contour[i], contour[(i + 1) % n]);
+            let (ab, bc) = ((b.0 - a.0, b.1 - a.1), (c.0 - b.0, c.1 - b.1));
+            if cross(ab, bc).abs() < 1e-9 && ab.0 * bc.0 + ab.1 * bc.1 >= 0.0 {
+                contour.remove(i);
+                changed = true;
+                break;
+            }
+        }
+    }
+    contour
+}
+
+// Closed edges of every contour, after snapping.
+fn edges(contours: &[Vec<Point>]) -> Vec<(Point, Point)> {
+    let mut out = Vec::new();
+    for contour in contours.iter().filter(|c| c.len() >= 3) {
+        for i in 0..contour.len() {
+            let (a, b) = (unsnap(snap(contour[i])), unsnap(snap(contour[(i + 1) % contour.len()])));
+            if a != b {
+                out.push((a, b));
+            }
+        }
+    }
+    out
+}
+
+// Splits every edge wherever another edge crosses or touches it, so no two pieces cross.
+fn split_edges(all: &[(Point, Point)]) -> Vec<((i64, i64), (i64, i64))> {
+    let boxes: Vec<(f64, f64, f64, f64)> = all
+        .iter()
+        .map(|(a, b)| (a.0.min(b.0), a.1.min(b.1), a.0.max(b.0), a.1.max(b.1)))
+        .collect();
+    let mut out = Vec::new();
+    for (i, &(p, p2)) in all.iter().enumerate() {
+        let r = (p2.0 - p.0, p2.1 - p.1);
+        let rr = r.0 * r.0 + r.1 * r.1;
+        let mut cuts = vec![0.0, 1.0];
+        for (j, &(q, q2)) in all.iter().enumerate() {
+            let (bi, bj) = (boxes[i], boxes[j]);
+            if i == j || bj.0 > bi.2 + 1e-9 || bj.2 < bi.0 - 1e-9 || bj.1 > bi.3 + 1e-9 || bj.3 < bi.1 - 1e-9 {
+                continue;
+            }
+            let s = (q2.0 - q.0, q2.1 - q.1);
+            let qp = (q.0 - p.0, q.1 - p.1);
+            let denom = cross(r, s);
+            if denom.abs() < 1e-12 {
+                if cross(qp, r).abs() / rr.sqrt() < 1e-9 {
+                    for end in [q, q2] {
+                        cuts.push(((end.0 - p.0) * r.0 + (end.1 - p.1) * r.1) / rr);
+                    }
+                }
+                continue;
+            }
+            let (t, u) = (cross(qp, s) / denom, cross(qp, r) / denom);
+            if (-1e-9..=1.0 + 1e-9).contains(&u) {
+                cuts.push(t);
+            }
+        }
+        cuts.retain(|t| (0.0..=1.0).contains(t));
+        cuts.sort_by(f64::total_cmp);
+        let mut keys: Vec<(i64, i64)> = cuts.into_iter().map(|t| snap((p.0 + r.0 * t, p.1 + r.1 * t))).collect();
+        keys.dedup();
+        out.extend(keys.windows(2).map(|w| (w[0], w[1])));
+    }
+    out
+}
+
+// Non-zero winding number of `p` against closed edges.
+fn winding(edges: &[(Point, Point)], p: Point) -> i32 {
+    let mut w = 0;
+    for &(a, b) in edges {
+        let side = cross((b.0 - a.0, b.1 - a.1), (p.0 - a.0, p.1 - a.1));
+        if a.1 <= p.1 {
+            if b.1 > p.1 && side > 0.0 {
+                w += 1;
+            }
+        } else if b.1 <= p.1 && side < 0.0 {
+            w -= 1;
+        }
+    }
+    w
+}
+
+// Every piece of either outline is kept when the result is filled on exactly one side of it,
+// turned so the filled side is on its left, and the pieces are chained back into contours.
+fn boolean(a: &Drawing, b: &Drawing, op: impl Fn(bool, bool) -> bool) -> Drawing {
+    let (edges_a, edges_b) = (edges(&flatten_f64(a, DEFAULT_TOLERANCE as f64)), edges(&flatten_f64(b, DEFAULT_TOLERANCE as f64)));
+    let all: Vec<(Point, Point)> = edges_a.iter().chain(&edges_b).copied().collect();
+    let mut pieces = split_edges(&all);
+    for piece in &mut pieces {
+        if piece.1 < piece.0 {
+            *piece = (piece.1, piece.0);
+        }
+    }
+    pieces.sort();
+    pieces.dedup();
+
+    let inside = |p: Point| op(winding(&edges_a, p) != 0, winding(&edges_b, p) != 0);
+    let mut outgoing: HashMap<(i64, i64), Vec<(i64, i64)>> = HashMap::new();
+    let mut count = 0;
+    for (s, e) in pieces {
+        if s == e {
+            continue;
+        }
+        let (ps, pe) = (unsnap(s), unsnap(e));
+        let (dx, dy) = (pe.0 - ps.0, pe.1 - ps.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        let mid = ((ps.0 + pe.0) / 2.0, (ps.1 + pe.1) / 2.0);
+        let normal = (-dy / length * SIDE, dx / length * SIDE);
+        let left = inside((mid.0 + normal.0, mid.1 + normal.1));
+        let right = inside((mid.0 - normal.0, mid.1 - normal.1));
+        if left == right {
+            continue;
+        }
+        let (from, to) = if left { (s, e) } else { (e, s) };
+        outgoing.entry(from).or_default().push(to);
+        count += 1;
+    }
+
+    let mut contours = Vec::new();
+    let mut starts: Vec<(i64, i64)> = outgoing.keys().copied().collect();
+    starts.sort();
+    for start in starts {
+        while let Some(next) = outgoing.get_mut(&start).and_then(|list| list.pop()) {
+            let mut contour = vec![unsnap(start)];
+            let mut at = next;
+            let mut guard = count;
+            while at != start && guard > 0 {
+                contour.push(unsnap(at));
+                match outgoing.get_mut(&at).and_then(|list| list.pop()) {
+                    Some(following) => at = following,
+                    None => break,
+                }
+                guard -= 1;
+            }
+            let contour = simplify(contour);
+            if contour.len() >= 3 {
+                contours.push(contour.into_iter().map(|(x, y)| (x as f32, y as f32)).collect());
+            }
+        }
+    }
+    Drawing::from_contours(&contours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawing(s: &str) -> Drawing {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn boolean_operations_on_overlapping_squares() {
+        let a = drawing("m 0 0 l 100 0 100 100 0 100");
+        let b = drawing("m 50 50 l 150 50 150 150 50 150");
+
+        assert!((a.union(&b).area() - 17500.0).abs() < 0.01);
+        let cut = a.difference(&b);
+        assert!((cut.area() - 7500.0).abs() < 0.01);
+        assert_eq!(cut.flatten(DEFAULT_TOLERANCE).len(), 1);
+        assert_eq!(a.intersection(&b).as_rect(), Some(Rect { x1: 50.0, y1: 50.0, x2: 100.0, y2: 100.0 }));
+        // A hole: the inner square comes back as a second contour wound the other way.
+        let ring = a.difference(&drawing("m 25 25 l 75 25 75 75 25 75"));
+        assert_eq!(ring.flatten(DEFAULT_TOLERANCE).len(), 2);
+        assert!((ring.area() - 7500.0).abs() < 0.01);
+        assert!(a.intersection(&drawing("m 200 200 l 300 200 300 300")).commands.is_empty());
+    }
+
+    #[test]
+    fn splines_become_cubics_and_transforms_follow_ass_tags() {
+        let spline = drawing("m 0 0 s 60 0 60 60 0 60 c");
+        let cubics = spline.to_cubics();
+        assert!(cubics.commands.iter().all(|c| !matches!(c, DrawingCommand::CubicBSpline(..) | DrawingCommand::CloseBSpline)));
+        assert_eq!(cubics.commands.iter().filter(|c| matches!(c, DrawingCommand::CubicBezier(..))).count(), 4);
+        let bbox = spline.bounding_box().unwrap();
+        // Segment ends sit at 10 and 50; the curve bulges to (P0 + 23 P1 + 23 P2 + P3) / 48 between them.
+        assert!((bbox.x1 - 2.5).abs() < 0.1 && (bbox.x2 - 57.5).abs() < 0.1, "{:?}", bbox);
+
+        let overrides = [ASSOverride::Org(0.0, 0.0), ASSOverride::Frz(90.0)];
+        let transform = AssTransform::from_overrides(&overrides, (10.0, 10.0));
+        let mut square = drawing("m 10 0 l 20 0");
+        square.transform(&transform.affine());
+        assert_eq!(square.stringify(), "m 0 -10 l 0 -20");
+    }
+
+    #[test]
+    fn rect_clips_round_trip_and_scaled_vector_clips_shrink() {
+        let (mask, inverse) = clip_mask(&ASSOverride::IclipRect(100.0, 50.0, 10.0, 20.0)).unwrap();
+        assert!(inverse);
+        assert_eq!(mask.stringify(), "m 10 20 l 100 20 100 50 10 50");
+        assert!(matches!(clip_override(&mask, inverse), ASSOverride::IclipRect(10.0, 20.0, 100.0, 50.0)));
+
+        let (mask, _) = clip_mask(&ASSOverride::ClipII(2.0, "m 0 0 l 200 0 100 100".to_string())).unwrap();
+        assert_eq!(mask.stringify(), "m 0 0 l 100 0 50 50");
+        assert!(matches!(clip_override(&mask, false), ASSOverride::ClipI(ref s) if s == "m 0 0 l 100 0 50 50"));
+    }
+}
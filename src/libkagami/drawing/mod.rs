@@ -1 +1,2 @@
-pub mod parse;
\ No newline at end of file
+pub mod parse;
+pub mod geometry;
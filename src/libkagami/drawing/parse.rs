@@ -1,6 +1,7 @@
 use std::convert::Infallible;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "cmd", content = "args")]
 pub enum DrawingCommand {
     Move(f32,f32),
     MoveN(f32,f32),
@@ -70,6 +71,27 @@ impl DrawingCommand {
             DrawingCommand::CloseBSpline | DrawingCommand::Invalid => {}
         }
     }
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        match self {
+            DrawingCommand::Move(x, y)
+            | DrawingCommand::MoveN(x, y)
+            | DrawingCommand::Line(x, y)
+            | DrawingCommand::ExtendBSpline(x, y) => {
+                *x += dx;
+                *y += dy;
+            }
+            DrawingCommand::CubicBezier(x0, y0, x1, y1, x2, y2)
+            | DrawingCommand::CubicBSpline(x0, y0, x1, y1, x2, y2) => {
+                *x0 += dx;
+                *x1 += dx;
+                *x2 += dx;
+                *y0 += dy;
+                *y1 += dy;
+                *y2 += dy;
+            }
+            DrawingCommand::CloseBSpline | DrawingCommand::Invalid => {}
+        }
+    }
     pub fn stringify(&self) -> String {
         match self {
             DrawingCommand::Move(x, y) => format!("m {} {}", format_drawing_num(*x), format_drawing_num(*y)),
@@ -100,7 +122,7 @@ impl DrawingCommand {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Drawing {
     pub commands: Vec<DrawingCommand>
 }
@@ -111,6 +133,11 @@ impl Drawing {
             command.scale(sx, sy);
         }
     }
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        for command in &mut self.commands {
+            command.translate(dx, dy);
+        }
+    }
     pub fn stringify(&self) -> String {
         let mut parts = Vec::new();
         let mut index = 0usize;
@@ -0,0 +1,451 @@
+// Pre-encode font coverage. Every visible character is attributed to the face libass would draw it
+// with — the style's font, bold and italic, then any inline `\fn`, `\b`, `\i` and `\r` — and that
+// face is looked up in the font directories the encoder stages. libass quietly falls back to
+// another family (usually ArialMT) for a missing font or glyph, which otherwise only shows up in
+// the encode log. Events should come from an `adv_parsing` load.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use ab_glyph::{Font, FontRef};
+use crate::libkagami::complex::overrides::ASSOverride;
+use crate::libkagami::core::{
+    cached_normalized_font_names, collect_font_files, normalize_font_name, read_u16, read_u32, sfnt_face_offsets,
+    sfnt_names, SubstationAlpha, V4pStyle,
+};
+use crate::libkagami::tags::ASSText;
+
+// A face some text asks for. `family` keeps the script's spelling for reports; lookups go through
+// `normalize_font_name`, the same matching release font collection uses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FontRequest {
+    pub family: String,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl FontRequest {
+    pub(crate) fn new(family: &str, bold: bool, italic: bool) -> Self {
+        // `@Family` is the vertical-writing variant of the same face.
+        let family = family.trim();
+        let family = family.strip_prefix('@').unwrap_or(family).trim();
+        Self { family: family.to_string(), bold, italic }
+    }
+
+    fn key(&self) -> (String, bool, bool) {
+        (normalize_font_name(&self.family), self.bold, self.italic)
+    }
+}
+
+impl std::fmt::Display for FontRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.family)?;
+        match (self.bold, self.italic) {
+            (true, true) => write!(f, " Bold Italic"),
+            (true, false) => write!(f, " Bold"),
+            (false, true) => write!(f, " Italic"),
+            (false, false) => Ok(()),
+        }
+    }
+}
+
+// Line numbers are 1-based positions in `SubstationAlpha::events`, as in the lint report.
+#[derive(Clone, Debug)]
+pub struct MissingFont {
+    pub family: String,
+    pub lines: Vec<usize>,
+}
+
+// The family exists but not in the requested weight/slant, so libass synthesizes it.
+#[derive(Clone, Debug)]
+pub struct FauxStyle {
+    pub request: FontRequest,
+    pub lines: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MissingGlyphs {
+    pub line: usize,
+    pub font: FontRequest,
+    pub chars: Vec<char>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FontReport {
+    pub faces_used: usize,
+    pub missing_fonts: Vec<MissingFont>,
+    pub faux_styles: Vec<FauxStyle>,
+    pub missing_glyphs: Vec<MissingGlyphs>,
+}
+
+impl FontReport {
+    // Faux bold/italic still draws the right glyphs from the right family, so it only warns.
+    pub fn passed(&self) -> bool {
+        self.missing_fonts.is_empty() && self.missing_glyphs.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} face(s) used: {} missing font(s), {} faux bold/italic, {} line(s) with missing glyphs",
+            self.faces_used,
+            self.missing_fonts.len(),
+            self.faux_styles.len(),
+            self.missing_glyphs.len()
+        )
+    }
+
+    // One human-readable line per problem, failures first.
+    pub fn messages(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for missing in &self.missing_fonts {
+            out.push(format!("missing font `{}` ({})", missing.family, format_lines(&missing.lines)));
+        }
+        for glyphs in &self.missing_glyphs {
+            let chars: Vec<String> = glyphs.chars.iter()
+                .map(|c| format!("`{}` (U+{:04X})", c, *c as u32))
+                .collect();
+            out.push(format!("line {}: `{}` has no glyph for {}", glyphs.line, glyphs.font, chars.join(", ")));
+        }
+        for faux in &self.faux_styles {
+            out.push(format!(
+                "no `{}` face, libass will fake it from the regular one ({})",
+                faux.request,
+                format_lines(&faux.lines)
+            ));
+        }
+        out
+    }
+}
+
+fn format_lines(lines: &[usize]) -> String {
+    const SHOWN: usize = 8;
+    let mut listed: Vec<String> = lines.iter().take(SHOWN).map(|n| n.to_string()).collect();
+    if lines.len() > SHOWN {
+        listed.push(format!("+{} more", lines.len() - SHOWN));
+    }
+    let noun = if lines.len() == 1 { "line" } else { "lines" };
+    format!("{} {}", noun, listed.join(", "))
+}
+
+// One font file (or [Fonts] attachment); `path` is None for fonts that only exist in memory.
+pub(crate) struct FontSource {
+    pub(crate) name: String,
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) data: Vec<u8>,
+}
+
+pub(crate) struct Face {
+    names: Vec<String>,
+    bold: bool,
+    italic: bool,
+    pub(crate) source: Arc<FontSource>,
+    index: u32,
+}
+
+impl Face {
+    pub(crate) fn font(&self) -> Option<FontRef<'_>> {
+        FontRef::try_from_slice_and_index(&self.source.data, self.index).ok()
+    }
+}
+
+#[derive(Default)]
+pub struct FontLibrary {
+    faces: Vec<Face>,
+}
+
+impl FontLibrary {
+    // Only files whose cached names include one of the normalized `families` are read, so a large
+    // fontconfig directory costs a walk and the name cache, not a parse of every font in it.
+    pub fn load(roots: &[PathBuf], families: &BTreeSet<String>) -> Self {
+        let mut library = Self::default();
+        for root in roots {
+            let mut files = Vec::new();
+            collect_font_files(root, &mut files);
+            for path in files {
+                if cached_normalized_font_names(&path).iter().any(|name| families.contains(name))
+                    && let Ok(data) = std::fs::read(&path)
+                {
+                    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                    library.add_source(FontSource { name, path: Some(path), data });
+                }
+            }
+        }
+        library
+    }
+
+    // Adds every face of an in-memory TTF/OTF/TTC, e.g. a [Fonts] attachment.
+    pub fn add(&mut self, name: &str, data: Vec<u8>) {
+        self.add_source(FontSource { name: name.to_string(), path: None, data });
+    }
+
+    // Faces ab_glyph can't parse are skipped, libass would not get anything out of them either.
+    fn add_source(&mut self, source: FontSource) {
+        let source = Arc::new(source);
+        let Ok(offsets) = sfnt_face_offsets(&source.data) else {
+            return;
+        };
+        for (index, offset) in offsets.into_iter().enumerate() {
+            let index = index as u32;
+            if FontRef::try_from_slice_and_index(&source.data, index).is_err() {
+                continue;
+            }
+            let names = sfnt_names(&source.data, offset)
+                .unwrap_or_default()
+                .iter()
+                .map(|name| normalize_font_name(name))
+                .filter(|name| !name.is_empty())
+                .collect();
+            let (bold, italic) = face_style(&source.data, offset);
+            self.faces.push(Face { names, bold, italic, source: source.clone(), index });
+        }
+    }
+
+    pub fn check(&self, sub: &SubstationAlpha) -> FontReport {
+        report(&font_usage(sub), self)
+    }
+
+    // Closest face of the family by weight and slant, the way libass picks one.
+    pub(crate) fn find(&self, request: &FontRequest) -> Option<&Face> {
+        let (key, _, _) = request.key();
+        self.faces
+            .iter()
+            .filter(|face| face.names.contains(&key))
+            .min_by_key(|face| (face.bold != request.bold) as u8 + (face.italic != request.italic) as u8)
+    }
+}
+
+// Checks `sub` against the fonts under `roots` plus the ones attached in its [Fonts] section,
+// which libass loads ahead of anything on disk.
+pub fn check_fonts(sub: &SubstationAlpha, roots: &[PathBuf]) -> FontReport {
+    let usage = font_usage(sub);
+    report(&usage, &script_library(sub, &usage, roots))
+}
+
+pub(crate) fn script_library(sub: &SubstationAlpha, usage: &FontUsage, roots: &[PathBuf]) -> FontLibrary {
+    let families: BTreeSet<String> = usage.keys().map(|(family, _, _)| family.clone()).collect();
+    let mut library = FontLibrary::load(roots, &families);
+    for font in &sub.fonts {
+        library.add(&font.name, font.data.clone());
+    }
+    library
+}
+
+pub fn check_ass_fonts(bytes: &[u8], roots: &[PathBuf]) -> Result<FontReport, String> {
+    let (sub, _) = SubstationAlpha::from_bytes(bytes, true).map_err(|e| e.to_string())?;
+    Ok(check_fonts(&sub, roots))
+}
+
+// Bold from usWeightClass (libass only emboldens a face more than 150 below the 700 it asks
+// for) or fsSelection, italic/oblique from fsSelection; head.macStyle for fonts without OS/2.
+fn face_style(data: &[u8], base: usize) -> (bool, bool) {
+    if let Some(os2) = sfnt_table(data, base, b"OS/2")
+        && let (Some(weight), Some(selection)) = (read_u16(data, os2 + 4), read_u16(data, os2 + 62))
+    {
+        return (weight >= 550 || selection & 0x20 != 0, selection & 0x201 != 0);
+    }
+    if let Some(head) = sfnt_table(data, base, b"head")
+        && let Some(mac_style) = read_u16(data, head + 44)
+    {
+        return (mac_style & 1 != 0, mac_style & 2 != 0);
+    }
+    (false, false)
+}
+
+fn sfnt_table(data: &[u8], base: usize, tag: &[u8; 4]) -> Option<usize> {
+    let num_tables = read_u16(data, base + 4)? as usize;
+    (0..num_tables)
+        .map(|i| base + 12 + i * 16)
+        .find(|rec| data.get(*rec..*rec + 4) == Some(tag.as_slice()))
+        .and_then(|rec| read_u32(data, rec + 8))
+        .map(|offset| offset as usize)
+}
+
+pub(crate) struct Usage {
+    pub(crate) request: FontRequest,
+    pub(crate) chars: BTreeMap<usize, BTreeSet<char>>,
+}
+
+// Keyed by (normalized family, bold, italic).
+pub(crate) type FontUsage = BTreeMap<(String, bool, bool), Usage>;
+
+#[derive(Clone)]
+struct FaceState {
+    font: String,
+    bold: bool,
+    italic: bool,
+}
+
+impl FaceState {
+    fn of(style: &V4pStyle) -> Self {
+        Self { font: style.fontname.clone(), bold: style.bold, italic: style.italic }
+    }
+}
+
+pub(crate) fn font_usage(sub: &SubstationAlpha) -> FontUsage {
+    let mut usage = FontUsage::new();
+    for (i, event) in sub.events.iter().enumerate() {
+        let Some(style) = find_style(sub, &event.style) else {
+            continue;
+        };
+        let line_style = FaceState::of(style);
+        let mut reset = line_style.clone();
+        let mut state = line_style.clone();
+        for item in &event.text.data {
+            match item {
+                ASSText::Override(ASSOverride::Fn(name)) => {
+                    let name = name.trim();
+                    state.font = if name.is_empty() || name == "0" { reset.font.clone() } else { name.to_string() };
+                }
+                ASSText::Override(ASSOverride::Bold(bold)) => state.bold = *bold,
+                ASSText::Override(ASSOverride::Italic(italic)) => state.italic = *italic,
+                ASSText::Override(ASSOverride::R(name)) => {
+                    reset = name
+                        .as_deref()
+                        .and_then(|name| sub.v4p_styles.iter().find(|style| style.name == name))
+                        .map(FaceState::of)
+                        .unwrap_or_else(|| line_style.clone());
+                    state = reset.clone();
+                }
+                ASSText::RawText(text) => {
+                    let request = FontRequest::new(&state.font, state.bold, state.italic);
+                    let chars: BTreeSet<char> = visible_chars(text).collect();
+                    if chars.is_empty() || request.family.is_empty() {
+                        continue;
+                    }
+                    usage
+                        .entry(request.key())
+                        .or_insert_with(|| Usage { request, chars: BTreeMap::new() })
+                        .chars
+                        .entry(i + 1)
+                        .or_default()
+                        .extend(chars);
+                }
+                _ => {}
+            }
+        }
+    }
+    usage
+}
+
+// libass falls back to `Default`, then to the first style, for a style name it doesn't know.
+pub(crate) fn find_style<'a>(sub: &'a SubstationAlpha, name: &str) -> Option<&'a V4pStyle> {
+    let name = name.trim_start_matches('*');
+    sub.v4p_styles
+        .iter()
+        .find(|style| style.name == name)
+        .or_else(|| sub.v4p_styles.iter().find(|style| style.name == "Default"))
+        .or_else(|| sub.v4p_styles.first())
+}
+
+// Text with override blocks (left in place when a line falls back to raw parsing) and the `\N`,
+// `\n`, `\h` escapes removed; whitespace needs no glyph.
+fn visible_chars(text: &str) -> impl Iterator<Item = char> + '_ {
+    let mut depth = 0usize;
+    let mut escaped = false;
+    text.chars().filter(move |c| {
+        if escaped {
+            escaped = false;
+            if matches!(c, 'N' | 'n' | 'h') {
+                return false;
+            }
+        }
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => {
+                depth -= 1;
+                return false;
+            }
+            '\\' if depth == 0 => escaped = true,
+            _ => {}
+        }
+        depth == 0 && !escaped && !c.is_whitespace() && !c.is_control()
+    })
+}
+
+fn report(usage: &FontUsage, library: &FontLibrary) -> FontReport {
+    let mut report = FontReport { faces_used: usage.len(), ..Default::default() };
+    let mut missing: BTreeMap<String, MissingFont> = BTreeMap::new();
+    for ((family, _, _), entry) in usage {
+        let lines: Vec<usize> = entry.chars.keys().copied().collect();
+        let Some(face) = library.find(&entry.request) else {
+            missing
+                .entry(family.clone())
+                .or_insert_with(|| MissingFont { family: entry.request.family.clone(), lines: Vec::new() })
+                .lines
+                .extend(lines);
+            continue;
+        };
+        if (entry.request.bold && !face.bold) || (entry.request.italic && !face.italic) {
+            report.faux_styles.push(FauxStyle { request: entry.request.clone(), lines });
+        }
+        let Ok(font) = FontRef::try_from_slice_and_index(&face.source.data, face.index) else {
+            continue;
+        };
+        for (line, chars) in &entry.chars {
+            let absent: Vec<char> = chars.iter().copied().filter(|c| font.glyph_id(*c).0 == 0).collect();
+            if !absent.is_empty() {
+                report.missing_glyphs.push(MissingGlyphs { line: *line, font: entry.request.clone(), chars: absent });
+            }
+        }
+    }
+    for mut font in missing.into_values() {
+        font.lines.sort_unstable();
+        font.lines.dedup();
+        report.missing_fonts.push(font);
+    }
+    report.missing_glyphs.sort_by_key(|glyphs| glyphs.line);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\n\
+        Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+        Style: Default,Liberation Mono,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,10,10,10,1\n\
+        Style: Title,Gandhi Sans,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,-1,0,0,0,100,100,0,0,1,2,0,8,10,10,10,1\n\n\
+        [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n";
+
+    fn check(events: &str) -> FontReport {
+        let (sub, _) = SubstationAlpha::parse(&format!("{}{}", HEADER, events), true);
+        let mut library = FontLibrary::default();
+        library.add("LiberationMono-Regular.ttf", include_bytes!("../lib/image/testdata/LiberationMono-Regular.ttf").to_vec());
+        library.check(&sub)
+    }
+
+    #[test]
+    fn effective_font_follows_fn_bold_italic_and_reset() {
+        let report = check(
+            "Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Çağrı şimdi İzmir'de, ı\n\
+             Dialogue: 0,0:00:02.00,0:00:03.00,Default,,0,0,0,,{\\b1}kalın {\\r}düz {\\i1}eğik\n\
+             Dialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,{\\fnGandhi Sans}başlık{\\r}geri\n\
+             Dialogue: 0,0:00:04.00,0:00:05.00,Title,,0,0,0,,{\\rDefault}normal\\Nsatır{\\p1}m 0 0 l 10 10{\\p0}\n",
+        );
+
+        assert_eq!(report.missing_fonts.len(), 1);
+        assert_eq!(report.missing_fonts[0].family, "Gandhi Sans");
+        assert_eq!(report.missing_fonts[0].lines, vec![3]);
+        let faux: Vec<String> = report.faux_styles.iter().map(|f| f.request.to_string()).collect();
+        assert_eq!(faux, vec!["Liberation Mono Italic", "Liberation Mono Bold"]);
+        assert!(report.missing_glyphs.is_empty());
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn glyphs_missing_from_the_resolved_face_are_listed_per_line() {
+        let report = check(
+            "Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,fine\n\
+             Dialogue: 0,0:00:02.00,0:00:03.00,Default,,0,0,0,,今日は ok\\h★\n",
+        );
+
+        assert!(report.missing_fonts.is_empty());
+        assert_eq!(report.missing_glyphs.len(), 1);
+        assert_eq!(report.missing_glyphs[0].line, 2);
+        assert_eq!(report.missing_glyphs[0].chars, vec!['★', 'は', '今', '日']);
+        assert_eq!(
+            report.messages()[0],
+            "line 2: `Liberation Mono` has no glyph for `★` (U+2605), `は` (U+306F), `今` (U+4ECA), `日` (U+65E5)"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+// Release font collection: the files a script's text is actually drawn with, resolved the same way
+// as the coverage check in `fontcheck`. A `\b1` line pulls in the bold face when there is one, and
+// families that never draw any text (unused styles, drawing-only lines) are left out.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use crate::libkagami::core::SubstationAlpha;
+use crate::libkagami::fontcheck::{font_usage, script_library};
+use crate::libkagami::sections::EmbeddedFile;
+
+// `path` is None for a font that came from the script's own [Fonts] section; `faces` are the
+// requests it serves, e.g. `Gandhi Sans Bold`.
+#[derive(Clone, Debug)]
+pub struct CollectedFont {
+    pub file_name: String,
+    pub path: Option<PathBuf>,
+    pub data: Vec<u8>,
+    pub faces: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FontCollection {
+    pub fonts: Vec<CollectedFont>,
+    pub missing: Vec<String>,
+}
+
+pub fn collect_fonts(sub: &SubstationAlpha, roots: &[PathBuf]) -> FontCollection {
+    let usage = font_usage(sub);
+    let library = script_library(sub, &usage, roots);
+    let mut collection = FontCollection::default();
+    let mut files: BTreeMap<String, CollectedFont> = BTreeMap::new();
+    for entry in usage.values() {
+        let Some(face) = library.find(&entry.request) else {
+            collection.missing.push(entry.request.to_string());
+            continue;
+        };
+        let source = &face.source;
+        let key = match &source.path {
+            Some(path) => path.display().to_string(),
+            None => format!("[Fonts] {}", source.name),
+        };
+        files
+            .entry(key)
+            .or_insert_with(|| CollectedFont {
+                file_name: source.name.clone(),
+                path: source.path.clone(),
+                data: source.data.clone(),
+                faces: Vec::new(),
+            })
+            .faces
+            .push(entry.request.to_string());
+    }
+    collection.fonts = files.into_values().collect();
+    unique_file_names(&mut collection.fonts);
+    collection
+}
+
+impl FontCollection {
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty()
+    }
+
+    // Attaches every collected file the script does not carry yet, named `<stem>_0.<ext>` the way
+    // Aegisub's attachment dialog names fonts. Returns how many were added.
+    pub fn embed_into(&self, sub: &mut SubstationAlpha) -> usize {
+        let mut added = 0;
+        for font in self.fonts.iter().filter(|font| font.path.is_some()) {
+            let name = attachment_name(&font.file_name);
+            if sub.fonts.iter().any(|attached| attached.name.eq_ignore_ascii_case(&name)) {
+                continue;
+            }
+            sub.fonts.push(EmbeddedFile { name, data: font.data.clone() });
+            added += 1;
+        }
+        added
+    }
+}
+
+fn attachment_name(file_name: &str) -> String {
+    match file_name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}_0.{}", stem, ext.to_ascii_lowercase()),
+        None => format!("{}_0.ttf", file_name),
+    }
+}
+
+// The server and global directories can both hold a `font.ttf`; zip entries need distinct names.
+fn unique_file_names(fonts: &mut [CollectedFont]) {
+    let mut taken: BTreeSet<String> = BTreeSet::new();
+    for font in fonts {
+        let mut name = font.file_name.clone();
+        let mut n = 2;
+        while !taken.insert(name.to_ascii_lowercase()) {
+            name = match font.file_name.rsplit_once('.') {
+                Some((stem, ext)) => format!("{}-{}.{}", stem, n, ext),
+                None => format!("{}-{}", font.file_name, n),
+            };
+            n += 1;
+        }
+        font.file_name = name;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_only_resolved_faces_and_embeds_them_once() {
+        let dir = std::env::temp_dir().join(format!("pandora_fontcollect_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("LiberationMono-Regular.ttf"),
+            include_bytes!("../lib/image/testdata/LiberationMono-Regular.ttf"),
+        )
+        .unwrap();
+        let script = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+            Style: Default,Liberation Mono,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,10,10,10,1\n\
+            Style: Unused,Gandhi Sans,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,10,10,10,1\n\n\
+            [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,düz {\\b1}kalın\n\
+            Dialogue: 0,0:00:02.00,0:00:03.00,Default,,0,0,0,,{\\fnNope}yok\n";
+        let (mut sub, _) = SubstationAlpha::parse(script, true);
+
+        let collection = collect_fonts(&sub, std::slice::from_ref(&dir));
+
+        assert_eq!(collection.fonts.len(), 1);
+        assert_eq!(collection.fonts[0].file_name, "LiberationMono-Regular.ttf");
+        assert_eq!(collection.fonts[0].faces, vec!["Liberation Mono", "Liberation Mono Bold"]);
+        assert_eq!(collection.missing, vec!["Nope"]);
+        assert_eq!(collection.embed_into(&mut sub), 1);
+        assert_eq!(collection.embed_into(&mut sub), 0);
+        assert!(sub.stringify().contains("[Fonts]\nfontname: LiberationMono-Regular_0.ttf\n"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
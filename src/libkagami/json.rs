@@ -0,0 +1,91 @@
+// The versioned JSON form of a script, for the web consoles and outside tooling. Every section is
+// kept, override blocks and drawings come through as parsed tags and commands, and times and
+// colours are strings in ASS notation. `from_json` followed by `stringify` writes the same file
+// that loading the original and stringifying it does. Bump the version when the shape changes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::libkagami::core::SubstationAlpha;
+use crate::libkagami::tags::final_overrides;
+
+pub const SUBS_SCHEMA_VERSION: u16 = 1;
+
+#[derive(Serialize)]
+struct ScriptJsonRef<'a> {
+    schema_version: u16,
+    script: &'a SubstationAlpha,
+}
+
+// `schema_version` is checked on the raw value before this is read.
+#[derive(Deserialize)]
+struct ScriptJson {
+    script: SubstationAlpha,
+}
+
+impl SubstationAlpha {
+    pub fn to_json(&self) -> Result<serde_json::Value, String> {
+        serde_json::to_value(ScriptJsonRef { schema_version: SUBS_SCHEMA_VERSION, script: self })
+            .map_err(|e| format!("could not serialise script: {}", e))
+    }
+
+    pub fn from_json(value: serde_json::Value) -> Result<Self, String> {
+        let version = value.get("schema_version").and_then(|v| v.as_u64());
+        if version != Some(SUBS_SCHEMA_VERSION as u64) {
+            return Err(match version {
+                Some(v) => format!("unsupported script schema version {} (expected {})", v, SUBS_SCHEMA_VERSION),
+                None => "script JSON has no schema_version".to_string(),
+            });
+        }
+        let ScriptJson { mut script } =
+            serde_json::from_value(value).map_err(|e| format!("invalid script JSON: {}", e))?;
+        // Seeded from the line's style, as loading a script seeds it.
+        for event in script.events.iter_mut().chain(script.comments.iter_mut()) {
+            let start = script.v4p_styles.iter()
+                .find(|style| style.name == event.style)
+                .map(|style| style.to_overrides())
+                .unwrap_or_default();
+            event.text.current_overrides = final_overrides(&event.text.data, &start);
+        }
+        Ok(script)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "[Script Info]\nTitle: json\nScriptType: v4.00+\nWrapStyle: 0\nScaledBorderAndShadow: yes\nPlayResX: 1920\nPlayResY: 1080\n\n\
+        [V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+        Style: Default,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,-1,0,0,0,100,100,0,0,1,3.75,0,2,50,50,38,1\n\n\
+        [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+        Dialogue: 0,0:00:01.00,0:00:03.50,Default,Ai,0,0,0,,{\\an8\\pos(960,80)\\t(0,500,\\fscx120\\1c&H0000FF&)}Hello\\Nworld\n\
+        Dialogue: 1,0:00:02.00,0:00:04.00,Default,,0,0,0,,{\\p1\\clip(0,0,500,500)}m 0 0 l 100 0 100 100 b 50 150 0 120 0 100{\\p0}\n\
+        Comment: 0,0:00:00.00,0:00:00.00,Default,,0,0,0,,note\n\n\
+        [Fonts]\nfontname: sign_0.ttf\nAAAAAAAAAAAAAAAAAAAAAAA\n";
+
+    #[test]
+    fn round_trips_to_the_same_script() {
+        let (sub, _) = SubstationAlpha::parse(SCRIPT, true);
+        let json = sub.to_json().unwrap();
+
+        assert_eq!(json["schema_version"], SUBS_SCHEMA_VERSION);
+        assert_eq!(json["script"]["events"][0]["start"], "0:00:01.00");
+        assert_eq!(json["script"]["v4p_styles"][0]["colours"][3], "&H80000000");
+        assert_eq!(json["script"]["events"][0]["text"]["data"][0]["value"]["tag"], "An");
+        let text = serde_json::to_string(&json).unwrap();
+        let back = SubstationAlpha::from_json(serde_json::from_str(&text).unwrap()).unwrap();
+        assert_eq!(back.stringify(), sub.stringify());
+        assert!(back.events[0].text.current_overrides == sub.events[0].text.current_overrides);
+    }
+
+    #[test]
+    fn rejects_other_schema_versions() {
+        let (sub, _) = SubstationAlpha::parse(SCRIPT, true);
+        let mut json = sub.to_json().unwrap();
+        json["schema_version"] = serde_json::json!(SUBS_SCHEMA_VERSION + 1);
+
+        let error = SubstationAlpha::from_json(json).err().unwrap();
+        assert!(error.contains("unsupported script schema version"), "{}", error);
+        assert!(SubstationAlpha::from_json(serde_json::json!({})).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+// Karaoke timing: a line split into syllables at its `\k`, `\kf`/`\K` and `\ko` tags, with the
+// furigana conventions Aegisub's karaoke tools use. `漢|かん` puts `かん` over `漢`, and a syllable
+// written `#|じ` is another timed part of the furigana over the syllable before it.
+
+use crate::libkagami::complex::overrides::ASSOverride;
+use crate::libkagami::tags::stringify::stringify_override;
+use crate::libkagami::tags::{ASSLine, ASSText};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KaraokeKind {
+    Fill,    // \k
+    Sweep,   // \kf, \K
+    Outline, // \ko
+}
+
+// A timed part of a syllable's furigana; times are line-relative like the syllable's.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Furigana {
+    pub text: String,
+    pub start_ms: u32,
+    pub duration_ms: u32,
+}
+
+// `start_ms` is relative to the line start. Index 0 is text written before the first karaoke tag,
+// and only exists when there is some; timed syllables count from 1 as in Aegisub. `tags` holds the
+// other override tags of the syllable's blocks, as written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Syllable {
+    pub index: usize,
+    pub kind: KaraokeKind,
+    pub start_ms: u32,
+    pub duration_ms: u32,
+    pub text: String,
+    pub tags: String,
+    pub furigana: Vec<Furigana>,
+}
+
+impl Syllable {
+    pub fn end_ms(&self) -> u32 {
+        self.start_ms + self.duration_ms
+    }
+
+    // The `\k` value, in centiseconds.
+    pub fn kdur(&self) -> u32 {
+        self.duration_ms / 10
+    }
+
+    pub fn stripped(&self) -> &str {
+        self.text.trim()
+    }
+
+    pub fn prespace(&self) -> &str {
+        &self.text[..self.text.len() - self.text.trim_start().len()]
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.stripped().is_empty()
+    }
+}
+
+pub fn is_karaoke(line: &ASSLine) -> bool {
+    line.data.iter().any(|item| matches!(item, ASSText::Override(ov) if karaoke_tag(ov).is_some()))
+}
+
+fn karaoke_tag(ov: &ASSOverride) -> Option<(KaraokeKind, u32)> {
+    match ov {
+        ASSOverride::K(cs) => Some((KaraokeKind::Fill, *cs)),
+        ASSOverride::Kf(cs) | ASSOverride::KSweep(cs) => Some((KaraokeKind::Sweep, *cs)),
+        ASSOverride::Ko(cs) => Some((KaraokeKind::Outline, *cs)),
+        _ => None,
+    }
+}
+
+// Splits an adv-parsed line into syllables. `\kt` moves the start of the syllables after it, as it
+// does in libass.
+pub fn syllables(line: &ASSLine) -> Vec<Syllable> {
+    let mut out: Vec<Syllable> = Vec::new();
+    let mut current = Syllable {
+        index: 0,
+        kind: KaraokeKind::Fill,
+        start_ms: 0,
+        duration_ms: 0,
+        text: String::new(),
+        tags: String::new(),
+        furigana: Vec::new(),
+    };
+    let mut cursor = 0u32;
+    for item in &line.data {
+        match item {
+            ASSText::Override(ov) => {
+                if let Some((kind, cs)) = karaoke_tag(ov) {
+                    let next = Syllable {
+                        index: current.index + 1,
+                        kind,
+                        start_ms: cursor,
+                        duration_ms: cs * 10,
+                        text: String::new(),
+                        tags: String::new(),
+                        furigana: Vec::new(),
+                    };
+                    let done = std::mem::replace(&mut current, next);
+                    if done.index > 0 || !done.text.is_empty() {
+                        out.push(done);
+                    }
+                    cursor += cs * 10;
+                } else if let ASSOverride::Kt(cs) = ov {
+                    cursor = cs * 10;
+                } else if !matches!(ov, ASSOverride::BlockText(_)) {
+                    current.tags.push('\\');
+                    current.tags.push_str(&stringify_override(ov));
+                }
+            }
+            ASSText::RawText(text) => current.text.push_str(text),
+            ASSText::Drawing(drawing) => current.text.push_str(&drawing.stringify()),
+        }
+    }
+    if current.index > 0 || !current.text.is_empty() {
+        out.push(current);
+    }
+    split_furigana(out)
+}
+
+fn split_furigana(syllables: Vec<Syllable>) -> Vec<Syllable> {
+    let mut out: Vec<Syllable> = Vec::new();
+    for mut syl in syllables {
+        let furigana = match syl.text.split_once('|') {
+            Some((base, furi)) => {
+                let furi = furi.to_string();
+                syl.text = base.to_string();
+                Some(furi)
+            }
+            None => None,
+        };
+        if syl.text.trim() == "#"
+            && let Some(previous) = out.last_mut()
+        {
+            previous.duration_ms = syl.end_ms().saturating_sub(previous.start_ms);
+            if let Some(text) = furigana {
+                previous.furigana.push(Furigana { text, start_ms: syl.start_ms, duration_ms: syl.duration_ms });
+            }
+            continue;
+        }
+        if let Some(text) = furigana {
+            syl.furigana.push(Furigana { text, start_ms: syl.start_ms, duration_ms: syl.duration_ms });
+        }
+        out.push(syl);
+    }
+    let lead = out.first().is_some_and(|syl| syl.index == 0) as usize;
+    for (i, syl) in out.iter_mut().enumerate() {
+        syl.index = i + 1 - lead;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_timed_syllables_and_folds_continued_furigana() {
+        let line = ASSLine::from_str_store(
+            "{\\be1}♪ {\\k20}ka{\\kf35\\1c&H0000FF&}ze {\\ko15}漢|かん{\\k10}#|じ",
+            vec![],
+        );
+        let syls = syllables(&line);
+
+        assert!(is_karaoke(&line));
+        assert_eq!(syls.len(), 4);
+        assert_eq!((syls[0].index, syls[0].text.as_str(), syls[0].duration_ms), (0, "♪ ", 0));
+        assert_eq!((syls[1].index, syls[1].start_ms, syls[1].kdur()), (1, 0, 20));
+        assert_eq!((syls[2].kind, syls[2].start_ms, syls[2].stripped()), (KaraokeKind::Sweep, 200, "ze"));
+        assert_eq!(syls[2].tags, "\\c&H0000FF&");
+        let kanji = &syls[3];
+        assert_eq!((kanji.kind, kanji.text.as_str(), kanji.start_ms, kanji.end_ms()), (KaraokeKind::Outline, "漢", 550, 800));
+        assert_eq!(
+            kanji.furigana,
+            vec![
+                Furigana { text: "かん".to_string(), start_ms: 550, duration_ms: 150 },
+                Furigana { text: "じ".to_string(), start_ms: 700, duration_ms: 100 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,415 @@
+// QC rules over a parsed script. Every rule works on the structured `ASSLine` data, so events
+// should come from an `adv_parsing` load; a raw-text load only gets the timing and style rules.
+
+use std::collections::{HashMap, HashSet};
+use crate::libkagami::complex::overrides::ASSOverride;
+use crate::libkagami::core::{Event, SubstationAlpha};
+use crate::libkagami::tags::ASSText;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl LintSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LintSeverity::Error => "error",
+            LintSeverity::Warning => "warning",
+            LintSeverity::Info => "info",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintRule {
+    Overlap,
+    Cps,
+    MissingStyle,
+    UnusedStyle,
+    BadDuration,
+    OutOfFrame,
+    TransformPastEnd,
+    DuplicateLine,
+}
+
+impl LintRule {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LintRule::Overlap => "overlap",
+            LintRule::Cps => "cps",
+            LintRule::MissingStyle => "missing-style",
+            LintRule::UnusedStyle => "unused-style",
+            LintRule::BadDuration => "bad-duration",
+            LintRule::OutOfFrame => "out-of-frame",
+            LintRule::TransformPastEnd => "transform-past-end",
+            LintRule::DuplicateLine => "duplicate-line",
+        }
+    }
+
+    pub fn severity(&self) -> LintSeverity {
+        match self {
+            LintRule::MissingStyle | LintRule::BadDuration => LintSeverity::Error,
+            LintRule::UnusedStyle => LintSeverity::Info,
+            _ => LintSeverity::Warning,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LintOptions {
+    pub max_cps: f32,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self { max_cps: DEFAULT_MAX_CPS }
+    }
+}
+
+pub const DEFAULT_MAX_CPS: f32 = 25.0;
+
+// `event` is the 1-based position in `SubstationAlpha::events`, the same numbering pnass uses for
+// its line-length warnings; findings about the script as a whole have none.
+#[derive(Clone, Debug)]
+pub struct LintFinding {
+    pub rule: LintRule,
+    pub event: Option<usize>,
+    pub message: String,
+}
+
+impl LintFinding {
+    fn new(rule: LintRule, event: Option<usize>, message: String) -> Self {
+        Self { rule, event, message }
+    }
+
+    pub fn severity(&self) -> LintSeverity {
+        self.rule.severity()
+    }
+}
+
+impl std::fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: ", self.severity().as_str(), self.rule.code())?;
+        if let Some(event) = self.event {
+            write!(f, "event {}: ", event)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+pub fn lint(sub: &SubstationAlpha, options: &LintOptions) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    check_styles(sub, &mut findings);
+    for (i, event) in sub.events.iter().enumerate() {
+        check_duration(i + 1, event, &mut findings);
+        check_cps(i + 1, event, options, &mut findings);
+        check_frame(i + 1, event, sub, &mut findings);
+        check_transforms(i + 1, event, &mut findings);
+    }
+    check_duplicates(sub, &mut findings);
+    check_overlaps(sub, &mut findings);
+    findings.sort_by_key(|finding| finding.event.unwrap_or(usize::MAX));
+    findings
+}
+
+fn check_styles(sub: &SubstationAlpha, findings: &mut Vec<LintFinding>) {
+    let defined: HashSet<&str> = sub.v4p_styles.iter().map(|style| style.name.as_str()).collect();
+    let mut used: HashSet<&str> = HashSet::new();
+    for (i, event) in sub.events.iter().enumerate() {
+        used.insert(event.style.as_str());
+        if !defined.contains(event.style.as_str()) {
+            findings.push(LintFinding::new(
+                LintRule::MissingStyle,
+                Some(i + 1),
+                format!("style `{}` is not defined", event.style),
+            ));
+        }
+        for item in &event.text.data {
+            if let ASSText::Override(ASSOverride::R(Some(name))) = item {
+                used.insert(name.as_str());
+                if !defined.contains(name.as_str()) {
+                    findings.push(LintFinding::new(
+                        LintRule::MissingStyle,
+                        Some(i + 1),
+                        format!("\\r references undefined style `{}`", name),
+                    ));
+                }
+            }
+        }
+    }
+    for style in &sub.v4p_styles {
+        if !used.contains(style.name.as_str()) {
+            findings.push(LintFinding::new(
+                LintRule::UnusedStyle,
+                None,
+                format!("style `{}` is never used", style.name),
+            ));
+        }
+    }
+}
+
+fn check_duration(number: usize, event: &Event, findings: &mut Vec<LintFinding>) {
+    let (start, end) = (event.start.total_centiseconds(), event.end.total_centiseconds());
+    if end == start {
+        findings.push(LintFinding::new(
+            LintRule::BadDuration,
+            Some(number),
+            format!("zero duration at {}", event.start),
+        ));
+    } else if end < start {
+        findings.push(LintFinding::new(
+            LintRule::BadDuration,
+            Some(number),
+            format!("ends at {} before it starts at {}", event.end, event.start),
+        ));
+    }
+}
+
+fn check_cps(number: usize, event: &Event, options: &LintOptions, findings: &mut Vec<LintFinding>) {
+    if !is_dialogue(event) {
+        return;
+    }
+    let duration = duration_centiseconds(event);
+    let chars = visible_char_count(event);
+    if duration == 0 || chars == 0 {
+        return;
+    }
+    let cps = chars as f32 * 100.0 / duration as f32;
+    if cps > options.max_cps {
+        findings.push(LintFinding::new(
+            LintRule::Cps,
+            Some(number),
+            format!(
+                "{} characters in {:.2}s is {:.1} CPS (limit {})",
+                chars,
+                duration as f32 / 100.0,
+                cps,
+                options.max_cps
+            ),
+        ));
+    }
+}
+
+fn check_frame(number: usize, event: &Event, sub: &SubstationAlpha, findings: &mut Vec<LintFinding>) {
+    let (width, height) = (sub.script_info.playresx as f32, sub.script_info.playresy as f32);
+    if width <= 0.0 || height <= 0.0 {
+        return;
+    }
+    let outside = |x: f32, y: f32| x < 0.0 || y < 0.0 || x > width || y > height;
+    for item in &event.text.data {
+        let (tag, points) = match item {
+            ASSText::Override(ASSOverride::Pos(x, y)) => ("\\pos", vec![(*x, *y)]),
+            ASSText::Override(ASSOverride::MoveI(x1, y1, x2, y2))
+            | ASSText::Override(ASSOverride::MoveII(x1, y1, x2, y2, _, _)) => {
+                ("\\move", vec![(*x1, *y1), (*x2, *y2)])
+            }
+            _ => continue,
+        };
+        if let Some((x, y)) = points.into_iter().find(|(x, y)| outside(*x, *y)) {
+            findings.push(LintFinding::new(
+                LintRule::OutOfFrame,
+                Some(number),
+                format!("{} point ({},{}) is outside PlayRes {}x{}", tag, x, y, width, height),
+            ));
+        }
+    }
+}
+
+fn check_transforms(number: usize, event: &Event, findings: &mut Vec<LintFinding>) {
+    let length_ms = duration_centiseconds(event) as f32 * 10.0;
+    if length_ms <= 0.0 {
+        return;
+    }
+    for item in &event.text.data {
+        let (t1, t2) = match item {
+            ASSText::Override(ASSOverride::TransformIII(t1, t2, _))
+            | ASSText::Override(ASSOverride::TransformIV(t1, t2, _, _)) => (*t1, *t2),
+            _ => continue,
+        };
+        if t1.max(t2) > length_ms {
+            findings.push(LintFinding::new(
+                LintRule::TransformPastEnd,
+                Some(number),
+                format!("\\t({},{}) runs past the event's {}ms", t1, t2, length_ms),
+            ));
+        }
+    }
+}
+
+// Consecutive in file order, same layer and style, identical text, and touching or overlapping in
+// time: almost always a line pasted twice. Frame-by-frame typesetting differs in its tags.
+fn check_duplicates(sub: &SubstationAlpha, findings: &mut Vec<LintFinding>) {
+    for (i, pair) in sub.events.windows(2).enumerate() {
+        let (prev, next) = (&pair[0], &pair[1]);
+        let text = prev.text.stringify();
+        if text.trim().is_empty()
+            || prev.layer != next.layer
+            || prev.style != next.style
+            || text != next.text.stringify()
+            || next.start.total_centiseconds() > prev.end.total_centiseconds()
+        {
+            continue;
+        }
+        findings.push(LintFinding::new(
+            LintRule::DuplicateLine,
+            Some(i + 2),
+            format!("repeats event {}", i + 1),
+        ));
+    }
+}
+
+// Only plain dialogue can collide: positioned lines, drawings and sign styles are placed on
+// purpose. Each overlap is reported once, on the later of the two events.
+fn check_overlaps(sub: &SubstationAlpha, findings: &mut Vec<LintFinding>) {
+    let mut groups: HashMap<(&str, u8), Vec<usize>> = HashMap::new();
+    for (i, event) in sub.events.iter().enumerate() {
+        if is_dialogue(event) && duration_centiseconds(event) > 0 && visible_char_count(event) > 0 {
+            groups.entry((event.style.as_str(), alignment(event, sub))).or_default().push(i);
+        }
+    }
+    for ((style, align), mut indices) in groups {
+        indices.sort_by_key(|&i| (sub.events[i].start.total_centiseconds(), i));
+        let mut active: Vec<usize> = Vec::new();
+        for i in indices {
+            let start = sub.events[i].start.total_centiseconds();
+            active.retain(|&j| sub.events[j].end.total_centiseconds() > start);
+            for &j in &active {
+                let (first, second) = (i.min(j), i.max(j));
+                findings.push(LintFinding::new(
+                    LintRule::Overlap,
+                    Some(second + 1),
+                    format!("overlaps event {} (style `{}`, \\an{})", first + 1, style, align),
+                ));
+            }
+            active.push(i);
+        }
+    }
+}
+
+fn is_dialogue(event: &Event) -> bool {
+    !event.style.to_lowercase().contains("sign")
+        && !event.has_drawing()
+        && !event.text.data.iter().any(|item| matches!(
+            item,
+            ASSText::Override(ASSOverride::Pos(..))
+                | ASSText::Override(ASSOverride::MoveI(..))
+                | ASSText::Override(ASSOverride::MoveII(..))
+        ))
+}
+
+fn alignment(event: &Event, sub: &SubstationAlpha) -> u8 {
+    for item in &event.text.data {
+        match item {
+            ASSText::Override(ASSOverride::An(an)) => return *an,
+            ASSText::Override(ASSOverride::A(a)) => return legacy_alignment(*a),
+            _ => {}
+        }
+    }
+    sub.v4p_styles
+        .iter()
+        .rev()
+        .find(|style| style.name == event.style)
+        .map(|style| style.alignment)
+        .unwrap_or(2)
+}
+
+// SSA's \a: 1-3 bottom, +4 top, +8 middle.
+fn legacy_alignment(a: u8) -> u8 {
+    match a {
+        5..=7 => a + 2,
+        9..=11 => a - 5,
+        _ => a,
+    }
+}
+
+fn duration_centiseconds(event: &Event) -> u64 {
+    event.end.total_centiseconds().saturating_sub(event.start.total_centiseconds())
+}
+
+// Whitespace and line breaks do not count toward CPS.
+fn visible_char_count(event: &Event) -> usize {
+    event
+        .raw_text()
+        .replace("\\N", " ")
+        .replace("\\n", " ")
+        .replace("\\h", " ")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(findings: &[LintFinding]) -> Vec<(Option<usize>, &'static str)> {
+        findings.iter().map(|f| (f.event, f.rule.code())).collect()
+    }
+
+    const HEADER: &str = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\n\
+        Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+        Style: Default,Arial,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,10,10,10,1\n\
+        Style: Sign,Arial,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,8,10,10,10,1\n\
+        Style: Spare,Arial,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,10,10,10,1\n\n\
+        [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n";
+
+    fn lint_events(events: &str) -> Vec<LintFinding> {
+        let (sub, _) = SubstationAlpha::parse(&format!("{}{}", HEADER, events), true);
+        lint(&sub, &LintOptions::default())
+    }
+
+    #[test]
+    fn clean_script_only_reports_unused_styles() {
+        let findings = lint_events(
+            "Dialogue: 0,0:00:01.00,0:00:03.00,Default,,0,0,0,,Hello there.\n\
+             Dialogue: 0,0:00:03.00,0:00:05.00,Default,,0,0,0,,General Kenobi.\n\
+             Dialogue: 0,0:00:01.00,0:00:05.00,Sign,,0,0,0,,{\\pos(960,100)}SHOP\n",
+        );
+        assert_eq!(codes(&findings), vec![(None, "unused-style")]);
+        assert_eq!(findings[0].to_string(), "[info] unused-style: style `Spare` is never used");
+    }
+
+    #[test]
+    fn every_rule_fires_on_its_own_event() {
+        let findings = lint_events(
+            "Dialogue: 0,0:00:01.00,0:00:04.00,Default,,0,0,0,,First line.\n\
+             Dialogue: 0,0:00:02.00,0:00:05.00,Default,,0,0,0,,Talking over it.\n\
+             Dialogue: 0,0:00:06.00,0:00:06.50,Default,,0,0,0,,This is far too much text for half a second.\n\
+             Dialogue: 0,0:00:07.00,0:00:08.00,Missing,,0,0,0,,Who styled this?\n\
+             Dialogue: 0,0:00:09.00,0:00:09.00,Spare,,0,0,0,,Gone.\n\
+             Dialogue: 0,0:00:10.00,0:00:12.00,Sign,,0,0,0,,{\\pos(2000,50)\\t(0,3000,\\fscx120)}Edge\n\
+             Dialogue: 0,0:00:13.00,0:00:14.00,Spare,,0,0,0,,Again.\n\
+             Dialogue: 0,0:00:14.00,0:00:15.00,Spare,,0,0,0,,Again.\n",
+        );
+        assert_eq!(
+            codes(&findings),
+            vec![
+                (Some(2), "overlap"),
+                (Some(3), "cps"),
+                (Some(4), "missing-style"),
+                (Some(5), "bad-duration"),
+                (Some(6), "out-of-frame"),
+                (Some(6), "transform-past-end"),
+                (Some(8), "duplicate-line"),
+            ]
+        );
+        assert_eq!(findings[0].severity(), LintSeverity::Warning);
+        assert_eq!(findings[2].severity(), LintSeverity::Error);
+    }
+
+    #[test]
+    fn overlap_needs_the_same_alignment() {
+        let findings = lint_events(
+            "Dialogue: 0,0:00:01.00,0:00:04.00,Default,,0,0,0,,Bottom.\n\
+             Dialogue: 0,0:00:02.00,0:00:05.00,Default,,0,0,0,,{\\an8}Top.\n\
+             Dialogue: 0,0:00:03.00,0:00:05.00,Default,,0,0,0,,{\\a6}Also top.\n\
+             Dialogue: 0,0:00:00.00,0:00:01.00,Spare,,0,0,0,,x\n\
+             Dialogue: 0,0:00:00.00,0:00:01.00,Sign,,0,0,0,,x\n",
+        );
+        assert_eq!(codes(&findings), vec![(Some(3), "overlap")]);
+        assert!(findings[0].message.contains("overlaps event 2"));
+    }
+}
@@ -2,4 +2,19 @@ pub mod core;
 pub mod tags;
 pub mod complex;
 pub mod drawing;
-pub mod tracing;
\ No newline at end of file
+pub mod tracing;
+pub mod sections;
+pub mod lint;pub mod textsubs;
+pub mod timing;
+pub mod tpp;
+pub mod diff;
+pub mod fontcheck;
+pub mod fontcollect;
+pub mod styles;
+pub mod karaoke;
+pub mod templater;
+pub mod motion;
+pub mod render;
+pub mod json;
+pub mod spellcheck;
+pub mod consistency;
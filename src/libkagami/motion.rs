@@ -0,0 +1,538 @@
+// Motion tracking in the style of Aegisub-Motion: After Effects "Keyframe Data" (position, scale
+// and rotation per frame, as Mocha and AE export it) or Mocha's Shake rotoshape export (a clip
+// shape per frame) applied to a typeset line by splitting it into one line per video frame.
+
+use crate::libkagami::complex::overrides::ASSOverride;
+use crate::libkagami::complex::types::AssTime;
+use crate::libkagami::core::{Event, SubstationAlpha, V4pStyle};
+use crate::libkagami::drawing::geometry::{clip_mask, clip_override, Affine};
+use crate::libkagami::drawing::parse::{Drawing, DrawingCommand};
+use crate::libkagami::tags::ASSText;
+use crate::libkagami::timing::{FrameTimes, shift_relative_times, to_ms};
+
+// Numbers per vertex in a Shake rotoshape `vertex_data` list: centre, in and out tangents, then
+// feather values pandora does not use.
+const SHAKE_VERTEX_FIELDS: usize = 16;
+
+// Tracked state of one frame. Scale is in percent and rotation in degrees clockwise, as After
+// Effects writes them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackFrame {
+    pub x: f64,
+    pub y: f64,
+    pub scale_x: f64,
+    pub scale_y: f64,
+    pub rotation: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tracking {
+    Transform(Vec<TrackFrame>),
+    // Shapes as Shake writes them: y counts up from the bottom of the frame.
+    Shape(Vec<Drawing>),
+}
+
+// `fps` is the data's `Units Per Second` and `source` its `Source Width`/`Source Height`; positions
+// are scaled from the source size to the script's PlayRes. Shake shapes carry no size, so they
+// are read against the script's LayoutRes, the video Aegisub typeset it on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MotionData {
+    pub fps: Option<f64>,
+    pub source: Option<(f64, f64)>,
+    pub tracking: Tracking,
+}
+
+impl MotionData {
+    pub fn frames(&self) -> usize {
+        match &self.tracking {
+            Tracking::Transform(frames) => frames.len(),
+            Tracking::Shape(shapes) => shapes.len(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MotionOutcome {
+    pub lines: usize,
+    pub events: usize,
+}
+
+pub fn parse_motion(text: &str) -> Result<MotionData, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    if text.contains("shake_shape_data") {
+        parse_shake_shapes(text)
+    } else if text.lines().any(|line| line.trim().ends_with("Keyframe Data")) {
+        parse_after_effects(text)
+    } else {
+        Err("not After Effects keyframe data or a Mocha shape export".to_string())
+    }
+}
+
+#[derive(Default)]
+struct Channel {
+    keys: Vec<(Option<i64>, Vec<f64>)>,
+}
+
+impl Channel {
+    // AE only lists keyframes, and a value holds until the next one; a key without a frame number
+    // is a property that never changes.
+    fn value(&self, frame: i64, index: usize, default: f64) -> f64 {
+        let key = self.keys.iter()
+            .rev()
+            .find(|(at, _)| at.is_none_or(|at| at <= frame))
+            .or(self.keys.first());
+        key.and_then(|(_, values)| values.get(index).copied()).unwrap_or(default)
+    }
+
+    fn frames(&self) -> impl Iterator<Item = i64> + '_ {
+        self.keys.iter().filter_map(|(at, _)| *at)
+    }
+}
+
+fn parse_after_effects(text: &str) -> Result<MotionData, String> {
+    let (mut fps, mut width, mut height) = (None, None, None);
+    let (mut position, mut scale, mut rotation) = (Channel::default(), Channel::default(), Channel::default());
+    let mut section: Option<String> = None;
+    for (index, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !raw.starts_with([' ', '\t']) {
+            let name = line.strip_prefix("Transform").unwrap_or(line).trim();
+            section = (!name.ends_with("Keyframe Data")).then(|| name.to_string());
+            continue;
+        }
+        let Some(name) = section.as_deref() else {
+            let number = |key: &str| line.strip_prefix(key).and_then(|v| v.trim().parse::<f64>().ok());
+            fps = number("Units Per Second").or(fps);
+            width = number("Source Width").or(width);
+            height = number("Source Height").or(height);
+            continue;
+        };
+        let channel = match name {
+            "Position" => &mut position,
+            "Scale" => &mut scale,
+            "Rotation" => &mut rotation,
+            _ => continue,
+        };
+        // Tab-separated as exported; pasted copies often have spaces instead, and then the frame
+        // number is always there.
+        let fields: Vec<&str> = if raw.contains('\t') {
+            raw.trim_end().split('\t').skip(1).map(str::trim).collect()
+        } else {
+            line.split_whitespace().collect()
+        };
+        if fields.first() == Some(&"Frame") {
+            continue;
+        }
+        let frame = match fields.first() {
+            Some(&"") => None,
+            Some(value) => Some(value.parse::<i64>().map_err(|_| format!("line {}: `{}` is not a frame number", index + 1, value))?),
+            None => continue,
+        };
+        let values = fields[1..].iter()
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<f64>().map_err(|_| format!("line {}: `{}` is not a number", index + 1, value)))
+            .collect::<Result<Vec<f64>, String>>()?;
+        channel.keys.push((frame, values));
+    }
+    if position.keys.is_empty() {
+        return Err("the keyframe data has no Position section".to_string());
+    }
+    let all = || position.frames().chain(scale.frames()).chain(rotation.frames());
+    let first = all().min().unwrap_or(0);
+    let last = all().max().unwrap_or(0);
+    let frames = (first..=last)
+        .map(|frame| TrackFrame {
+            x: position.value(frame, 0, 0.0),
+            y: position.value(frame, 1, 0.0),
+            scale_x: scale.value(frame, 0, 100.0),
+            scale_y: scale.value(frame, 1, 100.0),
+            rotation: rotation.value(frame, 0, 0.0),
+        })
+        .collect();
+    Ok(MotionData { fps, source: width.zip(height), tracking: Tracking::Transform(frames) })
+}
+
+// Every `vertex_data` list is the shape of the next frame. The curve runs from each vertex's out
+// tangent to the next vertex's in tangent and closes back on the first.
+fn parse_shake_shapes(text: &str) -> Result<MotionData, String> {
+    let mut shapes = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let Some((_, list)) = line.split_once("vertex_data") else {
+            continue;
+        };
+        let values: Vec<f32> = list
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ',')
+            .filter_map(|token| token.parse().ok())
+            .collect();
+        if values.len() < SHAKE_VERTEX_FIELDS * 3 || !values.len().is_multiple_of(SHAKE_VERTEX_FIELDS) {
+            return Err(format!("line {}: vertex_data is not a list of whole vertices", index + 1));
+        }
+        let vertices: Vec<&[f32]> = values.chunks(SHAKE_VERTEX_FIELDS).collect();
+        let mut commands = vec![DrawingCommand::Move(vertices[0][0], vertices[0][1])];
+        for (i, vertex) in vertices.iter().enumerate() {
+            let next = vertices[(i + 1) % vertices.len()];
+            commands.push(DrawingCommand::CubicBezier(vertex[4], vertex[5], next[2], next[3], next[0], next[1]));
+        }
+        shapes.push(Drawing { commands });
+    }
+    if shapes.is_empty() {
+        return Err("the shape export has no vertex_data".to_string());
+    }
+    Ok(MotionData { fps: None, source: None, tracking: Tracking::Shape(shapes) })
+}
+
+// Splits each of the given events (1-based, as pnass reports lines) into one event per frame it
+// covers and moves it with the tracking data. `origin` is the 1-based tracking frame the line was
+// typeset on; other frames are applied relative to it. The data must cover the line exactly.
+pub fn apply_motion(
+    sub: &mut SubstationAlpha,
+    lines: &[usize],
+    data: &MotionData,
+    times: &FrameTimes,
+    origin: usize,
+) -> Result<MotionOutcome, String> {
+    if origin == 0 || origin > data.frames() {
+        return Err(format!("origin frame {} is outside the {} tracked frames", origin, data.frames()));
+    }
+    let mut lines = lines.to_vec();
+    lines.sort_unstable();
+    lines.dedup();
+    let playres = (sub.script_info.playresx as f64, sub.script_info.playresy as f64);
+    let layout = (sub.script_info.layout_res_x as f64, sub.script_info.layout_res_y as f64);
+    let video = data.source.or((layout.0 > 0.0 && layout.1 > 0.0).then_some(layout));
+    let mut split = Vec::with_capacity(lines.len());
+    for &line in &lines {
+        let event = line.checked_sub(1)
+            .and_then(|i| sub.events.get(i))
+            .ok_or_else(|| format!("there is no line {}", line))?;
+        let style = sub.v4p_styles.iter().find(|style| style.name == event.style);
+        let frames = track_event(event, style, playres, video, data, times, origin - 1)
+            .map_err(|e| format!("line {}: {}", line, e))?;
+        split.push((line - 1, frames));
+    }
+    let mut outcome = MotionOutcome::default();
+    for (index, frames) in split.into_iter().rev() {
+        outcome.lines += 1;
+        outcome.events += frames.len();
+        sub.events.splice(index..=index, frames);
+    }
+    Ok(outcome)
+}
+
+// First frame that starts at or after `ms`: a line starting at `ms` is first shown on it, and a
+// line ending at `ms` is gone by then.
+fn frame_at(times: &FrameTimes, ms: f64) -> i64 {
+    let frame = times.ms_to_frame(ms);
+    if times.frame_to_ms(frame) < ms - 1e-6 { frame + 1 } else { frame }
+}
+
+// Rounded down so the frame that starts at `ms` is still inside a line starting there.
+fn floor_time(ms: f64) -> AssTime {
+    AssTime::from_centiseconds((ms.max(0.0) / 10.0 + 1e-6).floor() as u64)
+}
+
+fn track_event(
+    event: &Event,
+    style: Option<&V4pStyle>,
+    playres: (f64, f64),
+    video: Option<(f64, f64)>,
+    data: &MotionData,
+    times: &FrameTimes,
+    origin: usize,
+) -> Result<Vec<Event>, String> {
+    let (start, end) = (to_ms(&event.start), to_ms(&event.end));
+    let (first, last) = (frame_at(times, start), frame_at(times, end));
+    let count = (last - first).max(0) as usize;
+    if count != data.frames() {
+        return Err(format!("the line is {} frames long but the tracking data has {}", count, data.frames()));
+    }
+    let tags = || event.text.data.iter().filter_map(|item| if let ASSText::Override(ov) = item { Some(ov) } else { None });
+    if tags().any(|ov| matches!(ov, ASSOverride::MoveI(..) | ASSOverride::MoveII(..))) {
+        return Err("\\move lines cannot be tracked; use \\pos".to_string());
+    }
+    if matches!(data.tracking, Tracking::Transform(_)) && !tags().any(|ov| matches!(ov, ASSOverride::Pos(..))) {
+        return Err("tracking needs \\pos on the line".to_string());
+    }
+
+    // Tracking data is in video pixels; without a known video size it is taken as script pixels.
+    let (factor, video_height) = match video.filter(|(w, h)| *w > 0.0 && *h > 0.0 && playres.0 > 0.0 && playres.1 > 0.0) {
+        Some((w, h)) => ((playres.0 / w, playres.1 / h), h),
+        None => ((1.0, 1.0), playres.1),
+    };
+    let mut out = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut frame = event.clone();
+        if i > 0 {
+            frame.start = floor_time(times.frame_to_ms(first + i as i64));
+        }
+        if i + 1 < count {
+            frame.end = floor_time(times.frame_to_ms(first + i as i64 + 1));
+            // Above 100 fps neighbouring frames can floor to the same centisecond; the later one
+            // covers it, so this one would only be an empty event.
+            if frame.end.total_centiseconds() <= frame.start.total_centiseconds() {
+                continue;
+            }
+        }
+        // Times inside \t and \fad(e) count from the line start, which moves to the frame's start.
+        // Every frame gets explicit times, the first included: an untimed \t or a \fad would
+        // otherwise run over that one frame instead of the whole line.
+        let offset = (to_ms(&frame.start) - start) as f32;
+        for item in &mut frame.text.data {
+            if let ASSText::Override(ov) = item {
+                shift_relative_times(ov, offset, (end - start) as f32);
+            }
+        }
+        match &data.tracking {
+            Tracking::Transform(track) => {
+                apply_track_frame(&mut frame, style, &track[origin], &track[i], factor)?;
+            }
+            Tracking::Shape(shapes) => {
+                let mut mask = shapes[i].clone();
+                mask.transform(&Affine::scale(1.0, -1.0)
+                    .then(Affine::translate(0.0, video_height))
+                    .then(Affine::scale(factor.0, factor.1)));
+                set_clip(&mut frame, &mask);
+            }
+        }
+        out.push(frame);
+    }
+    Ok(out)
+}
+
+// Moves the line from where it was typeset (`origin`) to `frame`: points follow the tracked point
+// through its scale and rotation, and sizes scale with it. Scale, border, shadow and angle the
+// line takes from its style are written out as tags when they change.
+fn apply_track_frame(
+    event: &mut Event,
+    style: Option<&V4pStyle>,
+    origin: &TrackFrame,
+    frame: &TrackFrame,
+    factor: (f64, f64),
+) -> Result<(), String> {
+    if origin.scale_x == 0.0 || origin.scale_y == 0.0 {
+        return Err("the origin frame has zero scale".to_string());
+    }
+    let (sx, sy) = (frame.scale_x / origin.scale_x, frame.scale_y / origin.scale_y);
+    let turn = frame.rotation - origin.rotation;
+    let affine = Affine::translate(-origin.x * factor.0, -origin.y * factor.1)
+        .then(Affine::scale(sx, sy))
+        .then(Affine::rotate(-turn, (0.0, 0.0)))
+        .then(Affine::translate(frame.x * factor.0, frame.y * factor.1));
+    let (sx, sy, turn) = (sx as f32, sy as f32, turn as f32);
+
+    // Tags the line sets by the end of its \pos block decide what it starts with; anything else
+    // comes from the style.
+    let data = &mut event.text.data;
+    let pos = data.iter().position(|item| matches!(item, ASSText::Override(ASSOverride::Pos(..)))).unwrap_or(0);
+    let block_end = data[pos..].iter()
+        .position(|item| !matches!(item, ASSText::Override(_)))
+        .map_or(data.len(), |n| pos + n);
+    let sets = |pick: fn(&ASSOverride) -> bool| {
+        data[..block_end].iter().any(|item| matches!(item, ASSText::Override(ov) if pick(ov)))
+    };
+    let defaults = style.map(|s| (s.scale_x as f32, s.scale_y as f32, s.outline, s.shadow, s.angle))
+        .unwrap_or((100.0, 100.0, 0.0, 0.0, 0.0));
+    let mut implicit = Vec::new();
+    if (sx != 1.0 || sy != 1.0) && !sets(|ov| matches!(ov, ASSOverride::Fscx(_) | ASSOverride::Fscy(_) | ASSOverride::Fsc(_))) {
+        implicit.push(ASSOverride::Fscx(defaults.0 * sx));
+        implicit.push(ASSOverride::Fscy(defaults.1 * sy));
+    }
+    if sx != 1.0 && defaults.2 != 0.0 && !sets(|ov| matches!(ov, ASSOverride::Bord(_) | ASSOverride::Xbord(_) | ASSOverride::Ybord(_))) {
+        implicit.push(ASSOverride::Bord(defaults.2 * sx));
+    }
+    if sx != 1.0 && defaults.3 != 0.0 && !sets(|ov| matches!(ov, ASSOverride::Shad(_) | ASSOverride::Xshad(_) | ASSOverride::Yshad(_))) {
+        implicit.push(ASSOverride::Shad(defaults.3 * sx));
+    }
+    if turn != 0.0 && !sets(|ov| matches!(ov, ASSOverride::Frz(_) | ASSOverride::Fr(_))) {
+        implicit.push(ASSOverride::Frz(defaults.4 - turn));
+    }
+
+    let mut tracked = Vec::with_capacity(data.len() + implicit.len());
+    for (i, item) in std::mem::take(data).into_iter().enumerate() {
+        if i == block_end {
+            tracked.extend(implicit.drain(..).map(ASSText::Override));
+        }
+        match item {
+            ASSText::Override(ov) => tracked.extend(track_override(ov, &affine, sx, sy, turn).into_iter().map(ASSText::Override)),
+            other => tracked.push(other),
+        }
+    }
+    tracked.extend(implicit.into_iter().map(ASSText::Override));
+    *data = tracked;
+    Ok(())
+}
+
+fn track_override(ov: ASSOverride, affine: &Affine, sx: f32, sy: f32, turn: f32) -> Vec<ASSOverride> {
+    let tracked = match ov {
+        ASSOverride::Pos(x, y) => {
+            let (x, y) = affine.apply(x, y);
+            ASSOverride::Pos(x, y)
+        }
+        ASSOverride::Org(x, y) => {
+            let (x, y) = affine.apply(x, y);
+            ASSOverride::Org(x, y)
+        }
+        ASSOverride::Fsc(v) if sx != sy => return vec![ASSOverride::Fscx(v * sx), ASSOverride::Fscy(v * sy)],
+        ASSOverride::Fsc(v) => ASSOverride::Fsc(v * sx),
+        ASSOverride::Fscx(v) => ASSOverride::Fscx(v * sx),
+        ASSOverride::Fscy(v) => ASSOverride::Fscy(v * sy),
+        ASSOverride::Bord(v) => ASSOverride::Bord(v * sx),
+        ASSOverride::Xbord(v) => ASSOverride::Xbord(v * sx),
+        ASSOverride::Ybord(v) => ASSOverride::Ybord(v * sy),
+        ASSOverride::Shad(v) => ASSOverride::Shad(v * sx),
+        ASSOverride::Xshad(v) => ASSOverride::Xshad(v * sx),
+        ASSOverride::Yshad(v) => ASSOverride::Yshad(v * sy),
+        ASSOverride::Frz(v) => ASSOverride::Frz(v - turn),
+        ASSOverride::Fr(v) => ASSOverride::Fr(v - turn),
+        ASSOverride::TransformI(tags) => ASSOverride::TransformI(track_all(tags, affine, sx, sy, turn)),
+        ASSOverride::TransformII(a, tags) => ASSOverride::TransformII(a, track_all(tags, affine, sx, sy, turn)),
+        ASSOverride::TransformIII(t1, t2, tags) => ASSOverride::TransformIII(t1, t2, track_all(tags, affine, sx, sy, turn)),
+        ASSOverride::TransformIV(t1, t2, a, tags) => {
+            ASSOverride::TransformIV(t1, t2, a, track_all(tags, affine, sx, sy, turn))
+        }
+        other => match clip_mask(&other) {
+            Some((mut mask, inverse)) => {
+                mask.transform(affine);
+                clip_override(&mask, inverse)
+            }
+            None => other,
+        },
+    };
+    vec![tracked]
+}
+
+fn track_all(tags: Vec<ASSOverride>, affine: &Affine, sx: f32, sy: f32, turn: f32) -> Vec<ASSOverride> {
+    tags.into_iter().flat_map(|ov| track_override(ov, affine, sx, sy, turn)).collect()
+}
+
+// Replaces the line's clips with the tracked shape, keeping `\iclip` if that is what it used.
+fn set_clip(event: &mut Event, mask: &Drawing) {
+    let mut inverse = false;
+    event.text.data.retain(|item| match item {
+        ASSText::Override(ov) => match clip_mask(ov) {
+            Some((_, iclip)) => {
+                inverse |= iclip;
+                false
+            }
+            None => true,
+        },
+        _ => true,
+    });
+    let clip = ASSText::Override(clip_override(mask, inverse));
+    if matches!(event.text.data.first(), Some(ASSText::Override(_))) {
+        event.text.data.insert(1, clip);
+    } else {
+        event.text.data.insert(0, clip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYFRAMES: &str = "Adobe After Effects 6.0 Keyframe Data
+
+\tUnits Per Second\t25
+\tSource Width\t960
+\tSource Height\t540
+\tSource Pixel Aspect Ratio\t1
+\tComp Pixel Aspect Ratio\t1
+
+Transform\tPosition
+\tFrame\tX pixels\tY pixels\tZ pixels\t
+\t0\t100\t100\t0\t
+\t1\t110\t100\t0\t
+\t2\t120\t100\t0\t
+
+Transform\tScale
+\tFrame\tX percent\tY percent\tZ percent\t
+\t0\t100\t100\t100\t
+\t2\t200\t200\t100\t
+
+Transform\tRotation
+\tFrame\tDegrees\t
+\t0\t0\t
+\t1\t90\t
+
+End of Keyframe Data
+";
+
+    #[test]
+    fn parses_after_effects_keyframes_and_holds_missing_frames() {
+        let data = parse_motion(KEYFRAMES).unwrap();
+        assert_eq!((data.fps, data.source), (Some(25.0), Some((960.0, 540.0))));
+        let Tracking::Transform(frames) = &data.tracking else { panic!("expected transform data") };
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1], TrackFrame { x: 110.0, y: 100.0, scale_x: 100.0, scale_y: 100.0, rotation: 90.0 });
+        assert_eq!((frames[2].scale_x, frames[2].rotation), (200.0, 90.0));
+    }
+
+    #[test]
+    fn splits_a_line_per_frame_and_follows_the_track() {
+        let script = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\n\
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+Style: Sign,Arial,40,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,7,10,10,10,1\n\n\
+[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+Dialogue: 0,0:00:00.00,0:00:00.12,Sign,,0,0,0,,{\\pos(220,200)\\fscx50\\t(\\fscx80)}Sign\n";
+        let (mut sub, _) = SubstationAlpha::parse(script, true);
+        let data = parse_motion(KEYFRAMES).unwrap();
+
+        let outcome = apply_motion(&mut sub, &[1], &data, &FrameTimes::Constant(25.0), 1).unwrap();
+
+        assert_eq!(outcome, MotionOutcome { lines: 1, events: 3 });
+        let texts: Vec<String> = sub.events.iter().map(|ev| ev.text.stringify()).collect();
+        assert_eq!(texts[0], "{\\pos(220,200)\\fscx50\\t(0,120,\\fscx80)}Sign");
+        // The tracked point moved 20 script pixels right and turned 90° clockwise, so the sign
+        // that sat 20 px right of it is now 20 px below it.
+        assert_eq!(texts[1], "{\\pos(220,220)\\fscx50\\t(-40,80,\\fscx80)\\frz-90}Sign");
+        assert_eq!(texts[2], "{\\pos(240,240)\\fscx100\\t(-80,40,\\fscx160)\\bord4\\frz-90}Sign");
+        let times: Vec<(u64, u64)> = sub.events.iter()
+            .map(|ev| (ev.start.total_centiseconds(), ev.end.total_centiseconds()))
+            .collect();
+        assert_eq!(times, vec![(0, 4), (4, 8), (8, 12)]);
+    }
+
+    #[test]
+    fn frames_that_floor_to_one_centisecond_are_merged() {
+        let script = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[Events]\n\
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+Dialogue: 0,0:00:00.00,0:00:00.02,Sign,,0,0,0,,{\\pos(220,200)}Sign\n";
+        let (mut sub, _) = SubstationAlpha::parse(script, true);
+        let data = parse_motion(KEYFRAMES).unwrap();
+
+        // At 120 fps the three frames start at 0, 0.83 and 1.67 cs: the first two share 0.
+        let outcome = apply_motion(&mut sub, &[1], &data, &FrameTimes::Constant(120.0), 1).unwrap();
+
+        assert_eq!(outcome, MotionOutcome { lines: 1, events: 2 });
+        let times: Vec<(u64, u64)> = sub.events.iter()
+            .map(|ev| (ev.start.total_centiseconds(), ev.end.total_centiseconds()))
+            .collect();
+        assert_eq!(times, vec![(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn shapes_are_flipped_in_video_pixels_and_scaled_to_playres() {
+        // Straight edges: every tangent sits on its vertex.
+        let vertex = |x: f32, y: f32| format!("{x} {y} {x} {y} {x} {y} 0 0 0 0 0 0 0 0 0 0");
+        let square = |x: f32| {
+            let corners = [vertex(x, 0.0), vertex(x + 100.0, 0.0), vertex(x + 100.0, 100.0), vertex(x, 100.0)];
+            format!("vertex_data {}", corners.join(" "))
+        };
+        let shake = format!("shake_shape_data 4.0\nnum_shapes 1\n{}\n{}\n", square(0.0), square(10.0));
+        let script = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\nLayoutResX: 960\nLayoutResY: 540\n\n[Events]\n\
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+Dialogue: 0,0:00:00.00,0:00:00.08,Sign,,0,0,0,,{\\pos(220,200)}Sign\n";
+        let (mut sub, _) = SubstationAlpha::parse(script, true);
+        let data = parse_motion(&shake).unwrap();
+
+        apply_motion(&mut sub, &[1], &data, &FrameTimes::Constant(25.0), 1).unwrap();
+
+        // The video is half the PlayRes: the square on Shake's bottom edge covers y 440-540 of the
+        // video, so 880-1080 of the script, not 980-1080 as a flip against PlayResY would put it.
+        let texts: Vec<String> = sub.events.iter().map(|ev| ev.text.stringify()).collect();
+        assert_eq!(texts, vec!["{\\pos(220,200)\\clip(0,880,200,1080)}Sign", "{\\pos(220,200)\\clip(20,880,220,1080)}Sign"]);
+    }
+}
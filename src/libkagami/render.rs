@@ -0,0 +1,737 @@
+// Lays events out the way libass draws them, for the software renderer in `lib::image::ass`: every
+// glyph and `\p` drawing becomes a shape in script coordinates, with the shadow and border passes
+// libass puts under the fill. Covered: `\pos`/`\move`, alignment and margins, hard breaks and greedy
+// wrapping, font, size, `\fscx`/`\fscy`, spacing, colours and alpha, `\fad`/`\fade`, border,
+// shadow and the opaque box, `\frz` around `\org`, drawings, rect and vector clips, and `\t` on the
+// numeric and colour tags. Blur, `\frx`/`\fry`, shearing, faux bold and karaoke fills are not drawn.
+
+use ab_glyph::{Font, FontRef, OutlineCurve, PxScale, ScaleFont};
+
+use crate::libkagami::complex::overrides::ASSOverride;
+use crate::libkagami::core::{Event, SubstationAlpha, V4pStyle};
+use crate::libkagami::drawing::geometry::{clip_mask, Affine, Rect};
+use crate::libkagami::drawing::parse::{Drawing, DrawingCommand};
+use crate::libkagami::fontcheck::{find_style, font_usage, script_library, FontLibrary, FontRequest};
+use crate::libkagami::tags::ASSText;
+use crate::libkagami::timing::to_ms;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    Shadow,
+    Border,
+    Fill,
+}
+
+// `stroke` is the border width in script pixels: the shape is filled and stroked at twice that.
+// Colours are straight RGBA with the fade already applied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderShape {
+    pub pass: Pass,
+    pub shape: Drawing,
+    pub stroke: f32,
+    pub rgba: [u8; 4],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventRender {
+    pub index: usize,
+    pub layer: u16,
+    pub shapes: Vec<RenderShape>,
+    // The clip shape in script coordinates, and whether it is an `\iclip`.
+    pub clip: Option<(Drawing, bool)>,
+}
+
+pub struct Renderer<'s> {
+    sub: &'s SubstationAlpha,
+    fonts: FontLibrary,
+    fallback: Option<FontRef<'static>>,
+    border_scale: f32,
+}
+
+impl<'s> Renderer<'s> {
+    // Fonts come from `font_roots` and the script's [Fonts]; `fallback` draws text whose font is
+    // not there. `video_width` is the output width, which unscaled borders and shadows count in.
+    pub fn new(sub: &'s SubstationAlpha, font_roots: &[std::path::PathBuf], fallback: Option<FontRef<'static>>, video_width: f32) -> Self {
+        let fonts = script_library(sub, &font_usage(sub), font_roots);
+        let border_scale = if sub.script_info.scaled_border_and_shadow || video_width <= 0.0 {
+            1.0
+        } else {
+            play_res(sub).0 / video_width
+        };
+        Self { sub, fonts, fallback, border_scale }
+    }
+
+    // The script's coordinate space, which shapes are laid out in.
+    pub fn play_res(&self) -> (f32, f32) {
+        play_res(self.sub)
+    }
+
+    // Every event on screen at `time_ms`, bottom layer first and in script order within a layer.
+    pub fn frame(&self, time_ms: f64) -> Vec<EventRender> {
+        let mut out: Vec<EventRender> = (0..self.sub.events.len())
+            .filter_map(|index| self.event(index, time_ms))
+            .collect();
+        out.sort_by_key(|event| event.layer);
+        out
+    }
+
+    // The event at `index` as it looks at `time_ms`, or None when it is not on screen then.
+    pub fn event(&self, index: usize, time_ms: f64) -> Option<EventRender> {
+        let event = self.sub.events.get(index)?;
+        let (start, end) = (to_ms(&event.start), to_ms(&event.end));
+        if time_ms < start || time_ms >= end {
+            return None;
+        }
+        let style = find_style(self.sub, &event.style)?;
+        let mut layout = Layout::new(self, style, time_ms - start, end - start);
+        layout.walk(event);
+        Some(layout.finish(index, event))
+    }
+
+    fn font(&self, run: &Run) -> Option<FontRef<'_>> {
+        self.fonts
+            .find(&FontRequest::new(&run.family, run.bold, run.italic))
+            .and_then(|face| face.font())
+            .or_else(|| self.fallback.clone())
+    }
+}
+
+fn play_res(sub: &SubstationAlpha) -> (f32, f32) {
+    match (sub.script_info.playresx, sub.script_info.playresy) {
+        (0, _) | (_, 0) => (384.0, 288.0),
+        (x, y) => (x as f32, y as f32),
+    }
+}
+
+// Override state that can change inside a line. Colours are `&HBBGGRR`, alpha 0 is opaque.
+#[derive(Clone, Debug)]
+struct Run {
+    family: String,
+    bold: bool,
+    italic: bool,
+    size: f32,
+    scale_x: f32,
+    scale_y: f32,
+    spacing: f32,
+    colours: [u32; 4],
+    alphas: [f32; 4],
+    border: (f32, f32),
+    shadow: (f32, f32),
+    frz: f32,
+    drawing: u8,
+}
+
+impl Run {
+    fn of(style: &V4pStyle) -> Self {
+        let colour = |i: usize| style.colours[i].as_u32() & 0xFF_FFFF;
+        let alpha = |i: usize| (style.colours[i].as_u32() >> 24) as f32;
+        Self {
+            family: style.fontname.clone(),
+            bold: style.bold,
+            italic: style.italic,
+            size: style.fontsize as f32,
+            scale_x: style.scale_x as f32,
+            scale_y: style.scale_y as f32,
+            spacing: style.spacing,
+            colours: [colour(0), colour(1), colour(2), colour(3)],
+            alphas: [alpha(0), alpha(1), alpha(2), alpha(3)],
+            border: (style.outline, style.outline),
+            shadow: (style.shadow, style.shadow),
+            frz: style.angle,
+            drawing: 0,
+        }
+    }
+
+    // `progress` is 1 for a plain tag and the \t progress for an animated one.
+    fn apply(&mut self, ov: &ASSOverride, progress: f32, reset: &Run) {
+        let mix = |from: f32, to: f32| from + (to - from) * progress;
+        match ov {
+            ASSOverride::Fn(name) => {
+                let name = name.trim();
+                self.family = if name.is_empty() || name == "0" { reset.family.clone() } else { name.to_string() };
+            }
+            ASSOverride::Bold(on) => self.bold = *on,
+            ASSOverride::Italic(on) => self.italic = *on,
+            ASSOverride::Fs(v) => self.size = mix(self.size, if *v > 0.0 { *v } else { reset.size }),
+            ASSOverride::Fscx(v) => self.scale_x = mix(self.scale_x, *v),
+            ASSOverride::Fscy(v) => self.scale_y = mix(self.scale_y, *v),
+            ASSOverride::Fsc(v) => {
+                self.scale_x = mix(self.scale_x, *v);
+                self.scale_y = mix(self.scale_y, *v);
+            }
+            ASSOverride::Fsp(v) => self.spacing = mix(self.spacing, *v),
+            ASSOverride::Bord(v) => self.border = (mix(self.border.0, *v), mix(self.border.1, *v)),
+            ASSOverride::Xbord(v) => self.border.0 = mix(self.border.0, *v),
+            ASSOverride::Ybord(v) => self.border.1 = mix(self.border.1, *v),
+            ASSOverride::Shad(v) => self.shadow = (mix(self.shadow.0, *v), mix(self.shadow.1, *v)),
+            ASSOverride::Xshad(v) => self.shadow.0 = mix(self.shadow.0, *v),
+            ASSOverride::Yshad(v) => self.shadow.1 = mix(self.shadow.1, *v),
+            ASSOverride::Frz(v) | ASSOverride::Fr(v) => self.frz = mix(self.frz, *v),
+            ASSOverride::ColorI(c) => self.colours[0] = mix_colour(self.colours[0], *c, progress),
+            ASSOverride::ColorII(c) => self.colours[1] = mix_colour(self.colours[1], *c, progress),
+            ASSOverride::ColorIII(c) => self.colours[2] = mix_colour(self.colours[2], *c, progress),
+            ASSOverride::ColorIV(c) => self.colours[3] = mix_colour(self.colours[3], *c, progress),
+            ASSOverride::Alpha(a) => {
+                for alpha in &mut self.alphas {
+                    *alpha = mix(*alpha, *a as f32);
+                }
+            }
+            ASSOverride::AlphaI(a) => self.alphas[0] = mix(self.alphas[0], *a as f32),
+            ASSOverride::AlphaII(a) => self.alphas[1] = mix(self.alphas[1], *a as f32),
+            ASSOverride::AlphaIII(a) => self.alphas[2] = mix(self.alphas[2], *a as f32),
+            ASSOverride::AlphaIV(a) => self.alphas[3] = mix(self.alphas[3], *a as f32),
+            ASSOverride::P(n) => self.drawing = *n,
+            _ => {}
+        }
+    }
+
+    fn rgba(&self, i: usize, opacity: f32) -> [u8; 4] {
+        let c = self.colours[i];
+        let alpha = (255.0 - self.alphas[i].clamp(0.0, 255.0)) * opacity.clamp(0.0, 1.0);
+        [(c & 0xFF) as u8, (c >> 8 & 0xFF) as u8, (c >> 16 & 0xFF) as u8, alpha.round() as u8]
+    }
+}
+
+fn mix_colour(from: u32, to: u32, progress: f32) -> u32 {
+    (0..3).fold(0, |out, channel| {
+        let shift = channel * 8;
+        let (a, b) = ((from >> shift & 0xFF) as f32, (to >> shift & 0xFF) as f32);
+        out | (((a + (b - a) * progress).round().clamp(0.0, 255.0) as u32) << shift)
+    })
+}
+
+// A glyph or drawing on a line: its shape with the pen at (0, 0) on the baseline.
+struct Item {
+    shape: Drawing,
+    advance: f32,
+    ascent: f32,
+    descent: f32,
+    space: bool,
+    run: usize,
+}
+
+struct Layout<'r, 's> {
+    renderer: &'r Renderer<'s>,
+    style: &'s V4pStyle,
+    time: f32,
+    duration: f32,
+    runs: Vec<Run>,
+    current: Run,
+    lines: Vec<Vec<Item>>,
+    pos: Option<(f32, f32)>,
+    org: Option<(f32, f32)>,
+    align: Option<u8>,
+    clip: Option<(Drawing, bool)>,
+    opacity: Option<f32>,
+}
+
+impl<'r, 's> Layout<'r, 's> {
+    fn new(renderer: &'r Renderer<'s>, style: &'s V4pStyle, time: f64, duration: f64) -> Self {
+        let current = Run::of(style);
+        Self {
+            renderer,
+            style,
+            time: time as f32,
+            duration: duration as f32,
+            runs: Vec::new(),
+            current,
+            lines: vec![Vec::new()],
+            pos: None,
+            org: None,
+            align: None,
+            clip: None,
+            opacity: None,
+        }
+    }
+
+    fn walk(&mut self, event: &Event) {
+        let reset = Run::of(self.style);
+        for item in &event.text.data {
+            match item {
+                ASSText::Override(ov) => self.tag(ov, &reset),
+                ASSText::RawText(text) => self.text(text),
+                ASSText::Drawing(drawing) => self.drawing(drawing),
+            }
+        }
+    }
+
+    // \pos, \move, \org and \an take the first one, a clip the last one, as libass does.
+    fn tag(&mut self, ov: &ASSOverride, reset: &Run) {
+        match ov {
+            ASSOverride::Pos(x, y) => {
+                self.pos.get_or_insert((*x, *y));
+            }
+            ASSOverride::MoveI(x1, y1, x2, y2) => {
+                let at = self.progress(0.0, self.duration, 1.0);
+                self.pos.get_or_insert((x1 + (x2 - x1) * at, y1 + (y2 - y1) * at));
+            }
+            ASSOverride::MoveII(x1, y1, x2, y2, t1, t2) => {
+                let (t1, t2) = if t1 == t2 { (0.0, self.duration) } else { (*t1, *t2) };
+                let at = self.progress(t1, t2, 1.0);
+                self.pos.get_or_insert((x1 + (x2 - x1) * at, y1 + (y2 - y1) * at));
+            }
+            ASSOverride::Org(x, y) => {
+                self.org.get_or_insert((*x, *y));
+            }
+            ASSOverride::An(a) if (1..=9).contains(a) => {
+                self.align.get_or_insert(*a);
+            }
+            ASSOverride::A(a) => {
+                self.align.get_or_insert(legacy_alignment(*a));
+            }
+            ASSOverride::Fad(fade_in, fade_out) => {
+                let opacity = self.fade(255.0, 0.0, 255.0, 0.0, *fade_in, self.duration - fade_out, self.duration);
+                self.opacity.get_or_insert(opacity);
+            }
+            ASSOverride::Fade(a1, a2, a3, t1, t2, t3, t4) => {
+                let opacity = self.fade(*a1, *a2, *a3, *t1, *t2, *t3, *t4);
+                self.opacity.get_or_insert(opacity);
+            }
+            ASSOverride::R(name) => {
+                self.current = name.as_deref()
+                    .and_then(|name| self.renderer.sub.v4p_styles.iter().find(|style| style.name == name))
+                    .map(Run::of)
+                    .unwrap_or_else(|| reset.clone());
+            }
+            ASSOverride::TransformI(tags) => self.animate(0.0, self.duration, 1.0, tags, reset),
+            ASSOverride::TransformII(accel, tags) => self.animate(0.0, self.duration, *accel, tags, reset),
+            ASSOverride::TransformIII(t1, t2, tags) => self.animate(*t1, *t2, 1.0, tags, reset),
+            ASSOverride::TransformIV(t1, t2, accel, tags) => self.animate(*t1, *t2, *accel, tags, reset),
+            other => {
+                if let Some(clip) = clip_mask(other) {
+                    self.clip = Some(clip);
+                } else {
+                    self.current.apply(other, 1.0, reset);
+                }
+            }
+        }
+    }
+
+    fn animate(&mut self, t1: f32, t2: f32, accel: f32, tags: &[ASSOverride], reset: &Run) {
+        let progress = self.progress(t1, t2, accel);
+        for tag in tags {
+            self.current.apply(tag, progress, reset);
+        }
+    }
+
+    fn progress(&self, t1: f32, t2: f32, accel: f32) -> f32 {
+        if self.time <= t1 {
+            0.0
+        } else if self.time >= t2 || t2 <= t1 {
+            1.0
+        } else {
+            ((self.time - t1) / (t2 - t1)).powf(accel.max(0.0))
+        }
+    }
+
+    // Opacity from \fade's alpha values (0 opaque) at the current time.
+    #[allow(clippy::too_many_arguments)]
+    fn fade(&self, a1: f32, a2: f32, a3: f32, t1: f32, t2: f32, t3: f32, t4: f32) -> f32 {
+        let t = self.time;
+        let between = |from: f32, to: f32, start: f32, end: f32| {
+            if end <= start { to } else { from + (to - from) * ((t - start) / (end - start)) }
+        };
+        let alpha = if t < t1 {
+            a1
+        } else if t < t2 {
+            between(a1, a2, t1, t2)
+        } else if t < t3 {
+            a2
+        } else if t < t4 {
+            between(a2, a3, t3, t4)
+        } else {
+            a3
+        };
+        1.0 - alpha.clamp(0.0, 255.0) / 255.0
+    }
+
+    fn run(&mut self) -> usize {
+        if self.runs.last().is_none_or(|run| !same_run(run, &self.current)) {
+            self.runs.push(self.current.clone());
+        }
+        self.runs.len() - 1
+    }
+
+    fn text(&mut self, text: &str) {
+        let wrap_style = self.renderer.sub.script_info.wrap_style;
+        let run = self.run();
+        let font = self.renderer.font(&self.runs[run]);
+        let style = self.runs[run].clone();
+        let mut chars = text.chars().peekable();
+        let mut previous = None;
+        while let Some(c) = chars.next() {
+            let c = if c == '\\' {
+                match chars.peek() {
+                    Some('N') => {
+                        chars.next();
+                        self.lines.push(Vec::new());
+                        previous = None;
+                        continue;
+                    }
+                    Some('n') if wrap_style == 2 => {
+                        chars.next();
+                        self.lines.push(Vec::new());
+                        previous = None;
+                        continue;
+                    }
+                    Some('n') => {
+                        chars.next();
+                        ' '
+                    }
+                    Some('h') => {
+                        chars.next();
+                        '\u{a0}'
+                    }
+                    _ => c,
+                }
+            } else {
+                c
+            };
+            let item = match &font {
+                Some(font) => glyph(font, &style, c, &mut previous),
+                None => placeholder(&style, c),
+            };
+            self.lines.last_mut().expect("layout starts with a line").push(Item { run, ..item });
+        }
+    }
+
+    // Drawings count in 2^(p-1) units. Their pen sits at the drawing's own (0, 0), and the line
+    // height they take is their extent below it, so `\an7` puts (0, 0) on `\pos`.
+    fn drawing(&mut self, drawing: &Drawing) {
+        let run = self.run();
+        let style = &self.runs[run];
+        let units = 2f32.powi(style.drawing.max(1) as i32 - 1);
+        let mut shape = drawing.to_cubics();
+        shape.scale(style.scale_x / 100.0 / units, style.scale_y / 100.0 / units);
+        let Some(bounds) = shape.bounding_box() else {
+            return;
+        };
+        let ascent = bounds.y2.max(0.0);
+        shape.translate(0.0, -ascent);
+        let item = Item { shape, advance: bounds.x2.max(0.0), ascent, descent: 0.0, space: false, run };
+        self.lines.last_mut().expect("layout starts with a line").push(item);
+    }
+
+    fn finish(mut self, index: usize, event: &Event) -> EventRender {
+        let (res_x, res_y) = play_res(self.renderer.sub);
+        let margin = |line: u16, style: u16| if line != 0 { line as f32 } else { style as f32 };
+        let (margin_l, margin_r, margin_v) = (
+            margin(event.margin_l, self.style.margin_l),
+            margin(event.margin_r, self.style.margin_r),
+            margin(event.margin_v, self.style.margin_v),
+        );
+        if self.renderer.sub.script_info.wrap_style != 2 {
+            self.lines = std::mem::take(&mut self.lines)
+                .into_iter()
+                .flat_map(|line| wrap(line, res_x - margin_l - margin_r))
+                .collect();
+        }
+        let align = self.align.unwrap_or(if (1..=9).contains(&self.style.alignment) { self.style.alignment } else { 2 });
+        let column = (align - 1) % 3;
+
+        let empty_height = self.current.size * self.current.scale_y / 100.0;
+        let metrics: Vec<(f32, f32, f32)> = self.lines.iter()
+            .map(|line| {
+                let width: f32 = line.iter().map(|item| item.advance).sum();
+                let ascent = line.iter().map(|item| item.ascent).fold(0.0, f32::max);
+                let descent = line.iter().map(|item| item.descent).fold(0.0, f32::max);
+                if line.is_empty() { (0.0, empty_height, 0.0) } else { (width, ascent, descent) }
+            })
+            .collect();
+        let width = metrics.iter().map(|m| m.0).fold(0.0, f32::max);
+        let height: f32 = metrics.iter().map(|m| m.1 + m.2).sum();
+
+        let anchor = self.pos.unwrap_or_else(|| {
+            let x = match column {
+                0 => margin_l,
+                1 => margin_l + (res_x - margin_l - margin_r) / 2.0,
+                _ => res_x - margin_r,
+            };
+            let y = match align {
+                1..=3 => res_y - margin_v,
+                4..=6 => res_y / 2.0,
+                _ => margin_v,
+            };
+            (x, y)
+        });
+        let left = anchor.0 - [0.0, width / 2.0, width][column as usize];
+        let mut top = anchor.1 - match align {
+            1..=3 => height,
+            4..=6 => height / 2.0,
+            _ => 0.0,
+        };
+        let origin = self.org.unwrap_or(anchor);
+        let opacity = self.opacity.unwrap_or(1.0);
+        let border_scale = self.renderer.border_scale;
+
+        let mut passes: [Vec<RenderShape>; 3] = [Vec::new(), Vec::new(), Vec::new()];
+        for (line, (line_width, ascent, descent)) in self.lines.iter().zip(&metrics) {
+            let baseline = top + ascent;
+            let mut pen = left + [0.0, (width - line_width) / 2.0, width - line_width][column as usize];
+            for item in line {
+                let run = &self.runs[item.run];
+                let place = Affine::translate(pen as f64, baseline as f64)
+                    .then(Affine::rotate(run.frz as f64, (origin.0 as f64, origin.1 as f64)));
+                pen += item.advance;
+                if item.space || item.shape.commands.is_empty() {
+                    continue;
+                }
+                let mut shape = item.shape.clone();
+                shape.transform(&place);
+                let border = run.border.0.max(run.border.1).max(0.0) * border_scale;
+                let shadow = (run.shadow.0 * border_scale, run.shadow.1 * border_scale);
+                // BorderStyle 3 draws its shadow and border as boxes below; the glyphs are still filled.
+                if self.style.border_style != 3 {
+                    if shadow != (0.0, 0.0) {
+                        let mut cast = shape.clone();
+                        cast.translate(shadow.0, shadow.1);
+                        passes[0].push(RenderShape { pass: Pass::Shadow, shape: cast, stroke: border, rgba: run.rgba(3, opacity) });
+                    }
+                    if border > 0.0 {
+                        passes[1].push(RenderShape { pass: Pass::Border, shape: shape.clone(), stroke: border, rgba: run.rgba(2, opacity) });
+                    }
+                }
+                passes[2].push(RenderShape { pass: Pass::Fill, shape, stroke: 0.0, rgba: run.rgba(0, opacity) });
+            }
+            top += ascent + descent;
+        }
+        if self.style.border_style == 3 {
+            self.opaque_boxes(&mut passes, &metrics, left, width, anchor.1, height, align, opacity, origin);
+        }
+
+        let [shadows, borders, fills] = passes;
+        EventRender {
+            index,
+            layer: event.layer,
+            shapes: shadows.into_iter().chain(borders).chain(fills).collect(),
+            clip: self.clip,
+        }
+    }
+
+    // BorderStyle 3: each line gets a box in the outline colour, padded by the border, and the
+    // shadow is that box moved. The glyphs are filled on top without an outline. Each box takes its
+    // colours and size from the first run on its line.
+    #[allow(clippy::too_many_arguments)]
+    fn opaque_boxes(
+        &self,
+        passes: &mut [Vec<RenderShape>; 3],
+        metrics: &[(f32, f32, f32)],
+        left: f32,
+        width: f32,
+        anchor_y: f32,
+        height: f32,
+        align: u8,
+        opacity: f32,
+        origin: (f32, f32),
+    ) {
+        let column = ((align - 1) % 3) as usize;
+        let mut top = anchor_y - match align {
+            1..=3 => height,
+            4..=6 => height / 2.0,
+            _ => 0.0,
+        };
+        for (line, (line_width, ascent, descent)) in self.lines.iter().zip(metrics) {
+            let line_top = top;
+            top += ascent + descent;
+            let Some(first) = line.first() else {
+                continue;
+            };
+            let run = &self.runs[first.run];
+            let pad = run.border.0.max(run.border.1).max(0.0) * self.renderer.border_scale;
+            let x = left + [0.0, (width - line_width) / 2.0, width - line_width][column];
+            let mut shape = Drawing::from_rect(Rect { x1: x - pad, y1: line_top - pad, x2: x + line_width + pad, y2: top + pad });
+            shape.transform(&Affine::rotate(run.frz as f64, (origin.0 as f64, origin.1 as f64)));
+            if run.shadow != (0.0, 0.0) {
+                let mut cast = shape.clone();
+                cast.translate(run.shadow.0 * self.renderer.border_scale, run.shadow.1 * self.renderer.border_scale);
+                passes[0].push(RenderShape { pass: Pass::Shadow, shape: cast, stroke: 0.0, rgba: run.rgba(3, opacity) });
+            }
+            passes[1].push(RenderShape { pass: Pass::Border, shape, stroke: 0.0, rgba: run.rgba(2, opacity) });
+        }
+    }
+}
+
+// Two runs with the same look share an entry, so a line of plain text is one run.
+fn same_run(a: &Run, b: &Run) -> bool {
+    a.family == b.family
+        && a.bold == b.bold
+        && a.italic == b.italic
+        && a.size == b.size
+        && a.scale_x == b.scale_x
+        && a.scale_y == b.scale_y
+        && a.spacing == b.spacing
+        && a.colours == b.colours
+        && a.alphas == b.alphas
+        && a.border == b.border
+        && a.shadow == b.shadow
+        && a.frz == b.frz
+        && a.drawing == b.drawing
+}
+
+// \a counts 1-3 bottom, +4 top, +8 middle (SSA); the numpad value libass maps it to.
+fn legacy_alignment(a: u8) -> u8 {
+    let column = (a & 3).max(1);
+    match a & 12 {
+        4 => column + 6,
+        8 => column + 3,
+        _ => column,
+    }
+}
+
+// Sized the way libass sizes fonts, ascender to descender = font size.
+fn glyph(font: &FontRef<'_>, run: &Run, c: char, previous: &mut Option<ab_glyph::GlyphId>) -> Item {
+    let scaled = font.as_scaled(PxScale::from(run.size));
+    let (sx, sy) = (run.scale_x / 100.0, run.scale_y / 100.0);
+    let id = scaled.glyph_id(c);
+    let kern = previous.map(|p| scaled.kern(p, id)).unwrap_or(0.0) * sx;
+    *previous = Some(id);
+    let (h, v) = (scaled.h_scale_factor() * sx, scaled.v_scale_factor() * sy);
+    let point = |p: ab_glyph::Point| (kern + p.x * h, -p.y * v);
+    let mut commands = Vec::new();
+    let mut pen: Option<(f32, f32)> = None;
+    for curve in font.outline(id).map(|outline| outline.curves).unwrap_or_default() {
+        let (start, segment) = match curve {
+            OutlineCurve::Line(a, b) => {
+                let b = point(b);
+                (point(a), DrawingCommand::Line(b.0, b.1))
+            }
+            OutlineCurve::Quad(a, c, b) => {
+                let (a, c, b) = (point(a), point(c), point(b));
+                (a, DrawingCommand::CubicBezier(
+                    a.0 + (c.0 - a.0) * 2.0 / 3.0, a.1 + (c.1 - a.1) * 2.0 / 3.0,
+                    b.0 + (c.0 - b.0) * 2.0 / 3.0, b.1 + (c.1 - b.1) * 2.0 / 3.0,
+                    b.0, b.1,
+                ))
+            }
+            OutlineCurve::Cubic(a, c1, c2, b) => {
+                let (c1, c2, b) = (point(c1), point(c2), point(b));
+                (point(a), DrawingCommand::CubicBezier(c1.0, c1.1, c2.0, c2.1, b.0, b.1))
+            }
+        };
+        if pen != Some(start) {
+            commands.push(DrawingCommand::Move(start.0, start.1));
+        }
+        pen = match segment {
+            DrawingCommand::Line(x, y) | DrawingCommand::CubicBezier(_, _, _, _, x, y) => Some((x, y)),
+            _ => pen,
+        };
+        commands.push(segment);
+    }
+    Item {
+        shape: Drawing { commands },
+        advance: kern + (scaled.h_advance(id) + run.spacing) * sx,
+        ascent: scaled.ascent() * sy,
+        descent: -scaled.descent() * sy,
+        space: c.is_whitespace(),
+        run: 0,
+    }
+}
+
+// Without any font the text still takes room, half the size per character, so the placement of
+// the rest of the line stays right.
+fn placeholder(run: &Run, c: char) -> Item {
+    let height = run.size * run.scale_y / 100.0;
+    Item {
+        shape: Drawing { commands: Vec::new() },
+        advance: (run.size / 2.0 + run.spacing) * run.scale_x / 100.0,
+        ascent: height * 0.8,
+        descent: height * 0.2,
+        space: c.is_whitespace(),
+        run: 0,
+    }
+}
+
+// Breaks at the last space that keeps a line inside `max_width`; the space itself is dropped.
+fn wrap(line: Vec<Item>, max_width: f32) -> Vec<Vec<Item>> {
+    let mut out = Vec::new();
+    let mut current: Vec<Item> = Vec::new();
+    let mut width = 0.0;
+    for item in line {
+        width += item.advance;
+        current.push(item);
+        if width <= max_width || max_width <= 0.0 {
+            continue;
+        }
+        if let Some(space) = current.iter().rposition(|item| item.space && item.shape.commands.is_empty()).filter(|i| *i > 0) {
+            let rest = current.split_off(space + 1);
+            current.pop();
+            out.push(std::mem::replace(&mut current, rest));
+            width = current.iter().map(|item| item.advance).sum();
+        }
+    }
+    out.push(current);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(events: &str) -> SubstationAlpha {
+        let text = format!(
+            "[Script Info]\nPlayResX: 640\nPlayResY: 360\nScaledBorderAndShadow: yes\n\n[V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+            Style: Default,Liberation Mono,40,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,2,2,2,20,20,30,1\n\n\
+            [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n{}",
+            events
+        );
+        SubstationAlpha::parse(&text, true).0
+    }
+
+    fn fallback() -> FontRef<'static> {
+        FontRef::try_from_slice(include_bytes!("../lib/image/testdata/LiberationMono-Regular.ttf")).unwrap()
+    }
+
+    #[test]
+    fn text_sits_on_the_bottom_margin_with_shadow_border_and_fill_passes() {
+        let sub = script("Dialogue: 0,0:00:01.00,0:00:03.00,Default,,0,0,0,,Hi\n");
+        let renderer = Renderer::new(&sub, &[], Some(fallback()), 640.0);
+
+        assert!(renderer.event(0, 500.0).is_none());
+        let event = renderer.event(0, 1500.0).unwrap();
+        let passes: Vec<Pass> = event.shapes.iter().map(|shape| shape.pass).collect();
+        assert_eq!(passes, vec![Pass::Shadow, Pass::Shadow, Pass::Border, Pass::Border, Pass::Fill, Pass::Fill]);
+        let fills: Vec<Rect> = event.shapes[4..].iter().map(|shape| shape.shape.bounding_box().unwrap()).collect();
+        // Centred between the margins, glyph bottoms on the baseline 30 px above the bottom edge
+        // less the font's descent.
+        let (left, right) = (fills[0].x1, fills[1].x2);
+        assert!((((left + right) / 2.0) - 320.0).abs() < 2.0, "{} {}", left, right);
+        assert!(fills.iter().all(|b| b.y2 < 330.0 && b.y2 > 315.0), "{:?}", fills);
+        assert_eq!(event.shapes[0].rgba, [0, 0, 0, 127]);
+        assert_eq!(event.shapes[2].stroke, 2.0);
+    }
+
+    #[test]
+    fn opaque_box_lines_keep_their_glyphs_and_own_colours() {
+        let mut sub = script("Dialogue: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,{\\3c&H0000FF&}Hi\\N{\\3c&H00FF00&}Yo\n");
+        sub.v4p_styles[0].border_style = 3;
+        let renderer = Renderer::new(&sub, &[], Some(fallback()), 640.0);
+
+        let event = renderer.event(0, 500.0).unwrap();
+        let passes: Vec<Pass> = event.shapes.iter().map(|shape| shape.pass).collect();
+        assert_eq!(passes, vec![
+            Pass::Shadow, Pass::Shadow, Pass::Border, Pass::Border,
+            Pass::Fill, Pass::Fill, Pass::Fill, Pass::Fill,
+        ]);
+        assert_eq!(event.shapes[2].rgba, [255, 0, 0, 255]);
+        assert_eq!(event.shapes[3].rgba, [0, 255, 0, 255]);
+        assert!(event.shapes[4..].iter().all(|shape| shape.stroke == 0.0 && shape.rgba == [255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn drawings_follow_pos_an7_rotation_fade_and_clip() {
+        let sub = script(
+            "Dialogue: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,{\\an7\\pos(100,50)\\bord0\\shad0\\frz90\\org(100,50)\\fad(500,0)\\clip(0,0,120,120)\\c&H0000FF&\\p1}m 0 0 l 40 0 40 20 0 20\n",
+        );
+        let renderer = Renderer::new(&sub, &[], None, 640.0);
+
+        let event = renderer.event(0, 250.0).unwrap();
+        assert_eq!(event.shapes.len(), 1);
+        let fill = &event.shapes[0];
+        assert_eq!(fill.rgba, [255, 0, 0, 128]);
+        // 40x20 turned 90° counter-clockwise around its top-left corner.
+        let bounds = fill.shape.bounding_box().unwrap();
+        assert_eq!((bounds.x1, bounds.y1, bounds.x2, bounds.y2), (100.0, 10.0, 120.0, 50.0));
+        assert_eq!(event.clip.as_ref().map(|(clip, inverse)| (clip.as_rect(), *inverse)), Some((Some(Rect { x1: 0.0, y1: 0.0, x2: 120.0, y2: 120.0 }), false)));
+    }
+}
@@ -0,0 +1,277 @@
+// Sections that are not subtitle content but still have to survive a load/stringify round trip:
+// embedded attachments ([Fonts], [Graphics]) and Aegisub's own project state
+// ([Aegisub Project Garbage], [Aegisub Extradata]).
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EmbeddedFile {
+    pub name: String,
+    #[serde(with = "uu_bytes")]
+    pub data: Vec<u8>,
+}
+
+impl EmbeddedFile {
+    // `header` is `fontname` for [Fonts] and `filename` for [Graphics].
+    pub fn stringify(&self, header: &str) -> String {
+        let mut out = format!("{}: {}\n", header, self.name);
+        let encoded = uuencode(&self.data);
+        let chars: Vec<char> = encoded.chars().collect();
+        for chunk in chars.chunks(UU_LINE_LENGTH) {
+            out.extend(chunk);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ProjectGarbage {
+    pub entries: Vec<(String, String)>,
+}
+
+impl ProjectGarbage {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => self.entries.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn audio_file(&self) -> Option<&str> {
+        self.get("Audio File")
+    }
+
+    pub fn video_file(&self) -> Option<&str> {
+        self.get("Video File")
+    }
+
+    pub fn keyframes_file(&self) -> Option<&str> {
+        self.get("Keyframes File")
+    }
+
+    pub fn timecodes_file(&self) -> Option<&str> {
+        self.get("Timecodes File")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stringify(&self) -> String {
+        let mut out = String::new();
+        for (key, value) in &self.entries {
+            out.push_str(&format!("{}: {}\n", key, value));
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ExtradataEncoding {
+    Inline,
+    UUEncoded,
+}
+
+// `value` is kept as bytes because uuencoded values are not guaranteed to be text; `encoding`
+// remembers which form the file used so an untouched entry is written back byte-for-byte.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExtradataEntry {
+    pub id: u32,
+    pub key: String,
+    #[serde(with = "uu_bytes")]
+    pub value: Vec<u8>,
+    pub encoding: ExtradataEncoding,
+}
+
+impl ExtradataEntry {
+    pub fn value_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.value).ok()
+    }
+
+    pub fn stringify(&self) -> String {
+        let value = match self.encoding {
+            ExtradataEncoding::Inline => match std::str::from_utf8(&self.value) {
+                Ok(text) => format!("e{}", inline_encode(text)),
+                Err(_) => format!("u{}", uuencode(&self.value)),
+            },
+            ExtradataEncoding::UUEncoded => format!("u{}", uuencode(&self.value)),
+        };
+        format!("Data: {},{},{}\n", self.id, inline_encode(&self.key), value)
+    }
+}
+
+// `Data: <id>,<key>,<e|u><value>` — the key is always inline-encoded, the value's first byte
+// selects the encoding.
+pub fn parse_extradata_line(data: &str) -> Option<ExtradataEntry> {
+    let mut fields = data.trim_start().splitn(3, ',');
+    let id = fields.next()?.trim().parse().ok()?;
+    let key = inline_decode(fields.next()?);
+    let value = fields.next()?;
+    let (encoding, value) = match value.chars().next()? {
+        'e' => (ExtradataEncoding::Inline, inline_decode(&value[1..]).into_bytes()),
+        'u' => (ExtradataEncoding::UUEncoded, uudecode(&value[1..])),
+        _ => return None,
+    };
+    Some(ExtradataEntry { id, key, value, encoding })
+}
+
+// Aegisub stores the extradata ids an event uses as a leading `{=1=2}` block. Returns the ids and
+// the remaining text; text without a well-formed reference block is returned unchanged.
+pub fn split_extradata_refs(text: &str) -> (Vec<u32>, &str) {
+    let Some(rest) = text.strip_prefix("{=") else {
+        return (Vec::new(), text);
+    };
+    let Some(end) = rest.find('}') else {
+        return (Vec::new(), text);
+    };
+    let mut ids = Vec::new();
+    for id in rest[..end].split('=') {
+        match id.parse() {
+            Ok(id) => ids.push(id),
+            Err(_) => return (Vec::new(), text),
+        }
+    }
+    (ids, &rest[end + 1..])
+}
+
+pub fn format_extradata_refs(ids: &[u32]) -> String {
+    if ids.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from("{");
+    for id in ids {
+        out.push_str(&format!("={}", id));
+    }
+    out.push('}');
+    out
+}
+
+const UU_LINE_LENGTH: usize = 80;
+
+// SSA's variant of uuencoding: every 6 bits become one character offset by 33, with no length
+// prefix per line. A trailing group of 2 or 3 characters carries 1 or 2 bytes.
+pub fn uuencode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = chunk.get(1).copied().unwrap_or(0);
+        let b2 = chunk.get(2).copied().unwrap_or(0);
+        let sextets = [
+            b0 >> 2,
+            ((b0 & 0x03) << 4) | (b1 >> 4),
+            ((b1 & 0x0F) << 2) | (b2 >> 6),
+            b2 & 0x3F,
+        ];
+        for sextet in &sextets[..chunk.len() + 1] {
+            out.push((sextet + 33) as char);
+        }
+    }
+    out
+}
+
+pub fn uudecode(text: &str) -> Vec<u8> {
+    let sextets: Vec<u8> = text
+        .bytes()
+        .filter(|b| (33..=96).contains(b))
+        .map(|b| b - 33)
+        .collect();
+    let mut out = Vec::with_capacity(sextets.len() / 4 * 3 + 2);
+    for group in sextets.chunks(4) {
+        let s = |i: usize| group.get(i).copied().unwrap_or(0);
+        let bytes = [
+            (s(0) << 2) | (s(1) >> 4),
+            ((s(1) & 0x0F) << 4) | (s(2) >> 2),
+            ((s(2) & 0x03) << 6) | s(3),
+        ];
+        out.extend_from_slice(&bytes[..group.len().saturating_sub(1)]);
+    }
+    out
+}
+
+// Attachment and extradata bytes go to JSON in the same uuencoding the file uses, one string
+// without line breaks.
+mod uu_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::uuencode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Ok(super::uudecode(&text))
+    }
+}
+
+// Aegisub's inline string encoding: control characters and the separators `#`, `,`, `:` and `|`
+// become `#XX`.
+pub fn inline_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if (c as u32) <= 0x1F || matches!(c, '#' | ',' | ':' | '|') {
+            out.push_str(&format!("#{:02X}", c as u32));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub fn inline_decode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('#') {
+        out.push_str(&rest[..pos]);
+        let escape = rest.get(pos + 1..pos + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[pos + 3..];
+            }
+            None => {
+                out.push('#');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuencode_round_trips_every_tail_length() {
+        for len in 0..8 {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37 + 200) as u8).collect();
+            assert_eq!(uudecode(&uuencode(&data)), data);
+        }
+        assert_eq!(uuencode(b"Man"), "47&O");
+    }
+
+    #[test]
+    fn extradata_lines_decode_both_encodings() {
+        let inline = parse_extradata_line("1,_aegi_perspective_ambient_plane,e1.5;2#2C0#7C3;4").unwrap();
+        assert_eq!(inline.id, 1);
+        assert_eq!(inline.key, "_aegi_perspective_ambient_plane");
+        assert_eq!(inline.value_str(), Some("1.5;2,0|3;4"));
+        assert_eq!(inline.stringify(), "Data: 1,_aegi_perspective_ambient_plane,e1.5;2#2C0#7C3;4\n");
+
+        let uu = parse_extradata_line(&format!("7,blob,u{}", uuencode(&[0, 255, 1]))).unwrap();
+        assert_eq!(uu.encoding, ExtradataEncoding::UUEncoded);
+        assert_eq!(uu.value, vec![0, 255, 1]);
+    }
+
+    #[test]
+    fn extradata_refs_only_split_well_formed_blocks() {
+        assert_eq!(split_extradata_refs("{=1=22}text"), (vec![1, 22], "text"));
+        assert_eq!(split_extradata_refs(r"{=x}{\i1}text"), (Vec::new(), r"{=x}{\i1}text"));
+        assert_eq!(split_extradata_refs("plain"), (Vec::new(), "plain"));
+        assert_eq!(format_extradata_refs(&[1, 22]), "{=1=22}");
+    }
+}
@@ -0,0 +1,736 @@
+// Offline spellchecking and glossary enforcement for TL files.
+//
+// `Dictionary` reads the Hunspell `.aff`/`.dic` pair of one language (the server keeps them under
+// `DB/config/global/dictionaries`, `tr_TR` first). The subset covered is what word lists for
+// Turkish and the usual Latin-script languages lean on: FLAG (single char, UTF-8, long, num) and
+// AF aliases, SET UTF-8 / ISO8859-1 / ISO8859-9, PFX/SFX with conditions and cross products,
+// suffixes stacked two deep through continuation classes, NEEDAFFIX, FORBIDDENWORD, NOSUGGEST,
+// and TRY/REP for suggestions. Compounding rules are ignored, so compound-only words read as
+// misspelled. Turkish dictionaries (`LANG tr_TR`, or a `tr` file name) lowercase I to ı and İ to i.
+//
+// `Glossary` is the per-anime `GLOSSARY.md` of the repo: the spellings of names, terms and
+// honorifics to keep across episodes, and the wrong spellings to flag.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::libkagami::core::{Event, SubstationAlpha};
+
+pub const DICTIONARIES_DIR: &str = "DB/config/global/dictionaries";
+pub const GLOSSARY_FILE: &str = "GLOSSARY.md";
+const MAX_SUGGESTIONS: usize = 5;
+
+type Flag = u64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FlagMode {
+    Char,
+    Long,
+    Num,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum CondPart {
+    Any,
+    Set(bool, Vec<char>), // (negated, chars)
+}
+
+#[derive(Clone, Debug)]
+struct Affix {
+    flag: Flag,
+    strip: String,
+    cont: Vec<Flag>,
+    condition: Vec<CondPart>,
+    cross: bool,
+}
+
+impl Affix {
+    fn matches(&self, chars: &[char]) -> bool {
+        chars.len() >= self.condition.len()
+            && chars.iter().zip(&self.condition).all(|(c, part)| match part {
+                CondPart::Any => true,
+                CondPart::Set(negated, set) => set.contains(c) != *negated,
+            })
+    }
+
+    // Suffix conditions look at the end of the stem.
+    fn matches_end(&self, stem: &str) -> bool {
+        let chars: Vec<char> = stem.chars().collect();
+        chars.len() >= self.condition.len() && self.matches(&chars[chars.len() - self.condition.len()..])
+    }
+
+    fn matches_start(&self, stem: &str) -> bool {
+        let chars: Vec<char> = stem.chars().take(self.condition.len()).collect();
+        self.matches(&chars)
+    }
+}
+
+pub struct Dictionary {
+    words: HashMap<String, Vec<Vec<Flag>>>,
+    // Keyed by the text the affix adds, so a word only meets the rules that could have made it.
+    prefixes: HashMap<String, Vec<Affix>>,
+    suffixes: HashMap<String, Vec<Affix>>,
+    try_chars: Vec<char>,
+    rep: Vec<(String, String)>,
+    need_affix: Option<Flag>,
+    forbidden: Option<Flag>,
+    no_suggest: Option<Flag>,
+    turkish: bool,
+}
+
+impl Dictionary {
+    // `<dir>/<language>.aff` and `<dir>/<language>.dic`.
+    pub fn load(dir: &Path, language: &str) -> Result<Self, String> {
+        let read = |ext: &str| {
+            let path = dir.join(format!("{}.{}", language, ext));
+            std::fs::read(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))
+        };
+        let mut dictionary = Self::from_bytes(&read("aff")?, &read("dic")?)?;
+        dictionary.turkish |= language.to_ascii_lowercase().starts_with("tr");
+        Ok(dictionary)
+    }
+
+    pub fn from_bytes(aff: &[u8], dic: &[u8]) -> Result<Self, String> {
+        // SET is plain ASCII, so it can be found before the encoding is known.
+        let encoding = String::from_utf8_lossy(aff)
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("SET ").map(|v| v.trim().to_ascii_uppercase()))
+            .unwrap_or_else(|| "UTF-8".to_string());
+        let aff = decode(aff, &encoding)?;
+        let dic = decode(dic, &encoding)?;
+
+        let mut dictionary = Dictionary {
+            words: HashMap::new(),
+            prefixes: HashMap::new(),
+            suffixes: HashMap::new(),
+            try_chars: Vec::new(),
+            rep: Vec::new(),
+            need_affix: None,
+            forbidden: None,
+            no_suggest: None,
+            turkish: false,
+        };
+        let mut mode = FlagMode::Char;
+        let mut aliases: Vec<Vec<Flag>> = Vec::new();
+        // Header lines (`SFX A Y 3`) say whether the class combines with the other side.
+        let mut cross: HashMap<(bool, Flag), bool> = HashMap::new();
+
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let Some(&directive) = fields.first() else {
+                continue;
+            };
+            match (directive, fields.len()) {
+                ("FLAG", 2..) => {
+                    mode = match fields[1] {
+                        "long" => FlagMode::Long,
+                        "num" => FlagMode::Num,
+                        _ => FlagMode::Char,
+                    }
+                }
+                ("LANG", 2..) => dictionary.turkish = matches!(fields[1].get(..2), Some("tr" | "az" | "TR" | "AZ")),
+                ("TRY", 2..) => dictionary.try_chars = fields[1].chars().collect(),
+                ("REP", 3..) => dictionary.rep.push((fields[1].replace('_', " "), fields[2].replace('_', " "))),
+                ("AF", 2..) if fields[1].parse::<usize>().is_err() => aliases.push(parse_flags(fields[1], mode)),
+                ("NEEDAFFIX", 2..) => dictionary.need_affix = parse_flags(fields[1], mode).first().copied(),
+                ("FORBIDDENWORD", 2..) => dictionary.forbidden = parse_flags(fields[1], mode).first().copied(),
+                ("NOSUGGEST", 2..) => dictionary.no_suggest = parse_flags(fields[1], mode).first().copied(),
+                ("PFX" | "SFX", 4..) => {
+                    let suffix = directive == "SFX";
+                    let Some(&flag) = parse_flags(fields[1], mode).first() else {
+                        continue;
+                    };
+                    if fields.len() == 4 && fields[3].parse::<usize>().is_ok() && matches!(fields[2], "Y" | "N") {
+                        cross.insert((suffix, flag), fields[2] == "Y");
+                        continue;
+                    }
+                    let (add, cont) = match fields[3].split_once('/') {
+                        Some((add, cont)) => (add, resolve_flags(cont, mode, &aliases)),
+                        None => (fields[3], Vec::new()),
+                    };
+                    let add = if add == "0" { String::new() } else { add.to_string() };
+                    let affix = Affix {
+                        flag,
+                        strip: if fields[2] == "0" { String::new() } else { fields[2].to_string() },
+                        cont,
+                        condition: fields.get(4).map(|c| parse_condition(c)).unwrap_or_default(),
+                        cross: cross.get(&(suffix, flag)).copied().unwrap_or(false),
+                    };
+                    let table = if suffix { &mut dictionary.suffixes } else { &mut dictionary.prefixes };
+                    table.entry(add).or_default().push(affix);
+                }
+                _ => {}
+            }
+        }
+
+        // The first line is the word count; morphological fields follow the word after whitespace.
+        for line in dic.lines().skip(1) {
+            let Some(entry) = line.split_whitespace().next() else {
+                continue;
+            };
+            let (word, flags) = match entry.split_once('/') {
+                Some((word, flags)) => (word, resolve_flags(flags, mode, &aliases)),
+                None => (entry, Vec::new()),
+            };
+            if !word.is_empty() {
+                dictionary.words.entry(word.to_string()).or_default().push(flags);
+            }
+        }
+        if dictionary.words.is_empty() {
+            return Err("dictionary has no words".to_string());
+        }
+        Ok(dictionary)
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.words.len()
+    }
+
+    // Accepts the word as written, a capitalised word in lower case, and an all-caps word in lower
+    // or title case.
+    pub fn check(&self, word: &str) -> bool {
+        if self.check_form(word) {
+            return true;
+        }
+        let mut chars = word.chars();
+        let Some(first) = chars.next() else {
+            return true;
+        };
+        if !first.is_uppercase() {
+            return false;
+        }
+        let rest: String = chars.collect();
+        let lowered_first = format!("{}{}", self.lowercase(&first.to_string()), rest);
+        if self.check_form(&lowered_first) {
+            return true;
+        }
+        if rest.chars().any(|c| c.is_uppercase()) {
+            let lower = self.lowercase(word);
+            let title = format!("{}{}", first, self.lowercase(&rest));
+            return self.check_form(&lower) || self.check_form(&title);
+        }
+        false
+    }
+
+    // Up to five corrections, one edit away: REP replacements first, then swapped, missing,
+    // mistyped and extra letters from TRY, then a missing space.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        let consider = |candidate: String, out: &mut Vec<String>| {
+            if out.len() < MAX_SUGGESTIONS && candidate != word && !out.contains(&candidate) && self.suggestable(&candidate) {
+                out.push(candidate);
+            }
+        };
+        for (from, to) in &self.rep {
+            let (from, anchored_start, anchored_end) = anchors(from);
+            for (at, _) in word.match_indices(from) {
+                if (anchored_start && at != 0) || (anchored_end && at + from.len() != word.len()) {
+                    continue;
+                }
+                consider(format!("{}{}{}", &word[..at], to, &word[at + from.len()..]), &mut out);
+            }
+        }
+        let chars: Vec<char> = word.chars().collect();
+        let join = |chars: &[char]| chars.iter().collect::<String>();
+        for i in 0..chars.len().saturating_sub(1) {
+            let mut swapped = chars.clone();
+            swapped.swap(i, i + 1);
+            consider(join(&swapped), &mut out);
+        }
+        for i in 0..chars.len() {
+            let mut shorter = chars.clone();
+            shorter.remove(i);
+            consider(join(&shorter), &mut out);
+        }
+        for i in 0..chars.len() {
+            for &c in &self.try_chars {
+                if c != chars[i] {
+                    let mut changed = chars.clone();
+                    changed[i] = c;
+                    consider(join(&changed), &mut out);
+                }
+            }
+        }
+        for i in 0..=chars.len() {
+            for &c in &self.try_chars {
+                let mut longer = chars.clone();
+                longer.insert(i, c);
+                consider(join(&longer), &mut out);
+            }
+        }
+        for i in 1..chars.len() {
+            let (left, right) = (join(&chars[..i]), join(&chars[i..]));
+            if self.suggestable(&left) && self.suggestable(&right) {
+                consider(format!("{} {}", left, right), &mut out);
+            }
+        }
+        out
+    }
+
+    fn suggestable(&self, word: &str) -> bool {
+        let hidden = self.no_suggest.is_some_and(|flag| {
+            self.words.get(word).is_some_and(|homonyms| homonyms.iter().all(|flags| flags.contains(&flag)))
+        });
+        !hidden && self.check(word)
+    }
+
+    fn check_form(&self, word: &str) -> bool {
+        if let Some(homonyms) = self.words.get(word) {
+            if self.forbidden.is_some_and(|flag| homonyms.iter().any(|flags| flags.contains(&flag))) {
+                return false;
+            }
+            if homonyms.iter().any(|flags| self.need_affix.is_none_or(|flag| !flags.contains(&flag))) {
+                return true;
+            }
+        }
+        self.suffixed(word, None, None, true) || self.prefixed(word)
+    }
+
+    fn stem_has(&self, stem: &str, flag: Flag, also: Option<Flag>) -> bool {
+        self.words.get(stem).is_some_and(|homonyms| {
+            homonyms.iter().any(|flags| {
+                flags.contains(&flag)
+                    && also.is_none_or(|also| flags.contains(&also))
+                    && self.forbidden.is_none_or(|forbidden| !flags.contains(&forbidden))
+            })
+        })
+    }
+
+    // Strips one suffix. `outer` is the flag of a suffix already stripped after this one, which
+    // this one has to allow through its continuation classes; `prefix` is a prefix flag the stem
+    // also needs (a cross product).
+    fn suffixed(&self, word: &str, outer: Option<Flag>, prefix: Option<Flag>, stack: bool) -> bool {
+        let boundaries = word.char_indices().map(|(i, _)| i).chain(std::iter::once(word.len()));
+        for at in boundaries {
+            let Some(rules) = self.suffixes.get(&word[at..]) else {
+                continue;
+            };
+            for affix in rules {
+                if outer.is_some_and(|outer| !affix.cont.contains(&outer)) || (prefix.is_some() && !affix.cross) {
+                    continue;
+                }
+                let stem = format!("{}{}", &word[..at], affix.strip);
+                if stem.is_empty() || !affix.matches_end(&stem) {
+                    continue;
+                }
+                if self.stem_has(&stem, affix.flag, prefix) {
+                    return true;
+                }
+                if stack && self.suffixed(&stem, Some(affix.flag), prefix, false) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn prefixed(&self, word: &str) -> bool {
+        for (at, _) in word.char_indices() {
+            let Some(rules) = self.prefixes.get(&word[..at]) else {
+                continue;
+            };
+            for affix in rules {
+                let stem = format!("{}{}", affix.strip, &word[at..]);
+                if !affix.matches_start(&stem) {
+                    continue;
+                }
+                if self.stem_has(&stem, affix.flag, None) {
+                    return true;
+                }
+                if affix.cross && self.suffixed(&stem, None, Some(affix.flag), true) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn lowercase(&self, text: &str) -> String {
+        text.chars()
+            .flat_map(|c| match c {
+                'I' if self.turkish => vec!['ı'],
+                'İ' => vec!['i'],
+                _ => c.to_lowercase().collect(),
+            })
+            .collect()
+    }
+}
+
+fn anchors(pattern: &str) -> (&str, bool, bool) {
+    let start = pattern.starts_with('^');
+    let end = pattern.len() > 1 && pattern.ends_with('$');
+    let pattern = pattern.strip_prefix('^').unwrap_or(pattern);
+    (pattern.strip_suffix('$').filter(|_| end).unwrap_or(pattern), start, end)
+}
+
+// ISO-8859-9 is Latin-1 with the six Turkish letters in place of Icelandic ones.
+fn decode(bytes: &[u8], encoding: &str) -> Result<String, String> {
+    match encoding {
+        "UTF-8" | "UTF8" => String::from_utf8(bytes.to_vec()).map_err(|_| "dictionary is not valid UTF-8".to_string()),
+        "ISO8859-1" | "ISO-8859-1" => Ok(bytes.iter().map(|&b| b as char).collect()),
+        "ISO8859-9" | "ISO-8859-9" => Ok(bytes
+            .iter()
+            .map(|&b| match b {
+                0xD0 => 'Ğ',
+                0xDD => 'İ',
+                0xDE => 'Ş',
+                0xF0 => 'ğ',
+                0xFD => 'ı',
+                0xFE => 'ş',
+                _ => b as char,
+            })
+            .collect()),
+        other => Err(format!("unsupported dictionary encoding {}", other)),
+    }
+}
+
+fn parse_flags(text: &str, mode: FlagMode) -> Vec<Flag> {
+    match mode {
+        FlagMode::Char => text.chars().map(|c| c as Flag).collect(),
+        FlagMode::Long => text
+            .chars()
+            .collect::<Vec<_>>()
+            .chunks(2)
+            .map(|pair| pair.iter().fold(0, |flag, &c| (flag << 21) | c as Flag))
+            .collect(),
+        FlagMode::Num => text.split(',').filter_map(|n| n.trim().parse().ok()).collect(),
+    }
+}
+
+// With AF aliases a word's flags are the 1-based number of an `AF` line.
+fn resolve_flags(text: &str, mode: FlagMode, aliases: &[Vec<Flag>]) -> Vec<Flag> {
+    if !aliases.is_empty()
+        && let Ok(index) = text.parse::<usize>()
+    {
+        return aliases.get(index.wrapping_sub(1)).cloned().unwrap_or_default();
+    }
+    parse_flags(text, mode)
+}
+
+fn parse_condition(text: &str) -> Vec<CondPart> {
+    if text == "." {
+        return Vec::new();
+    }
+    let mut parts = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => parts.push(CondPart::Any),
+            '[' => {
+                let mut set: Vec<char> = chars.by_ref().take_while(|&c| c != ']').collect();
+                let negated = set.first() == Some(&'^');
+                if negated {
+                    set.remove(0);
+                }
+                parts.push(CondPart::Set(negated, set));
+            }
+            c => parts.push(CondPart::Set(false, vec![c])),
+        }
+    }
+    parts
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct GlossaryEntry {
+    preferred: String,
+    variants: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Glossary {
+    entries: Vec<GlossaryEntry>,
+}
+
+impl Glossary {
+    // One entry per line: the spelling to use, optionally followed by `=` and the spellings to
+    // flag, comma separated. Markdown list markers, `#` headings and blank lines are skipped, so
+    // the file reads as a list on Forgejo.
+    //
+    //     # Names
+    //     - Rimuru-sama = Rimuru sama, Rimurusama
+    //     - Shion
+    pub fn parse(text: &str) -> Self {
+        let entries = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.trim_start_matches(['-', '*', '+']).trim().trim_matches('`'))
+            .filter_map(|line| {
+                let (preferred, variants) = line.split_once('=').unwrap_or((line, ""));
+                let preferred = preferred.trim().trim_matches('`').to_string();
+                let variants = variants
+                    .split(',')
+                    .map(|v| v.trim().trim_matches('`').to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect();
+                (!preferred.is_empty()).then_some(GlossaryEntry { preferred, variants })
+            })
+            .collect();
+        Glossary { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Glossary spellings are always correct, whatever the dictionary says.
+    fn accepts(&self, word: &str) -> bool {
+        let word = fold(word);
+        self.entries.iter().any(|entry| {
+            entry.preferred.split(|c: char| c.is_whitespace() || c == '-').any(|part| fold(part) == word)
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpellIssue {
+    Unknown { word: String, suggestions: Vec<String> },
+    Glossary { found: String, preferred: String },
+}
+
+impl std::fmt::Display for SpellIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpellIssue::Unknown { word, suggestions } if suggestions.is_empty() => write!(f, "{}?", word),
+            SpellIssue::Unknown { word, suggestions } => write!(f, "{}? ({})", word, suggestions.join(", ")),
+            SpellIssue::Glossary { found, preferred } => write!(f, "{} → {}", found, preferred),
+        }
+    }
+}
+
+// `event` is the 1-based position in `SubstationAlpha::events`, as in lint findings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineSpelling {
+    pub event: usize,
+    pub issues: Vec<SpellIssue>,
+}
+
+impl std::fmt::Display for LineSpelling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let issues: Vec<String> = self.issues.iter().map(|issue| issue.to_string()).collect();
+        write!(f, "{}: {}", self.event, issues.join("; "))
+    }
+}
+
+// Every line with something to fix. Either check can run alone: without a dictionary only the
+// glossary is enforced, and an empty glossary only spellchecks.
+pub fn spellcheck(sub: &SubstationAlpha, dictionary: Option<&Dictionary>, glossary: &Glossary) -> Vec<LineSpelling> {
+    let mut known: HashMap<String, Option<Vec<String>>> = HashMap::new();
+    let mut out = Vec::new();
+    for (i, event) in sub.events.iter().enumerate() {
+        let Some(text) = visible_text(event) else {
+            continue;
+        };
+        // Paired with where they start so the report reads in line order.
+        let mut issues: Vec<(usize, SpellIssue)> = Vec::new();
+        let mut covered: Vec<(usize, usize)> = Vec::new();
+        for entry in &glossary.entries {
+            for (start, end) in find_folded(&text, &entry.preferred) {
+                let found = &text[start..end];
+                covered.push((start, end));
+                if found != entry.preferred && found != capitalise(&entry.preferred) {
+                    issues.push((start, SpellIssue::Glossary { found: found.to_string(), preferred: entry.preferred.clone() }));
+                }
+            }
+            for variant in &entry.variants {
+                for (start, end) in find_folded(&text, variant) {
+                    if covered.iter().any(|&(s, e)| start < e && s < end) {
+                        continue;
+                    }
+                    covered.push((start, end));
+                    issues.push((start, SpellIssue::Glossary { found: text[start..end].to_string(), preferred: entry.preferred.clone() }));
+                }
+            }
+        }
+        if let Some(dictionary) = dictionary {
+            for (start, word) in words(&text) {
+                if covered.iter().any(|&(s, e)| start >= s && start < e) || glossary.accepts(word) {
+                    continue;
+                }
+                let verdict = known.entry(word.to_string()).or_insert_with(|| {
+                    (!dictionary.check(word)).then(|| dictionary.suggest(word))
+                });
+                if let Some(suggestions) = verdict
+                    && !issues.iter().any(|(_, issue)| matches!(issue, SpellIssue::Unknown { word: w, .. } if w == word))
+                {
+                    issues.push((start, SpellIssue::Unknown { word: word.to_string(), suggestions: suggestions.clone() }));
+                }
+            }
+        }
+        if !issues.is_empty() {
+            issues.sort_by_key(|(start, _)| *start);
+            let issues = issues.into_iter().map(|(_, issue)| issue).collect();
+            out.push(LineSpelling { event: i + 1, issues });
+        }
+    }
+    out
+}
+
+// The text a viewer reads, with `\N`/`\n`/`\h` as spaces. Works on both parsed and raw-text
+// loads; lines that switch to drawing mode are skipped.
+fn visible_text(event: &Event) -> Option<String> {
+    let raw = event.raw_text();
+    if event.has_drawing() {
+        return None;
+    }
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let block: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let drawing = block.split('\\').any(|tag| {
+                    tag.strip_prefix('p').and_then(|n| n.trim().parse::<u32>().ok()).is_some_and(|n| n > 0)
+                });
+                if drawing {
+                    return None;
+                }
+            }
+            '\\' if matches!(chars.peek(), Some('N' | 'n' | 'h')) => {
+                chars.next();
+                out.push(' ');
+            }
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+// Runs of letters, with apostrophes and hyphens inside them. Only the part before an apostrophe
+// is checked (Turkish writes suffixes on proper names as `Ahmet'in`), and hyphenated words are
+// checked part by part. Anything with a digit is skipped.
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    let joiner = |c: char| matches!(c, '\'' | '’' | '-');
+    let boundaries = text.char_indices().chain(std::iter::once((text.len(), ' ')));
+    for (at, c) in boundaries {
+        match (start, c.is_alphanumeric() || joiner(c)) {
+            (None, true) if !joiner(c) => start = Some(at),
+            (Some(s), false) => {
+                let token = text[s..at].trim_end_matches(joiner);
+                start = None;
+                if token.chars().any(|c| c.is_ascii_digit()) {
+                    continue;
+                }
+                let token = token.split(['\'', '’']).next().unwrap_or(token);
+                let mut offset = s;
+                for part in token.split('-') {
+                    if part.chars().count() > 1 {
+                        out.push((offset, part));
+                    }
+                    offset += part.len() + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+// Case-insensitive and dotless-i-insensitive, so `Ichigo`, `ichigo` and Turkish `İchigo` match.
+fn fold(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            'I' | 'İ' | 'ı' => vec!['i'],
+            _ => c.to_lowercase().collect(),
+        })
+        .collect()
+}
+
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// Byte ranges of whole-word matches of `needle` in `text`, compared folded.
+fn find_folded(text: &str, needle: &str) -> Vec<(usize, usize)> {
+    let needle: Vec<char> = fold(needle).chars().collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    // One folded char per source char keeps offsets lined up; the rare multi-char lowercase
+    // (only İ, handled in `fold`) never reaches here.
+    let folded: Vec<(usize, char)> = text
+        .char_indices()
+        .map(|(at, c)| (at, fold(&c.to_string()).chars().next().unwrap_or(c)))
+        .collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= folded.len() {
+        let matches = folded[i..i + needle.len()].iter().zip(&needle).all(|((_, a), b)| a == b);
+        let before = i == 0 || !folded[i - 1].1.is_alphanumeric();
+        let after = folded.get(i + needle.len()).is_none_or(|(_, c)| !c.is_alphanumeric());
+        if matches && before && after {
+            let end = folded.get(i + needle.len()).map(|(at, _)| *at).unwrap_or(text.len());
+            out.push((folded[i].0, end));
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8\nLANG tr_TR\nTRY aeıioöuüklmnrst\nREP 1\nREP ğ g\n\
+        SFX A Y 2\nSFX A 0 lar/B [aıou][^aeıioöuü]\nSFX A 0 ler/B [eiöü][^aeıioöuü]\n\
+        SFX B Y 2\nSFX B 0 ı [aıou][^aeıioöuü]\nSFX B 0 i [eiöü][^aeıioöuü]\n";
+    const DIC: &str = "4\nkitap/A\nev/A\nmerhaba\nIrmak\n";
+
+    fn script(lines: &[&str]) -> SubstationAlpha {
+        let events: String = lines
+            .iter()
+            .map(|text| format!("Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{}\n", text))
+            .collect();
+        SubstationAlpha::parse(&format!("[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n{}", events), false).0
+    }
+
+    #[test]
+    fn affixes_stack_and_turkish_case_rules_apply() {
+        let dictionary = Dictionary::from_bytes(AFF.as_bytes(), DIC.as_bytes()).unwrap();
+
+        assert!(dictionary.check("kitap"));
+        assert!(dictionary.check("kitaplar"));
+        assert!(dictionary.check("kitapları"));
+        assert!(dictionary.check("evleri"));
+        assert!(!dictionary.check("evlari"));
+        assert!(!dictionary.check("kitapı"));
+        assert!(dictionary.check("Merhaba"));
+        assert!(dictionary.check("MERHABA"));
+        // Turkish lowercase of I is ı, so IRMAK is only right because the word is stored as Irmak.
+        assert!(dictionary.check("IRMAK"));
+        assert_eq!(dictionary.suggest("kitaplr"), vec!["kitaplar"]);
+        assert!(dictionary.suggest("merhba").contains(&"merhaba".to_string()));
+    }
+
+    #[test]
+    fn reports_unknown_words_and_glossary_spellings_per_line() {
+        let dictionary = Dictionary::from_bytes(AFF.as_bytes(), DIC.as_bytes()).unwrap();
+        let glossary = Glossary::parse("# Names\n- Rimuru-sama = Rimuru sama, Rimurusama\n- Shion\n");
+        let sub = script(&[
+            "{\\i1}Merhaba Rimuru-sama, kitaplar!",
+            "merhba rimuru sama\\NShion'un evleri",
+            "{\\p1}m 0 0 l 10 0 10 10",
+            "shion Rimurusama 2024",
+        ]);
+
+        let report = spellcheck(&sub, Some(&dictionary), &glossary);
+        let lines: Vec<String> = report.iter().map(|line| line.to_string()).collect();
+        assert_eq!(lines, vec![
+            "2: merhba? (merhaba); rimuru sama → Rimuru-sama",
+            "4: shion → Shion; Rimurusama → Rimuru-sama",
+        ]);
+        assert_eq!(spellcheck(&sub, None, &Glossary::default()), vec![]);
+    }
+}
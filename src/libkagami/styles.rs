@@ -0,0 +1,219 @@
+// Server style libraries: a styles-only ASS (`[Script Info]` for the PlayRes the styles were made
+// at, then `[V4+ Styles]`) whose styles are copied into episode scripts by name, scaled to each
+// script's resolution the same way a merge scales a TS.
+
+use crate::libkagami::core::{ResampleMode, SubstationAlpha};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StyleChanges {
+    pub replaced: Vec<String>,
+    pub added: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub not_found: Vec<String>,
+}
+
+impl StyleChanges {
+    pub fn is_empty(&self) -> bool {
+        self.replaced.is_empty() && self.added.is_empty()
+    }
+
+    // "replaced Default, Sign; added Title" — empty when nothing changed.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.replaced.is_empty() {
+            parts.push(format!("replaced {}", self.replaced.join(", ")));
+        }
+        if !self.added.is_empty() {
+            parts.push(format!("added {}", self.added.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
+// Brings `other` to `playresx`x`playresy`. Same-ratio resolutions just scale; another ratio needs
+// a resample mode, as in `pnass --merge`. A PlayRes of 0 is taken as already matching.
+fn fit_resolution(
+    other: &mut SubstationAlpha,
+    playresx: u16,
+    playresy: u16,
+    resample: Option<ResampleMode>,
+) -> Result<(), String> {
+    let (x, y) = (other.script_info.playresx, other.script_info.playresy);
+    if x == 0 || y == 0 || playresx == 0 || playresy == 0 || (x == playresx && y == playresy) {
+        return Ok(());
+    }
+    if x as u32 * playresy as u32 == y as u32 * playresx as u32 {
+        return other.scale(playresx, playresy);
+    }
+    match resample {
+        Some(mode) => other.resample(playresx, playresy, mode),
+        None => Err(format!(
+            "style library is {}x{} but the script is {}x{}; pick a resample mode",
+            x, y, playresx, playresy
+        )),
+    }
+}
+
+// Replaces every style of `sub` that the library also defines, and adds the library styles the
+// script's events use but do not define, plus the ones named in `add`. Names match exactly, as
+// libass looks styles up.
+pub fn apply_styles(
+    sub: &mut SubstationAlpha,
+    mut library: SubstationAlpha,
+    add: &[String],
+    resample: Option<ResampleMode>,
+) -> Result<StyleChanges, String> {
+    fit_resolution(&mut library, sub.script_info.playresx, sub.script_info.playresy, resample)?;
+    let mut changes = StyleChanges::default();
+    for style in &mut sub.v4p_styles {
+        let Some(replacement) = library.v4p_styles.iter().find(|s| s.name == style.name) else {
+            continue;
+        };
+        if style.same_definition(replacement) {
+            changes.unchanged.push(style.name.clone());
+        } else {
+            *style = replacement.clone();
+            changes.replaced.push(style.name.clone());
+        }
+    }
+
+    let mut wanted: Vec<String> = sub
+        .events
+        .iter()
+        .map(|event| event.style.clone())
+        .filter(|name| !sub.v4p_styles.iter().any(|s| &s.name == name))
+        .collect();
+    wanted.extend(add.iter().cloned());
+    for name in wanted {
+        if sub.v4p_styles.iter().any(|s| s.name == name) {
+            continue;
+        }
+        match library.v4p_styles.iter().find(|s| s.name == name) {
+            Some(style) => {
+                sub.v4p_styles.push(style.clone());
+                changes.added.push(name);
+            }
+            None if add.contains(&name) && !changes.not_found.contains(&name) => changes.not_found.push(name),
+            None => {}
+        }
+    }
+    Ok(changes)
+}
+
+// Adds or replaces `source`'s styles in `library`, scaled to the library's PlayRes. An empty
+// library takes the source's PlayRes.
+pub fn import_styles(
+    library: &mut SubstationAlpha,
+    mut source: SubstationAlpha,
+    resample: Option<ResampleMode>,
+) -> Result<StyleChanges, String> {
+    if library.v4p_styles.is_empty() {
+        library.script_info.playresx = source.script_info.playresx;
+        library.script_info.playresy = source.script_info.playresy;
+        library.script_info.layout_res_x = source.script_info.playresx;
+        library.script_info.layout_res_y = source.script_info.playresy;
+    }
+    fit_resolution(&mut source, library.script_info.playresx, library.script_info.playresy, resample)?;
+    let mut changes = StyleChanges::default();
+    for style in source.v4p_styles {
+        match library.v4p_styles.iter_mut().find(|s| s.name == style.name) {
+            Some(existing) if existing.same_definition(&style) => changes.unchanged.push(style.name),
+            Some(existing) => {
+                changes.replaced.push(style.name.clone());
+                *existing = style;
+            }
+            None => {
+                changes.added.push(style.name.clone());
+                library.v4p_styles.push(style);
+            }
+        }
+    }
+    Ok(changes)
+}
+
+pub fn remove_styles(library: &mut SubstationAlpha, names: &[String]) -> Vec<String> {
+    let mut removed = Vec::new();
+    library.v4p_styles.retain(|style| {
+        let drop = names.contains(&style.name);
+        if drop {
+            removed.push(style.name.clone());
+        }
+        !drop
+    });
+    removed
+}
+
+// The library as it is stored: header and styles only, whatever else the imported file carried.
+pub fn library_text(library: &SubstationAlpha) -> String {
+    let mut text = String::from("[Script Info]\n");
+    text.push_str(&library.script_info.stringify());
+    text.push_str("\n[V4+ Styles]\n");
+    text.push_str("Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n");
+    for style in &library.v4p_styles {
+        text.push_str(&style.stringify());
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: &str = "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding";
+
+    fn script(res: (u16, u16), styles: &[&str], events: &[&str]) -> SubstationAlpha {
+        let mut text = format!("[Script Info]\nPlayResX: {}\nPlayResY: {}\n\n[V4+ Styles]\n{}\n", res.0, res.1, FORMAT);
+        for style in styles {
+            text.push_str(&format!("Style: {}\n", style));
+        }
+        text.push_str("\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n");
+        for style in events {
+            text.push_str(&format!("Dialogue: 0,0:00:01.00,0:00:02.00,{},,0,0,0,,line\n", style));
+        }
+        SubstationAlpha::parse(&text, true).0
+    }
+
+    #[test]
+    fn library_styles_replace_by_name_and_fill_in_missing_ones_at_the_script_resolution() {
+        let library = script(
+            (1280, 720),
+            &[
+                "Default,Gandhi Sans,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,20,20,30,1",
+                "Sign,Arial,40,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,0,0,8,10,10,10,1",
+                "Title,Arial,80,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,-1,0,0,0,100,100,0,0,1,4,0,8,10,10,10,1",
+            ],
+            &[],
+        );
+        let mut sub = script(
+            (1920, 1080),
+            &["Default,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,3,0,2,30,30,45,1"],
+            &["Default", "Sign"],
+        );
+
+        let changes = apply_styles(&mut sub, library, &["Title".to_string(), "Nope".to_string()], None).unwrap();
+
+        assert_eq!(changes.replaced, vec!["Default"]);
+        assert_eq!(changes.added, vec!["Sign", "Title"]);
+        assert_eq!(changes.not_found, vec!["Nope"]);
+        let default = &sub.v4p_styles[0];
+        assert_eq!((default.fontname.as_str(), default.fontsize, default.outline), ("Gandhi Sans", 72, 3.0));
+        assert_eq!((default.margin_l, default.margin_v), (30, 45));
+        assert_eq!(sub.v4p_styles[2].fontsize, 120);
+    }
+
+    #[test]
+    fn importing_an_identical_style_changes_nothing_and_another_ratio_needs_a_mode() {
+        let style = "Default,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,3,0,2,30,30,45,1";
+        let mut library = script((1920, 1080), &[style], &[]);
+
+        let changes = import_styles(&mut library, script((1920, 1080), &[style], &[]), None).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(changes.unchanged, vec!["Default"]);
+
+        assert!(import_styles(&mut library, script((640, 480), &[style], &[]), None).is_err());
+        let changes = import_styles(&mut library, script((640, 480), &[style], &[]), Some(ResampleMode::Letterbox)).unwrap();
+        assert_eq!(changes.replaced, vec!["Default"]);
+        assert_eq!(remove_styles(&mut library, &["Default".to_string()]), vec!["Default"]);
+        assert!(library_text(&library).ends_with("Encoding\n"));
+    }
+}
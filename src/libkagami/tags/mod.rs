@@ -12,15 +12,18 @@ pub mod stringify;
 pub mod state;
 pub mod transform;
 
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum ASSText {
     Override(ASSOverride),
     Drawing(Drawing),
     RawText(String),
 }
 
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct ASSLine {
+    // Derived from `data`, so it is left out of JSON and rebuilt on load.
+    #[serde(skip)]
     pub current_overrides: Vec<ASSOverride>,
     pub data: Vec<ASSText>,
 }
@@ -357,7 +360,7 @@ fn trim_tags_without_text_after(data: &mut Vec<ASSText>) {
     data.truncate(last_text + 1);
 }
 
-fn final_overrides(data: &[ASSText], start: &[ASSOverride]) -> Vec<ASSOverride> {
+pub(crate) fn final_overrides(data: &[ASSText], start: &[ASSOverride]) -> Vec<ASSOverride> {
     let mut current = start.to_vec();
     for item in data {
         let ASSText::Override(ov) = item else {
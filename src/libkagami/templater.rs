@@ -0,0 +1,897 @@
+// The common subset of Aegisub's kara-templater, so OP/ED effects can be generated without opening
+// Aegisub. Templates are Comment lines whose Effect is `template <class> [modifiers]` and apply to
+// the karaoke lines of the same style; `code once` lines set variables first. Lua is not run: code
+// is `name = expression` statements and `!...!` takes the same expressions (arithmetic, `..`, the
+// `math` functions, `retime` and `relayer`). Positions come from the style's font metrics through
+// ab_glyph, placed the way karaskel places them.
+//
+// As in Aegisub, previous `fx` lines are dropped, generated lines get the Effect `fx` and karaoke
+// lines become Comment lines with the Effect `karaoke`, so running it again regenerates the effect.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use crate::libkagami::complex::types::AssTime;
+use crate::libkagami::core::{normalize_font_name, Event, SubstationAlpha};
+use crate::libkagami::fontcheck::{find_style, FontLibrary, FontRequest};
+use crate::libkagami::karaoke::{syllables, Syllable};
+use crate::libkagami::tags::ASSLine;
+
+#[derive(Clone, Debug, Default)]
+pub struct TemplaterOutcome {
+    pub templates: usize,
+    pub lines: usize,
+    pub generated: usize,
+    pub removed: usize,
+    pub warnings: Vec<String>,
+}
+
+impl TemplaterOutcome {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} templates on {} karaoke lines: {} fx lines ({} old ones removed)",
+            self.templates, self.lines, self.generated, self.removed
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Class {
+    PreLine,
+    Line,
+    Syl,
+    Char,
+    Furi,
+}
+
+struct Template {
+    class: Class,
+    style: String,
+    layer: u16,
+    text: String,
+    noblank: bool,
+    notext: bool,
+    all: bool,
+    loops: u32,
+}
+
+pub fn apply_templates(sub: &mut SubstationAlpha, font_roots: &[PathBuf]) -> Result<TemplaterOutcome, String> {
+    let mut outcome = TemplaterOutcome::default();
+    let mut templates = Vec::new();
+    let mut globals: HashMap<String, Value> = HashMap::new();
+    for comment in &sub.comments {
+        let effect = comment.effect.trim().to_ascii_lowercase();
+        let mut words = effect.split_whitespace();
+        match words.next() {
+            Some("template") => {
+                templates.push(parse_template(comment, words, &mut outcome.warnings)?);
+            }
+            Some("code") if words.next() == Some("once") => {
+                let mut scope = Scope::new(globals, (0, 0), (0, 0));
+                run_code(&comment.text.stringify(), &mut scope)?;
+                globals = scope.vars;
+            }
+            Some("code") => outcome.warnings.push(format!(
+                "`{}` skipped: only `code once` is supported",
+                comment.effect.trim()
+            )),
+            _ => {}
+        }
+    }
+    if templates.is_empty() {
+        return Err("no `template` Comment lines in the script".to_string());
+    }
+    outcome.templates = templates.len();
+
+    let templated = |style: &str| templates.iter().any(|t| t.all || t.style == style);
+    let is_source = |event: &Event, effect: &str| {
+        event.effect.trim().eq_ignore_ascii_case(effect) && !event.is_stamp() && templated(&event.style)
+    };
+    let families: BTreeSet<String> = sub
+        .events
+        .iter()
+        .chain(&sub.comments)
+        .filter_map(|event| find_style(sub, &event.style))
+        .map(|style| normalize_font_name(&style.fontname))
+        .collect();
+    let mut library = FontLibrary::load(font_roots, &families);
+    for font in &sub.fonts {
+        library.add(&font.name, font.data.clone());
+    }
+
+    let mut events = Vec::with_capacity(sub.events.len());
+    let mut sources = Vec::new();
+    let mut line_index = 0;
+    for event in std::mem::take(&mut sub.events) {
+        if event.effect.trim().eq_ignore_ascii_case("fx") {
+            outcome.removed += 1;
+        } else if is_source(&event, "") || is_source(&event, "karaoke") {
+            line_index += 1;
+            events.extend(generate(sub, &library, &templates, &globals, &event, line_index, &mut outcome.warnings)?);
+            sources.push(event);
+        } else {
+            events.push(event);
+        }
+    }
+    // Lines Aegisub already commented out: their old `fx` lines were removed above.
+    for comment in &sub.comments {
+        if is_source(comment, "karaoke") {
+            line_index += 1;
+            events.extend(generate(sub, &library, &templates, &globals, comment, line_index, &mut outcome.warnings)?);
+        }
+    }
+    outcome.lines = line_index;
+    outcome.generated = events.iter().filter(|event| event.effect == "fx").count();
+    for mut source in sources {
+        source.effect = "karaoke".to_string();
+        sub.comments.push(source);
+    }
+    sub.events = events;
+    Ok(outcome)
+}
+
+fn parse_template<'a>(
+    comment: &Event,
+    mut words: impl Iterator<Item = &'a str>,
+    warnings: &mut Vec<String>,
+) -> Result<Template, String> {
+    let class = match words.next() {
+        Some("pre-line") => Class::PreLine,
+        Some("line") => Class::Line,
+        Some("syl") => Class::Syl,
+        Some("char") => Class::Char,
+        Some("furi") => Class::Furi,
+        other => {
+            return Err(format!(
+                "template `{}`: expected pre-line, line, syl, char or furi, got `{}`",
+                comment.effect.trim(),
+                other.unwrap_or("")
+            ))
+        }
+    };
+    let mut template = Template {
+        class,
+        style: comment.style.clone(),
+        layer: comment.layer,
+        text: comment.text.stringify(),
+        noblank: false,
+        notext: false,
+        all: false,
+        loops: 1,
+    };
+    while let Some(word) = words.next() {
+        match word {
+            "noblank" => template.noblank = true,
+            "notext" => template.notext = true,
+            "all" => template.all = true,
+            "loop" | "repeat" => {
+                template.loops = words
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| format!("template `{}`: `loop` needs a positive count", comment.effect.trim()))?;
+            }
+            other => warnings.push(format!("template `{}`: ignoring `{}`", comment.effect.trim(), other)),
+        }
+    }
+    Ok(template)
+}
+
+// Text extents at a style's size, scale and spacing. ab_glyph's pixel scale is ascender minus
+// descender, the same height libass sizes fonts to. Without the font a half-em per character is
+// used, which keeps templates running but puts positions off.
+struct Metrics<'f> {
+    font: Option<FontRef<'f>>,
+    size: f64,
+    scale_x: f64,
+    scale_y: f64,
+    spacing: f64,
+}
+
+impl Metrics<'_> {
+    fn width(&self, text: &str) -> f64 {
+        let chars = text.chars().count() as f64;
+        let advance: f64 = match &self.font {
+            Some(font) => {
+                let scaled = font.as_scaled(PxScale::from(self.size as f32));
+                text.chars().map(|c| scaled.h_advance(font.glyph_id(c)) as f64).sum()
+            }
+            None => self.size * 0.5 * chars,
+        };
+        (advance + self.spacing * chars) * self.scale_x / 100.0
+    }
+
+    fn height(&self) -> f64 {
+        self.size * self.scale_y / 100.0
+    }
+
+    fn furigana(&self) -> Self {
+        Metrics { font: self.font.clone(), size: self.size / 2.0, ..*self }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct TextBox {
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+}
+
+// A syllable, character or furigana part as templates see it; times are line-relative.
+struct Unit {
+    index: usize,
+    start: i64,
+    end: i64,
+    text: String,
+    stripped: String,
+    place: TextBox,
+}
+
+impl Unit {
+    // What `noblank` skips: nothing to show, or no time to show it in.
+    fn is_blank(&self) -> bool {
+        self.stripped.is_empty() || self.end <= self.start
+    }
+}
+
+struct Line<'e> {
+    event: &'e Event,
+    index: usize,
+    align: u8,
+    start: i64,
+    end: i64,
+    place: TextBox,
+    text: String,
+    syllables: Vec<Unit>,
+    chars: Vec<Unit>,
+    furigana: Vec<Unit>,
+}
+
+fn layout<'e>(
+    sub: &SubstationAlpha,
+    library: &FontLibrary,
+    event: &'e Event,
+    index: usize,
+    warnings: &mut Vec<String>,
+) -> Option<Line<'e>> {
+    let Some(style) = find_style(sub, &event.style) else {
+        warnings.push(format!("style `{}` not found; its karaoke lines were skipped", event.style));
+        return None;
+    };
+    let face = library.find(&FontRequest::new(&style.fontname, style.bold, style.italic));
+    let font = face.and_then(|face| face.font());
+    if font.is_none() {
+        let warning = format!("font `{}` not found; `{}` positions are estimated", style.fontname, style.name);
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+    let metrics = Metrics {
+        font,
+        size: style.fontsize as f64,
+        scale_x: style.scale_x as f64,
+        scale_y: style.scale_y as f64,
+        spacing: style.spacing as f64,
+    };
+
+    let parsed = ASSLine::from_str_store(&event.text.stringify(), vec![]);
+    let syls: Vec<Syllable> = syllables(&parsed);
+    let text: String = syls.iter().map(|syl| syl.text.as_str()).collect();
+    let (res_x, res_y) = match (sub.script_info.playresx, sub.script_info.playresy) {
+        (0, _) | (_, 0) => (384.0, 288.0),
+        (x, y) => (x as f64, y as f64),
+    };
+    let margin = |line: u16, style: u16| if line != 0 { line as f64 } else { style as f64 };
+    let (margin_l, margin_r, margin_v) = (
+        margin(event.margin_l, style.margin_l),
+        margin(event.margin_r, style.margin_r),
+        margin(event.margin_v, style.margin_v),
+    );
+    let align = if (1..=9).contains(&style.alignment) { style.alignment } else { 2 };
+    let width = metrics.width(text.trim());
+    let height = metrics.height();
+    let left = match align % 3 {
+        1 => margin_l,
+        2 => (res_x - margin_l - margin_r - width) / 2.0 + margin_l,
+        _ => res_x - margin_r - width,
+    };
+    let top = match (align - 1) / 3 {
+        2 => margin_v,
+        1 => (res_y - height) / 2.0,
+        _ => res_y - margin_v - height,
+    };
+    let place = TextBox { left, top, width, height };
+
+    let furi_metrics = metrics.furigana();
+    let mut line = Line {
+        event,
+        index,
+        align,
+        start: event.start.total_centiseconds() as i64 * 10,
+        end: event.end.total_centiseconds() as i64 * 10,
+        place,
+        text: text.clone(),
+        syllables: Vec::new(),
+        chars: Vec::new(),
+        furigana: Vec::new(),
+    };
+    let mut cursor = left - metrics.width(&text[..text.len() - text.trim_start().len()]);
+    for syl in &syls {
+        let syl_left = cursor + metrics.width(syl.prespace());
+        let syl_place = TextBox { left: syl_left, top, width: metrics.width(syl.stripped()), height };
+        let mut char_left = syl_left;
+        for c in syl.stripped().chars() {
+            let c = c.to_string();
+            let char_width = metrics.width(&c);
+            line.chars.push(Unit {
+                index: line.chars.len() + 1,
+                start: syl.start_ms as i64,
+                end: syl.end_ms() as i64,
+                stripped: c.clone(),
+                text: c,
+                place: TextBox { left: char_left, top, width: char_width, height },
+            });
+            char_left += char_width;
+        }
+        // Furigana parts sit side by side, centred over their syllable, right above the line.
+        let furi_width: f64 = syl.furigana.iter().map(|furi| furi_metrics.width(&furi.text)).sum();
+        let mut furi_left = syl_left + (syl_place.width - furi_width) / 2.0;
+        for furi in &syl.furigana {
+            let furi_place = TextBox {
+                left: furi_left,
+                top: top - furi_metrics.height(),
+                width: furi_metrics.width(&furi.text),
+                height: furi_metrics.height(),
+            };
+            furi_left += furi_place.width;
+            line.furigana.push(Unit {
+                index: line.furigana.len() + 1,
+                start: furi.start_ms as i64,
+                end: (furi.start_ms + furi.duration_ms) as i64,
+                text: furi.text.clone(),
+                stripped: furi.text.trim().to_string(),
+                place: furi_place,
+            });
+        }
+        line.syllables.push(Unit {
+            index: syl.index,
+            start: syl.start_ms as i64,
+            end: syl.end_ms() as i64,
+            text: syl.text.clone(),
+            stripped: syl.stripped().to_string(),
+            place: syl_place,
+        });
+        cursor += metrics.width(&syl.text);
+    }
+    Some(line)
+}
+
+fn generate(
+    sub: &SubstationAlpha,
+    library: &FontLibrary,
+    templates: &[Template],
+    globals: &HashMap<String, Value>,
+    event: &Event,
+    index: usize,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Event>, String> {
+    let Some(line) = layout(sub, library, event, index, warnings) else {
+        return Ok(Vec::new());
+    };
+    let mut out = Vec::new();
+    for template in templates.iter().filter(|t| t.all || t.style == event.style) {
+        for j in 1..=template.loops {
+            let scope_for = |unit: Option<&Unit>| {
+                let mut scope = Scope::new(globals.clone(), (line.start, line.end), (line.start, line.end));
+                line_vars(&mut scope.vars, &line);
+                if let Some(unit) = unit {
+                    scope.syl = (line.start + unit.start, line.start + unit.end);
+                    unit_vars(&mut scope.vars, unit, line.align);
+                }
+                scope.vars.insert("j".to_string(), Value::Num(j as f64));
+                scope.vars.insert("maxj".to_string(), Value::Num(template.loops as f64));
+                scope
+            };
+            match template.class {
+                Class::PreLine => {
+                    let mut scope = scope_for(None);
+                    let mut text = expand(&template.text, &mut scope)?;
+                    if !template.notext {
+                        text.push_str(&line.text);
+                    }
+                    out.push(fx_line(sub, &line, template, &scope, text));
+                }
+                Class::Line => {
+                    let mut text = String::new();
+                    let mut last = scope_for(None);
+                    for unit in line.syllables.iter().filter(|unit| !template.noblank || !unit.is_blank()) {
+                        let mut scope = scope_for(Some(unit));
+                        text.push_str(&expand(&template.text, &mut scope)?);
+                        if !template.notext {
+                            text.push_str(&unit.text);
+                        }
+                        last.retime = scope.retime.or(last.retime);
+                        last.layer = scope.layer.or(last.layer);
+                    }
+                    out.push(fx_line(sub, &line, template, &last, text));
+                }
+                Class::Syl | Class::Char | Class::Furi => {
+                    let units = match template.class {
+                        Class::Syl => &line.syllables,
+                        Class::Char => &line.chars,
+                        _ => &line.furigana,
+                    };
+                    for unit in units.iter().filter(|unit| !template.noblank || !unit.is_blank()) {
+                        let mut scope = scope_for(Some(unit));
+                        let mut text = expand(&template.text, &mut scope)?;
+                        if !template.notext {
+                            text.push_str(&unit.stripped);
+                        }
+                        out.push(fx_line(sub, &line, template, &scope, text));
+                    }
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn fx_line(sub: &SubstationAlpha, line: &Line, template: &Template, scope: &Scope, text: String) -> Event {
+    let (start, end) = scope.retime.unwrap_or((line.start, line.end));
+    let time = |ms: i64| AssTime::from_centiseconds((ms.max(0) / 10) as u64);
+    let overrides = find_style(sub, &line.event.style).map(|style| style.to_overrides()).unwrap_or_default();
+    Event {
+        layer: scope.layer.unwrap_or(template.layer),
+        start: time(start),
+        end: time(end.max(start)),
+        effect: "fx".to_string(),
+        text: ASSLine::from_str_store(&text, overrides),
+        extradata: Vec::new(),
+        ..line.event.clone()
+    }
+}
+
+const PLACES: [&str; 10] = ["left", "center", "right", "top", "middle", "bottom", "width", "height", "x", "y"];
+
+fn place_values(place: &TextBox, align: u8) -> [f64; 10] {
+    let (left, top, width, height) = (place.left, place.top, place.width, place.height);
+    let x = match align % 3 {
+        1 => left,
+        2 => left + width / 2.0,
+        _ => left + width,
+    };
+    let y = match (align - 1) / 3 {
+        2 => top,
+        1 => top + height / 2.0,
+        _ => top + height,
+    };
+    [left, left + width / 2.0, left + width, top, top + height / 2.0, top + height, width, height, x, y]
+}
+
+// `$lleft`, `line.left`, ... and, until a syllable overrides them, the plain `$left`, `$start`, ...
+fn line_vars(vars: &mut HashMap<String, Value>, line: &Line) {
+    let event = line.event;
+    let mut put = |name: &str, value: Value| {
+        vars.insert(name.to_string(), value);
+    };
+    for (name, value) in PLACES.iter().zip(place_values(&line.place, line.align)) {
+        put(&format!("l{}", name), Value::Num(value));
+        put(&format!("line.{}", name), Value::Num(value));
+        put(name, Value::Num(value));
+    }
+    let (start, end) = (line.start as f64, line.end as f64);
+    for (names, value) in [
+        (["lstart", "line.start_time", "start"], start),
+        (["lend", "line.end_time", "end"], end),
+        (["ldur", "line.duration", "dur"], end - start),
+        (["lmid", "line.mid", "mid"], start + (end - start) / 2.0),
+        (["li", "line.i", "i"], line.index as f64),
+    ] {
+        for name in names {
+            put(name, Value::Num(value));
+        }
+    }
+    put("kdur", Value::Num((end - start) / 10.0));
+    put("layer", Value::Num(event.layer as f64));
+    put("line.layer", Value::Num(event.layer as f64));
+    put("syln", Value::Num(line.syllables.len() as f64));
+    put("margin_l", Value::Num(event.margin_l as f64));
+    put("margin_r", Value::Num(event.margin_r as f64));
+    put("margin_v", Value::Num(event.margin_v as f64));
+    put("style", Value::Str(event.style.clone()));
+    put("actor", Value::Str(event.name.clone()));
+    put("line.text_stripped", Value::Str(line.text.trim().to_string()));
+}
+
+fn unit_vars(vars: &mut HashMap<String, Value>, unit: &Unit, align: u8) {
+    let mut put = |name: &str, value: Value| {
+        vars.insert(name.to_string(), value);
+    };
+    for (name, value) in PLACES.iter().zip(place_values(&unit.place, align)) {
+        put(&format!("s{}", name), Value::Num(value));
+        put(&format!("syl.{}", name), Value::Num(value));
+        put(name, Value::Num(value));
+    }
+    let (start, end) = (unit.start as f64, unit.end as f64);
+    for (names, value) in [
+        (["sstart", "syl.start_time", "start"], start),
+        (["send", "syl.end_time", "end"], end),
+        (["sdur", "syl.duration", "dur"], end - start),
+        (["skdur", "syl.kdur", "kdur"], (end - start) / 10.0),
+        (["smid", "syl.mid", "mid"], start + (end - start) / 2.0),
+        (["si", "syl.i", "i"], unit.index as f64),
+    ] {
+        for name in names {
+            put(name, Value::Num(value));
+        }
+    }
+    put("syl.text", Value::Str(unit.text.clone()));
+    put("syl.text_stripped", Value::Str(unit.stripped.clone()));
+}
+
+// `$name` first, then `!expression!`, as kara-templater does; unknown `$names` stay as written.
+fn expand(template: &str, scope: &mut Scope) -> Result<String, String> {
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(at) = rest.find('$') {
+        text.push_str(&rest[..at]);
+        let name_len = rest[at + 1..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len() - at - 1);
+        let name = &rest[at + 1..at + 1 + name_len];
+        match scope.vars.get(name) {
+            Some(value) if !name.is_empty() => text.push_str(&value.text()),
+            _ => {
+                text.push('$');
+                text.push_str(name);
+            }
+        }
+        rest = &rest[at + 1 + name_len..];
+    }
+    text.push_str(rest);
+
+    let parts: Vec<&str> = text.split('!').collect();
+    if parts.len().is_multiple_of(2) {
+        return Err(format!("template `{}`: unbalanced `!`", template));
+    }
+    let mut out = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i % 2 == 0 {
+            out.push_str(part);
+        } else {
+            let value = evaluate(part, scope).map_err(|e| format!("template `{}`: `!{}!`: {}", template, part, e))?;
+            out.push_str(&value.text());
+        }
+    }
+    Ok(out)
+}
+
+fn run_code(code: &str, scope: &mut Scope) -> Result<(), String> {
+    for statement in code.split([';', '\n']).map(str::trim).filter(|s| !s.is_empty()) {
+        let statement = statement.strip_prefix("local ").unwrap_or(statement);
+        let assignment = statement
+            .split_once('=')
+            .filter(|(name, _)| is_identifier(name.trim()))
+            .filter(|(_, expr)| !expr.starts_with('='));
+        let Some((name, expr)) = assignment else {
+            return Err(format!("`code once`: can't run `{}`; only `name = expression` is supported", statement));
+        };
+        let value = evaluate(expr, scope).map_err(|e| format!("`code once`: `{}`: {}", statement, e))?;
+        scope.vars.insert(name.trim().to_string(), value);
+    }
+    Ok(())
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Num(f64),
+    Str(String),
+}
+
+impl Value {
+    fn num(&self) -> Result<f64, String> {
+        match self {
+            Value::Num(n) => Ok(*n),
+            Value::Str(s) => s.trim().parse().map_err(|_| format!("`{}` is not a number", s)),
+        }
+    }
+
+    // Whole numbers print without a fraction, as Lua prints them in templates.
+    fn text(&self) -> String {
+        match self {
+            Value::Num(n) if n.fract() == 0.0 && n.abs() < 1e15 => format!("{}", *n as i64),
+            Value::Num(n) => {
+                let text = format!("{:.3}", n);
+                text.trim_end_matches('0').trim_end_matches('.').to_string()
+            }
+            Value::Str(s) => s.clone(),
+        }
+    }
+}
+
+// Variables plus the side effects of `retime` and `relayer`; times are absolute milliseconds.
+struct Scope {
+    vars: HashMap<String, Value>,
+    line: (i64, i64),
+    syl: (i64, i64),
+    retime: Option<(i64, i64)>,
+    layer: Option<u16>,
+}
+
+impl Scope {
+    fn new(vars: HashMap<String, Value>, line: (i64, i64), syl: (i64, i64)) -> Self {
+        Self { vars, line, syl, retime: None, layer: None }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(f64),
+    Str(String),
+    Name(String),
+    Op(&'static str),
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || (chars[i] == '.' && chars.get(i + 1) != Some(&'.'))) {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(number.parse().map_err(|_| format!("bad number `{}`", number))?));
+        } else if c == '"' || c == '\'' {
+            let end = chars[i + 1..].iter().position(|&q| q == c).ok_or("unterminated string")?;
+            tokens.push(Token::Str(chars[i + 1..i + 1 + end].iter().collect()));
+            i += end + 2;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else if c == '.' && chars.get(i + 1) == Some(&'.') {
+            tokens.push(Token::Op(".."));
+            i += 2;
+        } else {
+            let op = ["+", "-", "*", "/", "%", "^", "(", ")", ","]
+                .into_iter()
+                .find(|op| op.starts_with(c))
+                .ok_or_else(|| format!("unexpected `{}`", c))?;
+            tokens.push(Token::Op(op));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+fn evaluate(src: &str, scope: &mut Scope) -> Result<Value, String> {
+    let mut parser = Parser { tokens: tokenize(src)?, pos: 0, scope };
+    let value = parser.concat()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(token) => Err(format!("unexpected {:?}", token)),
+    }
+}
+
+// Precedence follows Lua: `..` below `+ -`, below `* / %`, below unary minus, below `^`.
+struct Parser<'s> {
+    tokens: Vec<Token>,
+    pos: usize,
+    scope: &'s mut Scope,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, op: &str) -> bool {
+        let matched = matches!(self.tokens.get(self.pos), Some(Token::Op(o)) if *o == op);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn concat(&mut self) -> Result<Value, String> {
+        let mut value = self.additive()?;
+        while self.eat("..") {
+            let right = self.additive()?;
+            value = Value::Str(value.text() + &right.text());
+        }
+        Ok(value)
+    }
+
+    fn additive(&mut self) -> Result<Value, String> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat("+") {
+                value = Value::Num(value.num()? + self.term()?.num()?);
+            } else if self.eat("-") {
+                value = Value::Num(value.num()? - self.term()?.num()?);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Value, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat("*") {
+                value = Value::Num(value.num()? * self.unary()?.num()?);
+            } else if self.eat("/") {
+                value = Value::Num(value.num()? / self.unary()?.num()?);
+            } else if self.eat("%") {
+                let (a, b) = (value.num()?, self.unary()?.num()?);
+                value = Value::Num(a - (a / b).floor() * b);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        if self.eat("-") {
+            return Ok(Value::Num(-self.unary()?.num()?));
+        }
+        let base = self.atom()?;
+        if self.eat("^") {
+            return Ok(Value::Num(base.num()?.powf(self.unary()?.num()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Value, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("expression ends early")?;
+        self.pos += 1;
+        match token {
+            Token::Num(n) => Ok(Value::Num(n)),
+            Token::Str(s) => Ok(Value::Str(s)),
+            Token::Op("(") => {
+                let value = self.concat()?;
+                if !self.eat(")") {
+                    return Err("missing `)`".to_string());
+                }
+                Ok(value)
+            }
+            Token::Name(name) if self.eat("(") => {
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.concat()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        if !self.eat(",") {
+                            return Err(format!("missing `)` after `{}(`", name));
+                        }
+                    }
+                }
+                self.call(&name, &args)
+            }
+            Token::Name(name) if name == "math.pi" => Ok(Value::Num(std::f64::consts::PI)),
+            Token::Name(name) => self.scope.vars.get(&name).cloned().ok_or_else(|| format!("unknown name `{}`", name)),
+            Token::Op(op) => Err(format!("unexpected `{}`", op)),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
+        let num = |i: usize| -> Result<f64, String> {
+            args.get(i).ok_or_else(|| format!("`{}` needs {} arguments", name, i + 1))?.num()
+        };
+        let result = match name {
+            "math.floor" => num(0)?.floor(),
+            "math.ceil" => num(0)?.ceil(),
+            "math.abs" => num(0)?.abs(),
+            "math.sqrt" => num(0)?.sqrt(),
+            "math.sin" => num(0)?.sin(),
+            "math.cos" => num(0)?.cos(),
+            "math.rad" => num(0)?.to_radians(),
+            "math.deg" => num(0)?.to_degrees(),
+            "math.min" | "math.max" => {
+                let mut best = num(0)?;
+                for i in 1..args.len() {
+                    best = if name == "math.min" { best.min(num(i)?) } else { best.max(num(i)?) };
+                }
+                best
+            }
+            "tostring" => return Ok(Value::Str(args.first().map(Value::text).unwrap_or_default())),
+            "relayer" => {
+                self.scope.layer = Some(num(0)?.max(0.0) as u16);
+                return Ok(Value::Str(String::new()));
+            }
+            "retime" => {
+                let mode = args.first().map(Value::text).unwrap_or_default();
+                let (a, b) = (num(1).unwrap_or(0.0) as i64, num(2).unwrap_or(0.0) as i64);
+                let ((line_start, line_end), (syl_start, syl_end)) = (self.scope.line, self.scope.syl);
+                let (start, end) = match mode.as_str() {
+                    "syl" => (syl_start, syl_end),
+                    "presyl" => (syl_start, syl_start),
+                    "postsyl" => (syl_end, syl_end),
+                    "line" => (line_start, line_end),
+                    "preline" => (line_start, line_start),
+                    "postline" => (line_end, line_end),
+                    "start2syl" => (line_start, syl_start),
+                    "syl2end" => (syl_end, line_end),
+                    "abs" => (0, 0),
+                    other => return Err(format!("unknown retime mode `{}`", other)),
+                };
+                self.scope.retime = Some((start + a, end + b));
+                return Ok(Value::Str(String::new()));
+            }
+            other => return Err(format!("unknown function `{}`", other)),
+        };
+        Ok(Value::Num(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_syllable_lines_from_templates_and_comments_out_the_karaoke() {
+        let dir = std::env::temp_dir().join(format!("pandora_templater_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("LiberationMono-Regular.ttf"),
+            include_bytes!("../lib/image/testdata/LiberationMono-Regular.ttf"),
+        )
+        .unwrap();
+        let script = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+            Style: Kara,Liberation Mono,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,8,10,10,20,1\n\n\
+            [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Comment: 0,0:00:00.00,0:00:00.00,Kara,,0,0,0,code once,lift = 10; fade = 2 * 100\n\
+            Comment: 1,0:00:00.00,0:00:00.00,Kara,,0,0,0,template syl noblank,{\\an5\\pos($scenter,!$smiddle - lift!)\\fad(!fade!,0)!retime(\"syl\", -100, 0)!}\n\
+            Dialogue: 0,0:00:10.00,0:00:12.00,Kara,,0,0,0,,{\\k50}ab {\\k100}cd\n\
+            Dialogue: 0,0:00:13.00,0:00:14.00,Kara,,0,0,0,fx,{\\pos(1,1)}stale\n\
+            Dialogue: 0,0:00:13.00,0:00:14.00,Default,,0,0,0,,untouched\n";
+        let (mut sub, _) = SubstationAlpha::parse(script, true);
+
+        let outcome = apply_templates(&mut sub, std::slice::from_ref(&dir)).unwrap();
+
+        assert!(outcome.warnings.is_empty(), "{:?}", outcome.warnings);
+        assert_eq!((outcome.templates, outcome.lines, outcome.generated, outcome.removed), (1, 1, 2, 1));
+        // Liberation Mono advances 1229 of its 2320 units of height: 31.78px a character at 60px.
+        let fx: Vec<String> = sub.events.iter().filter(|e| e.effect == "fx").map(|e| e.stringify()).collect();
+        assert_eq!(fx[0], "Dialogue: 1,0:00:09.90,0:00:10.50,Kara,,0,0,0,fx,{\\an5\\pos(912.323,40)\\fad(200,0)}ab\n");
+        assert_eq!(fx[1], "Dialogue: 1,0:00:10.40,0:00:11.50,Kara,,0,0,0,fx,{\\an5\\pos(1007.677,40)\\fad(200,0)}cd\n");
+        assert!(sub.events.iter().any(|e| e.raw_text() == "untouched"));
+        assert!(sub.comments.iter().any(|e| e.effect == "karaoke" && e.style == "Kara"));
+
+        // Written out and loaded again, the templates and the commented source regenerate the same lines.
+        let output = sub.stringify();
+        assert!(output.contains("Comment: 0,0:00:10.00,0:00:12.00,Kara,,0,0,0,karaoke,{\\k50}ab {\\k100}cd\n"), "{}", output);
+        let (mut reloaded, _) = SubstationAlpha::parse(&output, true);
+        let again = apply_templates(&mut reloaded, std::slice::from_ref(&dir)).unwrap();
+        assert_eq!((again.templates, again.lines, again.generated, again.removed), (1, 1, 2, 2));
+        let refx: Vec<String> = reloaded.events.iter().filter(|e| e.effect == "fx").map(|e| e.stringify()).collect();
+        assert_eq!(refx, fx);
+        assert_eq!(reloaded.comments.iter().filter(|e| e.effect == "karaoke").count(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn expressions_follow_lua_precedence_and_concatenation() {
+        let mut scope = Scope::new(HashMap::new(), (1000, 3000), (1500, 2000));
+        run_code("local base = 2 ^ 3 * 2; label = \"x\" .. base + 1", &mut scope).unwrap();
+
+        assert_eq!(scope.vars["base"], Value::Num(16.0));
+        assert_eq!(expand("!label!|!math.floor(7 / 2) % 2!|!base / 3!", &mut scope).unwrap(), "x17|1|5.333");
+        assert_eq!(expand("!retime(\"presyl\", -200, 50)!", &mut scope).unwrap(), "");
+        assert_eq!(scope.retime, Some((1300, 1550)));
+        assert!(expand("!nope!", &mut scope).is_err());
+    }
+}
@@ -0,0 +1,827 @@
+use crate::libkagami::complex::overrides::ASSOverride;
+use crate::libkagami::complex::types::{AssColour, AssTime};
+use crate::libkagami::core::{Event, PandoraMeta, ScriptInfo, SubstationAlpha, V4pStyle};
+use crate::libkagami::sections::ProjectGarbage;
+use crate::libkagami::tags::state::{already_active, upsert_override};
+use crate::libkagami::tags::stringify::stringify_override;
+use crate::libkagami::tags::{ASSLine, ASSText};
+
+// Frame rate MicroDVD timings are read at when the file does not open with a `{1}{1}fps` line.
+pub const DEFAULT_MICRODVD_FPS: f64 = 23.976;
+
+const PLAY_RES_X: u16 = 1920;
+const PLAY_RES_Y: u16 = 1080;
+
+#[derive(Clone, Debug, Default)]
+pub struct TextExportOptions {
+    // Cues with identical start and end times are written as one multi-line cue.
+    pub merge_simultaneous: bool,
+}
+
+// Imported scripts get one Default style, since none of these formats carry a stylesheet ASS can use.
+fn default_style() -> V4pStyle {
+    V4pStyle {
+        name: "Default".to_string(),
+        fontname: "Arial".to_string(),
+        fontsize: 60,
+        colours: [
+            AssColour::opaque_white(),
+            AssColour::new(0x00, 0x00, 0x00, 0xFF),
+            AssColour::new(0x00, 0x00, 0x00, 0x00),
+            AssColour::new(0x00, 0x00, 0x00, 0x00),
+        ],
+        bold: false,
+        italic: false,
+        underline: false,
+        strikeout: false,
+        scale_x: 100,
+        scale_y: 100,
+        spacing: 0.0,
+        angle: 0.0,
+        border_style: 1,
+        outline: 3.75,
+        shadow: 0.0,
+        alignment: 2,
+        margin_l: 50,
+        margin_r: 50,
+        margin_v: 38,
+        encoding: 1,
+    }
+}
+
+fn text_script(title: &str, events: Vec<Event>) -> SubstationAlpha {
+    SubstationAlpha {
+        script_info: ScriptInfo {
+            title: title.to_string(),
+            script_type: "v4.00+".to_string(),
+            wrap_style: 0,
+            scaled_border_and_shadow: true,
+            playresx: PLAY_RES_X,
+            playresy: PLAY_RES_Y,
+            ycbcr_matrix: "TV.709".to_string(),
+            layout_res_x: PLAY_RES_X,
+            layout_res_y: PLAY_RES_Y,
+        },
+        v4p_styles: vec![default_style()],
+        events,
+        comments: Vec::new(),
+        pandora_meta: PandoraMeta::default(),
+        fonts: Vec::new(),
+        graphics: Vec::new(),
+        project_garbage: ProjectGarbage::default(),
+        extradata: Vec::new(),
+    }
+}
+
+fn text_event(start_ms: u64, end_ms: u64, actor: String, out: &AssTextBuilder) -> Event {
+    let mut text = ASSLine::from_str_store(&out.text, default_style().to_overrides());
+    // The parser drops tags with no text after them; closing tags are kept so the line still
+    // reads like its source markup (`<i>world</i>` becomes `{\i1}world{\i0}`).
+    if text.data.iter().any(|item| matches!(item, ASSText::RawText(_))) {
+        for ov in &out.closing {
+            if !already_active(&text.current_overrides, ov) {
+                upsert_override(&mut text.current_overrides, ov.clone());
+                text.data.push(ASSText::Override(ov.clone()));
+            }
+        }
+    }
+    Event {
+        layer: 0,
+        start: AssTime::from_centiseconds((start_ms + 5) / 10),
+        end: AssTime::from_centiseconds((end_ms + 5) / 10),
+        style: "Default".to_string(),
+        name: actor,
+        margin_l: 0,
+        margin_r: 0,
+        margin_v: 0,
+        effect: String::new(),
+        text,
+        extradata: Vec::new(),
+    }
+}
+
+// Builds ASS event text while the source markup is walked. Tags are written through the same
+// stringifier the serializer uses, then the whole line is parsed back into an ASSLine.
+#[derive(Default)]
+struct AssTextBuilder {
+    text: String,
+    // Tags written since the last text, which the parser would otherwise trim.
+    closing: Vec<ASSOverride>,
+}
+
+impl AssTextBuilder {
+    fn tag(&mut self, ov: ASSOverride) {
+        self.text.push_str("{\\");
+        self.text.push_str(&stringify_override(&ov));
+        self.text.push('}');
+        self.closing.push(ov);
+    }
+
+    fn raw(&mut self, s: &str) {
+        if !s.is_empty() {
+            self.closing.clear();
+        }
+        for ch in s.chars() {
+            match ch {
+                '{' => self.text.push_str("\\{"),
+                '}' => self.text.push_str("\\}"),
+                _ => self.text.push(ch),
+            }
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.text.push_str("\\N");
+        self.closing.clear();
+    }
+}
+
+fn parse_timestamp_ms(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (clock, fraction) = match s.rfind([',', '.']) {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => (s, "0"),
+    };
+    let parts: Vec<&str> = clock.split(':').collect();
+    let (h, m, sec) = match parts.as_slice() {
+        [h, m, sec] => (h.parse::<u64>().ok()?, m.parse::<u64>().ok()?, sec.parse::<u64>().ok()?),
+        [m, sec] => (0, m.parse::<u64>().ok()?, sec.parse::<u64>().ok()?),
+        _ => return None,
+    };
+    if m > 59 || sec > 59 || fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits: String = fraction.chars().chain("000".chars()).take(3).collect();
+    let ms = digits.parse::<u64>().ok()?;
+    Some(((h * 60 + m) * 60 + sec) * 1000 + ms)
+}
+
+// `00:00:01,000 --> 00:00:02,000` plus whatever follows the end time (SRT coordinates, WebVTT
+// cue settings), which is returned untouched.
+fn parse_timing_line(line: &str) -> Option<(u64, u64, &str)> {
+    let (start, rest) = line.split_once("-->")?;
+    let rest = rest.trim_start();
+    let end_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let start = parse_timestamp_ms(start)?;
+    let end = parse_timestamp_ms(&rest[..end_len])?;
+    Some((start, end, rest[end_len..].trim()))
+}
+
+struct RawCue<'a> {
+    start: u64,
+    end: u64,
+    settings: &'a str,
+    lines: Vec<&'a str>,
+}
+
+// Shared block reader for SRT and WebVTT: a timing line opens a cue and a blank line closes it.
+// Index and identifier lines are skipped because they never parse as a timing. WebVTT NOTE,
+// STYLE and REGION blocks are skipped whole.
+fn read_cues(text: &str, webvtt: bool) -> Vec<RawCue<'_>> {
+    let text = text.trim_start_matches('\u{feff}');
+    let mut cues: Vec<RawCue> = Vec::new();
+    let mut in_cue = false;
+    let mut skipping = false;
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            in_cue = false;
+            skipping = false;
+            continue;
+        }
+        if skipping {
+            continue;
+        }
+        if in_cue {
+            cues.last_mut().unwrap().lines.push(line);
+            continue;
+        }
+        if webvtt && ["NOTE", "STYLE", "REGION"].iter().any(|kw| {
+            line == *kw || line.starts_with(&format!("{} ", kw)) || line.starts_with(&format!("{}\t", kw))
+        }) {
+            skipping = true;
+            continue;
+        }
+        if let Some((start, end, settings)) = parse_timing_line(line) {
+            cues.push(RawCue { start, end, settings, lines: Vec::new() });
+            in_cue = true;
+        }
+    }
+    cues
+}
+
+fn named_colour(name: &str) -> Option<u32> {
+    let rgb = match name.to_ascii_lowercase().as_str() {
+        "white" => 0xFFFFFF,
+        "black" => 0x000000,
+        "red" => 0xFF0000,
+        "lime" => 0x00FF00,
+        "green" => 0x008000,
+        "blue" => 0x0000FF,
+        "yellow" => 0xFFFF00,
+        "cyan" | "aqua" => 0x00FFFF,
+        "magenta" | "fuchsia" => 0xFF00FF,
+        "silver" => 0xC0C0C0,
+        "gray" | "grey" => 0x808080,
+        "orange" => 0xFFA500,
+        _ => return None,
+    };
+    Some(rgb_to_bgr(rgb))
+}
+
+fn rgb_to_bgr(rgb: u32) -> u32 {
+    ((rgb & 0xFF) << 16) | (rgb & 0xFF00) | ((rgb >> 16) & 0xFF)
+}
+
+// HTML colour values as SRT `<font color>` writes them: `#RRGGBB`, `#RGB`, bare hex or a name.
+fn parse_html_colour(value: &str) -> Option<u32> {
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() == 6 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return u32::from_str_radix(hex, 16).ok().map(rgb_to_bgr);
+    }
+    if hex.len() == 3 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        let expanded: String = hex.chars().flat_map(|c| [c, c]).collect();
+        return u32::from_str_radix(&expanded, 16).ok().map(rgb_to_bgr);
+    }
+    named_colour(value)
+}
+
+fn font_colour_attribute(tag: &str) -> Option<u32> {
+    let lower = tag.to_ascii_lowercase();
+    let at = lower.find("color")?;
+    let rest = tag[at + 5..].trim_start().strip_prefix('=')?.trim_start();
+    let value = match rest.chars().next()? {
+        quote @ ('"' | '\'') => rest[1..].split(quote).next()?,
+        _ => rest.split(|c: char| c.is_whitespace() || c == '>').next()?,
+    };
+    parse_html_colour(value)
+}
+
+fn decode_entity(entity: &str) -> Option<&'static str> {
+    Some(match entity {
+        "amp" => "&",
+        "lt" => "<",
+        "gt" => ">",
+        "quot" => "\"",
+        "apos" => "'",
+        "nbsp" => "\u{a0}",
+        "lrm" => "\u{200e}",
+        "rlm" => "\u{200f}",
+        _ => return None,
+    })
+}
+
+// Walks one SRT/WebVTT cue line and writes the ASS equivalent. `<i>`, `<b>`, `<u>` and `<s>` map
+// to their ASS toggles, `<font color>` and WebVTT colour classes to `\c`, and a closing tag returns
+// the colour to whatever was active before. SRT lines may carry raw `{\an8}`-style blocks, which
+// players honour, so those are kept; any other brace is literal text.
+fn html_line(line: &str, webvtt: bool, out: &mut AssTextBuilder, colours: &mut Vec<u32>, actor: &mut String) {
+    let mut rest = line;
+    while let Some(ch) = rest.chars().next() {
+        if ch == '<'
+            && let Some(end) = rest.find('>')
+        {
+            html_tag(&rest[1..end], out, colours, actor);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        if ch == '{'
+            && !webvtt
+            && rest[1..].starts_with('\\')
+            && let Some(end) = rest.find('}')
+        {
+            out.text.push_str(&rest[..=end]);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        if ch == '&'
+            && webvtt
+            && let Some(end) = rest[1..].find(';').filter(|end| *end <= 6)
+            && let Some(decoded) = decode_entity(&rest[1..=end])
+        {
+            out.raw(decoded);
+            rest = &rest[end + 2..];
+            continue;
+        }
+        out.raw(&rest[..ch.len_utf8()]);
+        rest = &rest[ch.len_utf8()..];
+    }
+}
+
+fn html_tag(tag: &str, out: &mut AssTextBuilder, colours: &mut Vec<u32>, actor: &mut String) {
+    let tag = tag.trim();
+    let (closing, body) = match tag.strip_prefix('/') {
+        Some(body) => (true, body.trim()),
+        None => (false, tag),
+    };
+    let name_len = body.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(body.len());
+    let name = body[..name_len].to_ascii_lowercase();
+    match name.as_str() {
+        "i" => out.tag(ASSOverride::Italic(!closing)),
+        "b" => out.tag(ASSOverride::Bold(!closing)),
+        "u" => out.tag(ASSOverride::Underline(!closing)),
+        "s" => out.tag(ASSOverride::Strikeout(!closing)),
+        "font" | "c" if closing && colours.len() > 1 => {
+            colours.pop();
+            out.tag(ASSOverride::ColorI(*colours.last().unwrap()));
+        }
+        "font" | "c" if closing => {}
+        "font" => {
+            if let Some(colour) = font_colour_attribute(body) {
+                colours.push(colour);
+                out.tag(ASSOverride::ColorI(colour));
+            }
+        }
+        // WebVTT `<c.yellow.bg_black>`: the first class naming a colour wins; the rest are ignored.
+        "c" => {
+            let colour = body[name_len..]
+                .split('.')
+                .find_map(named_colour)
+                .unwrap_or(*colours.last().unwrap());
+            colours.push(colour);
+            out.tag(ASSOverride::ColorI(colour));
+        }
+        "v" if !closing && actor.is_empty() => {
+            *actor = body[name_len..].trim().to_string();
+        }
+        // Ruby, language spans, voice closers and karaoke timestamps have no ASS counterpart.
+        _ => {}
+    }
+}
+
+// Maps WebVTT `line`, `position` and `align` cue settings onto `\an`, and onto `\pos` when both
+// the line and the position are given as percentages.
+fn webvtt_placement(settings: &str, out: &mut AssTextBuilder) {
+    let mut column: Option<u8> = None;
+    let mut row: Option<u8> = None;
+    let mut line_percent: Option<f32> = None;
+    let mut position_percent: Option<f32> = None;
+    for setting in settings.split_whitespace() {
+        let Some((key, value)) = setting.split_once(':') else {
+            continue;
+        };
+        let value = value.split(',').next().unwrap_or("");
+        match key {
+            "align" => {
+                column = match value {
+                    "start" | "left" => Some(0),
+                    "end" | "right" => Some(2),
+                    "center" | "middle" => Some(1),
+                    _ => column,
+                }
+            }
+            "line" => {
+                if let Some(percent) = value.strip_suffix('%').and_then(|v| v.parse::<f32>().ok()) {
+                    line_percent = Some(percent);
+                    row = Some(if percent < 50.0 { 2 } else { 0 });
+                } else if let Ok(n) = value.parse::<i32>() {
+                    row = Some(if n >= 0 { 2 } else { 0 });
+                }
+            }
+            "position" => {
+                position_percent = value.strip_suffix('%').and_then(|v| v.parse::<f32>().ok());
+            }
+            _ => {}
+        }
+    }
+    let column = column.unwrap_or(1);
+    if let (Some(x), Some(y)) = (position_percent, line_percent) {
+        let row = if y < 50.0 { 2 } else { 0 };
+        out.tag(ASSOverride::An(1 + row * 3 + column));
+        out.tag(ASSOverride::Pos(
+            (x / 100.0 * PLAY_RES_X as f32).round(),
+            (y / 100.0 * PLAY_RES_Y as f32).round(),
+        ));
+        return;
+    }
+    let an = 1 + row.unwrap_or(0) * 3 + column;
+    if an != 2 {
+        out.tag(ASSOverride::An(an));
+    }
+}
+
+fn html_cues(text: &str, webvtt: bool, title: &str) -> Result<SubstationAlpha, String> {
+    let base_colour = default_style().colours[0].as_u32() & 0xFFFFFF;
+    let mut events = Vec::new();
+    for cue in read_cues(text, webvtt) {
+        let mut out = AssTextBuilder::default();
+        let mut colours = vec![base_colour];
+        let mut actor = String::new();
+        if webvtt {
+            webvtt_placement(cue.settings, &mut out);
+        }
+        for (i, line) in cue.lines.iter().enumerate() {
+            if i > 0 {
+                out.line_break();
+            }
+            html_line(line, webvtt, &mut out, &mut colours, &mut actor);
+        }
+        events.push(text_event(cue.start, cue.end, actor, &out));
+    }
+    if events.is_empty() {
+        return Err(format!("no {} cues found", title));
+    }
+    Ok(text_script(title, events))
+}
+
+pub fn parse_srt(text: &str) -> Result<SubstationAlpha, String> {
+    html_cues(text, false, "SRT")
+}
+
+pub fn parse_webvtt(text: &str) -> Result<SubstationAlpha, String> {
+    let header = text.trim_start_matches('\u{feff}').lines().next().unwrap_or("");
+    if !header.starts_with("WEBVTT") {
+        return Err("missing WEBVTT header".to_string());
+    }
+    html_cues(text, true, "WebVTT")
+}
+
+// True when the first non-blank line looks like a MicroDVD `{start}{end}` cue; `.sub` is also
+// used by SubViewer, which this module does not read.
+pub fn is_microdvd(text: &str) -> bool {
+    text.trim_start_matches('\u{feff}')
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .and_then(microdvd_frames)
+        .is_some()
+}
+
+fn microdvd_frames(line: &str) -> Option<(u64, Option<u64>, &str)> {
+    let rest = line.trim_start().strip_prefix('{')?;
+    let (start, rest) = rest.split_once('}')?;
+    let rest = rest.strip_prefix('{')?;
+    let (end, text) = rest.split_once('}')?;
+    let start = start.trim().parse::<u64>().ok()?;
+    let end = match end.trim() {
+        "" => None,
+        e => Some(e.parse::<u64>().ok()?),
+    };
+    Some((start, end, text))
+}
+
+// MicroDVD control codes are `{x:value}`; lowercase codes apply to one `|`-separated line and
+// uppercase ones to the whole cue. `y` styles (i/b/u/s), `c` colours ($BBGGRR, already ASS order),
+// `f` and `s` pick font and size, and `P:x,y` positions the cue. A line starting with `/` is
+// italic, as most MicroDVD writers emit it.
+fn microdvd_line(line: &str, out: &mut AssTextBuilder, line_resets: &mut Vec<ASSOverride>) {
+    let mut rest = line;
+    if let Some(stripped) = rest.strip_prefix('/') {
+        out.tag(ASSOverride::Italic(true));
+        line_resets.push(ASSOverride::Italic(false));
+        rest = stripped;
+    }
+    while let Some(ch) = rest.chars().next() {
+        if ch == '{'
+            && let Some(end) = rest.find('}')
+            && let Some((code, value)) = rest[1..end].split_once(':')
+            && code.len() == 1
+            && code.chars().all(|c| c.is_ascii_alphabetic())
+        {
+            microdvd_code(code, value, out, line_resets);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        out.raw(&rest[..ch.len_utf8()]);
+        rest = &rest[ch.len_utf8()..];
+    }
+}
+
+fn microdvd_code(code: &str, value: &str, out: &mut AssTextBuilder, line_resets: &mut Vec<ASSOverride>) {
+    let per_line = code.chars().all(|c| c.is_ascii_lowercase());
+    let mut apply = |on: ASSOverride, off: ASSOverride| {
+        out.tag(on);
+        if per_line {
+            line_resets.push(off);
+        }
+    };
+    match code.to_ascii_lowercase().as_str() {
+        "y" => {
+            for flag in value.chars().map(|c| c.to_ascii_lowercase()) {
+                match flag {
+                    'i' => apply(ASSOverride::Italic(true), ASSOverride::Italic(false)),
+                    'b' => apply(ASSOverride::Bold(true), ASSOverride::Bold(false)),
+                    'u' => apply(ASSOverride::Underline(true), ASSOverride::Underline(false)),
+                    's' => apply(ASSOverride::Strikeout(true), ASSOverride::Strikeout(false)),
+                    _ => {}
+                }
+            }
+        }
+        "c" => {
+            let hex = value.trim().trim_start_matches('$');
+            if let Ok(colour) = u32::from_str_radix(hex, 16) {
+                let base = default_style().colours[0].as_u32() & 0xFFFFFF;
+                apply(ASSOverride::ColorI(colour & 0xFFFFFF), ASSOverride::ColorI(base));
+            }
+        }
+        "f" => {
+            let default = default_style();
+            apply(ASSOverride::Fn(value.trim().to_string()), ASSOverride::Fn(default.fontname));
+        }
+        "s" => {
+            if let Ok(size) = value.trim().parse::<f32>() {
+                apply(ASSOverride::Fs(size), ASSOverride::Fs(default_style().fontsize as f32));
+            }
+        }
+        "p" => {
+            let mut coords = value.split(',').map(|v| v.trim().parse::<f32>());
+            if let (Some(Ok(x)), Some(Ok(y))) = (coords.next(), coords.next()) {
+                out.tag(ASSOverride::Pos(x, y));
+            }
+        }
+        _ => {}
+    }
+}
+
+// `fps` overrides the frame rate; otherwise a leading `{1}{1}23.976` line sets it and
+// DEFAULT_MICRODVD_FPS is the fallback.
+pub fn parse_microdvd(text: &str, fps: Option<f64>) -> Result<SubstationAlpha, String> {
+    let mut rate = fps;
+    let mut events = Vec::new();
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((start, end, body)) = microdvd_frames(line) else {
+            continue;
+        };
+        if events.is_empty()
+            && rate.is_none()
+            && start == end.unwrap_or(start)
+            && start <= 1
+            && let Ok(declared) = body.trim().parse::<f64>()
+            && declared > 0.0
+        {
+            rate = Some(declared);
+            continue;
+        }
+        let rate = rate.unwrap_or(DEFAULT_MICRODVD_FPS);
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(format!("invalid MicroDVD frame rate {}", rate));
+        }
+        // An open end frame shows the line for about as long as a short line needs.
+        let end = end.unwrap_or(start + (rate * 3.0).round() as u64);
+        let to_ms = |frame: u64| (frame as f64 * 1000.0 / rate).round() as u64;
+
+        let mut out = AssTextBuilder::default();
+        for (i, segment) in body.split('|').enumerate() {
+            if i > 0 {
+                out.line_break();
+            }
+            let mut line_resets = Vec::new();
+            microdvd_line(segment, &mut out, &mut line_resets);
+            for reset in line_resets {
+                out.tag(reset);
+            }
+        }
+        events.push(text_event(to_ms(start), to_ms(end), String::new(), &out));
+    }
+    if events.is_empty() {
+        return Err("no MicroDVD cues found".to_string());
+    }
+    Ok(text_script("MicroDVD", events))
+}
+
+struct TextCue {
+    start_ms: u64,
+    end_ms: u64,
+    lines: Vec<String>,
+}
+
+// Sign styles are typesetting whatever their tags say, matching pnass --split-signs; positioned
+// and drawn lines are caught while flattening.
+fn is_typesetting(event: &Event) -> bool {
+    event.style.to_lowercase().contains("sign")
+}
+
+// Flattens one event's text into display lines with only italics kept, as `<i>` spans that are
+// closed and reopened around each line break. Returns None for typeset and drawing lines.
+fn flatten_event(event: &Event, style_italic: bool, webvtt: bool) -> Option<Vec<String>> {
+    let text = event.text.stringify();
+    let chars: Vec<char> = text.chars().collect();
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut italic = style_italic;
+    let mut open = false;
+    let mut k = 0;
+    let set_italic = |current: &mut String, open: &mut bool, want: bool| {
+        if want && !*open {
+            current.push_str("<i>");
+        } else if !want && *open {
+            current.push_str("</i>");
+        }
+        *open = want;
+    };
+    while k < chars.len() {
+        let ch = chars[k];
+        if ch == '{'
+            && let Some(end) = chars[k + 1..].iter().position(|c| *c == '}').map(|p| p + k + 1)
+        {
+            let block: String = chars[k + 1..end].iter().collect();
+            for tag in block.split('\\').skip(1) {
+                let name_len = tag.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(tag.len());
+                let (name, arg) = tag.split_at(name_len);
+                match name {
+                    "pos" | "move" => return None,
+                    "p" if arg.trim().parse::<u32>().map(|n| n > 0).unwrap_or(false) => return None,
+                    "i" => italic = match arg.trim() {
+                        "" => style_italic,
+                        v => v != "0",
+                    },
+                    "r" => italic = style_italic,
+                    _ => {}
+                }
+            }
+            k = end + 1;
+            continue;
+        }
+        if ch == '\\' && k + 1 < chars.len() {
+            match chars[k + 1] {
+                'N' => {
+                    set_italic(&mut current, &mut open, false);
+                    lines.push(std::mem::take(&mut current));
+                    k += 2;
+                    continue;
+                }
+                'n' | 'h' => {
+                    current.push(' ');
+                    k += 2;
+                    continue;
+                }
+                '{' | '}' => {
+                    set_italic(&mut current, &mut open, italic);
+                    current.push(chars[k + 1]);
+                    k += 2;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        if !ch.is_whitespace() || !current.is_empty() {
+            set_italic(&mut current, &mut open, italic);
+        }
+        match ch {
+            '&' if webvtt => current.push_str("&amp;"),
+            '<' if webvtt => current.push_str("&lt;"),
+            '>' if webvtt => current.push_str("&gt;"),
+            _ => current.push(ch),
+        }
+        k += 1;
+    }
+    set_italic(&mut current, &mut open, false);
+    lines.push(current);
+    let lines: Vec<String> = lines
+        .into_iter()
+        .map(|line| line.trim().replace("<i></i>", "").replace("</i><i>", ""))
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    Some(lines)
+}
+
+fn export_cues(sub: &SubstationAlpha, options: &TextExportOptions, webvtt: bool) -> Vec<TextCue> {
+    let mut cues: Vec<TextCue> = Vec::new();
+    for event in &sub.events {
+        if is_typesetting(event) || event.has_drawing() {
+            continue;
+        }
+        let style_italic = sub
+            .v4p_styles
+            .iter()
+            .rev()
+            .find(|style| style.name == event.style)
+            .map(|style| style.italic)
+            .unwrap_or(false);
+        let Some(lines) = flatten_event(event, style_italic, webvtt) else {
+            continue;
+        };
+        let start_ms = event.start.total_centiseconds() * 10;
+        let end_ms = event.end.total_centiseconds() * 10;
+        if end_ms <= start_ms {
+            continue;
+        }
+        cues.push(TextCue { start_ms, end_ms, lines });
+    }
+    cues.sort_by_key(|cue| cue.start_ms);
+    if options.merge_simultaneous {
+        let mut merged: Vec<TextCue> = Vec::new();
+        for cue in cues {
+            match merged.iter_mut().find(|m| m.start_ms == cue.start_ms && m.end_ms == cue.end_ms) {
+                Some(existing) => existing.lines.extend(cue.lines),
+                None => merged.push(cue),
+            }
+        }
+        cues = merged;
+    }
+    cues
+}
+
+fn format_clock(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+// Dialogue only: sign styles, positioned lines and drawings are dropped, `\N` becomes a real line
+// break and italics survive as `<i>`.
+pub fn to_srt(sub: &SubstationAlpha, options: &TextExportOptions) -> String {
+    let mut out = String::new();
+    for (i, cue) in export_cues(sub, options, false).iter().enumerate() {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            format_clock(cue.start_ms, ','),
+            format_clock(cue.end_ms, ','),
+            cue.lines.join("\n")
+        ));
+    }
+    out
+}
+
+pub fn to_webvtt(sub: &SubstationAlpha, options: &TextExportOptions) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in export_cues(sub, options, true) {
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_clock(cue.start_ms, '.'),
+            format_clock(cue.end_ms, '.'),
+            cue.lines.join("\n")
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srt_tags_become_overrides() {
+        let srt = "1\r\n00:00:01,000 --> 00:00:03,500\r\nHello <i>world</i>\r\n<font color=\"#FF0000\">red</font> {\\an8}top\r\n\r\n2\n00:00:04,000 --> 00:00:05,000\nbrace {here}\n";
+        let sub = parse_srt(srt).unwrap();
+        assert_eq!(sub.events.len(), 2);
+        assert_eq!(sub.events[0].start.to_string(), "0:00:01.00");
+        assert_eq!(sub.events[0].end.to_string(), "0:00:03.50");
+        let text = sub.events[0].text.stringify();
+        assert!(text.starts_with("Hello {\\i1}world{\\i0}"), "{}", text);
+        assert!(text.contains("\\N{\\c&H0000FF&}red{\\c&HFFFFFF&} {\\an8}top"), "{}", text);
+        assert_eq!(sub.events[1].text.stringify(), "brace \\{here\\}");
+    }
+
+    #[test]
+    fn webvtt_settings_and_voices() {
+        let vtt = "WEBVTT\n\nNOTE a comment --> not a cue\n\nintro\n00:01.000 --> 00:02.000 line:0 align:start\n<v Mika>Top &amp; left</v>\n\n00:03.000 --> 00:04.000 position:25% line:10%\n<c.yellow>Placed</c>\n";
+        let sub = parse_webvtt(vtt).unwrap();
+        assert_eq!(sub.events.len(), 2);
+        assert_eq!(sub.events[0].name, "Mika");
+        assert_eq!(sub.events[0].text.stringify(), "{\\an7}Top & left");
+        let placed = sub.events[1].text.stringify();
+        assert!(placed.starts_with("{\\an8\\pos(480,108)\\c&H00FFFF&}Placed"), "{}", placed);
+        assert!(parse_webvtt("00:01.000 --> 00:02.000\nhi\n").is_err());
+    }
+
+    #[test]
+    fn microdvd_frames_and_codes() {
+        let sub = parse_microdvd("{1}{1}25\n{25}{50}{y:i}First|second\n{75}{100}/Slanted|{C:$0000FF}Red\n", None).unwrap();
+        assert_eq!(sub.events.len(), 2);
+        assert_eq!(sub.events[0].start.to_string(), "0:00:01.00");
+        assert_eq!(sub.events[0].end.to_string(), "0:00:02.00");
+        assert_eq!(sub.events[0].text.stringify(), "{\\i1}First{\\i0}\\Nsecond");
+        assert_eq!(sub.events[1].text.stringify(), "{\\i1}Slanted{\\i0}\\N{\\c&H0000FF&}Red");
+        assert!(is_microdvd("{0}{25}hello"));
+        assert!(!is_microdvd("[INFORMATION]\n00:00:01.00,00:00:02.00\nhello"));
+    }
+
+    #[test]
+    fn exports_keep_dialogue_and_italics() {
+        let (sub, _) = SubstationAlpha::parse(
+            "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\nStyle: Default,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,3.75,0,2,50,50,38,1\nStyle: Sign,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,3.75,0,2,50,50,38,1\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{\\i1}one\\Ntwo{\\i0} <three>\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{\\an8}Top & tail\nDialogue: 0,0:00:01.00,0:00:02.00,Sign,,0,0,0,,SIGN\nDialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,{\\pos(10,10)}placed\nDialogue: 0,0:00:05.00,0:00:06.00,Default,,0,0,0,,{\\p1}m 0 0 l 10 10{\\p0}\n",
+            true,
+        );
+        let srt = to_srt(&sub, &TextExportOptions::default());
+        assert_eq!(
+            srt,
+            "1\n00:00:01,000 --> 00:00:02,000\n<i>one</i>\n<i>two</i> <three>\n\n2\n00:00:01,000 --> 00:00:02,000\nTop & tail\n\n"
+        );
+        let vtt = to_webvtt(&sub, &TextExportOptions { merge_simultaneous: true });
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\n<i>one</i>\n<i>two</i> &lt;three&gt;\nTop &amp; tail\n\n"
+        );
+    }
+
+    #[test]
+    fn srt_round_trips_through_ass() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,500\n<i>Hello</i> there\nsecond line\n\n";
+        let sub = parse_srt(srt).unwrap();
+        assert_eq!(to_srt(&sub, &TextExportOptions::default()), srt);
+    }
+}
@@ -0,0 +1,473 @@
+use crate::libkagami::complex::overrides::ASSOverride;
+use crate::libkagami::complex::types::AssTime;
+use crate::libkagami::core::{Event, SubstationAlpha};
+use crate::libkagami::tags::ASSText;
+
+// Largest time an ASS timestamp can hold (255:59:59.99), in milliseconds.
+const MAX_ASS_MS: i64 = (255 * 3_600_000) + (59 * 60_000) + (59 * 1000) + 990;
+
+// Frame timing of a video: either a constant rate or the per-frame start times of an mkv
+// timecodes v2 file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameTimes {
+    Constant(f64),
+    Timecodes(Vec<f64>),
+}
+
+impl FrameTimes {
+    pub fn constant(fps: f64) -> Result<Self, String> {
+        if !fps.is_finite() || fps <= 0.0 {
+            return Err(format!("invalid frame rate {}", fps));
+        }
+        Ok(FrameTimes::Constant(fps))
+    }
+
+    // `# timecode format v2` followed by one millisecond start time per frame, non-decreasing.
+    pub fn parse_timecodes(text: &str) -> Result<Self, String> {
+        let mut lines = text.trim_start_matches('\u{feff}').lines().map(str::trim);
+        let header = lines.find(|line| !line.is_empty()).unwrap_or("");
+        if !header.eq_ignore_ascii_case("# timecode format v2") {
+            return Err("not an mkv timecodes v2 file (missing `# timecode format v2` header)".to_string());
+        }
+        let mut times: Vec<f64> = Vec::new();
+        for (index, line) in lines.enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let ms: f64 = line
+                .parse()
+                .map_err(|_| format!("timecodes line {}: `{}` is not a number", index + 2, line))?;
+            if times.last().is_some_and(|last| ms < *last) {
+                return Err(format!("timecodes line {}: {} goes backwards", index + 2, line));
+            }
+            times.push(ms);
+        }
+        if times.len() < 2 {
+            return Err("timecodes file needs at least two frames".to_string());
+        }
+        Ok(FrameTimes::Timecodes(times))
+    }
+
+    // Start time of a frame. Timecodes are extended past their last frame at the last frame's
+    // duration, which is how players treat the tail of the file.
+    pub fn frame_to_ms(&self, frame: i64) -> f64 {
+        match self {
+            FrameTimes::Constant(fps) => frame as f64 * 1000.0 / fps,
+            FrameTimes::Timecodes(times) => {
+                let last = times.len() as i64 - 1;
+                let step = times[times.len() - 1] - times[times.len() - 2];
+                if frame < 0 {
+                    times[0] + frame as f64 * (times[1] - times[0])
+                } else if frame > last {
+                    times[last as usize] + (frame - last) as f64 * step
+                } else {
+                    times[frame as usize]
+                }
+            }
+        }
+    }
+
+    // Frame on screen at `ms`: the last frame that starts at or before it.
+    pub fn ms_to_frame(&self, ms: f64) -> i64 {
+        match self {
+            FrameTimes::Constant(fps) => (ms * fps / 1000.0 + 1e-6).floor() as i64,
+            FrameTimes::Timecodes(times) => {
+                let last = times.len() - 1;
+                let step = times[last] - times[last - 1];
+                if ms < times[0] {
+                    return -1;
+                }
+                if ms >= times[last] + step && step > 0.0 {
+                    return last as i64 + ((ms - times[last]) / step).floor() as i64;
+                }
+                times.partition_point(|start| *start <= ms) as i64 - 1
+            }
+        }
+    }
+
+    // Position of `ms` in frames, fractional inside a frame, so a retime between two timings
+    // keeps an event's offset within its frame.
+    fn frame_position(&self, ms: f64) -> f64 {
+        let frame = self.ms_to_frame(ms);
+        let start = self.frame_to_ms(frame);
+        let length = self.frame_to_ms(frame + 1) - start;
+        if length <= 0.0 {
+            return frame as f64;
+        }
+        frame as f64 + (ms - start) / length
+    }
+
+    fn position_to_ms(&self, position: f64) -> f64 {
+        let frame = position.floor();
+        let start = self.frame_to_ms(frame as i64);
+        start + (position - frame) * (self.frame_to_ms(frame as i64 + 1) - start)
+    }
+}
+
+// Which events a timing change applies to. Empty lists match everything.
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter {
+    pub styles: Vec<String>,
+    pub layers: Vec<u16>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.styles.is_empty() || self.styles.contains(&event.style))
+            && (self.layers.is_empty() || self.layers.contains(&event.layer))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.styles.is_empty() && self.layers.is_empty()
+    }
+}
+
+pub(crate) fn to_ms(time: &AssTime) -> f64 {
+    time.total_centiseconds() as f64 * 10.0
+}
+
+pub(crate) fn from_ms(ms: f64) -> AssTime {
+    let ms = (ms.round() as i64).clamp(0, MAX_ASS_MS);
+    AssTime::from_centiseconds(((ms + 5) / 10) as u64)
+}
+
+// Applies `map` to the start and end of every matching event and comment, and to the Pandora
+// stamps when the filter is empty (stamps have no style or layer to filter on). Times inside
+// \t, \move, \fad/\fade and \k tags are relative to the line start, so they are scaled by how
+// much the line itself was stretched; when a line's start is clamped at zero they also lose the
+// part that was cut off, so the rest plays as before. Returns how many events and comments were
+// changed.
+fn apply_time_map(sub: &mut SubstationAlpha, filter: &EventFilter, map: impl Fn(f64) -> f64) -> usize {
+    apply_span_map(sub, filter, |start, end| (map(start), map(end)))
+}
+
+// Same as apply_time_map, for changes that have to see a line's start and end together.
+fn apply_span_map(sub: &mut SubstationAlpha, filter: &EventFilter, map: impl Fn(f64, f64) -> (f64, f64)) -> usize {
+    let mut changed = 0;
+    for event in sub.events.iter_mut().chain(sub.comments.iter_mut()) {
+        if !filter.matches(event) {
+            continue;
+        }
+        let (start, end) = (to_ms(&event.start), to_ms(&event.end));
+        let (new_start, new_end) = map(start, end);
+        event.start = from_ms(new_start);
+        event.end = from_ms(new_end);
+        if end > start {
+            let factor = (new_end - new_start) / (end - start);
+            if (factor - 1.0).abs() > 1e-9 {
+                scale_event_tags(event, factor);
+            }
+            if new_start < 0.0 {
+                cut_event_start(event, -new_start, new_end - new_start);
+            }
+        }
+        changed += 1;
+    }
+    if filter.is_empty() {
+        for stamp in &mut sub.pandora_meta.stamps {
+            let (start, end) = map(to_ms(&stamp.start), to_ms(&stamp.end));
+            stamp.start = from_ms(start);
+            stamp.end = from_ms(end);
+        }
+    }
+    changed
+}
+
+// Moves matching events by `offset_ms`; times that would go negative stop at zero.
+pub fn shift(sub: &mut SubstationAlpha, offset_ms: i64, filter: &EventFilter) -> usize {
+    apply_time_map(sub, filter, |ms| ms + offset_ms as f64)
+}
+
+// Moves matching events by a number of frames of `times`, keeping each time's offset inside
+// its frame. On variable frame rate timecodes this is not a constant millisecond shift.
+pub fn shift_frames(sub: &mut SubstationAlpha, frames: i64, times: &FrameTimes, filter: &EventFilter) -> usize {
+    apply_time_map(sub, filter, |ms| times.position_to_ms(times.frame_position(ms) + frames as f64))
+}
+
+// Multiplies every matching time by `factor` (1.0 leaves the script alone).
+pub fn stretch(sub: &mut SubstationAlpha, factor: f64, filter: &EventFilter) -> Result<usize, String> {
+    if !factor.is_finite() || factor <= 0.0 {
+        return Err(format!("invalid stretch factor {}", factor));
+    }
+    Ok(apply_time_map(sub, filter, |ms| ms * factor))
+}
+
+// Retimes a script timed against `from` so each line lands on the same frame of `to`: 25 fps to
+// 23.976 fps undoes a PAL speed-up, and a timecodes `to` follows a VFR encode.
+pub fn retime(sub: &mut SubstationAlpha, from: &FrameTimes, to: &FrameTimes, filter: &EventFilter) -> usize {
+    if let (FrameTimes::Constant(from), FrameTimes::Constant(to)) = (from, to) {
+        let factor = from / to;
+        return apply_time_map(sub, filter, |ms| ms * factor);
+    }
+    apply_time_map(sub, filter, |ms| to.position_to_ms(from.frame_position(ms)))
+}
+
+// A stretch of an old source that sits `offset_ms` later (or earlier, when negative) in a new
+// one. A sync map is a list of these in order, covering the old timeline from zero; the last
+// segment runs to the end of the script whatever its `end_ms` says.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SyncSegment {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub offset_ms: i64,
+}
+
+// One `start_ms end_ms offset_ms` line per segment; blank lines and `#` comments are skipped.
+pub fn parse_sync_map(text: &str) -> Result<Vec<SyncSegment>, String> {
+    let mut segments: Vec<SyncSegment> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<i64> = line
+            .split_whitespace()
+            .map(|field| field.parse::<i64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("sync map line {}: `{}` is not three millisecond values", index + 1, line))?;
+        let [start_ms, end_ms, offset_ms] = fields[..] else {
+            return Err(format!("sync map line {}: `{}` is not three millisecond values", index + 1, line));
+        };
+        if end_ms < start_ms || segments.last().is_some_and(|last| start_ms < last.end_ms) {
+            return Err(format!("sync map line {}: segments must be in order and not overlap", index + 1));
+        }
+        segments.push(SyncSegment { start_ms, end_ms, offset_ms });
+    }
+    if segments.is_empty() {
+        return Err("sync map has no segments".to_string());
+    }
+    Ok(segments)
+}
+
+pub fn format_sync_map(segments: &[SyncSegment]) -> String {
+    let mut out = String::from("# start_ms end_ms offset_ms\n");
+    for segment in segments {
+        out.push_str(&format!("{} {} {}\n", segment.start_ms, segment.end_ms, segment.offset_ms));
+    }
+    out
+}
+
+fn sync_offset(segments: &[SyncSegment], ms: f64) -> f64 {
+    segments
+        .iter()
+        .take_while(|segment| segment.start_ms as f64 <= ms)
+        .last()
+        .or(segments.first())
+        .map_or(0.0, |segment| segment.offset_ms as f64)
+}
+
+// Moves every matching line by the offset of the segment its start falls in. The whole line
+// moves together, so one that straddles a cut keeps its length instead of being torn apart.
+pub fn apply_sync_map(sub: &mut SubstationAlpha, segments: &[SyncSegment], filter: &EventFilter) -> usize {
+    apply_span_map(sub, filter, |start, end| {
+        let offset = sync_offset(segments, start);
+        (start + offset, end + offset)
+    })
+}
+
+fn scale_event_tags(event: &mut Event, factor: f64) {
+    for item in &mut event.text.data {
+        if let ASSText::Override(ov) = item {
+            scale_override(ov, factor);
+        }
+    }
+    for ov in &mut event.text.current_overrides {
+        scale_override(ov, factor);
+    }
+}
+
+fn scale_override(ov: &mut ASSOverride, factor: f64) {
+    let f = |v: &mut f32| *v = (*v as f64 * factor).round() as f32;
+    let k = |v: &mut u32| *v = (*v as f64 * factor).round() as u32;
+    match ov {
+        ASSOverride::TransformIII(t1, t2, _) | ASSOverride::TransformIV(t1, t2, _, _) => {
+            f(t1);
+            f(t2);
+        }
+        ASSOverride::MoveII(_, _, _, _, t1, t2) => {
+            f(t1);
+            f(t2);
+        }
+        ASSOverride::Fad(fade_in, fade_out) => {
+            f(fade_in);
+            f(fade_out);
+        }
+        ASSOverride::Fade(_, _, _, t1, t2, t3, t4) => {
+            f(t1);
+            f(t2);
+            f(t3);
+            f(t4);
+        }
+        ASSOverride::K(v) | ASSOverride::Kt(v) | ASSOverride::KSweep(v) | ASSOverride::Kf(v) | ASSOverride::Ko(v) => k(v),
+        _ => {}
+    }
+}
+
+// Drops the first `cut_ms` of a line `duration_ms` long from its relative tag times. Karaoke
+// syllables are consumed in order, so the ones that were cut off become zero-length.
+fn cut_event_start(event: &mut Event, cut_ms: f64, duration_ms: f64) {
+    let (cut, duration) = (cut_ms.round() as f32, duration_ms.round() as f32);
+    let mut karaoke_left = (cut_ms / 10.0).round() as u32;
+    for item in &mut event.text.data {
+        if let ASSText::Override(ov) = item {
+            match ov {
+                ASSOverride::K(v) | ASSOverride::Kt(v) | ASSOverride::KSweep(v) | ASSOverride::Kf(v) | ASSOverride::Ko(v) => {
+                    let taken = (*v).min(karaoke_left);
+                    *v -= taken;
+                    karaoke_left -= taken;
+                }
+                _ => shift_relative_times(ov, cut, duration),
+            }
+        }
+    }
+    for ov in &mut event.text.current_overrides {
+        shift_relative_times(ov, cut, duration);
+    }
+}
+
+// Times inside \t, \move and \fad(e) count from the line start; this moves that start `offset` ms
+// later. Forms without times span the whole `duration`-long line, so they get explicit ones.
+pub(crate) fn shift_relative_times(ov: &mut ASSOverride, offset: f32, duration: f32) {
+    // Not `-offset`: a zero offset would print as `-0`.
+    let start = 0.0 - offset;
+    *ov = match std::mem::replace(ov, ASSOverride::BlockText(String::new())) {
+        ASSOverride::TransformI(tags) => ASSOverride::TransformIII(start, duration - offset, tags),
+        ASSOverride::TransformII(accel, tags) => ASSOverride::TransformIV(start, duration - offset, accel, tags),
+        ASSOverride::TransformIII(t1, t2, tags) => ASSOverride::TransformIII(t1 - offset, t2 - offset, tags),
+        ASSOverride::TransformIV(t1, t2, accel, tags) => ASSOverride::TransformIV(t1 - offset, t2 - offset, accel, tags),
+        ASSOverride::MoveI(x1, y1, x2, y2) => ASSOverride::MoveII(x1, y1, x2, y2, start, duration - offset),
+        ASSOverride::MoveII(x1, y1, x2, y2, t1, t2) => ASSOverride::MoveII(x1, y1, x2, y2, t1 - offset, t2 - offset),
+        ASSOverride::Fad(fade_in, fade_out) => ASSOverride::Fade(
+            255.0, 0.0, 255.0,
+            start, fade_in - offset, duration - fade_out - offset, duration - offset,
+        ),
+        ASSOverride::Fade(a1, a2, a3, t1, t2, t3, t4) => ASSOverride::Fade(
+            a1, a2, a3, t1 - offset, t2 - offset, t3 - offset, t4 - offset,
+        ),
+        other => other,
+    };
+}
+
+// Offsets as users type them: `1.5s`, `-250ms`, `+24f` (frames), `-0:00:01.50`, or a bare
+// number of milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeOffset {
+    Ms(i64),
+    Frames(i64),
+}
+
+pub fn parse_offset(input: &str) -> Result<TimeOffset, String> {
+    let trimmed = input.trim();
+    let (negative, body) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let sign = if negative { -1 } else { 1 };
+    let bad = || format!("`{}` is not a time offset (try 1.5s, -250ms, +24f or 0:00:01.50)", input);
+    if let Some(frames) = body.strip_suffix('f') {
+        return frames.parse::<i64>().map(|n| TimeOffset::Frames(sign * n)).map_err(|_| bad());
+    }
+    let ms = if let Some(ms) = body.strip_suffix("ms") {
+        ms.parse::<f64>().map_err(|_| bad())?
+    } else if let Some(seconds) = body.strip_suffix('s') {
+        seconds.parse::<f64>().map_err(|_| bad())? * 1000.0
+    } else if body.contains(':') {
+        let time: AssTime = body.parse().map_err(|_| bad())?;
+        to_ms(&time)
+    } else {
+        body.parse::<f64>().map_err(|_| bad())?
+    };
+    if !ms.is_finite() {
+        return Err(bad());
+    }
+    Ok(TimeOffset::Ms(sign * ms.round() as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\nStyle: Default,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,3.75,0,2,50,50,38,1\nStyle: Sign,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,3.75,0,2,50,50,38,1\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:03.00,Default,,0,0,0,,{\\fad(200,300)\\k50}Hello\nDialogue: 1,0:00:10.00,0:00:12.00,Sign,,0,0,0,,{\\move(0,0,10,10,100,1000)\\t(0,500,\\fscx120)}Sign\n";
+
+    fn script() -> SubstationAlpha {
+        SubstationAlpha::parse(SCRIPT, true).0
+    }
+
+    #[test]
+    fn shift_clamps_and_filters() {
+        let mut sub = script();
+        let filter = EventFilter { styles: vec!["Sign".to_string()], layers: Vec::new() };
+        assert_eq!(shift(&mut sub, -1500, &filter), 1);
+        assert_eq!(sub.events[0].start.to_string(), "0:00:01.00");
+        assert_eq!(sub.events[1].start.to_string(), "0:00:08.50");
+
+        assert_eq!(shift(&mut sub, -2000, &EventFilter::default()), 2);
+        assert_eq!(sub.events[0].start.to_string(), "0:00:00.00");
+        assert_eq!(sub.events[0].end.to_string(), "0:00:01.00");
+        // The first second was cut off: the fade-in and the syllable are already over, and the
+        // fade-out keeps its length.
+        assert!(
+            sub.events[0].text.stringify().contains("\\fade(255,0,255,-1000,-800,700,1000)\\k0"),
+            "{}",
+            sub.events[0].text.stringify()
+        );
+        let sign = sub.events[1].text.stringify();
+        assert!(sign.contains("\\move(0,0,10,10,100,1000)\\t(0,500,\\fscx120)"), "{}", sign);
+    }
+
+    #[test]
+    fn retime_between_constant_rates_scales_tags() {
+        let mut sub = script();
+        let from = FrameTimes::constant(25.0).unwrap();
+        let to = FrameTimes::constant(23.976).unwrap();
+        assert_eq!(retime(&mut sub, &from, &to, &EventFilter::default()), 2);
+        assert_eq!(sub.events[0].start.to_string(), "0:00:01.04");
+        assert_eq!(sub.events[1].start.to_string(), "0:00:10.43");
+        let sign = sub.events[1].text.stringify();
+        assert!(sign.contains("\\move(0,0,10,10,104,1043)"), "{}", sign);
+        assert!(sign.contains("\\t(0,521,\\fscx120)"), "{}", sign);
+    }
+
+    #[test]
+    fn timecodes_map_frames_both_ways() {
+        let times = FrameTimes::parse_timecodes("# timecode format v2\n0\n40\n80\n100\n120\n").unwrap();
+        assert_eq!(times.ms_to_frame(0.0), 0);
+        assert_eq!(times.ms_to_frame(85.0), 2);
+        assert_eq!(times.ms_to_frame(100.0), 3);
+        assert_eq!(times.ms_to_frame(150.0), 5);
+        assert_eq!(times.frame_to_ms(6), 160.0);
+        assert!(FrameTimes::parse_timecodes("# timecode format v1\n").is_err());
+        assert!(FrameTimes::parse_timecodes("# timecode format v2\n0\n50\n40\n").is_err());
+
+        let cfr = FrameTimes::constant(23.976).unwrap();
+        assert_eq!(cfr.ms_to_frame(cfr.frame_to_ms(1000)), 1000);
+    }
+
+    #[test]
+    fn frame_shift_and_offsets() {
+        let mut sub = script();
+        let fps = FrameTimes::constant(25.0).unwrap();
+        shift_frames(&mut sub, 25, &fps, &EventFilter::default());
+        assert_eq!(sub.events[0].start.to_string(), "0:00:02.00");
+        assert_eq!(parse_offset("1.5s"), Ok(TimeOffset::Ms(1500)));
+        assert_eq!(parse_offset("-250ms"), Ok(TimeOffset::Ms(-250)));
+        assert_eq!(parse_offset("+24f"), Ok(TimeOffset::Frames(24)));
+        assert_eq!(parse_offset("-0:00:01.50"), Ok(TimeOffset::Ms(-1500)));
+        assert!(parse_offset("soon").is_err());
+    }
+
+    #[test]
+    fn sync_map_moves_whole_lines_by_their_start_segment() {
+        let mut sub = script();
+        let segments = parse_sync_map("# start_ms end_ms offset_ms\n0 2000 500\n2000 600000 90000\n").unwrap();
+        assert_eq!(format_sync_map(&segments), "# start_ms end_ms offset_ms\n0 2000 500\n2000 600000 90000\n");
+        assert_eq!(apply_sync_map(&mut sub, &segments, &EventFilter::default()), 2);
+        // Starts inside the first segment, ends inside the second: moves by the first offset only.
+        assert_eq!(sub.events[0].start.to_string(), "0:00:01.50");
+        assert_eq!(sub.events[0].end.to_string(), "0:00:03.50");
+        assert_eq!(sub.events[1].start.to_string(), "0:01:40.00");
+        assert!(sub.events[1].text.stringify().contains("\\t(0,500,\\fscx120)"));
+        assert!(parse_sync_map("0 1000 0\n500 2000 10\n").is_err());
+        assert!(parse_sync_map("0 1000\n").is_err());
+    }
+}
@@ -0,0 +1,284 @@
+// Timing post-processing in the style of Aegisub's TPP: lead-in/lead-out, linking lines that
+// almost touch, and snapping starts and ends to nearby keyframes. Only dialogue is touched — signs
+// are timed to the video by hand and comments are not shown.
+
+use crate::libkagami::core::{Event, SubstationAlpha};
+use crate::libkagami::timing::{EventFilter, FrameTimes, from_ms, to_ms};
+
+// Keyframe numbers of a video as Aegisub writes them: `# keyframe format v1`, an `fps` line,
+// then one frame number per line. `fps 0` (what Aegisub itself writes) means unknown.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframes {
+    pub frames: Vec<i64>,
+    pub fps: Option<f64>,
+}
+
+pub fn parse_keyframes(text: &str) -> Result<Keyframes, String> {
+    let mut lines = text.trim_start_matches('\u{feff}').lines().map(str::trim);
+    let header = lines.find(|line| !line.is_empty()).unwrap_or("");
+    if !header.eq_ignore_ascii_case("# keyframe format v1") {
+        return Err("not an Aegisub keyframes file (missing `# keyframe format v1` header)".to_string());
+    }
+    let mut frames: Vec<i64> = Vec::new();
+    let mut fps = None;
+    for (index, line) in lines.enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("fps") {
+            let value: f64 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("keyframes line {}: `{}` is not a frame rate", index + 2, line))?;
+            fps = (value.is_finite() && value > 0.0).then_some(value);
+            continue;
+        }
+        let frame: i64 = line
+            .parse()
+            .map_err(|_| format!("keyframes line {}: `{}` is not a frame number", index + 2, line))?;
+        if frame < 0 {
+            return Err(format!("keyframes line {}: frame {} is negative", index + 2, frame));
+        }
+        frames.push(frame);
+    }
+    if frames.is_empty() {
+        return Err("keyframes file lists no frames".to_string());
+    }
+    frames.sort_unstable();
+    frames.dedup();
+    Ok(Keyframes { frames, fps })
+}
+
+pub fn format_keyframes(frames: &[i64], fps: Option<f64>) -> String {
+    let mut out = format!("# keyframe format v1\nfps {}\n", fps.map_or("0".to_string(), |fps| format!("{:.6}", fps)));
+    for frame in frames {
+        out.push_str(&format!("{}\n", frame));
+    }
+    out
+}
+
+// Thresholds are in milliseconds except the keyframe windows, which are (frames before, frames
+// after) the line's own start or end frame. `link_bias` places a linked boundary inside the gap:
+// 0 starts the next line early, 1 holds the previous one until the next begins.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TppOptions {
+    pub lead_in_ms: i64,
+    pub lead_out_ms: i64,
+    pub link_gap_ms: i64,
+    pub link_bias: f64,
+    pub snap_start: (i64, i64),
+    pub snap_end: (i64, i64),
+    pub skip_signs: bool,
+}
+
+impl Default for TppOptions {
+    fn default() -> Self {
+        Self {
+            lead_in_ms: 120,
+            lead_out_ms: 250,
+            link_gap_ms: 500,
+            link_bias: 0.5,
+            snap_start: (5, 4),
+            snap_end: (5, 6),
+            skip_signs: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TppReport {
+    pub lead: usize,
+    pub linked: usize,
+    pub snapped: usize,
+}
+
+fn is_sign(event: &Event) -> bool {
+    event.style.to_lowercase().contains("sign")
+}
+
+// Runs the three passes in Aegisub's order: lead-in/out, then linking, then keyframe snapping, so
+// snapping has the last word and linked lines snap to the same keyframe together. `keyframes`
+// pairs the keyframe numbers with the frame timing they count in; without it nothing is snapped.
+pub fn apply_tpp(
+    sub: &mut SubstationAlpha,
+    options: &TppOptions,
+    keyframes: Option<(&[i64], &FrameTimes)>,
+    filter: &EventFilter,
+) -> Result<TppReport, String> {
+    if !(0.0..=1.0).contains(&options.link_bias) {
+        return Err(format!("link bias {} is outside 0..1", options.link_bias));
+    }
+    if [options.lead_in_ms, options.lead_out_ms, options.link_gap_ms].iter().any(|v| *v < 0)
+        || [options.snap_start, options.snap_end].iter().any(|(before, after)| *before < 0 || *after < 0)
+    {
+        return Err("timing post-processor thresholds cannot be negative".to_string());
+    }
+
+    // (event index, start, end) of every line the passes may touch, in display order.
+    let mut lines: Vec<(usize, f64, f64)> = sub
+        .events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| filter.matches(event) && !(options.skip_signs && is_sign(event)))
+        .map(|(index, event)| (index, to_ms(&event.start), to_ms(&event.end)))
+        .filter(|(_, start, end)| end > start)
+        .collect();
+    lines.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.2.total_cmp(&b.2)));
+    let original: Vec<(f64, f64)> = lines.iter().map(|(_, start, end)| (*start, *end)).collect();
+    let mut report = TppReport::default();
+
+    // A lead never runs into a line it did not already overlap. Lead-out is applied to a line
+    // before the next line's lead-in, so a short gap goes to the line that is ending.
+    for i in 0..lines.len() {
+        let (_, start, end) = lines[i];
+        let mut new_start = start;
+        if options.lead_in_ms > 0 {
+            let bound = lines
+                .iter()
+                .enumerate()
+                .filter(|(j, line)| *j != i && line.2 <= start)
+                .map(|(_, line)| line.2)
+                .fold(0.0, f64::max);
+            new_start = (start - options.lead_in_ms as f64).max(bound);
+        }
+        let mut new_end = end;
+        if options.lead_out_ms > 0 {
+            let bound = lines
+                .iter()
+                .enumerate()
+                .filter(|(j, line)| *j != i && line.1 >= end)
+                .map(|(_, line)| line.1)
+                .fold(f64::INFINITY, f64::min);
+            new_end = (end + options.lead_out_ms as f64).min(bound);
+        }
+        if new_start != start || new_end != end {
+            lines[i].1 = new_start;
+            lines[i].2 = new_end;
+            report.lead += 1;
+        }
+    }
+
+    if options.link_gap_ms > 0 {
+        for i in 1..lines.len() {
+            let gap = lines[i].1 - lines[i - 1].2;
+            if gap > 0.0 && gap <= options.link_gap_ms as f64 {
+                let point = lines[i - 1].2 + gap * options.link_bias;
+                lines[i - 1].2 = point;
+                lines[i].1 = point;
+                report.linked += 1;
+            }
+        }
+    }
+
+    if let Some((frames, times)) = keyframes.filter(|(frames, _)| !frames.is_empty()) {
+        for line in &mut lines {
+            let start_frame = frame_from(times, line.1);
+            let end_frame = frame_from(times, line.2);
+            let new_start = nearest_keyframe(frames, start_frame, options.snap_start)
+                .map_or(line.1, |frame| frame_boundary(times, frame));
+            let new_end = nearest_keyframe(frames, end_frame, options.snap_end)
+                .map_or(line.2, |frame| frame_boundary(times, frame));
+            if new_end > new_start && (new_start != line.1 || new_end != line.2) {
+                line.1 = new_start;
+                line.2 = new_end;
+                report.snapped += 1;
+            }
+        }
+    }
+
+    for ((index, start, end), (old_start, old_end)) in lines.into_iter().zip(original) {
+        if start != old_start || end != old_end {
+            let event = &mut sub.events[index];
+            event.start = from_ms(start);
+            event.end = from_ms(end);
+        }
+    }
+    Ok(report)
+}
+
+// First frame shown at or after `ms` — a line starting at `ms` appears on it, and a line ending at
+// `ms` is gone by it.
+fn frame_from(times: &FrameTimes, ms: f64) -> i64 {
+    let frame = times.ms_to_frame(ms);
+    if times.frame_to_ms(frame) < ms - 1e-6 { frame + 1 } else { frame }
+}
+
+// A time halfway between a frame's start and the previous frame's, so centisecond rounding can
+// never move the boundary onto the wrong frame.
+fn frame_boundary(times: &FrameTimes, frame: i64) -> f64 {
+    if frame <= 0 {
+        return 0.0;
+    }
+    (times.frame_to_ms(frame - 1) + times.frame_to_ms(frame)) / 2.0
+}
+
+fn nearest_keyframe(frames: &[i64], frame: i64, (before, after): (i64, i64)) -> Option<i64> {
+    let from = frames.partition_point(|k| *k < frame - before);
+    frames[from..]
+        .iter()
+        .take_while(|k| **k <= frame + after)
+        .min_by_key(|k| (**k - frame).abs())
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\nStyle: Default,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,3.75,0,2,50,50,38,1\nStyle: Sign,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,3.75,0,2,50,50,38,1\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:03.00,Default,,0,0,0,,One\nDialogue: 0,0:00:03.30,0:00:05.00,Default,,0,0,0,,Two\nDialogue: 0,0:00:08.00,0:00:09.00,Default,,0,0,0,,Three\nDialogue: 0,0:00:08.50,0:00:09.50,Sign,,0,0,0,,Sign\n";
+
+    fn script() -> SubstationAlpha {
+        SubstationAlpha::parse(SCRIPT, true).0
+    }
+
+    fn times(sub: &SubstationAlpha, index: usize) -> (String, String) {
+        (sub.events[index].start.to_string(), sub.events[index].end.to_string())
+    }
+
+    #[test]
+    fn keyframes_round_trip_and_reject_other_files() {
+        let keyframes = parse_keyframes("# keyframe format v1\nfps 0\n48\n0\n120\n48\n").unwrap();
+        assert_eq!(keyframes, Keyframes { frames: vec![0, 48, 120], fps: None });
+        let written = format_keyframes(&keyframes.frames, Some(23.976));
+        assert_eq!(parse_keyframes(&written).unwrap().fps, Some(23.976));
+        assert!(parse_keyframes("# timecode format v2\n0\n").is_err());
+        assert!(parse_keyframes("# keyframe format v1\nfps 0\n").is_err());
+    }
+
+    #[test]
+    fn leads_stop_at_neighbours_and_short_gaps_are_linked() {
+        let mut sub = script();
+        let options = TppOptions { lead_in_ms: 200, lead_out_ms: 200, link_gap_ms: 500, link_bias: 0.5, ..TppOptions::default() };
+        let report = apply_tpp(&mut sub, &options, None, &EventFilter::default()).unwrap();
+        // One's lead-out and Two's lead-in meet in the 300 ms gap instead of overlapping.
+        assert_eq!(times(&sub, 0), ("0:00:00.80".to_string(), "0:00:03.20".to_string()));
+        assert_eq!(times(&sub, 1), ("0:00:03.20".to_string(), "0:00:05.20".to_string()));
+        assert_eq!(times(&sub, 2), ("0:00:07.80".to_string(), "0:00:09.20".to_string()));
+        // The sign keeps its hand timing.
+        assert_eq!(times(&sub, 3), ("0:00:08.50".to_string(), "0:00:09.50".to_string()));
+        assert_eq!(report, TppReport { lead: 3, linked: 0, snapped: 0 });
+
+        let mut sub = script();
+        let options = TppOptions { lead_in_ms: 0, lead_out_ms: 0, link_gap_ms: 500, link_bias: 1.0, ..TppOptions::default() };
+        let report = apply_tpp(&mut sub, &options, None, &EventFilter::default()).unwrap();
+        assert_eq!(report.linked, 1);
+        assert_eq!(times(&sub, 0).1, "0:00:03.30");
+    }
+
+    #[test]
+    fn starts_and_ends_snap_to_keyframes_inside_their_windows() {
+        let mut sub = script();
+        let fps = FrameTimes::constant(25.0).unwrap();
+        // One starts on frame 25 and ends on frame 75; Two starts on 83 and ends on 125.
+        let keyframes = [0, 22, 78, 85, 140];
+        let options = TppOptions { lead_in_ms: 0, lead_out_ms: 0, link_gap_ms: 0, ..TppOptions::default() };
+        let report = apply_tpp(&mut sub, &options, Some((&keyframes, &fps)), &EventFilter::default()).unwrap();
+        assert_eq!(times(&sub, 0), ("0:00:00.86".to_string(), "0:00:03.10".to_string()));
+        assert_eq!(times(&sub, 1), ("0:00:03.38".to_string(), "0:00:05.00".to_string()));
+        assert_eq!(report.snapped, 2);
+        // Already on a keyframe boundary: a second run changes nothing.
+        let again = apply_tpp(&mut sub, &options, Some((&keyframes, &fps)), &EventFilter::default()).unwrap();
+        assert_eq!(again.snapped, 0);
+        assert!(apply_tpp(&mut sub, &TppOptions { link_bias: 2.0, ..TppOptions::default() }, None, &EventFilter::default()).is_err());
+    }
+}
@@ -1,10 +1,16 @@
-use kagami_trace::{Segment, Trace};
+use kagami_trace::{
+    Color, GradientShape, LayerGradient, Point, Segment, Trace, TraceFrame, TraceLayer,
+};
+use serde::{Deserialize, Serialize};
 
 use crate::libkagami::complex::overrides::ASSOverride;
 use crate::libkagami::complex::types::{AssColour, AssTime};
 use crate::libkagami::core::{Event, PandoraMeta, ScriptInfo, SubstationAlpha, V4pStyle};
+use crate::libkagami::sections::ProjectGarbage;
+use crate::libkagami::drawing::geometry::clip_override;
 use crate::libkagami::drawing::parse::{Drawing, DrawingCommand};
 use crate::libkagami::tags::{ASSLine, ASSText};
+use crate::libkagami::timing::FrameTimes;
 
 #[derive(Clone, Debug)]
 pub struct TraceAssOptions {
@@ -15,6 +21,25 @@ pub struct TraceAssOptions {
     pub end: AssTime,
     pub base_layer: u16,
     pub seam_overlap: f32,
+    pub mode: TraceAssMode,
+    // Output pixels per clipped color step when a layer carries a gradient.
+    pub gradient_band: f32,
+}
+
+// Upper bound on the events one gradient layer expands to; longer ramps get wider bands.
+const MAX_GRADIENT_EVENTS: usize = 256;
+// Control point distance for a quarter circle drawn as one cubic.
+const CIRCLE_KAPPA: f32 = 0.552_284_8;
+
+// Filled `\p1` drawings, one event per color layer, or the whole traced shape as a single vector
+// `\clip` / `\iclip` mask event whose tag can be lifted onto an existing line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceAssMode {
+    #[default]
+    Drawing,
+    Clip,
+    Iclip,
 }
 
 impl Default for TraceAssOptions {
@@ -27,6 +52,8 @@ impl Default for TraceAssOptions {
             end: AssTime::from_centiseconds(500),
             base_layer: 0,
             seam_overlap: 0.5,
+            mode: TraceAssMode::Drawing,
+            gradient_band: 2.0,
         }
     }
 }
@@ -46,6 +73,74 @@ pub fn trace_json_to_ass(
 }
 
 pub fn trace_to_ass(trace: &Trace, options: &TraceAssOptions) -> Result<SubstationAlpha, String> {
+    validate_trace(trace, options)?;
+    if options.end.total_centiseconds() <= options.start.total_centiseconds() {
+        return Err("ASS trace end time must be after its start time".to_string());
+    }
+    validate_ass_options(options)?;
+    let events = trace_events(trace, options.start, options.end, options)?;
+    Ok(trace_script(trace, options, events))
+}
+
+// One set of drawing events per animation frame, starting at `options.start` and ending with the
+// last frame (`options.end` is not used). Frame boundaries are snapped to the nearest video
+// frame at `fps` and written as the centisecond at or just before that frame starts, so each set
+// shows on exactly the frames it covers. Consecutive frames that trace identically share one set
+// of events; frames too short to reach the next video frame are dropped.
+pub fn trace_frames_to_ass(
+    frames: &[TraceFrame],
+    fps: f64,
+    options: &TraceAssOptions,
+) -> Result<SubstationAlpha, String> {
+    let first = &frames
+        .first()
+        .ok_or_else(|| "animation has no frames".to_string())?
+        .trace;
+    let times = FrameTimes::constant(fps)?;
+    if fps > 100.0 {
+        return Err(format!("{fps} fps is finer than ASS centisecond timing"));
+    }
+    validate_ass_options(options)?;
+
+    let start_ms = options.start.total_centiseconds() as f64 * 10.0;
+    let snap = |ms: f64| times.ms_to_frame(ms + 500.0 / fps);
+    let mut spans: Vec<(&Trace, i64, i64)> = Vec::new();
+    let mut elapsed = 0.0;
+    for frame in frames {
+        if (frame.trace.width, frame.trace.height) != (first.width, first.height) {
+            return Err("animation frames differ in size".to_string());
+        }
+        let from = snap(start_ms + elapsed);
+        elapsed += frame.duration_ms as f64;
+        let to = snap(start_ms + elapsed);
+        if to <= from {
+            continue;
+        }
+        match spans.last_mut() {
+            Some(last) if last.2 == from && *last.0 == frame.trace => last.2 = to,
+            _ => spans.push((&frame.trace, from, to)),
+        }
+    }
+    if spans.is_empty() {
+        return Err("animation is shorter than one video frame".to_string());
+    }
+
+    let frame_time =
+        |frame: i64| AssTime::from_centiseconds((times.frame_to_ms(frame) / 10.0).floor() as u64);
+    let mut events = Vec::new();
+    for (trace, from, to) in spans {
+        validate_trace(trace, options)?;
+        events.extend(trace_events(
+            trace,
+            frame_time(from),
+            frame_time(to),
+            options,
+        )?);
+    }
+    Ok(trace_script(first, options, events))
+}
+
+fn validate_trace(trace: &Trace, options: &TraceAssOptions) -> Result<(), String> {
     trace.validate()?;
     if trace.width > u16::MAX as u32 || trace.height > u16::MAX as u32 {
         return Err(format!(
@@ -53,22 +148,153 @@ pub fn trace_to_ass(trace: &Trace, options: &TraceAssOptions) -> Result<Substati
             trace.width, trace.height
         ));
     }
-    if options.end.total_centiseconds() <= options.start.total_centiseconds() {
-        return Err("ASS trace end time must be after its start time".to_string());
-    }
-    validate_ass_field("title", &options.title, false)?;
-    validate_ass_field("style", &options.style, true)?;
-    validate_ass_field("actor", &options.actor, true)?;
-    if !options.seam_overlap.is_finite() || !(0.0..=4.0).contains(&options.seam_overlap) {
-        return Err("ASS trace seam overlap must be finite and between 0 and 4".to_string());
-    }
     let final_offset = u16::try_from(trace.layers.len().saturating_sub(1))
         .map_err(|_| "trace contains too many layers for ASS".to_string())?;
     options
         .base_layer
         .checked_add(final_offset)
         .ok_or_else(|| "trace contains too many layers for ASS".to_string())?;
+    Ok(())
+}
+
+fn validate_ass_options(options: &TraceAssOptions) -> Result<(), String> {
+    validate_ass_field("title", &options.title, false)?;
+    validate_ass_field("style", &options.style, true)?;
+    validate_ass_field("actor", &options.actor, true)?;
+    if !options.seam_overlap.is_finite() || !(0.0..=4.0).contains(&options.seam_overlap) {
+        return Err("ASS trace seam overlap must be finite and between 0 and 4".to_string());
+    }
+    if !options.gradient_band.is_finite() || !(0.5..=64.0).contains(&options.gradient_band) {
+        return Err("ASS trace gradient band must be finite and between 0.5 and 64".to_string());
+    }
+    Ok(())
+}
+
+// Where a trace of part of a video frame lands in a script: trace coordinates are scaled by
+// `scale_x` / `scale_y` and moved so the trace's top-left corner sits at (`x`, `y`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TracePlacement {
+    pub play_res_x: u16,
+    pub play_res_y: u16,
+    pub x: f32,
+    pub y: f32,
+    pub scale_x: f32,
+    pub scale_y: f32,
+}
 
+impl TracePlacement {
+    // A trace of the crop whose top-left corner is `crop_origin` in a `video_width` x
+    // `video_height` frame, for a script at `play_res`.
+    pub fn for_crop(video: (u32, u32), crop_origin: (u32, u32), play_res: (u16, u16)) -> Self {
+        let scale_x = play_res.0 as f32 / video.0.max(1) as f32;
+        let scale_y = play_res.1 as f32 / video.1.max(1) as f32;
+        Self {
+            play_res_x: play_res.0,
+            play_res_y: play_res.1,
+            x: crop_origin.0 as f32 * scale_x,
+            y: crop_origin.1 as f32 * scale_y,
+            scale_x,
+            scale_y,
+        }
+    }
+}
+
+// The trace moved into script coordinates, with the placement's PlayRes as its size so
+// `trace_to_ass` writes that PlayRes. Outline widths and radial gradients take the mean scale.
+pub fn place_trace(trace: &Trace, placement: &TracePlacement) -> Trace {
+    let (sx, sy) = (placement.scale_x, placement.scale_y);
+    let mean = (sx * sy).abs().sqrt();
+    let map = |point: Point| Point {
+        x: placement.x + point.x * sx,
+        y: placement.y + point.y * sy,
+    };
+    let mut placed = trace.clone();
+    placed.width = placement.play_res_x as u32;
+    placed.height = placement.play_res_y as u32;
+    for layer in &mut placed.layers {
+        for path in &mut layer.paths {
+            path.start = map(path.start);
+            path.area *= (sx * sy) as f64;
+            for segment in &mut path.segments {
+                match segment {
+                    Segment::Line { to } => *to = map(*to),
+                    Segment::Cubic {
+                        control_1,
+                        control_2,
+                        to,
+                    } => {
+                        *control_1 = map(*control_1);
+                        *control_2 = map(*control_2);
+                        *to = map(*to);
+                    }
+                }
+            }
+        }
+        if let Some(outline) = &mut layer.outline {
+            outline.width *= mean;
+        }
+        if let Some(gradient) = &mut layer.gradient {
+            gradient.shape = match gradient.shape {
+                GradientShape::Linear { from, to } => GradientShape::Linear {
+                    from: map(from),
+                    to: map(to),
+                },
+                GradientShape::Radial { center, radius } => GradientShape::Radial {
+                    center: map(center),
+                    radius: radius * mean,
+                },
+            };
+        }
+    }
+    placed
+}
+
+// The traced shape as one `\clip` (or `\iclip` when `inverse`) in script coordinates: every
+// layer's contours together, which is the union of the colored regions. `None` for a trace with
+// no paths.
+pub fn trace_clip(trace: &Trace, inverse: bool) -> Option<ASSOverride> {
+    let commands: Vec<DrawingCommand> = trace
+        .layers
+        .iter()
+        .flat_map(|layer| layer_drawing(layer).commands)
+        .collect();
+    if commands.is_empty() {
+        return None;
+    }
+    Some(clip_override(&Drawing { commands }, inverse))
+}
+
+fn trace_events(
+    trace: &Trace,
+    start: AssTime,
+    end: AssTime,
+    options: &TraceAssOptions,
+) -> Result<Vec<Event>, String> {
+    let inverse = match options.mode {
+        TraceAssMode::Drawing => return layer_events(trace, start, end, options),
+        TraceAssMode::Clip => false,
+        TraceAssMode::Iclip => true,
+    };
+    let Some(clip) = trace_clip(trace, inverse) else {
+        return Ok(Vec::new());
+    };
+    let overrides = vec![clip];
+    Ok(vec![trace_event(
+        options.base_layer,
+        start,
+        end,
+        overrides,
+        None,
+        options,
+    )])
+}
+
+fn layer_events(
+    trace: &Trace,
+    start: AssTime,
+    end: AssTime,
+    options: &TraceAssOptions,
+) -> Result<Vec<Event>, String> {
     let mut events = Vec::with_capacity(trace.layers.len());
     for (index, layer) in trace.layers.iter().enumerate() {
         let color = trace
@@ -78,39 +304,295 @@ pub fn trace_to_ass(trace: &Trace, options: &TraceAssOptions) -> Result<Substati
         if drawing.commands.is_empty() {
             continue;
         }
-        let color_value = ((color.b as u32) << 16) | ((color.g as u32) << 8) | color.r as u32;
-        let alpha_value = 255u32 - color.a as u32;
-        let overrides = vec![
-            ASSOverride::An(7),
-            ASSOverride::Pos(0.0, 0.0),
-            ASSOverride::Bord(options.seam_overlap),
-            ASSOverride::Shad(0.0),
-            ASSOverride::ColorI(color_value),
-            ASSOverride::ColorIII(color_value),
-            ASSOverride::AlphaI(alpha_value),
-            ASSOverride::AlphaIII(alpha_value),
-            ASSOverride::P(1),
-        ];
-        let mut data: Vec<ASSText> = overrides.iter().cloned().map(ASSText::Override).collect();
-        data.push(ASSText::Drawing(drawing));
-        events.push(Event {
-            layer: options.base_layer + index as u16,
-            start: options.start,
-            end: options.end,
-            style: options.style.clone(),
-            name: options.actor.clone(),
-            margin_l: 0,
-            margin_r: 0,
-            margin_v: 0,
-            effect: String::new(),
-            text: ASSLine {
-                current_overrides: overrides,
-                data,
-            },
+        // A folded outline replaces the same-color seam underlap with a real border, widened by
+        // the same overlap so it still meets the layer behind it.
+        let (border, border_color) = match layer.outline {
+            Some(outline) => {
+                let outline_color = trace
+                    .palette
+                    .get(outline.palette_index as usize)
+                    .ok_or_else(|| format!("trace layer {} has an invalid outline color", index))?;
+                (outline.width + options.seam_overlap, *outline_color)
+            }
+            None => (options.seam_overlap, color),
+        };
+        let overrides = |color: Color, border_color: Color, clip: Option<ASSOverride>| {
+            let mut overrides = vec![
+                ASSOverride::An(7),
+                ASSOverride::Pos(0.0, 0.0),
+                ASSOverride::Bord(border),
+                ASSOverride::Shad(0.0),
+                ASSOverride::ColorI(ass_color(color)),
+                ASSOverride::ColorIII(ass_color(border_color)),
+                ASSOverride::AlphaI(ass_alpha(color)),
+                ASSOverride::AlphaIII(ass_alpha(border_color)),
+            ];
+            overrides.extend(clip);
+            overrides.push(ASSOverride::P(1));
+            overrides
+        };
+        let layer_number = options.base_layer + index as u16;
+        let Some(gradient) = &layer.gradient else {
+            events.push(trace_event(
+                layer_number,
+                start,
+                end,
+                overrides(color, border_color, None),
+                Some(drawing),
+                options,
+            ));
+            continue;
+        };
+        // Gradient by clip: the whole drawing once per band, each clipped to its slice of the
+        // ramp and filled with the band's color. The bands share the layer number so they stack
+        // in script order. Without an outline the seam underlap takes the band color too.
+        let bands = gradient_bands(trace, layer, gradient, options.gradient_band)
+            .ok_or_else(|| format!("trace layer {} has an invalid gradient", index))?;
+        for (band, clip) in band_clips(trace, gradient, &bands, border + 1.0) {
+            let band_color = bands[band].color;
+            let band_border = if layer.outline.is_some() {
+                border_color
+            } else {
+                band_color
+            };
+            events.push(trace_event(
+                layer_number,
+                start,
+                end,
+                overrides(band_color, band_border, clip),
+                Some(drawing.clone()),
+                options,
+            ));
+        }
+    }
+    Ok(events)
+}
+
+// One step of a gradient layer: ramp offsets `from..to` painted in `color`.
+struct Band {
+    from: f32,
+    to: f32,
+    color: Color,
+}
+
+// The part of the ramp the layer covers, cut into steps of about `band_size` output pixels and
+// colored at their midpoints. Neighbouring steps that come out the same color are merged.
+fn gradient_bands(
+    trace: &Trace,
+    layer: &TraceLayer,
+    gradient: &LayerGradient,
+    band_size: f32,
+) -> Option<Vec<Band>> {
+    let (mut low, mut high) = (f32::INFINITY, f32::NEG_INFINITY);
+    for path in &layer.paths {
+        let ends = path.segments.iter().map(|segment| match segment {
+            Segment::Line { to } | Segment::Cubic { to, .. } => *to,
         });
+        for point in std::iter::once(path.start).chain(ends) {
+            let offset = gradient.shape.offset_at(point);
+            low = low.min(offset);
+            high = high.max(offset);
+        }
     }
+    if !low.is_finite() || !high.is_finite() {
+        return None;
+    }
+    let span = high - low;
+    let count = ((span * gradient.shape.length() / band_size).ceil() as usize)
+        .clamp(1, MAX_GRADIENT_EVENTS);
+    let mut bands: Vec<Band> = Vec::with_capacity(count);
+    for step in 0..count {
+        let from = low + span * step as f32 / count as f32;
+        let to = low + span * (step + 1) as f32 / count as f32;
+        let color = gradient.color_at(&trace.palette, (from + to) / 2.0)?;
+        match bands.last_mut() {
+            Some(last) if last.color == color => last.to = to,
+            _ => bands.push(Band { from, to, color }),
+        }
+    }
+    Some(bands)
+}
 
-    Ok(SubstationAlpha {
+// The clip for each band, in the order the band events are written. Opaque ramps nest the clips
+// (every band is drawn over the whole rest of the ramp and the next one covers it) so antialiased
+// clip edges never leave a seam; translucent ones need disjoint clips so bands don't add up.
+// Axis-aligned linear ramps always use disjoint whole-pixel `\clip` rectangles, which libass
+// doesn't antialias. The outer ends reach `margin` pixels past the layer so its border survives.
+fn band_clips(
+    trace: &Trace,
+    gradient: &LayerGradient,
+    bands: &[Band],
+    margin: f32,
+) -> Vec<(usize, Option<ASSOverride>)> {
+    let opaque = gradient.stops.iter().all(|stop| {
+        trace
+            .palette
+            .get(stop.palette_index as usize)
+            .is_some_and(|color| color.a == 255)
+    });
+    let last = bands.len() - 1;
+    let (low, high) = (bands[0].from, bands[last].to);
+    let length = gradient.shape.length();
+    let mut clips = Vec::with_capacity(bands.len());
+    match gradient.shape {
+        GradientShape::Linear { from, to } => {
+            let (dx, dy) = (to.x - from.x, to.y - from.y);
+            let axis_aligned = dx == 0.0 || dy == 0.0;
+            let reach = (trace.width + trace.height) as f32;
+            let (nx, ny) = (-dy / length * reach, dx / length * reach);
+            let edge = |offset: f32| {
+                let (x, y) = (from.x + dx * offset, from.y + dy * offset);
+                if axis_aligned {
+                    (x.round(), y.round())
+                } else {
+                    (x, y)
+                }
+            };
+            let slice = |a: f32, b: f32| {
+                let ((ax, ay), (bx, by)) = (edge(a), edge(b));
+                let quad = Drawing {
+                    commands: vec![
+                        DrawingCommand::Move(ax + nx, ay + ny),
+                        DrawingCommand::Line(bx + nx, by + ny),
+                        DrawingCommand::Line(bx - nx, by - ny),
+                        DrawingCommand::Line(ax - nx, ay - ny),
+                    ],
+                };
+                ((ax, ay) != (bx, by)).then(|| clip_override(&quad, false))
+            };
+            let outside = margin / length;
+            for (index, band) in bands.iter().enumerate() {
+                let end = if index == last {
+                    high + outside
+                } else {
+                    band.to
+                };
+                let clip = if opaque && !axis_aligned {
+                    if index == 0 {
+                        clips.push((index, None));
+                        continue;
+                    }
+                    slice(band.from, high + outside)
+                } else {
+                    slice(if index == 0 { low - outside } else { band.from }, end)
+                };
+                // A rounded strip can come out empty; its neighbours cover it.
+                if clip.is_some() || bands.len() == 1 {
+                    clips.push((index, clip));
+                }
+            }
+        }
+        GradientShape::Radial { center, radius } => {
+            let disk = |r: f32, reverse: bool, commands: &mut Vec<DrawingCommand>| {
+                let k = r * CIRCLE_KAPPA;
+                let s = if reverse { -1.0 } else { 1.0 };
+                let (cx, cy) = (center.x, center.y);
+                commands.push(DrawingCommand::Move(cx + r, cy));
+                commands.push(DrawingCommand::CubicBezier(
+                    cx + r,
+                    cy + k * s,
+                    cx + k,
+                    cy + r * s,
+                    cx,
+                    cy + r * s,
+                ));
+                commands.push(DrawingCommand::CubicBezier(
+                    cx - k,
+                    cy + r * s,
+                    cx - r,
+                    cy + k * s,
+                    cx - r,
+                    cy,
+                ));
+                commands.push(DrawingCommand::CubicBezier(
+                    cx - r,
+                    cy - k * s,
+                    cx - k,
+                    cy - r * s,
+                    cx,
+                    cy - r * s,
+                ));
+                commands.push(DrawingCommand::CubicBezier(
+                    cx + k,
+                    cy - r * s,
+                    cx + r,
+                    cy - k * s,
+                    cx + r,
+                    cy,
+                ));
+            };
+            let clip = |outer: f32, inner: f32| {
+                let mut commands = Vec::new();
+                disk(outer, false, &mut commands);
+                if inner > 0.0 {
+                    disk(inner, true, &mut commands);
+                }
+                Some(clip_override(&Drawing { commands }, false))
+            };
+            if opaque {
+                // Outside in: each disk is drawn over the bands around it.
+                clips.push((last, None));
+                for index in (0..last).rev() {
+                    clips.push((index, clip(bands[index].to * radius, 0.0)));
+                }
+            } else {
+                for (index, band) in bands.iter().enumerate() {
+                    let outer = if index == last {
+                        high * radius + margin
+                    } else {
+                        band.to * radius
+                    };
+                    let inner = if index == 0 {
+                        low * radius - margin
+                    } else {
+                        band.from * radius
+                    };
+                    clips.push((index, clip(outer, inner)));
+                }
+            }
+        }
+    }
+    clips
+}
+
+fn trace_event(
+    layer: u16,
+    start: AssTime,
+    end: AssTime,
+    overrides: Vec<ASSOverride>,
+    drawing: Option<Drawing>,
+    options: &TraceAssOptions,
+) -> Event {
+    let mut data: Vec<ASSText> = overrides.iter().cloned().map(ASSText::Override).collect();
+    data.extend(drawing.map(ASSText::Drawing));
+    Event {
+        layer,
+        start,
+        end,
+        style: options.style.clone(),
+        name: options.actor.clone(),
+        margin_l: 0,
+        margin_r: 0,
+        margin_v: 0,
+        effect: String::new(),
+        text: ASSLine {
+            current_overrides: overrides,
+            data,
+        },
+        extradata: Vec::new(),
+    }
+}
+
+fn ass_color(color: Color) -> u32 {
+    ((color.b as u32) << 16) | ((color.g as u32) << 8) | color.r as u32
+}
+
+fn ass_alpha(color: Color) -> u32 {
+    255u32 - color.a as u32
+}
+
+fn trace_script(trace: &Trace, options: &TraceAssOptions, events: Vec<Event>) -> SubstationAlpha {
+    SubstationAlpha {
         script_info: ScriptInfo {
             title: options.title.clone(),
             script_type: "v4.00+".to_string(),
@@ -126,10 +608,14 @@ pub fn trace_to_ass(trace: &Trace, options: &TraceAssOptions) -> Result<Substati
         events,
         comments: Vec::new(),
         pandora_meta: PandoraMeta::default(),
-    })
+        fonts: Vec::new(),
+        graphics: Vec::new(),
+        project_garbage: ProjectGarbage::default(),
+        extradata: Vec::new(),
+    }
 }
 
-fn layer_drawing(layer: &kagami_trace::TraceLayer) -> Drawing {
+fn layer_drawing(layer: &TraceLayer) -> Drawing {
     let mut commands = Vec::new();
     for path in &layer.paths {
         commands.push(DrawingCommand::Move(path.start.x, path.start.y));
@@ -288,6 +774,8 @@ mod tests {
                     hole: false,
                     area: 7_850.0,
                 }],
+                outline: None,
+                gradient: None,
             }],
         };
         let output = trace_to_ass(&trace, &TraceAssOptions::default())
@@ -307,6 +795,173 @@ mod tests {
         assert!(parse_trace_json(&value.to_string()).is_err());
     }
 
+    #[test]
+    fn animation_frames_are_timed_to_video_frames() {
+        let still = traced_colors();
+        let mut moved = still.clone();
+        moved.layers.pop();
+        let frames = vec![
+            TraceFrame {
+                duration_ms: 80,
+                trace: still.clone(),
+            },
+            TraceFrame {
+                duration_ms: 80,
+                trace: still.clone(),
+            },
+            TraceFrame {
+                duration_ms: 10,
+                trace: moved.clone(),
+            },
+            TraceFrame {
+                duration_ms: 100,
+                trace: moved,
+            },
+        ];
+        let ass =
+            trace_frames_to_ass(&frames, 24000.0 / 1001.0, &TraceAssOptions::default()).unwrap();
+        let times: Vec<(u64, u64)> = ass
+            .events
+            .iter()
+            .map(|event| {
+                (
+                    event.start.total_centiseconds(),
+                    event.end.total_centiseconds(),
+                )
+            })
+            .collect();
+
+        // 160 ms of the still snaps to frame 4 (166.8 ms), the 10 ms frame never reaches a frame of its
+        // own, and the last ends on frame 6 (250.25 ms).
+        assert_eq!(times, vec![(0, 16), (0, 16), (16, 25)]);
+        assert!(trace_frames_to_ass(&[], 24.0, &TraceAssOptions::default()).is_err());
+    }
+
+    #[test]
+    fn clip_modes_and_folded_outlines() {
+        let mut options = TraceAssOptions {
+            mode: TraceAssMode::Iclip,
+            ..TraceAssOptions::default()
+        };
+        let ass = trace_to_ass(&traced_colors(), &options).unwrap();
+        assert_eq!(ass.events.len(), 1);
+        let output = ass.stringify();
+        assert!(output.contains(r"{\iclip(m "), "{}", output);
+        assert!(!output.contains(r"\p1"));
+
+        options.mode = TraceAssMode::Drawing;
+        let mut outlined = traced_colors();
+        outlined.layers[0].outline = Some(kagami_trace::LayerOutline {
+            palette_index: 1,
+            width: 2.0,
+        });
+        let outline = outlined.palette[1];
+        let output = trace_to_ass(&outlined, &options).unwrap().stringify();
+        assert!(output.contains(r"\bord2.5\shad0"), "{}", output);
+        assert!(output.contains(&format!(r"\3c&H{:06X}&", ass_color(outline))));
+    }
+
+    #[test]
+    fn gradient_layers_become_clipped_color_steps() {
+        let gray = |value: u8| Color {
+            r: value,
+            g: value,
+            b: value,
+            a: 255,
+        };
+        let square = Path {
+            start: Point { x: 0.0, y: 0.0 },
+            segments: vec![
+                Segment::Line {
+                    to: Point { x: 32.0, y: 0.0 },
+                },
+                Segment::Line {
+                    to: Point { x: 32.0, y: 32.0 },
+                },
+                Segment::Line {
+                    to: Point { x: 0.0, y: 32.0 },
+                },
+                Segment::Line {
+                    to: Point { x: 0.0, y: 0.0 },
+                },
+            ],
+            hole: false,
+            area: 1024.0,
+        };
+        let mut trace = Trace {
+            schema_version: TRACE_SCHEMA_VERSION,
+            width: 32,
+            height: 32,
+            sampled_width: 32,
+            sampled_height: 32,
+            palette: vec![gray(0), gray(240)],
+            layers: vec![TraceLayer {
+                palette_index: 0,
+                sample_pixel_count: 1024,
+                paths: vec![square],
+                outline: None,
+                gradient: Some(LayerGradient {
+                    shape: GradientShape::Linear {
+                        from: Point { x: 0.0, y: 16.0 },
+                        to: Point { x: 32.0, y: 16.0 },
+                    },
+                    stops: vec![
+                        kagami_trace::GradientStop {
+                            offset: 0.0,
+                            palette_index: 0,
+                        },
+                        kagami_trace::GradientStop {
+                            offset: 1.0,
+                            palette_index: 1,
+                        },
+                    ],
+                }),
+            }],
+        };
+        let options = TraceAssOptions {
+            gradient_band: 4.0,
+            ..TraceAssOptions::default()
+        };
+        let ass = trace_to_ass(&trace, &options).unwrap();
+        assert_eq!(ass.events.len(), 8);
+        assert!(ass.events.iter().all(|event| event.layer == 0));
+        let output = ass.stringify();
+        assert!(output.contains(r"\c&H0F0F0F&\3c&H0F0F0F&"), "{}", output);
+        assert!(output.contains(r"\c&HE1E1E1&"), "{}", output);
+        assert!(output.contains(r"\clip(4,-48,8,80)\p1"), "{}", output);
+
+        trace.layers[0].gradient.as_mut().unwrap().shape = GradientShape::Radial {
+            center: Point { x: 16.0, y: 16.0 },
+            radius: 24.0,
+        };
+        let ass = trace_to_ass(&trace, &options).unwrap();
+        let first = ass.events[0].text.stringify();
+        assert!(
+            !first.contains(r"\clip"),
+            "the outermost band is drawn unclipped"
+        );
+        assert!(
+            ass.events[1..]
+                .iter()
+                .all(|event| event.text.stringify().contains(r"\clip(m "))
+        );
+    }
+
+    #[test]
+    fn placed_traces_land_in_the_script_play_res() {
+        let trace = traced_colors();
+        let placement = TracePlacement::for_crop((3840, 2160), (100, 40), (1920, 1080));
+        let placed = place_trace(&trace, &placement);
+        let ass = trace_to_ass(&placed, &TraceAssOptions::default()).unwrap();
+        assert_eq!((ass.script_info.playresx, ass.script_info.playresy), (1920, 1080));
+
+        let original = &trace.layers[0].paths[0];
+        let moved = &placed.layers[0].paths[0];
+        assert_eq!(moved.start.x, 50.0 + original.start.x / 2.0);
+        assert_eq!(moved.start.y, 20.0 + original.start.y / 2.0);
+        assert_eq!(moved.area, original.area / 4.0);
+    }
+
     #[test]
     fn invalid_ass_options_are_rejected() {
         let mut options = TraceAssOptions::default();
@@ -316,5 +971,9 @@ mod tests {
         options.style = "Kagami Trace".to_string();
         options.seam_overlap = f32::NAN;
         assert!(trace_to_ass(&traced_colors(), &options).is_err());
+
+        options.seam_overlap = 0.5;
+        options.gradient_band = 0.0;
+        assert!(trace_to_ass(&traced_colors(), &options).is_err());
     }
 }
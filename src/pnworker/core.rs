@@ -1,6 +1,8 @@
+use crate::kagami_trace::TracePreset;
 use crate::lib::db::core::JobDb;
+use crate::lib::mpeg::clip::FrameCrop;
 use crate::lib::p2p::core::cleanup_torrent_runtime;
-use crate::lib::p2p::nyaaise::TorrentType;
+use crate::lib::p2p::nyaaise::{TorrentType, nyaaise};
 use crate::lib::subs::ensure_ass_bytes;
 use crate::pnworker::cache::{
     cache_encode_input, cleanup_expired_input_cache, cleanup_input_cache_startup,
@@ -49,7 +51,7 @@ use crate::pnworker::util::job_cancelled;
 use serenity::all::{Context, CreateEmbed, Message};
 use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs::{File, create_dir_all, remove_dir_all, rename, write};
 use tokio::sync::mpsc::Receiver;
@@ -70,6 +72,9 @@ pub enum WorkerMsg {
     Upload(UploadData),
     UploadAll(UploadAllData),
     Subs(SubsData),
+    Resync(ResyncData),
+    Keyframes(KeyframesData),
+    Trace(TraceData),
 }
 
 pub const STRUCT: [&str; 3] = ["contents", "work", "log"];
@@ -275,6 +280,9 @@ async fn queue_new_job(
         JobType::Encode => queue_encode_job(db, queue, shrine, job).await,
         JobType::Probe => queue_probe_job(db, queue, shrine, job).await,
         JobType::Subs => queue_subs_job(db, queue, shrine, job).await,
+        JobType::Resync => queue_resync_job(db, queue, shrine, job).await,
+        JobType::Keyframes => queue_keyframes_job(db, queue, shrine, job).await,
+        JobType::Trace => queue_trace_job(db, queue, shrine, job).await,
         JobType::Pancode => queue_pancode_job(db, queue, shrine, job).await,
         JobType::Batch => queue_batch_job(db, queue, shrine, job).await,
         JobType::Backup => queue_backup_job(db, queue, shrine, job).await,
@@ -311,13 +319,16 @@ async fn prepare_queued_job(job: &mut Job, worker: &str, write_subtitle: bool) -
         }
     }
     if write_subtitle {
-        // Anything ffmpeg can read as text is accepted and normalised here, since libass
+        // Any accepted text subtitle is normalised here, since libass
         // only takes ASS/SSA and the attachment arrives as raw bytes.
         if !job.attachment.is_empty() {
             match ensure_ass_bytes(&job.attachment).await {
                 Ok(converted) => {
-                    if converted.warning.is_some() {
-                        println!("[Pandora] job {} subtitle converted to ASS", job.job_id);
+                    // Conversion and re-encoding notes show up in the job embed's Warnings field
+                    // next to pnmpeg's own encode warnings.
+                    for warning in converted.warnings {
+                        println!("[Pandora] job {} subtitle: {}", job.job_id, warning);
+                        job.encode_warnings.push(warning);
                     }
                     job.attachment = converted.bytes;
                 }
@@ -513,6 +524,78 @@ async fn queue_subs_job(
     .await
 }
 
+// A resync downloads twice through the ordinary pool: the new release first, which is moved aside
+// to `contents/target.mkv`, then the release the repo was timed against. See the Downloaded arm of
+// do_job_progression_things for the hand-over between the two.
+async fn queue_resync_job(
+    db: &JobDb,
+    queue: &[Job],
+    shrine: &mut TypedShrine<WorkerMsg>,
+    job: &mut Job,
+) -> bool {
+    if job.resync.is_none() {
+        decline_job_setup(job, "resync request missing").await;
+        return true;
+    }
+    if let Err(reason) = prepare_queued_job(job, "dwl-pending", false).await {
+        decline_job_setup(job, &reason).await;
+        return true;
+    }
+    queue_download_job(db, queue, shrine, job, Vec::new(), false).await
+}
+
+// Keyframes need the release and nothing else; the commit back to the repo happens on the probe
+// pool once pnmpeg has written the file.
+async fn queue_keyframes_job(
+    db: &JobDb,
+    queue: &[Job],
+    shrine: &mut TypedShrine<WorkerMsg>,
+    job: &mut Job,
+) -> bool {
+    if job.keyframes.is_none() {
+        decline_job_setup(job, "keyframes request missing").await;
+        return true;
+    }
+    if let Err(reason) = prepare_queued_job(job, "dwl-pending", false).await {
+        decline_job_setup(job, &reason).await;
+        return true;
+    }
+    queue_download_job(db, queue, shrine, job, Vec::new(), false).await
+}
+
+// Tracing a frame needs only the video, which may be a probed file like `/subs`; the frame is
+// pulled and traced on the probe pool.
+async fn queue_trace_job(
+    db: &JobDb,
+    queue: &[Job],
+    shrine: &mut TypedShrine<WorkerMsg>,
+    job: &mut Job,
+) -> bool {
+    if job.trace.is_none() {
+        decline_job_setup(job, "trace request missing").await;
+        return true;
+    }
+    if let Err(reason) = prepare_queued_job(job, "dwl-pending", false).await {
+        decline_job_setup(job, &reason).await;
+        return true;
+    }
+    if let Some(probe_id) = job.probe_job_id {
+        if !adopt_probe_torrent(job, probe_id).await && job.torrent.get().trim().is_empty() {
+            decline_job_setup(job, "probe torrent data is no longer available").await;
+            return true;
+        }
+    }
+    queue_download_job(
+        db,
+        queue,
+        shrine,
+        job,
+        job.probe_file_index.into_iter().collect(),
+        false,
+    )
+    .await
+}
+
 // A batch owns one download of many files. Its own work directory only ever holds the torrent —
 // the per-episode subtitle goes to the child job that the finished file is handed to, so nothing
 // here writes `contents/subtitle.ass`.
@@ -1339,6 +1422,9 @@ fn job_type_label(job_type: JobType) -> &'static str {
         JobType::StudioPreview => "studio-preview",
         JobType::Batch => "batch",
         JobType::Subs => "subs",
+        JobType::Resync => "resync",
+        JobType::Keyframes => "keyframes",
+        JobType::Trace => "trace",
     }
 }
 
@@ -1943,6 +2029,18 @@ async fn do_batch_parent_things(db: &JobDb, queue: &mut Vec<Job>) {
     }
 }
 
+// Moves the first resync download out of the way so the reference can reuse the torrent layout,
+// including `fetch.torrent`, which would otherwise key the cache to the wrong release.
+async fn park_resync_target(directory: &Path) -> Result<(), String> {
+    let contents = directory.join("contents");
+    rename(contents.join("torrent").join("input.mkv"), contents.join("target.mkv"))
+        .await
+        .map_err(|e| format!("could not keep the new source: {}", e))?;
+    remove_dir_all(contents.join("torrent")).await.ok();
+    tokio::fs::remove_file(contents.join("fetch.torrent")).await.ok();
+    Ok(())
+}
+
 async fn requeue_duplicate_waiter(db: &JobDb, job: &mut Job) {
     job.duplicate_source = None;
     job.ready = Stage::Queued;
@@ -2046,7 +2144,11 @@ async fn do_queued_download_waiting_things(
                 && j.worker == "dwl-pending"
                 && matches!(
                     j.job_type,
-                    JobType::Encode | JobType::Pancode | JobType::Backup | JobType::Preview
+                    JobType::Encode
+                        | JobType::Pancode
+                        | JobType::Backup
+                        | JobType::Preview
+                        | JobType::Resync
                 )
         })
         .map(|j| j.job_id)
@@ -2180,6 +2282,153 @@ async fn do_job_progression_things(
                 job.frontend
                     .set_presence(Presence::Encoding { idx, total: qlen })
                     .await;
+            } else if job.job_type == JobType::Resync {
+                let Some(mut resync) = job.resync.clone() else {
+                    job.ready = Stage::Failed;
+                    db.update_stage(job.job_id, Stage::Failed).await.ok();
+                    render(
+                        job,
+                        MessagePayload::Progress(
+                            crate::pnworker::messages::RESYNC_FAIL,
+                            vec!["missing resync request".to_string()],
+                        ),
+                    )
+                    .await;
+                    dead.push(job.job_id);
+                    continue;
+                };
+                if !resync.target_ready {
+                    // First leg done: park the new release and send the job back through the
+                    // download queue for the reference, which lands in the usual input.mkv.
+                    if let Err(e) = park_resync_target(&job.directory).await {
+                        job.ready = Stage::Failed;
+                        db.update_stage(job.job_id, Stage::Failed).await.ok();
+                        render(
+                            job,
+                            MessagePayload::Progress(crate::pnworker::messages::RESYNC_FAIL, vec![e]),
+                        )
+                        .await;
+                        dead.push(job.job_id);
+                        continue;
+                    }
+                    resync.target_ready = true;
+                    job.torrent = nyaaise(&resync.reference);
+                    job.resync = Some(resync);
+                    job.duplicate_source = None;
+                    job.ready = Stage::Queued;
+                    job.worker = "dwl-pending".to_string();
+                    db.update_stage(job.job_id, Stage::Queued).await.ok();
+                    db.update_worker(job.job_id, &job.worker).await.ok();
+                    continue;
+                }
+                job.worker = "prw-pending".to_string();
+                db.update_worker(job.job_id, &job.worker).await.ok();
+                if !dispatch_or_kill(
+                    shrine,
+                    &Worker::Probe,
+                    WorkerMsg::Resync((
+                        job.directory.clone(),
+                        resync,
+                        job.server_id.unwrap_or_default(),
+                        job.channel_id,
+                        job.job_id,
+                    )),
+                    job,
+                    db,
+                    false,
+                )
+                .await
+                {
+                    dead.push(job.job_id);
+                    continue;
+                }
+                job.ready = Stage::Encoding;
+                db.update_stage(job.job_id, Stage::Encoding).await.ok();
+                job.frontend
+                    .set_presence(Presence::Encoding { idx, total: qlen })
+                    .await;
+            } else if job.job_type == JobType::Keyframes {
+                let Some(keyframes) = job.keyframes.clone() else {
+                    job.ready = Stage::Failed;
+                    db.update_stage(job.job_id, Stage::Failed).await.ok();
+                    render(
+                        job,
+                        MessagePayload::Progress(
+                            crate::pnworker::messages::KEYFRAMES_FAIL,
+                            vec!["missing keyframes request".to_string()],
+                        ),
+                    )
+                    .await;
+                    dead.push(job.job_id);
+                    continue;
+                };
+                job.worker = "prw-pending".to_string();
+                db.update_worker(job.job_id, &job.worker).await.ok();
+                if !dispatch_or_kill(
+                    shrine,
+                    &Worker::Probe,
+                    WorkerMsg::Keyframes((
+                        job.directory.clone(),
+                        keyframes,
+                        job.server_id.unwrap_or_default(),
+                        job.channel_id,
+                        job.job_id,
+                    )),
+                    job,
+                    db,
+                    false,
+                )
+                .await
+                {
+                    dead.push(job.job_id);
+                    continue;
+                }
+                job.ready = Stage::Encoding;
+                db.update_stage(job.job_id, Stage::Encoding).await.ok();
+                job.frontend
+                    .set_presence(Presence::Encoding { idx, total: qlen })
+                    .await;
+            } else if job.job_type == JobType::Trace {
+                let Some(trace) = job.trace.clone() else {
+                    job.ready = Stage::Failed;
+                    db.update_stage(job.job_id, Stage::Failed).await.ok();
+                    render(
+                        job,
+                        MessagePayload::Progress(
+                            crate::pnworker::messages::TRACE_FAIL,
+                            vec!["missing trace request".to_string()],
+                        ),
+                    )
+                    .await;
+                    dead.push(job.job_id);
+                    continue;
+                };
+                job.worker = "prw-pending".to_string();
+                db.update_worker(job.job_id, &job.worker).await.ok();
+                if !dispatch_or_kill(
+                    shrine,
+                    &Worker::Probe,
+                    WorkerMsg::Trace((
+                        job.directory.clone(),
+                        trace,
+                        job.server_id.unwrap_or_default(),
+                        job.channel_id,
+                        job.job_id,
+                    )),
+                    job,
+                    db,
+                    false,
+                )
+                .await
+                {
+                    dead.push(job.job_id);
+                    continue;
+                }
+                job.ready = Stage::Encoding;
+                db.update_stage(job.job_id, Stage::Encoding).await.ok();
+                job.frontend
+                    .set_presence(Presence::Encoding { idx, total: qlen })
+                    .await;
             } else if job.job_type == JobType::Preview {
                 let Some(preview) = job.preview.clone() else {
                     job.ready = Stage::Failed;
@@ -2634,6 +2883,34 @@ pub struct StudioJobRequest {
     pub manifest: PathBuf,
 }
 
+// `reference` is the release the repo's TL/TS are timed against (SOURCE.md unless overridden) and
+// `target` the one they are moved to. `target_ready` flips once the first download leg is parked.
+#[derive(Clone, Debug)]
+pub struct ResyncRequest {
+    pub episode: u32,
+    pub reference: String,
+    pub target: String,
+    pub target_ready: bool,
+}
+
+// `tpp` also runs the timing post-processor over the episode's TL against the new keyframes.
+#[derive(Clone, Debug)]
+pub struct KeyframesRequest {
+    pub episode: u32,
+    pub tpp: bool,
+}
+
+// One frame of the source traced into ASS drawings: `crop` picks the sign out of the frame (the
+// whole frame without one) and `episode` is the attached episode whose PlayRes the drawing is placed
+// in; without it the video's own size is used.
+#[derive(Clone, Debug)]
+pub struct TraceRequest {
+    pub at_centiseconds: u64,
+    pub crop: Option<FrameCrop>,
+    pub preset: TracePreset,
+    pub episode: Option<u32>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u16)]
 pub enum JobType {
@@ -2654,6 +2931,9 @@ pub enum JobType {
     StudioPreview = 015,
     Batch = 016,
     Subs = 017,
+    Resync = 018,
+    Keyframes = 019,
+    Trace = 020,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -2921,6 +3201,9 @@ pub struct Job {
     // encodes it spawns. Exactly one of the two is ever populated.
     pub batch: Option<BatchRequest>,
     pub batch_parent: Option<u64>,
+    pub resync: Option<ResyncRequest>,
+    pub keyframes: Option<KeyframesRequest>,
+    pub trace: Option<TraceRequest>,
 }
 
 impl PartialEq for Job {
@@ -3015,6 +3298,9 @@ impl Job {
             studio: None,
             batch: None,
             batch_parent: None,
+            resync: None,
+            keyframes: None,
+            trace: None,
         }
     }
 
@@ -3103,6 +3389,9 @@ impl Job {
             studio: None,
             batch: None,
             batch_parent: None,
+            resync: None,
+            keyframes: None,
+            trace: None,
         }
     }
 }
@@ -254,6 +254,9 @@ mod tests {
             studio: None,
             batch: None,
             batch_parent: None,
+            resync: None,
+            keyframes: None,
+            trace: None,
         }
     }
 
@@ -6,7 +6,8 @@ use crate::pnworker::core::Job;
 use crate::pnworker::messages::{
     get_message, MessagePayload, create_job_embed, PREVIEW_ATTACHMENT_MISSING,
     PREVIEW_ATTACHMENT_REJECTED, PREVIEW_DONE, PROBE_ROW, STUDIO_PREVIEW_ATTACHMENT_MISSING,
-    STUDIO_PREVIEW_DONE, SUBS_ATTACHMENT_MISSING, SUBS_DONE,
+    STUDIO_PREVIEW_DONE, SUBS_ATTACHMENT_MISSING, SUBS_DONE, KEYFRAMES_ATTACHMENT_MISSING, KEYFRAMES_DONE,
+    TRACE_ATTACHMENT_MISSING, TRACE_DONE,
 };
 use crate::pnworker::presence::{change_presence_job, global_context, Presence};
 use crate::pnworker::probe_pages::{probe_page_components, probe_page_count};
@@ -48,6 +49,10 @@ impl Frontend {
                                 STUDIO_PREVIEW_ATTACHMENT_MISSING
                             } else if is_subs_done(payload) {
                                 SUBS_ATTACHMENT_MISSING
+                            } else if is_keyframes_done(payload) {
+                                KEYFRAMES_ATTACHMENT_MISSING
+                            } else if is_trace_done(payload) {
+                                TRACE_ATTACHMENT_MISSING
                             } else {
                                 PREVIEW_ATTACHMENT_MISSING
                             };
@@ -192,8 +197,20 @@ fn is_subs_done(payload: &MessagePayload) -> bool {
     matches!(payload, MessagePayload::Progress(id, _) if *id == SUBS_DONE)
 }
 
+fn is_keyframes_done(payload: &MessagePayload) -> bool {
+    matches!(payload, MessagePayload::Progress(id, _) if *id == KEYFRAMES_DONE)
+}
+
+fn is_trace_done(payload: &MessagePayload) -> bool {
+    matches!(payload, MessagePayload::Progress(id, _) if *id == TRACE_DONE)
+}
+
 fn is_attachment_done(payload: &MessagePayload) -> bool {
-    is_preview_done(payload) || is_studio_preview_done(payload) || is_subs_done(payload)
+    is_preview_done(payload)
+        || is_studio_preview_done(payload)
+        || is_subs_done(payload)
+        || is_keyframes_done(payload)
+        || is_trace_done(payload)
 }
 
 // Probe file lists longer than one embed field get prev/next buttons; every other payload sends an
@@ -213,9 +230,26 @@ async fn preview_done_edit(job: &Job, payload: &MessagePayload) -> Option<EditMe
     let MessagePayload::Progress(id, args) = payload else {
         return None;
     };
-    if *id != PREVIEW_DONE && *id != STUDIO_PREVIEW_DONE && *id != SUBS_DONE {
+    if *id != PREVIEW_DONE && *id != STUDIO_PREVIEW_DONE && *id != SUBS_DONE && *id != KEYFRAMES_DONE && *id != TRACE_DONE {
         return None;
     }
+    // The keyframes file rides along so it can be loaded into Aegisub straight from the message, and
+    // so does a traced sign's ASS.
+    if *id == KEYFRAMES_DONE || *id == TRACE_DONE {
+        let path = args.get(2)?;
+        return match CreateAttachment::path(path).await {
+            Ok(attachment) => Some(
+                EditMessage::new()
+                    .content("")
+                    .embed(create_job_embed(job, payload))
+                    .new_attachment(attachment),
+            ),
+            Err(e) => {
+                eprintln!("[Pandora Attachment] failed to attach `{}`: {}", path, e);
+                None
+            }
+        };
+    }
     // Extraction always answers with exactly one attachment: the single track, or
     // the archive the worker bundled the tracks into.
     if *id == SUBS_DONE {
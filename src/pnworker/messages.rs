@@ -65,6 +65,14 @@ pub const SUBS_DONE: &str = "SUBS_DONE";
 pub const SUBS_NONE: &str = "SUBS_NONE";
 pub const SUBS_FAIL: &str = "SUBS_FAIL";
 pub const SUBS_ATTACHMENT_MISSING: &str = "SUBS_ATTACHMENT_MISSING";
+pub const RESYNC_DONE: &str = "RESYNC_DONE";
+pub const RESYNC_FAIL: &str = "RESYNC_FAIL";
+pub const KEYFRAMES_DONE: &str = "KEYFRAMES_DONE";
+pub const KEYFRAMES_FAIL: &str = "KEYFRAMES_FAIL";
+pub const KEYFRAMES_ATTACHMENT_MISSING: &str = "KEYFRAMES_ATTACHMENT_MISSING";
+pub const TRACE_DONE: &str = "TRACE_DONE";
+pub const TRACE_FAIL: &str = "TRACE_FAIL";
+pub const TRACE_ATTACHMENT_MISSING: &str = "TRACE_ATTACHMENT_MISSING";
 pub const PREVIEW_DONE: &str = "PREVIEW_DONE";
 pub const PREVIEW_FAIL: &str = "PREVIEW_FAIL";
 pub const STUDIO_PREVIEW_DONE: &str = "STUDIO_PREVIEW_DONE";
@@ -115,6 +123,8 @@ pub const FIELD_ANIMECIX_FANSUB: &str = "FIELD_ANIMECIX_FANSUB";
 pub const FIELD_OPENANIME_FANSUB: &str = "FIELD_OPENANIME_FANSUB";
 pub const FIELD_ANIZM_FANSUB: &str = "FIELD_ANIZM_FANSUB";
 pub const FIELD_OUTPUT: &str = "FIELD_OUTPUT";
+pub const FIELD_CHANGES: &str = "FIELD_CHANGES";
+pub const FIELD_STYLES: &str = "FIELD_STYLES";
 pub const LABEL_ETA: &str = "LABEL_ETA";
 pub const WARNINGS_MORE: &str = "WARNINGS_MORE";
 pub const STAGE_QUEUED: &str = "STAGE_QUEUED";
@@ -150,6 +160,9 @@ pub const JOB_TYPE_STUDIO: &str = "JOB_TYPE_STUDIO";
 pub const JOB_TYPE_STUDIO_PREVIEW: &str = "JOB_TYPE_STUDIO_PREVIEW";
 pub const JOB_TYPE_BATCH: &str = "JOB_TYPE_BATCH";
 pub const JOB_TYPE_SUBS: &str = "JOB_TYPE_SUBS";
+pub const JOB_TYPE_RESYNC: &str = "JOB_TYPE_RESYNC";
+pub const JOB_TYPE_KEYFRAMES: &str = "JOB_TYPE_KEYFRAMES";
+pub const JOB_TYPE_TRACE: &str = "JOB_TYPE_TRACE";
 pub const JOB_TYPE_UNKNOWN: &str = "JOB_TYPE_UNKNOWN";
 pub const VALUE_NONE: &str = "VALUE_NONE";
 pub const VALUE_NOT_AVAILABLE: &str = "VALUE_NOT_AVAILABLE";
@@ -177,6 +190,18 @@ pub const COMMAND_LOGS_READY: &str = "COMMAND_LOGS_READY";
 pub const COMMAND_UPDATED: &str = "COMMAND_UPDATED";
 pub const COMMAND_LIST: &str = "COMMAND_LIST";
 pub const COMMAND_REPO_PRESERVED: &str = "COMMAND_REPO_PRESERVED";
+pub const COMMAND_LINT_REPORT: &str = "COMMAND_LINT_REPORT";
+pub const LINT_SUMMARY: &str = "LINT_SUMMARY";
+pub const COMMAND_FONT_COVERAGE: &str = "COMMAND_FONT_COVERAGE";
+pub const FONT_COVERAGE_SUMMARY: &str = "FONT_COVERAGE_SUMMARY";
+pub const COMMAND_RETIMED: &str = "COMMAND_RETIMED";
+pub const RETIME_FILE_UPDATED: &str = "RETIME_FILE_UPDATED";
+pub const COMMAND_STYLE_LIBRARY: &str = "COMMAND_STYLE_LIBRARY";
+pub const STYLE_LIBRARY_SUMMARY: &str = "STYLE_LIBRARY_SUMMARY";
+pub const COMMAND_STYLES_APPLIED: &str = "COMMAND_STYLES_APPLIED";
+pub const STYLES_FILE_UNCHANGED: &str = "STYLES_FILE_UNCHANGED";
+pub const COMMAND_CONSISTENCY_REPORT: &str = "COMMAND_CONSISTENCY_REPORT";
+pub const CONSISTENCY_SUMMARY: &str = "CONSISTENCY_SUMMARY";
 pub const LINK_DOWNLOAD: &str = "LINK_DOWNLOAD";
 pub const CATLOGS_DESCRIPTION: &str = "CATLOGS_DESCRIPTION";
 pub const CATLOGS_NO_LOGS: &str = "CATLOGS_NO_LOGS";
@@ -652,6 +677,9 @@ pub fn get_job_type_text(job_type: JobType, lang: &str) -> String {
         JobType::StudioPreview => JOB_TYPE_STUDIO_PREVIEW,
         JobType::Batch => JOB_TYPE_BATCH,
         JobType::Subs => JOB_TYPE_SUBS,
+        JobType::Resync => JOB_TYPE_RESYNC,
+        JobType::Keyframes => JOB_TYPE_KEYFRAMES,
+        JobType::Trace => JOB_TYPE_TRACE,
         _ => JOB_TYPE_UNKNOWN,
     };
     get_message(id, lang)
@@ -728,6 +756,9 @@ mod tests {
             studio: None,
             batch: None,
             batch_parent: None,
+            resync: None,
+            keyframes: None,
+            trace: None,
         }
     }
 
@@ -567,6 +567,7 @@ mod tests {
     use crate::lib::image::{Canvas, Color};
     use crate::libkagami::complex::types::AssColour;
     use crate::libkagami::core::{Event, PandoraMeta, ScriptInfo, Stamp, V4pStyle};
+    use crate::libkagami::sections::ProjectGarbage;
     use crate::libkagami::tags::ASSLine;
 
     fn test_sub(events: Vec<Event>) -> SubstationAlpha {
@@ -612,6 +613,10 @@ mod tests {
             events,
             comments: Vec::new(),
             pandora_meta: PandoraMeta::default(),
+            fonts: Vec::new(),
+            graphics: Vec::new(),
+            project_garbage: ProjectGarbage::default(),
+            extradata: Vec::new(),
         }
     }
 
@@ -627,6 +632,7 @@ mod tests {
             margin_v: 0,
             effect: String::new(),
             text: text.parse::<ASSLine>().unwrap(),
+            extradata: Vec::new(),
         }
     }
 
@@ -355,6 +355,10 @@ pub const PNASS_JOB: &[CliParam] = &[
     CliParam::Literal("--wrap-style"),
     CliParam::Path("WRAPSTYLE"),
     CliParam::Literal("--no-adv-parsing"),
+    CliParam::Literal("--spellcheck"),
+    CliParam::Path("SPELLCHECK"),
+    CliParam::Literal("--glossary"),
+    CliParam::Path("GLOSSARY"),
     CliParam::Literal("--negkey"),
     CliParam::Literal("PNassJob"),
     CliParam::Literal("--negotiator"),
@@ -372,6 +376,9 @@ pub const PNASS_INJECT: &[CliParam] = &[
     CliParam::Path("OUTPUT"),
     CliParam::Literal("--duration-centiseconds"),
     CliParam::Path("DURATION"),
+    // A watermark drawn for another frame shape is padded in rather than distorted.
+    CliParam::Literal("--resample"),
+    CliParam::Literal("letterbox"),
     // Injection runs between the encode dispatch and ENCODE_START, where nothing else writes to the
     // job's log directory: without this the step is invisible on disk if it never returns.
     CliParam::Literal("--logfile"),
@@ -408,6 +415,8 @@ pub const PNASS_MERGE: &[CliParam] = &[
     CliParam::Path("MERGE"),
     CliParam::Literal("--output"),
     CliParam::Path("OUTPUT"),
+    CliParam::Literal("--resample"),
+    CliParam::Path("RESAMPLE"),
     CliParam::Literal("--smart-layer"),
     CliParam::Literal("9"),
     CliParam::Literal("--wrap-style"),
@@ -437,6 +446,59 @@ pub const PNASS_MERGE_TL_ONLY: &[CliParam] = &[
     CliParam::Literal("0.1.1"),
 ];
 
+pub const PNASS_LINT: &[CliParam] = &[
+    CliParam::Literal("--input"),
+    CliParam::Path("INPUT"),
+    CliParam::Literal("--lint"),
+    CliParam::Literal("--max-cps"),
+    CliParam::Path("MAXCPS"),
+    CliParam::Literal("--negkey"),
+    CliParam::Literal("PNassLint"),
+    CliParam::Literal("--negotiator"),
+    CliParam::Literal("PNdc"),
+    CliParam::Literal("--negver"),
+    CliParam::Literal("0.1.1"),
+];
+
+// pnass treats empty SHIFT/RETIME/STYLES/TIMECODES values as unset, so /retime fills every slot.
+pub const PNASS_RETIME: &[CliParam] = &[
+    CliParam::Literal("--input"),
+    CliParam::Path("INPUT"),
+    CliParam::Literal("--output"),
+    CliParam::Path("OUTPUT"),
+    CliParam::Literal("--shift"),
+    CliParam::Path("SHIFT"),
+    CliParam::Literal("--retime"),
+    CliParam::Path("RETIME"),
+    CliParam::Literal("--fps"),
+    CliParam::Path("FPS"),
+    CliParam::Literal("--timecodes"),
+    CliParam::Path("TIMECODES"),
+    CliParam::Literal("--only-styles"),
+    CliParam::Path("STYLES"),
+    CliParam::Literal("--negkey"),
+    CliParam::Literal("PNassRetime"),
+    CliParam::Literal("--negotiator"),
+    CliParam::Literal("PNdc"),
+    CliParam::Literal("--negver"),
+    CliParam::Literal("0.1.1"),
+];
+
+pub const PNASS_SYNCMAP: &[CliParam] = &[
+    CliParam::Literal("--input"),
+    CliParam::Path("INPUT"),
+    CliParam::Literal("--output"),
+    CliParam::Path("OUTPUT"),
+    CliParam::Literal("--sync-map"),
+    CliParam::Path("MAP"),
+    CliParam::Literal("--negkey"),
+    CliParam::Literal("PNassSync"),
+    CliParam::Literal("--negotiator"),
+    CliParam::Literal("PNdc"),
+    CliParam::Literal("--negver"),
+    CliParam::Literal("0.1.1"),
+];
+
 pub const PNMPEG_EXTRACT_SUBS: &[CliParam] = &[
     CliParam::Literal("--extractsubs"),
     CliParam::Literal("--input"),
@@ -450,3 +512,95 @@ pub const PNMPEG_EXTRACT_SUBS: &[CliParam] = &[
     CliParam::Literal("--negver"),
     CliParam::NegVer("1"),
 ];
+
+pub const PNMPEG_SYNCMAP: &[CliParam] = &[
+    CliParam::Literal("--syncmap"),
+    CliParam::Literal("--input"),
+    CliParam::Path("INPUT"),
+    CliParam::Literal("--subinput"),
+    CliParam::Path("REFERENCE"),
+    CliParam::Literal("--output"),
+    CliParam::Path("OUTPUT"),
+    CliParam::Literal("--negkey"),
+    CliParam::Path("NEGKEY"),
+    CliParam::Literal("--negotiator"),
+    CliParam::Literal("PNprobeworker"),
+    CliParam::Literal("--negver"),
+    CliParam::NegVer("1"),
+];
+
+// Scene detection is always on for the worker: release encodes often come from a source whose
+// encoder placed keyframes by GOP length rather than by cut.
+pub const PNMPEG_KEYFRAMES: &[CliParam] = &[
+    CliParam::Literal("--keyframes"),
+    CliParam::Literal("--scene-threshold"),
+    CliParam::Literal("0.3"),
+    CliParam::Literal("--input"),
+    CliParam::Path("INPUT"),
+    CliParam::Literal("--output"),
+    CliParam::Path("OUTPUT"),
+    CliParam::Literal("--negkey"),
+    CliParam::Path("NEGKEY"),
+    CliParam::Literal("--negotiator"),
+    CliParam::Literal("PNprobeworker"),
+    CliParam::Literal("--negver"),
+    CliParam::NegVer("1"),
+];
+
+pub const PNASS_TPP: &[CliParam] = &[
+    CliParam::Literal("--input"),
+    CliParam::Path("INPUT"),
+    CliParam::Literal("--output"),
+    CliParam::Path("OUTPUT"),
+    CliParam::Literal("--tpp"),
+    CliParam::Literal("--keyframes"),
+    CliParam::Path("KEYFRAMES"),
+    CliParam::Literal("--negkey"),
+    CliParam::Literal("PNassTpp"),
+    CliParam::Literal("--negotiator"),
+    CliParam::Literal("PNdc"),
+    CliParam::Literal("--negver"),
+    CliParam::Literal("0.1.1"),
+];
+
+// FPS is always filled: /api/v1/git/motion falls back to the tracking data's Units Per Second
+// before calling pnass.
+pub const PNASS_MOTION: &[CliParam] = &[
+    CliParam::Literal("--input"),
+    CliParam::Path("INPUT"),
+    CliParam::Literal("--output"),
+    CliParam::Path("OUTPUT"),
+    CliParam::Literal("--motion"),
+    CliParam::Path("MOTION"),
+    CliParam::Literal("--motion-lines"),
+    CliParam::Path("LINES"),
+    CliParam::Literal("--motion-origin"),
+    CliParam::Path("ORIGIN"),
+    CliParam::Literal("--fps"),
+    CliParam::Path("FPS"),
+    CliParam::Literal("--negkey"),
+    CliParam::Literal("PNassMotion"),
+    CliParam::Literal("--negotiator"),
+    CliParam::Literal("PNdc"),
+    CliParam::Literal("--negver"),
+    CliParam::Literal("0.1.1"),
+];
+
+pub const PNASS_APPLY_STYLES: &[CliParam] = &[
+    CliParam::Literal("--input"),
+    CliParam::Path("INPUT"),
+    CliParam::Literal("--output"),
+    CliParam::Path("OUTPUT"),
+    CliParam::Literal("--apply-styles"),
+    CliParam::Path("LIBRARY"),
+    CliParam::Literal("--add-styles"),
+    CliParam::Path("ADD"),
+    CliParam::Literal("--resample"),
+    CliParam::Path("RESAMPLE"),
+    CliParam::Literal("--negkey"),
+    CliParam::Literal("PNassStyles"),
+    CliParam::Literal("--negotiator"),
+    CliParam::Literal("PNdc"),
+    CliParam::Literal("--negver"),
+    CliParam::Literal("0.1.1"),
+];
@@ -5,15 +5,22 @@ use crate::lib::mpeg::preview::ffmpeg_screenshot;
 use crate::lib::p2p::nyaaise::TorrentType;
 use crate::lib::protocol::core::Protocol;
 use crate::libkagami::core::{SubstationAlpha, find_fonts_with_roots};
-use crate::pnworker::core::Stage;
+use crate::kagami_trace::{TraceOptions, trace_rgba};
+use crate::lib::git::{commit_keyframes, episode_play_res, resync_episode};
+use crate::lib::mpeg::clip::extract_frame;
+use crate::libkagami::complex::types::AssTime;
+use crate::libkagami::tracing::{TraceAssOptions, TracePlacement, place_trace, trace_to_ass};
+use crate::pnworker::core::{KeyframesRequest, ResyncRequest, Stage, TraceRequest};
 use crate::pnworker::core::{CommData, WorkerMsg};
 use crate::pnworker::messages::{
-    SUBS_DONE, SUBS_FAIL, SUBS_NONE,
+    SUBS_DONE, SUBS_FAIL, SUBS_NONE, RESYNC_DONE, RESYNC_FAIL, KEYFRAMES_DONE, KEYFRAMES_FAIL, TRACE_DONE, TRACE_FAIL,
     CTORRENT_DONE, CTORRENT_FAIL, ENCODE_PROG, ENCODE_START, ENCODE_WARNING, JOB_CANCELLED, MessagePayload, PREVIEW_DONE, PREVIEW_FAIL,
     PROBE_FAIL, PROBE_ROW, STUDIO_PREVIEW_DONE, STUDIO_PREVIEW_FAIL, WORKER_ASSIGN,
 };
 use crate::pnworker::preview::{compose_preview, merge_previews};
-use crate::pnworker::tools::{PNCURL_TORRENT, PNMPEG_EXTRACT_SUBS, PNMPEG_STUDIO, PNP2P_PROBE};
+use crate::pnworker::tools::{
+    PNCURL_TORRENT, PNMPEG_EXTRACT_SUBS, PNMPEG_KEYFRAMES, PNMPEG_STUDIO, PNMPEG_SYNCMAP, PNP2P_PROBE,
+};
 use crate::pnworker::util::PathValue;
 use crate::pnworker::util::{
     ToolResult, WorkerNamePool, job_cancelled, run_tool, string_byte_to_mb,
@@ -28,6 +35,9 @@ use tokio::time::{Duration, Instant, sleep};
 
 pub type ProbeData = (PathBuf, TorrentType, u64);
 pub type SubsData = (PathBuf, u64);
+pub type ResyncData = (PathBuf, ResyncRequest, u64, u64, u64); // (directory, request, server, channel, job)
+pub type KeyframesData = (PathBuf, KeyframesRequest, u64, u64, u64); // (directory, request, server, channel, job)
+pub type TraceData = (PathBuf, TraceRequest, u64, u64, u64); // (directory, request, server, channel, job)
 pub type PreviewData = (
     PathBuf,
     Vec<(u64, String)>,
@@ -68,6 +78,9 @@ pub async fn pn_probeworker(mut rx: Receiver<WorkerMsg>, tx: Sender<CommData>, p
                     | WorkerMsg::Preview(_)
                     | WorkerMsg::StudioPreview(_)
                     | WorkerMsg::Subs(_)
+                    | WorkerMsg::Resync(_)
+                    | WorkerMsg::Keyframes(_)
+                    | WorkerMsg::Trace(_)
             ) {
                 pending.push_back(msg);
             }
@@ -91,7 +104,10 @@ pub async fn pn_probeworker(mut rx: Receiver<WorkerMsg>, tx: Sender<CommData>, p
                     WorkerMsg::Probe((_, _, job_id))
                     | WorkerMsg::Preview((_, _, _, _, job_id, _))
                     | WorkerMsg::StudioPreview((_, _, job_id))
-                    | WorkerMsg::Subs((_, job_id)) => *job_id,
+                    | WorkerMsg::Subs((_, job_id))
+                    | WorkerMsg::Resync((_, _, _, _, job_id))
+                    | WorkerMsg::Keyframes((_, _, _, _, job_id))
+                    | WorkerMsg::Trace((_, _, _, _, job_id)) => *job_id,
                     _ => unreachable!(),
                 };
                 tx2.send((
@@ -131,6 +147,15 @@ pub async fn pn_probeworker(mut rx: Receiver<WorkerMsg>, tx: Sender<CommData>, p
                     WorkerMsg::Subs((directory, job_id)) => {
                         run_subs_job(directory, job_id, &pnmpeg_path2, &tx2, &pulse2).await;
                     }
+                    WorkerMsg::Resync(data) => {
+                        run_resync_job(data, &pnmpeg_path2, &tx2, &pulse2).await;
+                    }
+                    WorkerMsg::Keyframes(data) => {
+                        run_keyframes_job(data, &pnmpeg_path2, &tx2, &pulse2).await;
+                    }
+                    WorkerMsg::Trace(data) => {
+                        run_trace_job(data, &tx2, &pulse2).await;
+                    }
                     _ => unreachable!(),
                 }
                 done_tx2.send(name).await.ok();
@@ -651,6 +676,267 @@ async fn run_subs_job(
         .ok();
 }
 
+// pnmpeg measures the map between the two downloads; lib::git applies it to the repo's TL/TS and
+// moves SOURCE.md over, so this only has to carry the aligner's notes into the final message.
+async fn run_resync_job(
+    data: ResyncData,
+    pnmpeg_path: &str,
+    tx: &Sender<CommData>,
+    pulse: &Sender<()>,
+) {
+    let (directory, request, server_id, channel_id, job_id) = data;
+    if job_cancelled(&directory) {
+        tx.send((job_id, MessagePayload::Static(JOB_CANCELLED), Some(Stage::Cancelled)))
+            .await
+            .ok();
+        return;
+    }
+    let map = directory.join("work").join("resync.map");
+    let mut proto = Protocol::new(vec![1]);
+    let mut notes: Vec<String> = Vec::new();
+    let result = run_tool(
+        pnmpeg_path,
+        PNMPEG_SYNCMAP,
+        &HashMap::from([
+            ("INPUT", PathValue::from(directory.join("contents").join("target.mkv").display().to_string())),
+            (
+                "REFERENCE",
+                PathValue::from(directory.join("contents").join("torrent").join("input.mkv").display().to_string()),
+            ),
+            ("OUTPUT", PathValue::from(map.display().to_string())),
+            ("NEGKEY", PathValue::from(format!("pn-probe-{}", job_id))),
+        ]),
+        job_id,
+        &mut proto,
+        |data| {
+            let out: u16 = data.get(0).and_then(|v| v.parse())?;
+            match out {
+                1 => return Some(ToolResult::Success),
+                2 => return Some(ToolResult::Fail),
+                3 => return Some(ToolResult::Cancel),
+                4 => {
+                    if let Some(line) = data.get(1).and_then(|v| v.as_str()) {
+                        notes.push(line.to_string());
+                    }
+                }
+                _ => {}
+            }
+            None
+        },
+    )
+    .await;
+    pulse.try_send(()).ok();
+
+    match result {
+        ToolResult::Cancel => {
+            tx.send((job_id, MessagePayload::Static(JOB_CANCELLED), Some(Stage::Cancelled)))
+                .await
+                .ok();
+            return;
+        }
+        ToolResult::Fail => {
+            let reason = notes.pop().unwrap_or_else(|| "the audio could not be aligned".to_string());
+            tx.send((job_id, MessagePayload::Progress(RESYNC_FAIL, vec![reason]), Some(Stage::Failed)))
+                .await
+                .ok();
+            return;
+        }
+        ToolResult::Success => {}
+    }
+
+    match resync_episode(server_id, channel_id, request.episode, &map, &request.target).await {
+        Ok(outcome) => {
+            let files = outcome
+                .files
+                .iter()
+                .chain(std::iter::once(&outcome.source_path))
+                .map(|path| format!("`{}`", path))
+                .collect::<Vec<_>>()
+                .join("\n");
+            tx.send((
+                job_id,
+                MessagePayload::Progress(RESYNC_DONE, vec![files, notes.join("\n")]),
+                Some(Stage::Uploaded),
+            ))
+            .await
+            .ok();
+        }
+        Err(e) => {
+            tx.send((job_id, MessagePayload::Progress(RESYNC_FAIL, vec![e]), Some(Stage::Failed)))
+                .await
+                .ok();
+        }
+    }
+}
+
+// The file is written into the job's work folder first so the message can carry it even if the
+// repo commit fails; the commit (and the optional TL post-process) is lib::git's.
+async fn run_keyframes_job(
+    data: KeyframesData,
+    pnmpeg_path: &str,
+    tx: &Sender<CommData>,
+    pulse: &Sender<()>,
+) {
+    let (directory, request, server_id, channel_id, job_id) = data;
+    if job_cancelled(&directory) {
+        tx.send((job_id, MessagePayload::Static(JOB_CANCELLED), Some(Stage::Cancelled)))
+            .await
+            .ok();
+        return;
+    }
+    let output = directory.join("work").join("keyframes.txt");
+    let mut proto = Protocol::new(vec![1]);
+    let mut notes: Vec<String> = Vec::new();
+    let result = run_tool(
+        pnmpeg_path,
+        PNMPEG_KEYFRAMES,
+        &HashMap::from([
+            ("INPUT", PathValue::from(directory.join("contents").join("torrent").join("input.mkv").display().to_string())),
+            ("OUTPUT", PathValue::from(output.display().to_string())),
+            ("NEGKEY", PathValue::from(format!("pn-probe-{}", job_id))),
+        ]),
+        job_id,
+        &mut proto,
+        |data| {
+            let out: u16 = data.get(0).and_then(|v| v.parse())?;
+            match out {
+                1 => return Some(ToolResult::Success),
+                2 => return Some(ToolResult::Fail),
+                3 => return Some(ToolResult::Cancel),
+                4 => {
+                    if let Some(line) = data.get(1).and_then(|v| v.as_str()) {
+                        notes.push(line.to_string());
+                    }
+                }
+                _ => {}
+            }
+            None
+        },
+    )
+    .await;
+    pulse.try_send(()).ok();
+
+    match result {
+        ToolResult::Cancel => {
+            tx.send((job_id, MessagePayload::Static(JOB_CANCELLED), Some(Stage::Cancelled)))
+                .await
+                .ok();
+            return;
+        }
+        ToolResult::Fail => {
+            let reason = notes.pop().unwrap_or_else(|| "ffmpeg could not read the video".to_string());
+            tx.send((job_id, MessagePayload::Progress(KEYFRAMES_FAIL, vec![reason]), Some(Stage::Failed)))
+                .await
+                .ok();
+            return;
+        }
+        ToolResult::Success => {}
+    }
+
+    match commit_keyframes(server_id, channel_id, request.episode, &output, request.tpp).await {
+        Ok(outcome) => {
+            let files = std::iter::once(&outcome.keyframes_path)
+                .chain(outcome.tl_path.iter())
+                .map(|path| format!("`{}`", path))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let summary = notes.pop().unwrap_or_else(|| "keyframes".to_string());
+            tx.send((
+                job_id,
+                MessagePayload::Progress(KEYFRAMES_DONE, vec![summary, files, output.display().to_string()]),
+                Some(Stage::Uploaded),
+            ))
+            .await
+            .ok();
+        }
+        Err(e) => {
+            tx.send((job_id, MessagePayload::Progress(KEYFRAMES_FAIL, vec![e]), Some(Stage::Failed)))
+                .await
+                .ok();
+        }
+    }
+}
+
+// The traced events start at the frame and last the lab's default five seconds; the typesetter
+// retimes them anyway. PlayRes comes from the episode's script when one was named, else the video.
+async fn run_trace_job(data: TraceData, tx: &Sender<CommData>, pulse: &Sender<()>) {
+    let (directory, request, server_id, channel_id, job_id) = data;
+    if job_cancelled(&directory) {
+        tx.send((job_id, MessagePayload::Static(JOB_CANCELLED), Some(Stage::Cancelled)))
+            .await
+            .ok();
+        return;
+    }
+    let play_res = match request.episode {
+        Some(episode) => match episode_play_res(server_id, channel_id, episode).await {
+            Ok(play_res) => play_res,
+            Err(e) => {
+                tx.send((job_id, MessagePayload::Progress(TRACE_FAIL, vec![e]), Some(Stage::Failed)))
+                    .await
+                    .ok();
+                return;
+            }
+        },
+        None => None,
+    };
+    let input = directory.join("contents").join("torrent").join("input.mkv");
+    let output = directory.join("work").join("trace.ass");
+    let at = AssTime::from_centiseconds(request.at_centiseconds);
+    let traced = tokio::task::spawn_blocking(move || {
+        let frame = extract_frame(&input, request.at_centiseconds * 10, request.crop)?;
+        let trace = trace_rgba(
+            frame.width,
+            frame.height,
+            &frame.pixels,
+            &TraceOptions::for_preset(request.preset),
+        )
+        .map_err(|e| e.to_string())?;
+        let play_res = play_res.unwrap_or((
+            frame.video_width.min(u16::MAX as u32) as u16,
+            frame.video_height.min(u16::MAX as u32) as u16,
+        ));
+        let origin = request.crop.map(|crop| (crop.x, crop.y)).unwrap_or((0, 0));
+        let placement = TracePlacement::for_crop((frame.video_width, frame.video_height), origin, play_res);
+        let options = TraceAssOptions {
+            title: format!("Trace at {}", at),
+            start: at,
+            end: AssTime::from_centiseconds(request.at_centiseconds + 500),
+            ..TraceAssOptions::default()
+        };
+        let ass = trace_to_ass(&place_trace(&trace, &placement), &options)?;
+        let summary = format!("{} events at {}x{}", ass.events.len(), play_res.0, play_res.1);
+        Ok::<_, String>((ass.stringify(), summary))
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result);
+    pulse.try_send(()).ok();
+
+    let result = match traced {
+        Ok((ass, summary)) => tokio::fs::write(&output, ass)
+            .await
+            .map(|_| summary)
+            .map_err(|e| format!("failed to write the traced ASS: {}", e)),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(summary) => {
+            tx.send((
+                job_id,
+                MessagePayload::Progress(TRACE_DONE, vec![at.to_string(), summary, output.display().to_string()]),
+                Some(Stage::Uploaded),
+            ))
+            .await
+            .ok();
+        }
+        Err(e) => {
+            tx.send((job_id, MessagePayload::Progress(TRACE_FAIL, vec![e]), Some(Stage::Failed)))
+                .await
+                .ok();
+        }
+    }
+}
+
 // `0 · eng · Signs & Songs · ass` — enough for a human to tell two English
 // tracks apart, which is the whole reason for extracting them separately.
 fn subtitle_track_label(ordinal: &str, language: &str, title: &str, codec: &str) -> String {
@@ -752,7 +1038,9 @@ async fn stage_preview_fonts(directory: &Path, server_id: Option<u64>) -> PathBu
     let subtitle = directory.join("contents").join("subtitle.ass");
     let fonts_dir = directory.join("work").join("fonts");
     tokio::fs::create_dir_all(&fonts_dir).await.ok();
-    let sub = SubstationAlpha::load(subtitle, true).await;
+    let Ok(sub) = SubstationAlpha::load(subtitle, true).await else {
+        return fonts_dir;
+    };
     let names = sub.font_names();
     if names.is_empty() {
         return fonts_dir;